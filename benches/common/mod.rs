@@ -0,0 +1,15 @@
+//! Data shared by the benchmarks.
+
+/// Runs of single bytes and short repeating patterns, decoded mostly as overlapping matches.
+pub fn repetitive_data(len: usize) -> Vec<u8> {
+    let mut data = Vec::with_capacity(len);
+    let mut i = 0;
+    while data.len() < len {
+        let period = i % 9 + 1;
+        let pattern: Vec<u8> = (0..period).map(|j| (i + j) as u8).collect();
+        data.extend(pattern.iter().cycle().take(1000));
+        i += 1;
+    }
+    data.truncate(len);
+    data
+}
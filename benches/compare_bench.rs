@@ -0,0 +1,69 @@
+//! Decoding throughput of the toy decoder next to miniz_oxide, on several kinds of data.
+//! Run with `cargo bench --bench compare_bench`; the component benchmarks are in
+//! `inflate_bench`.
+//!
+//! Each corpus is compressed once by miniz_oxide, then decompressed by both decoders, so the
+//! two decode exactly the same streams.
+
+mod common;
+
+use common::repetitive_data;
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use inflate_toy::inflate::inflate_to_vec;
+use inflate_toy::roundtrip::{sample, SampleKind};
+use std::hint::black_box;
+
+const CORPUS_LEN: usize = 1 << 20;
+const LARGE_CORPUS_LEN: usize = 16 << 20;
+
+/// A large file mixing every kind of data, in pieces of a few hundred kilobytes.
+fn large_data(len: usize) -> Vec<u8> {
+    let mut data = Vec::with_capacity(len);
+    let mut seed = 0;
+    while data.len() < len {
+        let piece = match seed % 4 {
+            0 => sample(SampleKind::Text, seed, 300_000),
+            1 => sample(SampleKind::LongMatches, seed, 300_000),
+            2 => repetitive_data(100_000),
+            _ => sample(SampleKind::Random, seed, 50_000),
+        };
+        data.extend(piece);
+        seed += 1;
+    }
+    data.truncate(len);
+    data
+}
+
+fn corpora() -> Vec<(&'static str, Vec<u8>)> {
+    vec![
+        ("text", sample(SampleKind::Text, 1, CORPUS_LEN)),
+        ("random", sample(SampleKind::Random, 1, CORPUS_LEN)),
+        ("repetitive", repetitive_data(CORPUS_LEN)),
+        (
+            "long_matches",
+            sample(SampleKind::LongMatches, 1, CORPUS_LEN),
+        ),
+        ("large", large_data(LARGE_CORPUS_LEN)),
+    ]
+}
+
+fn bench_compare(c: &mut Criterion) {
+    for (name, raw) in corpora() {
+        let compressed = miniz_oxide::deflate::compress_to_vec(&raw, 6);
+        let mut group = c.benchmark_group(format!("compare/{}", name));
+        group.throughput(Throughput::Bytes(raw.len() as u64));
+        if raw.len() > CORPUS_LEN {
+            group.sample_size(10);
+        }
+        group.bench_function("inflate_toy", |b| {
+            b.iter(|| inflate_to_vec(black_box(&compressed)).unwrap())
+        });
+        group.bench_function("miniz_oxide", |b| {
+            b.iter(|| miniz_oxide::inflate::decompress_to_vec(black_box(&compressed)).unwrap())
+        });
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_compare);
+criterion_main!(benches);
@@ -0,0 +1,177 @@
+//! Decoding throughput, to measure changes to the hot decode loop, and the throughput of its
+//! components: bit reading, Huffman table construction, match copying and checksums.
+//! Run with `cargo bench --bench inflate_bench`; `compare_bench` compares whole-stream
+//! decoding with miniz_oxide.
+
+mod common;
+
+use common::repetitive_data;
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use inflate_toy::bit_stream::BitReader;
+use inflate_toy::checksum::{adler32, crc32};
+use inflate_toy::deflate::{
+    deflate_fixed_tokens_to_vec, deflate_stored_to_vec, Token, MAX_MATCH_LEN,
+};
+use inflate_toy::huffman::{build_code_lengths, HuffmanLookupTable};
+use inflate_toy::inflate::{
+    inflate_to_vec, inflate_to_vec_with_options, DecodeStrategy, InflateOptions,
+};
+use std::hint::black_box;
+
+/// Text-like data that compresses with plenty of matches and dynamic blocks.
+fn sample_data() -> Vec<u8> {
+    let words = [
+        "deflate", "huffman", "window", "literal", "match", "block", "stream",
+    ];
+    (0..200_000)
+        .map(|i: usize| words[i * 7 % words.len()].as_bytes()[i % 5])
+        .collect()
+}
+
+fn bench_bit_reader(c: &mut Criterion) {
+    let data = sample_data();
+    let mut group = c.benchmark_group("bit_reader");
+    group.throughput(Throughput::Bytes(data.len() as u64));
+    group.bench_function("read_bits", |b| {
+        b.iter(|| {
+            let mut reader = BitReader::new(black_box(&data));
+            let mut sum = 0;
+            let mut n_bits = 1;
+            while !reader.eof() {
+                sum ^= reader.read_bits(n_bits);
+                n_bits = n_bits % 15 + 1;
+            }
+            sum
+        })
+    });
+    group.bench_function("peek_advance", |b| {
+        b.iter(|| {
+            let mut reader = BitReader::new(black_box(&data));
+            let mut sum = 0;
+            let mut n_bits = 1;
+            while !reader.eof() {
+                sum ^= reader.peek_bits(15);
+                reader.advance(n_bits);
+                n_bits = n_bits % 15 + 1;
+            }
+            sum
+        })
+    });
+    group.bench_function("read_byte", |b| {
+        b.iter(|| {
+            let mut reader = BitReader::new(black_box(&data));
+            let mut sum = 0u8;
+            while !reader.eof() {
+                sum ^= reader.read_byte();
+            }
+            sum
+        })
+    });
+    group.finish();
+}
+
+fn bench_table_construction(c: &mut Criterion) {
+    // Literal/length code lengths as a dynamic block of the sample data would have them.
+    let mut freqs = vec![1; 286];
+    for &byte in &sample_data() {
+        freqs[byte as usize] += 1;
+    }
+    let code_len = build_code_lengths(&freqs, 15);
+    let mut group = c.benchmark_group("huffman_table");
+    group.bench_function("flat", |b| {
+        b.iter(|| HuffmanLookupTable::new(black_box(&code_len), 15))
+    });
+    group.bench_function("two_level", |b| {
+        b.iter(|| HuffmanLookupTable::new_two_level(black_box(&code_len), 15, 9))
+    });
+    group.finish();
+}
+
+fn bench_match_copy(c: &mut Criterion) {
+    // One fixed block of a few literals and then nothing but longest matches, so decoding
+    // is dominated by copying them.
+    let mut group = c.benchmark_group("match_copy");
+    for distance in [1, 3, 16, 300, 30000] {
+        let mut tokens: Vec<Token> = (0..distance).map(|i| Token::Literal(i as u8)).collect();
+        tokens.extend((0..4000).map(|_| Token::Match {
+            length: MAX_MATCH_LEN,
+            distance,
+        }));
+        let compressed = deflate_fixed_tokens_to_vec(&tokens);
+        let output_len = distance + 4000 * MAX_MATCH_LEN;
+        group.throughput(Throughput::Bytes(output_len as u64));
+        group.bench_function(format!("distance_{}", distance), |b| {
+            b.iter(|| inflate_to_vec(black_box(&compressed)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_inflate(c: &mut Criterion) {
+    let raw = sample_data();
+    let mut group = c.benchmark_group("inflate");
+    group.throughput(Throughput::Bytes(raw.len() as u64));
+    for level in [1, 6, 9] {
+        let compressed = miniz_oxide::deflate::compress_to_vec(&raw, level);
+        group.bench_function(format!("level_{}", level), |b| {
+            b.iter(|| inflate_to_vec(black_box(&compressed)).unwrap())
+        });
+    }
+    group.finish();
+
+    // Long matches at short distances, where copying matches dominates.
+    let raw = repetitive_data(2_000_000);
+    let mut group = c.benchmark_group("inflate_repetitive");
+    group.throughput(Throughput::Bytes(raw.len() as u64));
+    let compressed = miniz_oxide::deflate::compress_to_vec(&raw, 6);
+    group.bench_function("level_6", |b| {
+        b.iter(|| inflate_to_vec(black_box(&compressed)).unwrap())
+    });
+    group.finish();
+
+    // Stored blocks, where decoding is a copy of the input.
+    let raw = sample_data();
+    let mut group = c.benchmark_group("inflate_stored");
+    group.throughput(Throughput::Bytes(raw.len() as u64));
+    let compressed = deflate_stored_to_vec(&raw);
+    group.bench_function("stored", |b| {
+        b.iter(|| inflate_to_vec(black_box(&compressed)).unwrap())
+    });
+    group.finish();
+
+    // The same stream with each way of decoding Huffman codes.
+    let raw = sample_data();
+    let mut group = c.benchmark_group("inflate_decoder");
+    group.throughput(Throughput::Bytes(raw.len() as u64));
+    let compressed = miniz_oxide::deflate::compress_to_vec(&raw, 6);
+    for (name, decoder) in [
+        ("flat", DecodeStrategy::Flat),
+        ("two_level", DecodeStrategy::TwoLevel),
+        ("tree", DecodeStrategy::Tree),
+    ] {
+        let options = InflateOptions::new().decoder(decoder);
+        group.bench_function(name, |b| {
+            b.iter(|| inflate_to_vec_with_options(black_box(&compressed), &options).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_checksum(c: &mut Criterion) {
+    let data = sample_data();
+    let mut group = c.benchmark_group("checksum");
+    group.throughput(Throughput::Bytes(data.len() as u64));
+    group.bench_function("crc32", |b| b.iter(|| crc32(black_box(&data))));
+    group.bench_function("adler32", |b| b.iter(|| adler32(black_box(&data))));
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_bit_reader,
+    bench_table_construction,
+    bench_match_copy,
+    bench_inflate,
+    bench_checksum
+);
+criterion_main!(benches);
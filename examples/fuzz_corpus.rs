@@ -0,0 +1,14 @@
+//! Write the fuzzing seed corpus into the given directory, or `fuzz/corpus/inflate` by default.
+
+use std::io::Result;
+use std::path::PathBuf;
+
+fn main() -> Result<()> {
+    let dir = std::env::args()
+        .nth(1)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("fuzz/corpus/inflate"));
+    inflate_toy::fuzz_support::write_corpus(&dir)?;
+    println!("Wrote {}", dir.display());
+    Ok(())
+}
@@ -0,0 +1,18 @@
+//! Write the fuzzing dictionary to the given path, or to `fuzz/deflate.dict` by default.
+
+use std::fs::{self, File};
+use std::io::{BufWriter, Result};
+use std::path::PathBuf;
+
+fn main() -> Result<()> {
+    let path = std::env::args()
+        .nth(1)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("fuzz/deflate.dict"));
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    inflate_toy::fuzz_dict::write_dictionary(BufWriter::new(File::create(&path)?))?;
+    println!("Wrote {}", path.display());
+    Ok(())
+}
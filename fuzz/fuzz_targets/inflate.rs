@@ -0,0 +1,9 @@
+//! Decompress arbitrary inputs, in any container.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = inflate_toy::fuzz_support::decode_no_panic(data);
+});
@@ -0,0 +1,9 @@
+//! Run arbitrary inputs through the stepper, disassembler, statistics and validator.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    inflate_toy::fuzz_support::inspect_no_panic(data);
+});
@@ -0,0 +1,68 @@
+#ifndef INFLATE_TOY_H
+#define INFLATE_TOY_H
+
+/* Generated by cbindgen from src/ffi.rs. Do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// The outcome of a call to [`inflate_toy_decompress`]: success, or why it failed.
+// Every [`InflateError`] has its own status.
+typedef enum InflateToyStatus {
+  INFLATE_TOY_STATUS_OK = 0,
+  // A pointer argument is null while its length is not 0.
+  INFLATE_TOY_STATUS_INVALID_ARGUMENT = 1,
+  INFLATE_TOY_STATUS_EMPTY_INPUT = 2,
+  INFLATE_TOY_STATUS_UNEXPECTED_EOF = 3,
+  INFLATE_TOY_STATUS_INVALID_BLOCK_TYPE = 4,
+  INFLATE_TOY_STATUS_INVALID_LEN_NLEN = 5,
+  INFLATE_TOY_STATUS_INVALID_HUFFMAN_SYMBOL = 6,
+  INFLATE_TOY_STATUS_OVERSUBSCRIBED_TABLE = 7,
+  INFLATE_TOY_STATUS_INCOMPLETE_TABLE = 8,
+  INFLATE_TOY_STATUS_DISTANCE_TOO_FAR = 9,
+  INFLATE_TOY_STATUS_DISTANCE_OUTSIDE_WINDOW = 10,
+  INFLATE_TOY_STATUS_TRAILING_DATA = 11,
+  INFLATE_TOY_STATUS_DICTIONARY_REQUIRED = 12,
+  INFLATE_TOY_STATUS_DICTIONARY_MISMATCH = 13,
+  INFLATE_TOY_STATUS_INVALID_HEADER = 14,
+  INFLATE_TOY_STATUS_CHECKSUM_MISMATCH = 15,
+  INFLATE_TOY_STATUS_OUTPUT_LIMIT_EXCEEDED = 16,
+  // The output buffer is too small for the decompressed data.
+  INFLATE_TOY_STATUS_OUTPUT_TOO_SMALL = 17,
+  INFLATE_TOY_STATUS_BLOCK_LIMIT_EXCEEDED = 18,
+  INFLATE_TOY_STATUS_INVALID_CODE_LENGTH_ENCODING = 19,
+  INFLATE_TOY_STATUS_CODE_TOO_LONG = 20,
+  INFLATE_TOY_STATUS_UNSUPPORTED_OPTION = 21,
+} InflateToyStatus;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Decompress the `in_len` bytes at `in_ptr`, a raw DEFLATE, zlib or gzip stream, into the
+// `out_cap` bytes at `out_ptr`. On success, the number of bytes written is stored at `out_len`;
+// on failure, 0 is, and the content of the output buffer is unspecified.
+//
+// # Safety
+//
+// `in_ptr` must point to `in_len` readable bytes and `out_ptr` to `out_cap` writable bytes,
+// neither overlapping the other; both may be null if their length is 0. `out_len` must be
+// null or point to a writable `size_t`.
+InflateToyStatus inflate_toy_decompress(const uint8_t *in_ptr,
+                                        size_t in_len,
+                                        uint8_t *out_ptr,
+                                        size_t out_cap,
+                                        size_t *out_len);
+
+// Get a static, NUL-terminated description of a status.
+// The status is taken as a plain integer, so that any value is safe to pass: one that is not
+// a status gets "Unknown status".
+const char *inflate_toy_status_message(int status);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* INFLATE_TOY_H */
@@ -1,8 +1,14 @@
-//! A module that provides a struct to read bits from a byte array.
-use std::{default, ops};
+//! A module that provides structs to read bits from and write bits to a byte array.
+//! DEFLATE packs bits LSB-first; `BitReaderMsb` reads the opposite order used by other formats.
+use alloc::vec::Vec;
+use core::{default, ops};
+#[cfg(feature = "std")]
+use std::io::{self, Read};
 
 /// A struct representing the position of a bit in a byte array.
 /// The position is represented by the byte index and the bit index within the byte.
+/// Only `StreamBitReader`, which needs std, uses it.
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 struct BitPosition {
     pub byte_index: usize,
@@ -12,6 +18,7 @@ struct BitPosition {
 /// The number of bits in a byte.
 const BITS_PER_BYTE: usize = 8;
 
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
 impl BitPosition {
     /// Create a new BitPosition at the beginning of the byte array.
     fn new() -> Self {
@@ -87,72 +94,558 @@ impl ops::AddAssign for BitPosition {
     }
 }
 
+/// Counters describing how a bit stream has been used.
+/// Only available with the `stats` feature, so the hot paths stay free of bookkeeping otherwise.
+#[cfg(feature = "stats")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BitStats {
+    /// Total number of bits consumed by a reader or produced by a writer.
+    pub bits: u64,
+    /// Number of times a new byte was loaded from (or flushed to) the underlying buffer.
+    pub refills: u64,
+    /// Number of byte-alignment operations that actually skipped padding bits.
+    pub alignment_skips: u64,
+}
+
+/// Number of bits in the bit buffer of a `BitReader`.
+const BIT_BUF_LEN: usize = u64::BITS as usize;
+/// The bit buffer is refilled once it holds fewer bits than this, so that peeks of up to this
+/// many bits, which covers every DEFLATE field, are served from the buffer alone.
+const REFILL_THRESHOLD: usize = 32;
+
+/// A mask keeping the `n_bits` least significant bits of a u64.
+fn low_bits_mask(n_bits: usize) -> u64 {
+    if n_bits >= BIT_BUF_LEN {
+        u64::MAX
+    } else {
+        (1 << n_bits) - 1
+    }
+}
+
+/// A position of a [`BitReader`], to go back to with [`BitReader::reset_to`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BitMark {
+    bit_offset: usize,
+    eof: bool,
+}
+
+impl BitMark {
+    /// Get the number of bits consumed when the mark was taken.
+    pub fn bit_offset(&self) -> usize {
+        self.bit_offset
+    }
+}
+
 /// A struct that reads bits from a byte array.
+/// The upcoming bits are kept in a u64 bit buffer refilled 8 bytes at a time,
+/// so peeking and consuming bits are usually just a mask and a shift.
+///
+/// Peeking past the end of the data always gives zeros, so that a Huffman code near the end
+/// can be looked up with the longest code length. Consuming bits past the end gives zeros too,
+/// unless the reader is [strict](BitReader::new_strict).
+///
+/// The data can also be made of several slices read one after the other, as if concatenated,
+/// for data that arrives in pieces: see [`BitReader::from_chunks`].
 #[derive(Debug)]
 pub struct BitReader<'a> {
+    /// The chunk the next byte to load is in.
     data: &'a [u8],
-    position: BitPosition,
+    /// The first chunk of the data.
+    first: &'a [u8],
+    /// The chunks after the first one.
+    rest: &'a [&'a [u8]],
+    /// Index of `data` among the chunks, 0 for `first`.
+    chunk_index: usize,
+    /// Offset of `data` from the start of the data.
+    chunk_start: usize,
+    /// Number of bytes in all the chunks.
+    len: usize,
+    /// Whether consuming bits past the end of the data fails.
+    strict: bool,
+    /// Index of the next byte to load into the bit buffer, within `data`.
+    next_byte: usize,
+    /// Bits loaded from the data but not consumed yet, the next one in the least significant bit.
+    bit_buf: u64,
+    /// Number of bits in `bit_buf`.
+    bit_count: usize,
     eof: bool,
+    #[cfg(feature = "stats")]
+    stats: BitStats,
 }
 
 impl<'a> BitReader<'a> {
     /// Create a new BitReader with the given byte array.
     pub fn new(data: &'a [u8]) -> Self {
-        Self {
-            data,
-            position: BitPosition::new(),
+        Self::with_strictness(data, false)
+    }
+
+    /// Create a new BitReader with the given byte array, on which consuming bits past the end
+    /// of the data fails instead of reading zeros, so truncated data cannot go unnoticed.
+    pub fn new_strict(data: &'a [u8]) -> Self {
+        Self::with_strictness(data, true)
+    }
+
+    /// Create a new BitReader reading the given chunks one after the other, as if they were
+    /// a single byte array. Reads straddling two chunks work as within one, without copying
+    /// the chunks. Empty chunks are skipped.
+    pub fn from_chunks(chunks: &'a [&'a [u8]]) -> Self {
+        Self::chunks_with_strictness(chunks, false)
+    }
+
+    /// Create a new BitReader reading the given chunks one after the other, strict like
+    /// [`BitReader::new_strict`].
+    pub fn from_chunks_strict(chunks: &'a [&'a [u8]]) -> Self {
+        Self::chunks_with_strictness(chunks, true)
+    }
+
+    fn chunks_with_strictness(chunks: &'a [&'a [u8]], strict: bool) -> Self {
+        match chunks.split_first() {
+            Some((&first, rest)) => Self::with_chunks(first, rest, strict),
+            None => Self::with_chunks(&[], &[], strict),
+        }
+    }
+
+    fn with_strictness(data: &'a [u8], strict: bool) -> Self {
+        Self::with_chunks(data, &[], strict)
+    }
+
+    fn with_chunks(first: &'a [u8], rest: &'a [&'a [u8]], strict: bool) -> Self {
+        let mut reader = Self {
+            data: first,
+            first,
+            rest,
+            chunk_index: 0,
+            chunk_start: 0,
+            len: first.len() + rest.iter().map(|chunk| chunk.len()).sum::<usize>(),
+            strict,
+            next_byte: 0,
+            bit_buf: 0,
+            bit_count: 0,
             eof: false,
+            #[cfg(feature = "stats")]
+            stats: BitStats::default(),
+        };
+        reader.refill();
+        reader
+    }
+
+    /// Load as many whole bytes into the bit buffer as fit.
+    /// Away from the end of the data, this is a single load of 8 bytes.
+    fn refill(&mut self) {
+        let free_bytes = (BIT_BUF_LEN - self.bit_count) / BITS_PER_BYTE;
+        if free_bytes == 0 {
+            return;
+        }
+        if let Some(chunk) = self.data.get(self.next_byte..self.next_byte + 8) {
+            let word = u64::from_le_bytes(chunk.try_into().unwrap());
+            // Only the bytes that fit whole are taken; the others are loaded again next time.
+            self.bit_buf |= (word & low_bits_mask(free_bytes * BITS_PER_BYTE)) << self.bit_count;
+            self.next_byte += free_bytes;
+            self.bit_count += free_bytes * BITS_PER_BYTE;
+        } else {
+            // Near the end of a chunk: go on with the next one.
+            while self.bit_count <= BIT_BUF_LEN - BITS_PER_BYTE {
+                if self.next_byte < self.data.len() {
+                    self.bit_buf |= (self.data[self.next_byte] as u64) << self.bit_count;
+                    self.next_byte += 1;
+                    self.bit_count += BITS_PER_BYTE;
+                } else if let Some(&next) = self.rest.get(self.chunk_index) {
+                    self.chunk_start += self.data.len();
+                    self.chunk_index += 1;
+                    self.data = next;
+                    self.next_byte = 0;
+                } else {
+                    break;
+                }
+            }
         }
     }
 
+    /// Move the next byte to load to the given offset from the start of the data, emptying the
+    /// bit buffer. The offset must be within the data.
+    fn seek(&mut self, offset: usize) {
+        if offset < self.chunk_start {
+            self.data = self.first;
+            self.chunk_index = 0;
+            self.chunk_start = 0;
+        }
+        while offset >= self.chunk_start + self.data.len() && self.chunk_index < self.rest.len() {
+            self.chunk_start += self.data.len();
+            self.data = self.rest[self.chunk_index];
+            self.chunk_index += 1;
+        }
+        self.next_byte = offset - self.chunk_start;
+        self.bit_buf = 0;
+        self.bit_count = 0;
+    }
+
+    /// Iterate over the bytes that are not loaded into the bit buffer yet.
+    fn unloaded_bytes(&self) -> impl Iterator<Item = u8> + 'a {
+        let data: &'a [u8] = self.data;
+        data[self.next_byte..]
+            .iter()
+            .chain(self.rest[self.chunk_index..].iter().copied().flatten())
+            .copied()
+    }
+
     /// Check if the reader has reached the end of the data.
     pub fn eof(&self) -> bool {
         self.eof
     }
 
+    /// Get the number of bits consumed from the start of the data.
+    /// Reading past the end of the data does not count beyond its last bit.
+    pub fn bits_consumed(&self) -> usize {
+        (self.chunk_start + self.next_byte) * BITS_PER_BYTE - self.bit_count
+    }
+
+    /// Get the index of the byte holding the next bit, or the length of the data at its end.
+    pub fn byte_position(&self) -> usize {
+        self.bits_consumed() / BITS_PER_BYTE
+    }
+
+    /// Check if the next bit is the first bit of a byte.
+    pub fn is_byte_aligned(&self) -> bool {
+        self.bits_consumed().is_multiple_of(BITS_PER_BYTE)
+    }
+
+    /// Get the number of bits left to consume before the end of the data.
+    pub fn remaining_bits(&self) -> usize {
+        self.len * BITS_PER_BYTE - self.bits_consumed()
+    }
+
+    /// Get the number of whole bytes left after the current position.
+    pub(crate) fn bytes_remaining(&self) -> usize {
+        self.len
+            .saturating_sub(self.bits_consumed() / BITS_PER_BYTE)
+    }
+
+    /// Remember the current position, to decode speculatively and roll back on failure.
+    pub fn mark(&self) -> BitMark {
+        BitMark {
+            bit_offset: self.bits_consumed(),
+            eof: self.eof,
+        }
+    }
+
+    /// Go back, or forward, to a position taken by [`BitReader::mark`] on this reader.
+    /// The end of the data is only reached again if it had been when the mark was taken, so
+    /// rolling back after reading past the end clears [`BitReader::eof`].
+    /// The usage counters are left alone.
+    pub fn reset_to(&mut self, mark: BitMark) {
+        assert!(
+            mark.bit_offset <= self.len * BITS_PER_BYTE,
+            "mark taken on another reader"
+        );
+        self.seek(mark.bit_offset / BITS_PER_BYTE);
+        self.refill();
+        // At the end of the data, nothing is loaded and the offset is a whole number of bytes.
+        self.bit_buf >>= mark.bit_offset % BITS_PER_BYTE;
+        self.bit_count -= mark.bit_offset % BITS_PER_BYTE;
+        self.eof = mark.eof;
+    }
+
+    /// Get the usage counters collected so far.
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> BitStats {
+        self.stats
+    }
+
+    /// Reset the usage counters to zero.
+    #[cfg(feature = "stats")]
+    pub fn reset_stats(&mut self) {
+        self.stats = BitStats::default();
+    }
+
     /// Peek bits with given bit length without advancing the position.
-    ///
+    /// Bits past the end of the data read as zeros.
     pub fn try_peek_bits(&self, n_bits: usize) -> Option<usize> {
-        if n_bits == 0 {
-            return Some(0);
-        }
         // We can't read more than usize::BITS bits at once.
         if n_bits > usize::BITS as usize {
             return None;
         }
+        if n_bits <= self.bit_count {
+            return Some((self.bit_buf & low_bits_mask(n_bits)) as usize);
+        }
+
+        // The buffer only runs short near the end of the data, or for the widest reads.
+        let mut result = self.bit_buf;
+        let mut n_loaded = self.bit_count;
+        let mut bytes = self.unloaded_bytes();
+        while n_loaded < n_bits {
+            let Some(byte) = bytes.next() else {
+                break;
+            };
+            result |= (byte as u64) << n_loaded;
+            n_loaded += BITS_PER_BYTE;
+        }
+        Some((result & low_bits_mask(n_bits)) as usize)
+    }
+
+    /// Try to advance the position by the given number of bits.
+    /// On a strict reader, this fails when there are fewer bits left, and the reader is left
+    /// at the end of the data.
+    pub fn try_advance(&mut self, n_bits: usize) -> Option<()> {
+        if self.eof {
+            return (!self.strict || n_bits == 0).then_some(());
+        }
+        // The common case: the bits are in the buffer, so the end of the data is not reached.
+        if n_bits < self.bit_count {
+            #[cfg(feature = "stats")]
+            {
+                let consumed = self.bits_consumed();
+                self.stats.bits += n_bits as u64;
+                self.stats.refills +=
+                    ((consumed + n_bits) / BITS_PER_BYTE - consumed / BITS_PER_BYTE) as u64;
+            }
+            self.bit_buf >>= n_bits;
+            self.bit_count -= n_bits;
+            if self.bit_count < REFILL_THRESHOLD {
+                self.refill();
+            }
+            return Some(());
+        }
+
+        let consumed = self.bits_consumed();
+        let target = consumed.checked_add(n_bits)?;
+        #[cfg(feature = "stats")]
+        {
+            let end = (target / BITS_PER_BYTE).min(self.len);
+            self.stats.bits += n_bits as u64;
+            self.stats.refills += end.saturating_sub(consumed / BITS_PER_BYTE) as u64;
+        }
+        if target / BITS_PER_BYTE >= self.len {
+            self.eof = true;
+            self.seek(self.len);
+            if self.strict && target > self.len * BITS_PER_BYTE {
+                return None;
+            }
+        } else {
+            // Skip past the buffered bits, then drop the first bits of the new byte.
+            self.seek(target / BITS_PER_BYTE);
+            self.refill();
+            self.bit_buf >>= target % BITS_PER_BYTE;
+            self.bit_count -= target % BITS_PER_BYTE;
+        }
+        Some(())
+    }
+
+    /// Advance the position by the given number of bits.
+    pub fn advance(&mut self, n_bits: usize) {
+        self.try_advance(n_bits).unwrap();
+    }
+
+    /// Try to advance the position to the next byte boundary.
+    pub fn try_advance_to_byte_boundary(&mut self) -> Option<()> {
+        let bit_index = self.bits_consumed() % BITS_PER_BYTE;
+        if bit_index == 0 {
+            return Some(());
+        }
+        #[cfg(feature = "stats")]
+        {
+            self.stats.alignment_skips += 1;
+        }
+        self.try_advance(BITS_PER_BYTE - bit_index)
+    }
+
+    /// Advance the position to the next byte boundary.
+    pub fn advance_to_byte_boundary(&mut self) {
+        self.try_advance_to_byte_boundary().unwrap();
+    }
+
+    /// Try to read the given number of bits and advance the position.
+    pub fn try_read_bits(&mut self, bits: usize) -> Option<usize> {
+        let result = self.try_peek_bits(bits)?;
+        self.try_advance(bits)?;
+        Some(result)
+    }
 
-        let mut result = 0;
-        let mut n_bits_rem = n_bits;
+    /// Peek bits with given bit length without advancing the position.
+    pub fn peek_bits(&self, bits: usize) -> usize {
+        self.try_peek_bits(bits).unwrap()
+    }
+
+    /// Read the given number of bits and advance the position.
+    pub fn read_bits(&mut self, bits: usize) -> usize {
+        self.try_read_bits(bits).unwrap()
+    }
+
+    /// Try to read a byte and advance the position.
+    pub fn try_read_byte(&mut self) -> Option<u8> {
+        let byte = self.try_read_bits(BITS_PER_BYTE)?;
+        Some(byte as u8)
+    }
 
-        let mut cur_pos = self.position;
+    /// Read a byte and advance the position.
+    pub fn read_byte(&mut self) -> u8 {
+        self.try_read_byte().unwrap()
+    }
+
+    /// Try to read the given number of bytes, at most the length of the buffer, into the buffer.
+    /// Return the number of bytes that were available in the data; the others read as zeros,
+    /// unless the reader is strict, on which reading past the end fails.
+    /// On a byte boundary, the bytes are copied straight from the data instead of bit by bit.
+    pub fn try_read_bytes_to_slice(&mut self, n_bytes: usize, buf: &mut [u8]) -> Option<usize> {
+        let n_bytes = n_bytes.min(buf.len());
+        let buf = &mut buf[..n_bytes];
+        let available = n_bytes.min(self.remaining_bits() / BITS_PER_BYTE);
+        if !self.is_byte_aligned() {
+            for byte in buf.iter_mut() {
+                *byte = self.try_read_byte()?;
+            }
+            return Some(available);
+        }
 
-        while n_bits_rem > 0 {
-            // If we reach the end of the data, set the eof flag and return the result.
-            if cur_pos.byte_index >= self.data.len() {
-                return Some(result);
+        // The whole bytes in the bit buffer come first, then the bytes not loaded yet.
+        let mut bit_buf = self.bit_buf;
+        let buffered = (self.bit_count / BITS_PER_BYTE).min(n_bytes);
+        for byte in &mut buf[..buffered] {
+            *byte = bit_buf as u8;
+            bit_buf >>= BITS_PER_BYTE;
+        }
+        let mut copied = buffered;
+        let data: &'a [u8] = self.data;
+        let chunks = core::iter::once(&data[self.next_byte..])
+            .chain(self.rest[self.chunk_index..].iter().copied());
+        for chunk in chunks {
+            if copied == available {
+                break;
             }
+            let n = chunk.len().min(available - copied);
+            buf[copied..copied + n].copy_from_slice(&chunk[..n]);
+            copied += n;
+        }
+        buf[copied..].fill(0);
+        self.try_advance(n_bytes * BITS_PER_BYTE)?;
+        Some(available)
+    }
+
+    /// Read the given number of bytes, at most the length of the buffer, into the buffer.
+    /// Return the number of bytes that were available in the data.
+    pub fn read_bytes_to_slice(&mut self, n_bytes: usize, buf: &mut [u8]) -> usize {
+        self.try_read_bytes_to_slice(n_bytes, buf).unwrap()
+    }
+
+    /// Try to read a little-endian u16 starting on a byte boundary.
+    /// Fails if the reader is not byte-aligned, or on a strict reader, past the end.
+    pub fn try_read_u16_le(&mut self) -> Option<u16> {
+        if !self.is_byte_aligned() {
+            return None;
+        }
+        // LSB-first bits of little-endian bytes are the word itself.
+        self.try_read_bits(16).map(|word| word as u16)
+    }
 
-            // Current byte to read bits from.
-            let cur_byte = self.data[cur_pos.byte_index];
-            // Number of bits to read from the current byte.
-            // Max is the remaining bits in the current byte or the bits to read.
-            let bits_to_read = n_bits_rem.min(BITS_PER_BYTE - cur_pos.bit_index);
+    /// Read a little-endian u16 starting on a byte boundary.
+    pub fn read_u16_le(&mut self) -> u16 {
+        self.try_read_u16_le().unwrap()
+    }
 
-            // Create a mask to extract the bits to read.
-            // For example, if bits_to_read is 3, the mask will be 0b00000111.
-            let mask = (1usize << bits_to_read) - 1;
+    /// Try to read a little-endian u32 starting on a byte boundary.
+    /// Fails if the reader is not byte-aligned, or on a strict reader, past the end.
+    pub fn try_read_u32_le(&mut self) -> Option<u32> {
+        if !self.is_byte_aligned() {
+            return None;
+        }
+        self.try_read_bits(32).map(|word| word as u32)
+    }
 
-            // Extract the bits from the current byte.
-            let read_bits = (cur_byte as usize >> cur_pos.bit_index) & mask;
+    /// Read a little-endian u32 starting on a byte boundary.
+    pub fn read_u32_le(&mut self) -> u32 {
+        self.try_read_u32_le().unwrap()
+    }
+}
+
+/// A struct that reads bits from a byte array in MSB-first order, as used by formats like JPEG
+/// and bzip2: each byte is consumed from its most significant bit down, and the first bit read
+/// becomes the most significant bit of the value.
+/// Apart from the bit order, it works like `BitReader`, including the zeros past the end.
+#[derive(Debug)]
+pub struct BitReaderMsb<'a> {
+    data: &'a [u8],
+    /// Index of the next byte to load into the bit buffer.
+    next_byte: usize,
+    /// Bits loaded from the data but not consumed yet, the next one in the most significant bit.
+    bit_buf: u64,
+    /// Number of bits in `bit_buf`.
+    bit_count: usize,
+    eof: bool,
+}
 
-            // Shift the bits to the correct position in the result.
-            result |= read_bits << (n_bits - n_bits_rem);
+impl<'a> BitReaderMsb<'a> {
+    /// Create a new BitReaderMsb with the given byte array.
+    pub fn new(data: &'a [u8]) -> Self {
+        let mut reader = Self {
+            data,
+            next_byte: 0,
+            bit_buf: 0,
+            bit_count: 0,
+            eof: false,
+        };
+        reader.refill();
+        reader
+    }
 
-            // Update the remaining bits and the current bit index.
-            n_bits_rem -= bits_to_read;
-            cur_pos = cur_pos.add_bits(bits_to_read);
+    /// Load as many whole bytes into the bit buffer as fit.
+    fn refill(&mut self) {
+        let free_bytes = (BIT_BUF_LEN - self.bit_count) / BITS_PER_BYTE;
+        if free_bytes == 0 {
+            return;
         }
-        Some(result)
+        if let Some(chunk) = self.data.get(self.next_byte..self.next_byte + 8) {
+            let word = u64::from_be_bytes(chunk.try_into().unwrap());
+            let n_loaded = free_bytes * BITS_PER_BYTE;
+            // Only the bytes that fit whole are taken; the others are loaded again next time.
+            self.bit_buf |=
+                (word >> (BIT_BUF_LEN - n_loaded)) << (BIT_BUF_LEN - self.bit_count - n_loaded);
+            self.next_byte += free_bytes;
+            self.bit_count += n_loaded;
+        } else {
+            while self.bit_count <= BIT_BUF_LEN - BITS_PER_BYTE && self.next_byte < self.data.len()
+            {
+                self.bit_buf |= (self.data[self.next_byte] as u64)
+                    << (BIT_BUF_LEN - BITS_PER_BYTE - self.bit_count);
+                self.next_byte += 1;
+                self.bit_count += BITS_PER_BYTE;
+            }
+        }
+    }
+
+    /// Check if the reader has reached the end of the data.
+    pub fn eof(&self) -> bool {
+        self.eof
+    }
+
+    /// Get the number of bits consumed from the start of the data.
+    pub fn bits_consumed(&self) -> usize {
+        self.next_byte * BITS_PER_BYTE - self.bit_count
+    }
+
+    /// Peek bits with given bit length without advancing the position.
+    /// Bits past the end of the data read as zeros.
+    pub fn try_peek_bits(&self, n_bits: usize) -> Option<usize> {
+        // We can't read more than usize::BITS bits at once.
+        if n_bits > usize::BITS as usize {
+            return None;
+        }
+        if n_bits == 0 {
+            return Some(0);
+        }
+        if n_bits <= self.bit_count {
+            return Some((self.bit_buf >> (BIT_BUF_LEN - n_bits)) as usize);
+        }
+
+        // Near the end of the data, or for the widest reads, append the bytes after the buffer.
+        let mut result = (self.bit_buf as u128) << BIT_BUF_LEN;
+        let mut n_loaded = self.bit_count;
+        let mut byte_index = self.next_byte;
+        while n_loaded < n_bits && byte_index < self.data.len() {
+            result |=
+                (self.data[byte_index] as u128) << (2 * BIT_BUF_LEN - BITS_PER_BYTE - n_loaded);
+            n_loaded += BITS_PER_BYTE;
+            byte_index += 1;
+        }
+        Some((result >> (2 * BIT_BUF_LEN - n_bits)) as usize)
     }
 
     /// Try to advance the position by the given number of bits.
@@ -160,16 +653,27 @@ impl<'a> BitReader<'a> {
         if self.eof {
             return Some(());
         }
+        if n_bits < self.bit_count {
+            self.bit_buf <<= n_bits;
+            self.bit_count -= n_bits;
+            if self.bit_count < REFILL_THRESHOLD {
+                self.refill();
+            }
+            return Some(());
+        }
 
-        let new_pos = self.position.try_add_bits(n_bits)?;
-        if new_pos.byte_index >= self.data.len() {
+        let target = self.bits_consumed().checked_add(n_bits)?;
+        self.bit_buf = 0;
+        self.bit_count = 0;
+        if target / BITS_PER_BYTE >= self.data.len() {
             self.eof = true;
-            self.position = BitPosition {
-                byte_index: self.data.len(),
-                bit_index: 0,
-            };
+            self.next_byte = self.data.len();
         } else {
-            self.position = new_pos;
+            // Skip past the buffered bits, then drop the first bits of the new byte.
+            self.next_byte = target / BITS_PER_BYTE;
+            self.refill();
+            self.bit_buf <<= target % BITS_PER_BYTE;
+            self.bit_count -= target % BITS_PER_BYTE;
         }
         Some(())
     }
@@ -181,12 +685,11 @@ impl<'a> BitReader<'a> {
 
     /// Try to advance the position to the next byte boundary.
     pub fn try_advance_to_byte_boundary(&mut self) -> Option<()> {
-        if self.position.bit_index == 0 {
+        let bit_index = self.bits_consumed() % BITS_PER_BYTE;
+        if bit_index == 0 {
             return Some(());
         }
-        let bits_to_boundary = BITS_PER_BYTE - self.position.bit_index;
-        self.try_advance(bits_to_boundary)?;
-        Some(())
+        self.try_advance(BITS_PER_BYTE - bit_index)
     }
 
     /// Advance the position to the next byte boundary.
@@ -221,20 +724,233 @@ impl<'a> BitReader<'a> {
     pub fn read_byte(&mut self) -> u8 {
         self.try_read_byte().unwrap()
     }
+}
 
-    /// Try to read the given number of bytes and fill the buffer.
-    /// Return the number of bytes read.
-    pub fn try_read_bytes_to_slice(&mut self, n_bytes: usize, buf: &mut [u8]) -> Option<usize> {
-        for byte in buf.iter_mut().take(n_bytes) {
-            *byte = self.try_read_byte()?;
+/// Number of bytes read from the underlying reader of a `StreamBitReader` at a time.
+#[cfg(feature = "std")]
+const REFILL_LEN: usize = 4096;
+
+/// A struct that reads bits from any `Read` source, like `BitReader` does from a byte array.
+/// Bytes are pulled into an internal buffer as the bits are needed, and dropped once they are
+/// behind the position, so the whole input never has to be in memory.
+/// As with `BitReader`, bits past the end of the data read as zeros.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct StreamBitReader<R: Read> {
+    reader: R,
+    /// Bytes read from the reader and not dropped yet.
+    buf: Vec<u8>,
+    /// Position within `buf`.
+    position: BitPosition,
+    /// Number of bytes dropped from the front of `buf`.
+    dropped: usize,
+    reader_eof: bool,
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> StreamBitReader<R> {
+    /// Create a new StreamBitReader pulling bytes from the given reader.
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buf: Vec::new(),
+            position: BitPosition::new(),
+            dropped: 0,
+            reader_eof: false,
         }
-        Some(n_bytes.min(buf.len()))
     }
 
-    /// Read the given number of bytes and fill the buffer.
-    /// Return the number of bytes read.
-    pub fn read_bytes_to_slice(&mut self, n_bytes: usize, buf: &mut [u8]) -> usize {
-        self.try_read_bytes_to_slice(n_bytes, buf).unwrap()
+    /// Make sure the `n_bits` bits after the position are buffered, unless the reader ends first.
+    fn fill(&mut self, n_bits: usize) -> io::Result<()> {
+        let end = self.position.bit_index + n_bits;
+        let needed = self.position.byte_index + end.div_ceil(BITS_PER_BYTE);
+        if needed <= self.buf.len() || self.reader_eof {
+            return Ok(());
+        }
+        // Drop the bytes behind the position before the buffer grows.
+        if self.position.byte_index >= REFILL_LEN {
+            let byte_index = self.position.byte_index;
+            self.buf.drain(..byte_index);
+            self.dropped += byte_index;
+            self.position.byte_index = 0;
+        }
+        let needed = self.position.byte_index + end.div_ceil(BITS_PER_BYTE);
+        while self.buf.len() < needed {
+            let start = self.buf.len();
+            self.buf.resize(start + REFILL_LEN.max(needed - start), 0);
+            let result = self.reader.read(&mut self.buf[start..]);
+            self.buf.truncate(start + *result.as_ref().unwrap_or(&0));
+            match result {
+                Ok(0) => {
+                    self.reader_eof = true;
+                    break;
+                }
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// A `BitReader` over the buffered bytes, at the current position.
+    fn view(&self) -> BitReader<'_> {
+        let mut view = BitReader::new(&self.buf[self.position.byte_index.min(self.buf.len())..]);
+        view.advance(self.position.bit_index);
+        view
+    }
+
+    /// Check if the reader has reached the end of the data.
+    pub fn eof(&mut self) -> io::Result<bool> {
+        self.fill(1)?;
+        Ok(self.position.byte_index >= self.buf.len())
+    }
+
+    /// Get the number of bits consumed from the start of the data.
+    pub fn bits_consumed(&self) -> usize {
+        (self.dropped + self.position.byte_index) * BITS_PER_BYTE + self.position.bit_index
+    }
+
+    /// Peek bits with given bit length without advancing the position.
+    pub fn peek_bits(&mut self, n_bits: usize) -> io::Result<usize> {
+        self.fill(n_bits)?;
+        Ok(self.view().peek_bits(n_bits))
+    }
+
+    /// Advance the position by the given number of bits, stopping at the end of the data.
+    pub fn advance(&mut self, n_bits: usize) -> io::Result<()> {
+        self.fill(n_bits)?;
+        let new_pos = self.position.add_bits(n_bits);
+        self.position = if new_pos.byte_index >= self.buf.len() {
+            BitPosition::from(self.buf.len() * BITS_PER_BYTE)
+        } else {
+            new_pos
+        };
+        Ok(())
+    }
+
+    /// Advance the position to the next byte boundary.
+    pub fn advance_to_byte_boundary(&mut self) {
+        if self.position.bit_index != 0 {
+            self.position = BitPosition {
+                byte_index: self.position.byte_index + 1,
+                bit_index: 0,
+            };
+        }
+    }
+
+    /// Read the given number of bits and advance the position.
+    pub fn read_bits(&mut self, n_bits: usize) -> io::Result<usize> {
+        let bits = self.peek_bits(n_bits)?;
+        self.advance(n_bits)?;
+        Ok(bits)
+    }
+
+    /// Go to the next byte boundary, then read bytes to fill the buffer.
+    /// Return the number of bytes read, less than the buffer length only at the end of the data.
+    pub fn read_bytes_to_slice(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.advance_to_byte_boundary();
+        self.fill(buf.len() * BITS_PER_BYTE)?;
+        let start = self.position.byte_index.min(self.buf.len());
+        let n = buf.len().min(self.buf.len() - start);
+        buf[..n].copy_from_slice(&self.buf[start..start + n]);
+        self.position.byte_index = start + n;
+        Ok(n)
+    }
+}
+
+/// A struct that writes bits into a byte vector, the mirror image of `BitReader`.
+/// Bits are packed starting from the least significant bit of each byte, as DEFLATE does.
+#[derive(Debug, Default)]
+pub struct BitWriter {
+    bytes: Vec<u8>,
+    /// Number of bits used in the last byte, 0 meaning it is full.
+    bit_index: usize,
+    #[cfg(feature = "stats")]
+    stats: BitStats,
+}
+
+impl BitWriter {
+    /// Create a new empty BitWriter.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the number of bits written so far.
+    pub fn bits_written(&self) -> usize {
+        match self.bit_index {
+            0 => self.bytes.len() * BITS_PER_BYTE,
+            n => (self.bytes.len() - 1) * BITS_PER_BYTE + n,
+        }
+    }
+
+    /// Get the usage counters collected so far.
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> BitStats {
+        self.stats
+    }
+
+    /// Reset the usage counters to zero.
+    #[cfg(feature = "stats")]
+    pub fn reset_stats(&mut self) {
+        self.stats = BitStats::default();
+    }
+
+    /// Write the `n_bits` least significant bits of `value`, least significant first.
+    pub fn write_bits(&mut self, value: usize, n_bits: usize) {
+        assert!(n_bits <= usize::BITS as usize);
+        #[cfg(feature = "stats")]
+        {
+            self.stats.bits += n_bits as u64;
+        }
+        for i in 0..n_bits {
+            if self.bit_index == 0 {
+                self.bytes.push(0);
+                #[cfg(feature = "stats")]
+                {
+                    self.stats.refills += 1;
+                }
+            }
+            *self.bytes.last_mut().unwrap() |= (((value >> i) & 1) as u8) << self.bit_index;
+            self.bit_index = (self.bit_index + 1) % BITS_PER_BYTE;
+        }
+    }
+
+    /// Pad the last byte with zeros up to the next byte boundary.
+    pub fn align_to_byte(&mut self) {
+        if self.bit_index == 0 {
+            return;
+        }
+        #[cfg(feature = "stats")]
+        {
+            self.stats.bits += (BITS_PER_BYTE - self.bit_index) as u64;
+            self.stats.alignment_skips += 1;
+        }
+        self.bit_index = 0;
+    }
+
+    /// Align to the next byte boundary, then write the bytes as they are.
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.align_to_byte();
+        #[cfg(feature = "stats")]
+        {
+            self.stats.bits += (bytes.len() * BITS_PER_BYTE) as u64;
+            self.stats.refills += bytes.len() as u64;
+        }
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    /// Write all the bits of another writer after the ones written so far, at any bit offset.
+    pub fn append(&mut self, other: &BitWriter) {
+        let bits = other.bits_written();
+        for (i, &byte) in other.bytes.iter().enumerate() {
+            self.write_bits(byte as usize, (bits - i * BITS_PER_BYTE).min(BITS_PER_BYTE));
+        }
+    }
+
+    /// Get the bytes written, the last one padded with zeros.
+    pub fn into_vec(self) -> Vec<u8> {
+        self.bytes
     }
 }
 
@@ -279,8 +995,7 @@ mod tests {
     fn test_bit_reader_new() {
         let data = [0xFF, 0x00];
         let reader = BitReader::new(&data);
-        assert_eq!(reader.position.byte_index, 0);
-        assert_eq!(reader.position.bit_index, 0);
+        assert_eq!(reader.bits_consumed(), 0);
         assert!(!reader.eof());
     }
 
@@ -324,6 +1039,159 @@ mod tests {
         assert!(reader.eof());
     }
 
+    #[test]
+    fn test_bit_reader_position() {
+        let data = [0b10101100, 0xff, 0x00];
+        let mut reader = BitReader::new(&data);
+        assert_eq!((reader.bits_consumed(), reader.byte_position()), (0, 0));
+        assert!(reader.is_byte_aligned());
+        assert_eq!(reader.remaining_bits(), 24);
+
+        reader.advance(11);
+        assert_eq!((reader.bits_consumed(), reader.byte_position()), (11, 1));
+        assert!(!reader.is_byte_aligned());
+        assert_eq!(reader.remaining_bits(), 13);
+
+        reader.advance_to_byte_boundary();
+        assert_eq!((reader.bits_consumed(), reader.byte_position()), (16, 2));
+        assert!(reader.is_byte_aligned());
+
+        // Reading past the end stops counting at the end of the data.
+        let _ = reader.read_bits(12);
+        assert_eq!((reader.bits_consumed(), reader.byte_position()), (24, 3));
+        assert_eq!(reader.remaining_bits(), 0);
+    }
+
+    #[test]
+    fn test_bit_reader_chunks() {
+        let data: Vec<u8> = (0..300u32).map(|i| (i * 73 % 256) as u8).collect();
+        // Chunks of 0 to 12 bytes, so reads straddle several of them and skip empty ones.
+        let mut chunks = Vec::new();
+        let mut rest = &data[..];
+        for len in (0..).map(|i| i * 5 % 13) {
+            if rest.is_empty() {
+                break;
+            }
+            let (chunk, tail) = rest.split_at(len.min(rest.len()));
+            chunks.push(chunk);
+            rest = tail;
+        }
+        let mut contiguous = BitReader::new_strict(&data);
+        let mut chunked = BitReader::from_chunks_strict(&chunks);
+        let mut mark = chunked.mark();
+        for (i, n_bits) in (0..200).map(|i| (i, i * 7 % 65)).filter(|&(_, n)| n <= 64) {
+            assert_eq!(
+                chunked.try_peek_bits(n_bits),
+                contiguous.try_peek_bits(n_bits)
+            );
+            assert_eq!(
+                chunked.try_read_bits(n_bits),
+                contiguous.try_read_bits(n_bits)
+            );
+            assert_eq!(chunked.bits_consumed(), contiguous.bits_consumed());
+            assert_eq!(chunked.eof(), contiguous.eof());
+            if i == 40 {
+                mark = chunked.mark();
+            }
+        }
+        assert!(chunked.eof());
+
+        // Jumps land in the right chunk, backwards and forwards.
+        let offset = mark.bit_offset();
+        chunked.reset_to(mark);
+        contiguous.reset_to(mark);
+        assert_eq!(chunked.read_bits(64), contiguous.read_bits(64));
+        chunked.advance(1000);
+        contiguous.advance(1000);
+        assert_eq!(chunked.read_bits(33), contiguous.read_bits(33));
+        assert_eq!(chunked.bits_consumed(), offset + 1097);
+        assert_eq!(chunked.remaining_bits(), data.len() * 8 - offset - 1097);
+
+        let mut empty = BitReader::from_chunks_strict(&[]);
+        assert_eq!(empty.try_read_bits(1), None);
+    }
+
+    #[test]
+    fn test_bit_reader_read_bytes_to_slice() {
+        let data: Vec<u8> = (0..300u32).map(|i| (i * 73 % 256) as u8).collect();
+        let chunks: Vec<&[u8]> = data.chunks(7).collect();
+        for mut reader in [BitReader::new(&data), BitReader::from_chunks(&chunks)] {
+            let mut buf = [0xff; 40];
+            // Unaligned, bit by bit.
+            reader.advance(4);
+            assert_eq!(reader.read_bytes_to_slice(2, &mut buf), 2);
+            assert_eq!(
+                buf[..2],
+                [data[0] >> 4 | data[1] << 4, data[1] >> 4 | data[2] << 4]
+            );
+            // Aligned, partly from the bit buffer and partly straight from the data.
+            reader.advance(4);
+            assert_eq!(reader.read_bytes_to_slice(100, &mut buf), 40);
+            assert_eq!(buf, data[3..43]);
+            assert_eq!(reader.bits_consumed(), 43 * 8);
+            assert_eq!(reader.read_bits(8), data[43] as usize);
+            // Past the end, the missing bytes read as zeros.
+            reader.advance((300 - 44 - 10) * 8);
+            assert_eq!(reader.read_bytes_to_slice(40, &mut buf), 10);
+            assert_eq!(buf[..10], data[290..]);
+            assert!(buf[10..].iter().all(|&byte| byte == 0));
+            assert!(reader.eof());
+            assert_eq!(reader.read_bytes_to_slice(40, &mut buf), 0);
+        }
+
+        let mut reader = BitReader::new_strict(&data);
+        reader.advance(296 * 8);
+        let mut buf = [0; 8];
+        assert_eq!(reader.try_read_bytes_to_slice(8, &mut buf), None);
+        assert!(reader.eof());
+    }
+
+    #[test]
+    fn test_bit_reader_read_le() {
+        let data = [0x34, 0x12, 0x78, 0x56, 0x34, 0x12, 0xff];
+        let mut reader = BitReader::new_strict(&data);
+        assert_eq!(reader.read_u16_le(), 0x1234);
+        assert_eq!(reader.read_u32_le(), 0x12345678);
+        assert_eq!(reader.try_read_u16_le(), None);
+        assert!(reader.eof());
+
+        let mut reader = BitReader::new(&data);
+        reader.advance(4);
+        assert_eq!(reader.try_read_u16_le(), None);
+        assert_eq!(reader.bits_consumed(), 4);
+        reader.advance_to_byte_boundary();
+        assert_eq!(reader.read_u32_le(), 0x34567812);
+        // Past the end, the missing bytes are zeros.
+        assert_eq!(reader.read_u16_le(), 0xff12);
+        assert_eq!(reader.read_u16_le(), 0);
+    }
+
+    #[test]
+    fn test_bit_reader_mark() {
+        let data = [0b10101100, 0b0101_0011, 0xa5];
+        let mut reader = BitReader::new_strict(&data);
+        reader.advance(3);
+        let mark = reader.mark();
+        assert_eq!(mark.bit_offset(), 3);
+        let first = reader.read_bits(13);
+        assert_eq!(reader.try_read_bits(9), None);
+        assert!(reader.eof());
+
+        reader.reset_to(mark);
+        assert!(!reader.eof());
+        assert_eq!(reader.bits_consumed(), 3);
+        assert_eq!(reader.read_bits(13), first);
+        assert_eq!(reader.read_bits(8), 0xa5);
+
+        // A mark taken at the end keeps the reader there.
+        let _ = reader.try_read_bits(1);
+        let end = reader.mark();
+        reader.reset_to(mark);
+        reader.reset_to(end);
+        assert!(reader.eof());
+        assert_eq!(reader.remaining_bits(), 0);
+    }
+
     #[test]
     fn test_bit_reader_peek_bits_not_enough_data() {
         let data = [0b10101100];
@@ -341,6 +1209,218 @@ mod tests {
         assert!(reader.eof());
     }
 
+    #[test]
+    fn test_bit_reader_matches_bit_by_bit() {
+        let data: Vec<u8> = (0..300u32).map(|i| (i * 73 % 256) as u8).collect();
+        // The bit at a position, read one byte at a time.
+        let bit_at = |pos: usize| {
+            data.get(pos / 8)
+                .map_or(0, |b| (*b as usize >> (pos % 8)) & 1)
+        };
+        let mut reader = BitReader::new(&data);
+        let mut pos = 0;
+        for n_bits in (0..200).map(|i| i * 7 % 65) {
+            let expected = (0..n_bits).fold(0, |acc, i| acc | (bit_at(pos + i) << i));
+            assert_eq!(
+                reader.peek_bits(n_bits),
+                expected,
+                "{} bits at {}",
+                n_bits,
+                pos
+            );
+            reader.advance(n_bits);
+            pos = (pos + n_bits).min(data.len() * 8);
+            assert_eq!(reader.bits_consumed(), pos);
+        }
+        assert!(reader.eof());
+    }
+
+    #[test]
+    fn test_bit_reader_msb_read_bits() {
+        let data = [0b1010_0110, 0b1100_0011];
+        let mut reader = BitReaderMsb::new(&data);
+        assert_eq!(reader.read_bits(3), 0b101);
+        assert_eq!(reader.read_bits(7), 0b001_1011);
+        reader.advance_to_byte_boundary();
+        assert_eq!(reader.read_byte(), 0);
+        assert!(reader.eof());
+    }
+
+    #[test]
+    fn test_bit_reader_msb_matches_bit_by_bit() {
+        let data: Vec<u8> = (0..300u32).map(|i| (i * 73 % 256) as u8).collect();
+        let bit_at = |pos: usize| {
+            data.get(pos / 8)
+                .map_or(0, |b| (*b as usize >> (7 - pos % 8)) & 1)
+        };
+        let mut reader = BitReaderMsb::new(&data);
+        let mut pos = 0;
+        for n_bits in (0..200).map(|i| i * 7 % 65) {
+            let expected = (0..n_bits).fold(0, |acc, i| (acc << 1) | bit_at(pos + i));
+            assert_eq!(
+                reader.peek_bits(n_bits),
+                expected,
+                "{} bits at {}",
+                n_bits,
+                pos
+            );
+            reader.advance(n_bits);
+            pos = (pos + n_bits).min(data.len() * 8);
+            assert_eq!(reader.bits_consumed(), pos);
+        }
+        assert!(reader.eof());
+    }
+
+    #[test]
+    fn test_bit_reader_strict() {
+        let data = [0b1010_1010, 0xff];
+        let mut reader = BitReader::new_strict(&data);
+        assert_eq!(reader.try_read_bits(12), Some(0xfaa));
+        // Peeking still pads with zeros.
+        assert_eq!(reader.try_peek_bits(8), Some(0x0f));
+        assert_eq!(reader.try_read_bits(5), None);
+        assert!(reader.eof());
+        assert_eq!(reader.bits_consumed(), 16);
+        assert_eq!(reader.try_read_bits(1), None);
+        assert_eq!(reader.try_read_bits(0), Some(0));
+
+        let mut reader = BitReader::new_strict(&data);
+        assert_eq!(reader.try_read_bits(16), Some(0xffaa));
+        assert!(reader.eof());
+        let mut reader = BitReader::new(&data);
+        assert_eq!(reader.try_read_bits(17), Some(0xffaa));
+    }
+
+    /// A reader handing out its data a few bytes at a time.
+    #[cfg(feature = "std")]
+    struct Trickle<'a>(&'a [u8]);
+
+    #[cfg(feature = "std")]
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = buf.len().min(self.0.len()).min(3);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_stream_bit_reader() {
+        let data: Vec<u8> = (0..50_000u32).map(|i| (i * 31 % 256) as u8).collect();
+        let mut reader = BitReader::new(&data);
+        let mut stream_reader = StreamBitReader::new(Trickle(&data));
+        for n_bits in (0..30_000).map(|i| i % 17) {
+            assert_eq!(
+                stream_reader.read_bits(n_bits).unwrap(),
+                reader.read_bits(n_bits)
+            );
+            assert_eq!(stream_reader.bits_consumed(), reader.bits_consumed());
+        }
+        // Bytes behind the position were dropped along the way.
+        assert!(stream_reader.dropped > 0);
+        assert!(stream_reader.buf.len() < 2 * REFILL_LEN);
+
+        stream_reader.advance_to_byte_boundary();
+        reader.advance_to_byte_boundary();
+        let mut expected = [0; 100];
+        reader.read_bytes_to_slice(100, &mut expected);
+        let mut buf = [0; 100];
+        assert_eq!(stream_reader.read_bytes_to_slice(&mut buf).unwrap(), 100);
+        assert_eq!(buf, expected);
+        assert!(!stream_reader.eof().unwrap());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_stream_bit_reader_end() {
+        let mut reader = StreamBitReader::new(Trickle(&[0b10101100, 0b01010101]));
+        assert_eq!(reader.read_bits(12).unwrap(), 0b010110101100);
+        assert!(!reader.eof().unwrap());
+        // Bits past the end read as zeros.
+        assert_eq!(reader.read_bits(8).unwrap(), 0b0101);
+        assert!(reader.eof().unwrap());
+        let mut buf = [0; 4];
+        assert_eq!(reader.read_bytes_to_slice(&mut buf).unwrap(), 0);
+        assert_eq!(reader.bits_consumed(), 16);
+    }
+
+    #[test]
+    fn test_bit_writer() {
+        let mut writer = BitWriter::new();
+        writer.write_bits(0b1100, 4);
+        writer.write_bits(0b1010, 4);
+        writer.write_bits(0b0101, 3);
+        assert_eq!(writer.bits_written(), 11);
+        writer.align_to_byte();
+        assert_eq!(writer.bits_written(), 16);
+        writer.write_bits(0b1, 1);
+        writer.write_bytes(&[0xAB, 0xCD]);
+        let data = writer.into_vec();
+        assert_eq!(data, [0b10101100, 0b00000101, 0b00000001, 0xAB, 0xCD]);
+
+        let mut reader = BitReader::new(&data);
+        assert_eq!(reader.read_bits(8), 0b10101100);
+        assert_eq!(reader.read_bits(3), 0b0101);
+    }
+
+    #[test]
+    fn test_bit_writer_append() {
+        let mut other = BitWriter::new();
+        other.write_bits(0b1_0110_0111, 9);
+        let mut writer = BitWriter::new();
+        writer.write_bits(0b101, 3);
+        writer.append(&other);
+        assert_eq!(writer.bits_written(), 12);
+        assert_eq!(writer.into_vec(), [0b0011_1101, 0b1011]);
+    }
+
+    #[cfg(feature = "stats")]
+    #[test]
+    fn test_bit_reader_stats() {
+        let data = [0b10101100, 0b01010101, 0xFF];
+        let mut reader = BitReader::new(&data);
+        let _ = reader.read_bits(3);
+        assert_eq!(reader.stats().bits, 3);
+        assert_eq!(reader.stats().refills, 0);
+        reader.advance_to_byte_boundary();
+        reader.advance_to_byte_boundary();
+        assert_eq!(reader.stats().alignment_skips, 1);
+        assert_eq!(reader.stats().refills, 1);
+        let _ = reader.read_bits(12);
+        assert_eq!(reader.stats().bits, 20);
+        assert_eq!(reader.stats().refills, 2);
+        reader.reset_stats();
+        assert_eq!(reader.stats(), BitStats::default());
+    }
+
+    #[cfg(feature = "stats")]
+    #[test]
+    fn test_bit_writer_stats() {
+        let mut writer = BitWriter::new();
+        writer.write_bits(0b101, 3);
+        assert_eq!(writer.stats().bits, 3);
+        assert_eq!(writer.stats().refills, 1);
+        // The padding bits count as written, as skipped ones count as read.
+        writer.align_to_byte();
+        writer.align_to_byte();
+        assert_eq!(writer.stats().alignment_skips, 1);
+        assert_eq!(writer.stats().bits, 8);
+        writer.write_bytes(&[0xAB, 0xCD]);
+        assert_eq!(writer.stats().bits, 24);
+        assert_eq!(writer.stats().refills, 3);
+        let mut other = BitWriter::new();
+        other.write_bits(0b1_0110_0111, 9);
+        writer.write_bits(0b1, 1);
+        writer.append(&other);
+        assert_eq!(writer.stats().bits, 34);
+        assert_eq!(writer.stats().bits, writer.bits_written() as u64);
+        assert_eq!(writer.stats().refills, 5);
+        writer.reset_stats();
+        assert_eq!(writer.stats(), BitStats::default());
+    }
+
     #[test]
     #[should_panic]
     fn test_bit_reader_peek_bits_panic() {
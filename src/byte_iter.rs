@@ -0,0 +1,184 @@
+//! Decode a DEFLATE stream lazily, one output byte at a time.
+//!
+//! An [`InflateByteIter`] decodes only as far as the bytes asked for, so `take_while`, `find`
+//! or `take` stop decoding as soon as they stop pulling. It never holds more than the window
+//! and one piece of output: a match, or a slice of a stored block of at most
+//! [`STORED_PIECE_LEN`] bytes.
+
+use crate::bit_stream::BitReader;
+use crate::error::{InflateError, Result};
+use crate::inflate::{
+    copy_stored, inflate_step, BlockState, InflateOptions, InflateState, TailHandling,
+};
+use core::iter::FusedIterator;
+
+/// Most bytes of a stored block decoded at once.
+pub const STORED_PIECE_LEN: usize = 4096;
+
+/// An iterator over the decompressed bytes of a raw DEFLATE stream.
+/// It yields an error once if decoding fails, and ends after it.
+#[derive(Debug)]
+pub struct InflateByteIter<'a> {
+    data: &'a [u8],
+    bit_reader: BitReader<'a>,
+    state: InflateState,
+    /// Position within the current block.
+    block: BlockState,
+    /// Index in the output of the next byte to yield.
+    next: usize,
+    /// Whether decoding failed, so the iterator is over.
+    failed: bool,
+}
+
+impl<'a> InflateByteIter<'a> {
+    /// Create an iterator over the output of a raw DEFLATE stream.
+    pub fn new(data: &'a [u8]) -> Self {
+        Self::configured(data, &InflateOptions::default())
+    }
+
+    /// Create an iterator configured by the given options.
+    /// The stream is always raw DEFLATE and nothing after it is looked at, so the format must
+    /// be [`Format::Raw`](crate::container::Format::Raw) and the tail handling
+    /// [`TailHandling::Ignore`]; anything else fails with [`InflateError::UnsupportedOption`].
+    /// All the other options are honored.
+    pub fn with_options(data: &'a [u8], options: &InflateOptions) -> Result<Self> {
+        options.check_raw(&[TailHandling::Ignore])?;
+        Ok(Self::configured(data, options))
+    }
+
+    /// Create the decoder with options already checked.
+    fn configured(data: &'a [u8], options: &InflateOptions) -> Self {
+        let mut state = InflateState::from_options(options);
+        if let Some(dictionary) = &options.dictionary {
+            state.set_dictionary(dictionary);
+        }
+        Self {
+            data,
+            bit_reader: BitReader::new_strict(data),
+            next: state.output.len(),
+            state,
+            block: BlockState::Header,
+            failed: false,
+        }
+    }
+
+    /// Number of output bytes yielded so far.
+    pub fn total_out(&self) -> usize {
+        self.state.total_out() - (self.state.output.len() - self.next)
+    }
+
+    /// Number of input bits decoded so far. It runs ahead of the bytes yielded by at most
+    /// one piece of output.
+    pub fn bits_consumed(&self) -> usize {
+        self.bit_reader.bits_consumed()
+    }
+
+    /// Decode the next piece of the stream that produces output, after moving the bytes
+    /// already yielded to the window.
+    fn decode_more(&mut self) -> Result<()> {
+        if self.data.is_empty() {
+            return Err(InflateError::EmptyInput);
+        }
+        self.state.discard_output(0);
+        self.next = 0;
+        while self.state.output.is_empty() && !matches!(self.block, BlockState::Done) {
+            let next = match self.block {
+                BlockState::Stored {
+                    remaining,
+                    is_final,
+                } => {
+                    // Not the whole block at once: it may be 64 KiB.
+                    let n = remaining.min(STORED_PIECE_LEN);
+                    Some(copy_stored(
+                        &mut self.bit_reader,
+                        &mut self.state,
+                        remaining,
+                        is_final,
+                        n,
+                    )?)
+                }
+                _ => inflate_step(&mut self.bit_reader, &mut self.state, &self.block)?,
+            };
+            if let Some(next) = next {
+                self.block = next;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for InflateByteIter<'_> {
+    type Item = Result<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        if self.next == self.state.output.len() {
+            if let Err(error) = self.decode_more() {
+                self.failed = true;
+                return Some(Err(self.state.surface(error)));
+            }
+        }
+        let byte = *self.state.output.get(self.next)?;
+        self.next += 1;
+        Some(Ok(byte))
+    }
+}
+
+impl FusedIterator for InflateByteIter<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deflate::deflate_stored_to_vec;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn test_byte_iter() {
+        let raw = b"one line\nanother line\n".repeat(3000);
+        let data = miniz_oxide::deflate::compress_to_vec(&raw, 6);
+        let bytes: Result<Vec<u8>> = InflateByteIter::new(&data).collect();
+        assert_eq!(bytes.unwrap(), raw);
+
+        // Only the first line is decoded.
+        let mut iter = InflateByteIter::new(&data);
+        let line: Vec<u8> = iter
+            .by_ref()
+            .map_while(|byte| byte.ok().filter(|&byte| byte != b'\n'))
+            .collect();
+        assert_eq!(line, b"one line");
+        assert_eq!(iter.total_out(), 9);
+        assert!(iter.bits_consumed() < data.len() * 8 / 2);
+        assert!(iter.state.output.len() < 300);
+    }
+
+    #[test]
+    fn test_byte_iter_stored() {
+        let raw: Vec<u8> = (0..50_000u32).map(|i| (i % 251) as u8).collect();
+        let data = deflate_stored_to_vec(&raw);
+        let mut iter = InflateByteIter::new(&data);
+        assert_eq!(iter.by_ref().take(10).count(), 10);
+        assert_eq!(iter.state.output.len(), STORED_PIECE_LEN);
+        let rest: Result<Vec<u8>> = iter.collect();
+        assert_eq!(rest.unwrap(), &raw[10..]);
+    }
+
+    #[test]
+    fn test_byte_iter_errors() {
+        let mut iter = InflateByteIter::new(&[]);
+        assert_eq!(iter.next(), Some(Err(InflateError::EmptyInput)));
+        assert_eq!(iter.next(), None);
+
+        let data = miniz_oxide::deflate::compress_to_vec(&b"truncated".repeat(100), 6);
+        let bytes: Vec<Result<u8>> = InflateByteIter::new(&data[..data.len() / 2]).collect();
+        assert_eq!(bytes.last(), Some(&Err(InflateError::UnexpectedEof)));
+        assert!(bytes[..bytes.len() - 1].iter().all(|byte| byte.is_ok()));
+
+        let options = InflateOptions::new().tail(TailHandling::Reject);
+        assert!(matches!(
+            InflateByteIter::with_options(&data, &options),
+            Err(InflateError::UnsupportedOption(_))
+        ));
+    }
+}
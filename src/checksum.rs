@@ -0,0 +1,210 @@
+//! Checksums used by the containers wrapping DEFLATE data: Adler-32 for zlib, CRC-32 for gzip
+//! and ZIP.
+
+/// The largest prime smaller than 65536, the modulus of Adler-32.
+const ADLER_MOD: u32 = 65521;
+
+/// The number of bytes that can be summed before the Adler-32 sums may overflow a u32.
+const ADLER_NMAX: usize = 5552;
+
+/// Adler-32 checksum, as used in the zlib trailer.
+/// Defined in RFC 1950, section 8.2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Adler32 {
+    a: u32,
+    b: u32,
+}
+
+impl Adler32 {
+    /// Create a new checksum over no data.
+    pub fn new() -> Self {
+        Self { a: 1, b: 0 }
+    }
+
+    /// Add the given data to the checksum.
+    pub fn update(&mut self, data: &[u8]) {
+        // Reduce only once per chunk, as zlib does.
+        for chunk in data.chunks(ADLER_NMAX) {
+            for &byte in chunk {
+                self.a += byte as u32;
+                self.b += self.a;
+            }
+            self.a %= ADLER_MOD;
+            self.b %= ADLER_MOD;
+        }
+    }
+
+    /// Get the checksum of the data added so far.
+    pub fn finish(&self) -> u32 {
+        (self.b << 16) | self.a
+    }
+}
+
+impl Default for Adler32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Compute the Adler-32 checksum of the given data.
+pub fn adler32(data: &[u8]) -> u32 {
+    let mut adler = Adler32::new();
+    adler.update(data);
+    adler.finish()
+}
+
+/// The reversed polynomial of CRC-32, as used by gzip.
+const CRC32_POLYNOMIAL: u32 = 0xEDB8_8320;
+
+/// The number of bytes the table-driven CRC-32 processes at a time.
+const CRC32_SLICES: usize = 8;
+
+/// Lookup tables for the slice-by-8 algorithm.
+/// `CRC32_TABLES[0]` is the CRC-32 of every byte value, as in the byte-at-a-time algorithm;
+/// `CRC32_TABLES[k]` is the CRC of every byte value followed by `k` zero bytes, which lets
+/// eight bytes be folded into the CRC with eight independent lookups.
+const CRC32_TABLES: [[u32; 256]; CRC32_SLICES] = {
+    let mut tables = [[0; 256]; CRC32_SLICES];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ CRC32_POLYNOMIAL
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        tables[0][i] = crc;
+        i += 1;
+    }
+    let mut k = 1;
+    while k < CRC32_SLICES {
+        let mut i = 0;
+        while i < 256 {
+            let previous = tables[k - 1][i];
+            tables[k][i] = (previous >> 8) ^ tables[0][(previous & 0xFF) as usize];
+            i += 1;
+        }
+        k += 1;
+    }
+    tables
+};
+
+/// CRC-32 checksum, as used in the gzip trailer.
+/// Defined in RFC 1952, section 8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Crc32 {
+    /// The complement of the CRC so far.
+    crc: u32,
+}
+
+impl Crc32 {
+    /// Create a new checksum over no data.
+    pub fn new() -> Self {
+        Self { crc: !0 }
+    }
+
+    /// Add the given data to the checksum.
+    /// Eight bytes are processed at a time, the rest one at a time.
+    pub fn update(&mut self, data: &[u8]) {
+        let t = &CRC32_TABLES;
+        let mut chunks = data.chunks_exact(CRC32_SLICES);
+        for chunk in &mut chunks {
+            let lo = self.crc ^ u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+            let hi = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]);
+            self.crc = t[7][(lo & 0xFF) as usize]
+                ^ t[6][((lo >> 8) & 0xFF) as usize]
+                ^ t[5][((lo >> 16) & 0xFF) as usize]
+                ^ t[4][(lo >> 24) as usize]
+                ^ t[3][(hi & 0xFF) as usize]
+                ^ t[2][((hi >> 8) & 0xFF) as usize]
+                ^ t[1][((hi >> 16) & 0xFF) as usize]
+                ^ t[0][(hi >> 24) as usize];
+        }
+        for &byte in chunks.remainder() {
+            self.crc = t[0][((self.crc ^ byte as u32) & 0xFF) as usize] ^ (self.crc >> 8);
+        }
+    }
+
+    /// Get the checksum of the data added so far.
+    pub fn finish(&self) -> u32 {
+        !self.crc
+    }
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Compute the CRC-32 checksum of the given data.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(data);
+    crc.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn test_adler32() {
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+
+    #[test]
+    fn test_adler32_incremental() {
+        let data = vec![0xFFu8; 3 * ADLER_NMAX + 17];
+        let mut adler = Adler32::new();
+        data.chunks(1000).for_each(|chunk| adler.update(chunk));
+        assert_eq!(adler.finish(), adler32(&data));
+        assert_eq!(adler.finish(), 0xC09A_E3A0);
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        let mut crc = Crc32::new();
+        b"The quick brown fox jumps over the lazy dog"
+            .chunks(5)
+            .for_each(|chunk| crc.update(chunk));
+        assert_eq!(crc.finish(), 0x414F_A339);
+    }
+
+    /// Compute the CRC-32 one bit at a time, straight from the polynomial.
+    fn crc32_bitwise(data: &[u8]) -> u32 {
+        let mut crc = !0u32;
+        for &byte in data {
+            crc ^= byte as u32;
+            for _ in 0..8 {
+                crc = if crc & 1 == 1 {
+                    (crc >> 1) ^ CRC32_POLYNOMIAL
+                } else {
+                    crc >> 1
+                };
+            }
+        }
+        !crc
+    }
+
+    #[test]
+    fn test_crc32_slice_by_8() {
+        let data: Vec<u8> = (0..1000u32).map(|i| (i * 31 % 256) as u8).collect();
+        for len in [0, 1, 7, 8, 9, 15, 16, 17, 100, 1000] {
+            assert_eq!(crc32(&data[..len]), crc32_bitwise(&data[..len]), "{}", len);
+        }
+        // Chunk boundaries that don't fall on multiples of 8.
+        let mut crc = Crc32::new();
+        data.chunks(13).for_each(|chunk| crc.update(chunk));
+        assert_eq!(crc.finish(), crc32_bitwise(&data));
+    }
+}
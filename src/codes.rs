@@ -0,0 +1,261 @@
+//! The length and distance codes of DEFLATE, and the mappings between them and the values
+//! they encode.
+//! Defined in RFC 1951, section 3.2.5.
+//!
+//! A match length is written as a literal/length symbol from 257 to 285 followed by some extra
+//! bits, and a match distance as a distance symbol from 0 to 29 followed by some extra bits.
+//! The value is the base of the code plus the extra bits read as a number. The decoder, the
+//! compressor and the annotators all go through this module, as can outside tools.
+//!
+//! ```
+//! use inflate_toy::codes::{code_to_length, length_to_code};
+//!
+//! assert_eq!(length_to_code(100), Some((279, 4, 1)));
+//! assert_eq!(code_to_length(279, 1), Some(100));
+//! ```
+
+use crate::error::{InflateError, Result};
+
+/// The symbol of the literal/length alphabet that ends a block.
+pub const END_BLOCK_CODE: usize = 256;
+const LITERAL_CODE_MAX: usize = 255;
+const LENGTH_CODE_BASE: usize = 257;
+const LENGTH_CODE_MAX: usize = 285;
+
+/// A symbol of the literal/length alphabet, by what it stands for.
+/// Symbols 286 and 287 take part in the fixed code but stand for nothing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Symbol {
+    /// A byte copied to the output as is: symbols 0 to 255.
+    Literal(u8),
+    /// The end of the block: symbol 256.
+    EndOfBlock,
+    /// A length code, from 257 to 285, starting a match. Its extra bits and a distance follow.
+    Length(u16),
+}
+
+impl Symbol {
+    /// Classify a decoded literal/length symbol.
+    /// Fails with `InvalidHuffmanSymbol` for 286, 287 or anything above.
+    pub fn classify(symbol: usize) -> Result<Self> {
+        match symbol {
+            0..=LITERAL_CODE_MAX => Ok(Symbol::Literal(symbol as u8)),
+            END_BLOCK_CODE => Ok(Symbol::EndOfBlock),
+            LENGTH_CODE_BASE..=LENGTH_CODE_MAX => Ok(Symbol::Length(symbol as u16)),
+            _ => Err(InflateError::InvalidHuffmanSymbol),
+        }
+    }
+
+    /// Get the symbol back as a number of the literal/length alphabet.
+    pub fn code(self) -> usize {
+        match self {
+            Symbol::Literal(byte) => byte as usize,
+            Symbol::EndOfBlock => END_BLOCK_CODE,
+            Symbol::Length(code) => code as usize,
+        }
+    }
+}
+
+/// Length code table for DEFLATE.
+/// length_code_table[i] = (length_code, length_base, extra_bits)
+pub const LENGTH_CODE_TABLE: &[(usize, usize, usize)] = &[
+    (257, 3, 0),
+    (258, 4, 0),
+    (259, 5, 0),
+    (260, 6, 0),
+    (261, 7, 0),
+    (262, 8, 0),
+    (263, 9, 0),
+    (264, 10, 0),
+    (265, 11, 1),
+    (266, 13, 1),
+    (267, 15, 1),
+    (268, 17, 1),
+    (269, 19, 2),
+    (270, 23, 2),
+    (271, 27, 2),
+    (272, 31, 2),
+    (273, 35, 3),
+    (274, 43, 3),
+    (275, 51, 3),
+    (276, 59, 3),
+    (277, 67, 4),
+    (278, 83, 4),
+    (279, 99, 4),
+    (280, 115, 4),
+    (281, 131, 5),
+    (282, 163, 5),
+    (283, 195, 5),
+    (284, 227, 5),
+    (285, 258, 0),
+];
+
+/// Distance code table for DEFLATE.
+/// distance_code_table[i] = (distance_code, distance_base, extra_bits)
+pub const DISTANCE_CODE_TABLE: &[(usize, usize, usize)] = &[
+    (0, 1, 0),
+    (1, 2, 0),
+    (2, 3, 0),
+    (3, 4, 0),
+    (4, 5, 1),
+    (5, 7, 1),
+    (6, 9, 2),
+    (7, 13, 2),
+    (8, 17, 3),
+    (9, 25, 3),
+    (10, 33, 4),
+    (11, 49, 4),
+    (12, 65, 5),
+    (13, 97, 5),
+    (14, 129, 6),
+    (15, 193, 6),
+    (16, 257, 7),
+    (17, 385, 7),
+    (18, 513, 8),
+    (19, 769, 8),
+    (20, 1025, 9),
+    (21, 1537, 9),
+    (22, 2049, 10),
+    (23, 3073, 10),
+    (24, 4097, 11),
+    (25, 6145, 11),
+    (26, 8193, 12),
+    (27, 12289, 12),
+    (28, 16385, 13),
+    (29, 24577, 13),
+];
+
+/// Length code 285 in Deflate64: lengths of 3 to 65538, with 16 extra bits, where DEFLATE has
+/// the single length 258.
+pub const DEFLATE64_LENGTH_CODE: (usize, usize, usize) = (285, 3, 16);
+
+/// The distance codes Deflate64 adds, reaching up to `DEFLATE64_MAX_DISTANCE`.
+pub const DEFLATE64_DISTANCE_CODES: &[(usize, usize, usize)] = &[(30, 32769, 14), (31, 49153, 14)];
+
+/// Get the `(length_code, length_base, extra_bits)` of a literal/length symbol,
+/// or `None` if it is not a length code.
+pub fn length_code_entry(code: usize, deflate64: bool) -> Option<(usize, usize, usize)> {
+    if deflate64 && code == DEFLATE64_LENGTH_CODE.0 {
+        return Some(DEFLATE64_LENGTH_CODE);
+    }
+    LENGTH_CODE_TABLE
+        .get(code.checked_sub(LENGTH_CODE_BASE)?)
+        .cloned()
+}
+
+/// Get the `(distance_code, distance_base, extra_bits)` of a distance symbol,
+/// or `None` if it is not a distance code.
+/// Codes 30 and 31 take part in the fixed code but only occur in the data of Deflate64.
+pub fn distance_code_entry(code: usize, deflate64: bool) -> Option<(usize, usize, usize)> {
+    match DISTANCE_CODE_TABLE.get(code) {
+        Some(&entry) => Some(entry),
+        None if deflate64 => DEFLATE64_DISTANCE_CODES
+            .get(code - DISTANCE_CODE_TABLE.len())
+            .cloned(),
+        None => None,
+    }
+}
+
+/// Find the entry of the table covering `value`: the last one whose base is not above it.
+/// Returns the code, the number of extra bits and their value, or `None` if the value is out
+/// of the range of the table.
+fn find_code(table: &[(usize, usize, usize)], value: usize) -> Option<(usize, usize, usize)> {
+    let &(code, base, extra_bits) = table.iter().rev().find(|&&(_, base, _)| base <= value)?;
+    let extra_value = value - base;
+    (extra_value < 1 << extra_bits).then_some((code, extra_bits, extra_value))
+}
+
+/// Get the `(code, extra_bits, extra_value)` a match length of DEFLATE is written as,
+/// or `None` if it is not between 3 and 258.
+pub fn length_to_code(length: usize) -> Option<(usize, usize, usize)> {
+    find_code(LENGTH_CODE_TABLE, length)
+}
+
+/// Get the `(code, extra_bits, extra_value)` a match distance of DEFLATE is written as,
+/// or `None` if it is not between 1 and `MAX_DISTANCE`.
+pub fn distance_to_code(distance: usize) -> Option<(usize, usize, usize)> {
+    find_code(DISTANCE_CODE_TABLE, distance)
+}
+
+/// Get the match length a length code of DEFLATE and the value of its extra bits stand for,
+/// or `None` if the code is not a length code or the value does not fit in its extra bits.
+pub fn code_to_length(code: usize, extra_value: usize) -> Option<usize> {
+    let (_, base, extra_bits) = length_code_entry(code, false)?;
+    (extra_value < 1 << extra_bits).then_some(base + extra_value)
+}
+
+/// Get the match distance a distance code of DEFLATE and the value of its extra bits stand
+/// for, or `None` if the code is not a distance code or the value does not fit in its extra
+/// bits.
+pub fn code_to_distance(code: usize, extra_value: usize) -> Option<usize> {
+    let (_, base, extra_bits) = distance_code_entry(code, false)?;
+    (extra_value < 1 << extra_bits).then_some(base + extra_value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inflate::MAX_DISTANCE;
+
+    #[test]
+    fn test_symbol_classify() {
+        assert_eq!(Symbol::classify(0), Ok(Symbol::Literal(0)));
+        assert_eq!(Symbol::classify(255), Ok(Symbol::Literal(255)));
+        assert_eq!(Symbol::classify(256), Ok(Symbol::EndOfBlock));
+        assert_eq!(Symbol::classify(257), Ok(Symbol::Length(257)));
+        assert_eq!(Symbol::classify(285), Ok(Symbol::Length(285)));
+        for symbol in [286, 287, 1000] {
+            assert_eq!(
+                Symbol::classify(symbol),
+                Err(InflateError::InvalidHuffmanSymbol)
+            );
+        }
+        for symbol in 0..286 {
+            assert_eq!(Symbol::classify(symbol).unwrap().code(), symbol);
+        }
+    }
+
+    #[test]
+    fn test_length_codes() {
+        assert_eq!(length_to_code(3), Some((257, 0, 0)));
+        assert_eq!(length_to_code(11), Some((265, 1, 0)));
+        assert_eq!(length_to_code(12), Some((265, 1, 1)));
+        assert_eq!(length_to_code(257), Some((284, 5, 30)));
+        assert_eq!(length_to_code(258), Some((285, 0, 0)));
+        assert_eq!(length_to_code(2), None);
+        assert_eq!(length_to_code(259), None);
+        for length in 3..=258 {
+            let (code, extra_bits, extra_value) = length_to_code(length).unwrap();
+            assert!(extra_value < 1 << extra_bits);
+            assert_eq!(code_to_length(code, extra_value), Some(length));
+        }
+        assert_eq!(code_to_length(256, 0), None);
+        assert_eq!(code_to_length(286, 0), None);
+        assert_eq!(code_to_length(265, 2), None);
+    }
+
+    #[test]
+    fn test_distance_codes() {
+        assert_eq!(distance_to_code(1), Some((0, 0, 0)));
+        assert_eq!(distance_to_code(6), Some((4, 1, 1)));
+        assert_eq!(distance_to_code(MAX_DISTANCE), Some((29, 13, 8191)));
+        assert_eq!(distance_to_code(0), None);
+        assert_eq!(distance_to_code(MAX_DISTANCE + 1), None);
+        for distance in 1..=MAX_DISTANCE {
+            let (code, _, extra_value) = distance_to_code(distance).unwrap();
+            assert_eq!(code_to_distance(code, extra_value), Some(distance));
+        }
+        assert_eq!(code_to_distance(30, 0), None);
+        assert_eq!(code_to_distance(0, 1), None);
+    }
+
+    #[test]
+    fn test_deflate64_code_entries() {
+        assert_eq!(length_code_entry(285, false), Some((285, 258, 0)));
+        assert_eq!(length_code_entry(285, true), Some(DEFLATE64_LENGTH_CODE));
+        assert_eq!(length_code_entry(100, true), None);
+        assert_eq!(distance_code_entry(30, false), None);
+        assert_eq!(distance_code_entry(31, true), Some((31, 49153, 14)));
+        assert_eq!(distance_code_entry(32, true), None);
+    }
+}
@@ -0,0 +1,177 @@
+//! Differential decoding against a reference inflater, miniz_oxide.
+//!
+//! [`compare_with_reference`] decodes a stream with both decoders and finds the first output
+//! byte they disagree on, along with the fields decoded around it. This narrows a correctness
+//! bug down to a few symbols without writing a test for it first.
+
+use crate::container::{detect_format, header_len, Format};
+use crate::disasm::Field;
+use crate::error::{InflateError, Result};
+use crate::stepper::InflateStepper;
+use std::fmt::Write;
+
+/// The number of fields kept on each side of the divergence.
+const CONTEXT_FIELDS: usize = 8;
+
+/// The outcome of decoding a stream with both this crate and the reference.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Comparison {
+    /// The container of the stream. Only its DEFLATE data is compared, not its trailer.
+    pub format: Format,
+    /// The output of this crate, up to where it stopped.
+    pub output: Vec<u8>,
+    /// The error this crate stopped at, if any.
+    pub error: Option<InflateError>,
+    /// The output of the reference, up to where it stopped.
+    pub reference_output: Vec<u8>,
+    /// The error the reference stopped at, if any.
+    pub reference_error: Option<String>,
+    /// Offset of the first output byte the decoders disagree on, if they do. A decoder stopping
+    /// early diverges at the end of its output.
+    pub divergence: Option<usize>,
+    /// The fields this crate decoded around the divergence. Their bit offsets are relative to
+    /// the start of the DEFLATE data.
+    pub context: Vec<Field>,
+}
+
+impl Comparison {
+    /// Write a human-readable report of the comparison.
+    pub fn report(&self) -> String {
+        let mut report = String::new();
+        let _ = writeln!(report, "format: {:?}", self.format);
+        let _ = writeln!(
+            report,
+            "inflate-toy: {} bytes, {}",
+            self.output.len(),
+            self.error.map_or("ok".to_string(), |e| e.to_string())
+        );
+        let _ = writeln!(
+            report,
+            "reference:   {} bytes, {}",
+            self.reference_output.len(),
+            self.reference_error.as_deref().unwrap_or("ok")
+        );
+        let Some(divergence) = self.divergence else {
+            report.push_str("outputs match\n");
+            return report;
+        };
+        let byte = |output: &[u8]| {
+            output
+                .get(divergence)
+                .map_or("end of output".to_string(), |byte| format!("{:#04x}", byte))
+        };
+        let _ = writeln!(
+            report,
+            "first divergence at output offset {}: inflate-toy {}, reference {}",
+            divergence,
+            byte(&self.output),
+            byte(&self.reference_output)
+        );
+        for field in &self.context {
+            let _ = writeln!(
+                report,
+                "  bit {:>8}  out {:>8}  {}",
+                field.bit_offset,
+                field.output_offset,
+                field.describe()
+            );
+        }
+        report
+    }
+}
+
+/// Find the first output offset where two decodings, each given as its output and whether it
+/// failed, disagree.
+fn first_divergence(a: (&[u8], bool), b: (&[u8], bool)) -> Option<usize> {
+    let ((a, a_failed), (b, b_failed)) = (a, b);
+    let common = a.len().min(b.len());
+    a.iter()
+        .zip(b)
+        .position(|(x, y)| x != y)
+        .or_else(|| (a.len() != b.len() || a_failed != b_failed).then_some(common))
+}
+
+/// Decode a raw DEFLATE, zlib or gzip stream with both this crate and miniz_oxide, and compare
+/// their outputs. Fails only if the container header cannot be parsed.
+pub fn compare_with_reference(data: &[u8]) -> Result<Comparison> {
+    let format = detect_format(data);
+    let deflate = &data[header_len(data, format)?..];
+
+    let mut stepper = InflateStepper::new(deflate);
+    while let Ok(false) = stepper.step() {}
+    let (reference_output, reference_error) = match miniz_oxide::inflate::decompress_to_vec(deflate)
+    {
+        Ok(output) => (output, None),
+        Err(e) => (e.output.clone(), Some(e.to_string())),
+    };
+
+    let output = stepper.output();
+    let error = stepper.error();
+    let divergence = first_divergence(
+        (output, error.is_some()),
+        (&reference_output, reference_error.is_some()),
+    );
+    let context = match divergence {
+        Some(offset) => {
+            let fields = stepper.fields();
+            let end = fields.partition_point(|field| field.output_offset <= offset);
+            let start = end.saturating_sub(CONTEXT_FIELDS + 1);
+            fields[start..(end + CONTEXT_FIELDS).min(fields.len())].to_vec()
+        }
+        None => Vec::new(),
+    };
+
+    Ok(Comparison {
+        format,
+        output: output.to_vec(),
+        error,
+        reference_output,
+        reference_error,
+        divergence,
+        context,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare_matching_outputs() {
+        let raw = b"compare me, compare me again".repeat(50);
+        let data = miniz_oxide::deflate::compress_to_vec_zlib(&raw, 6);
+        let comparison = compare_with_reference(&data).unwrap();
+        assert_eq!(comparison.format, Format::Zlib);
+        assert_eq!(comparison.output, raw);
+        assert_eq!(comparison.divergence, None);
+        assert!(comparison.context.is_empty());
+        assert!(comparison.report().ends_with("outputs match\n"));
+    }
+
+    #[test]
+    fn test_first_divergence() {
+        assert_eq!(first_divergence((b"abc", false), (b"abc", false)), None);
+        assert_eq!(first_divergence((b"abc", true), (b"abc", true)), None);
+        assert_eq!(first_divergence((b"abd", false), (b"abc", false)), Some(2));
+        assert_eq!(first_divergence((b"ab", true), (b"abc", false)), Some(2));
+        assert_eq!(first_divergence((b"abc", true), (b"abc", false)), Some(3));
+    }
+
+    #[test]
+    fn test_compare_truncated() {
+        let raw: String = (0..500).map(|i| format!("{} ", i * 7919 % 1000)).collect();
+        let data = miniz_oxide::deflate::compress_to_vec(raw.as_bytes(), 6);
+        let comparison = compare_with_reference(&data[..data.len() / 2]).unwrap();
+        assert_eq!(comparison.format, Format::Raw);
+        assert_eq!(comparison.error, Some(InflateError::UnexpectedEof));
+        assert!(comparison.reference_error.is_some());
+        // Both decoders agree on everything before the cut.
+        let divergence = comparison.divergence.unwrap();
+        assert!(divergence > 0);
+        assert_eq!(
+            comparison.output[..divergence],
+            raw.as_bytes()[..divergence]
+        );
+        assert!(!comparison.context.is_empty());
+    }
+}
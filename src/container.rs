@@ -0,0 +1,385 @@
+//! Parse the containers that wrap DEFLATE data.
+//! The zlib format is defined in RFC 1950 and the gzip format in RFC 1952.
+//! This module only deals with headers and trailers; the compressed data in between
+//! is handled by the `inflate` module.
+
+use crate::checksum::{adler32, crc32};
+use crate::error::{InflateError, Result};
+use alloc::vec::Vec;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The container around a DEFLATE stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Format {
+    /// Bare DEFLATE data without any header or trailer.
+    #[default]
+    Raw,
+    /// zlib wrapper with an Adler-32 trailer.
+    Zlib,
+    /// gzip wrapper with a CRC-32 and size trailer.
+    Gzip,
+}
+
+/// Compression method of DEFLATE in both zlib and gzip headers.
+pub(crate) const CM_DEFLATE: u8 = 8;
+
+const ZLIB_HEADER_LEN: usize = 2;
+/// Length of the zlib trailer: the Adler-32 of the uncompressed data.
+pub const ZLIB_TRAILER_LEN: usize = 4;
+pub(crate) const ZLIB_MAX_CINFO: u8 = 7;
+pub(crate) const ZLIB_FDICT: u8 = 0x20;
+pub(crate) const ZLIB_FCHECK_MOD: u16 = 31;
+
+pub(crate) const GZIP_ID: [u8; 2] = [0x1f, 0x8b];
+const GZIP_HEADER_LEN: usize = 10;
+/// Length of the gzip trailer: the CRC-32 and the size of the uncompressed data.
+pub const GZIP_TRAILER_LEN: usize = 8;
+pub(crate) const GZIP_FTEXT: u8 = 0x01;
+pub(crate) const GZIP_FHCRC: u8 = 0x02;
+pub(crate) const GZIP_FEXTRA: u8 = 0x04;
+pub(crate) const GZIP_FNAME: u8 = 0x08;
+pub(crate) const GZIP_FCOMMENT: u8 = 0x10;
+const GZIP_FRESERVED: u8 = 0xe0;
+/// The OS field of a gzip header written by this crate: unknown.
+const GZIP_OS_UNKNOWN: u8 = 255;
+
+/// The fields of a zlib header.
+/// Defined in RFC 1950, section 2.2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ZlibHeader {
+    /// Base-two logarithm of the window size used by the compressor (8 to 15).
+    pub window_bits: u8,
+    /// Compression level hint (0 = fastest to 3 = maximum compression).
+    pub level: u8,
+    /// Whether a preset dictionary is needed, identified by its Adler-32.
+    pub dictionary_id: Option<u32>,
+}
+
+/// The fields of a gzip member header.
+/// Defined in RFC 1952, section 2.3.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct GzipHeader {
+    /// Whether the data is probably ASCII text.
+    pub is_text: bool,
+    /// Modification time of the original file, in seconds since the Unix epoch (0 if unknown).
+    pub mtime: u32,
+    /// Extra flags, describing the compression level.
+    pub extra_flags: u8,
+    /// The operating system the data was compressed on.
+    pub os: u8,
+    /// The extra field, if present.
+    pub extra: Option<Vec<u8>>,
+    /// The original file name, without its terminating zero.
+    pub name: Option<Vec<u8>>,
+    /// The file comment, without its terminating zero.
+    pub comment: Option<Vec<u8>>,
+    /// The CRC-16 of the header, if present.
+    pub header_crc: Option<u16>,
+}
+
+/// Read a little-endian u16 at the given position.
+fn read_u16_le(data: &[u8], pos: usize) -> Result<u16> {
+    let bytes = data.get(pos..pos + 2).ok_or(InflateError::UnexpectedEof)?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+/// Read a little-endian u32 at the given position.
+fn read_u32_le(data: &[u8], pos: usize) -> Result<u32> {
+    let bytes = data.get(pos..pos + 4).ok_or(InflateError::UnexpectedEof)?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Read a big-endian u32 at the given position.
+fn read_u32_be(data: &[u8], pos: usize) -> Result<u32> {
+    let bytes = data.get(pos..pos + 4).ok_or(InflateError::UnexpectedEof)?;
+    Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Parse a zlib header.
+/// Returns the header and its length in bytes.
+pub fn parse_zlib_header(data: &[u8]) -> Result<(ZlibHeader, usize)> {
+    let header = data
+        .get(..ZLIB_HEADER_LEN)
+        .ok_or(InflateError::UnexpectedEof)?;
+    let (cmf, flg) = (header[0], header[1]);
+    if cmf & 0x0f != CM_DEFLATE {
+        return Err(InflateError::InvalidHeader(
+            "Unsupported compression method",
+        ));
+    }
+    let cinfo = cmf >> 4;
+    if cinfo > ZLIB_MAX_CINFO {
+        return Err(InflateError::InvalidHeader("Invalid zlib window size"));
+    }
+    if u16::from_be_bytes([cmf, flg]) % ZLIB_FCHECK_MOD != 0 {
+        return Err(InflateError::InvalidHeader(
+            "Invalid zlib header check bits",
+        ));
+    }
+
+    let mut len = ZLIB_HEADER_LEN;
+    let dictionary_id = if flg & ZLIB_FDICT != 0 {
+        len += 4;
+        Some(read_u32_be(data, ZLIB_HEADER_LEN)?)
+    } else {
+        None
+    };
+    let header = ZlibHeader {
+        window_bits: cinfo + 8,
+        level: flg >> 6,
+        dictionary_id,
+    };
+    Ok((header, len))
+}
+
+/// Guess the container of the given data from its first bytes.
+/// Data that is neither a gzip nor a valid zlib header is taken as raw DEFLATE.
+/// A raw stream can occasionally look like a zlib header, so callers should be ready to
+/// fall back to `Format::Raw` when the zlib stream does not decode.
+pub fn detect_format(data: &[u8]) -> Format {
+    if data.starts_with(&GZIP_ID) {
+        Format::Gzip
+    } else if parse_zlib_header(data).is_ok() {
+        Format::Zlib
+    } else {
+        Format::Raw
+    }
+}
+
+/// Get the length of the container header at the start of the data, where its DEFLATE
+/// stream starts: 0 for raw DEFLATE.
+pub fn header_len(data: &[u8], format: Format) -> Result<usize> {
+    match format {
+        Format::Raw => Ok(0),
+        Format::Zlib => parse_zlib_header(data).map(|(_, len)| len),
+        Format::Gzip => parse_gzip_header(data).map(|(_, len)| len),
+    }
+}
+
+/// Check the zlib trailer against the decompressed data.
+pub fn check_zlib_trailer(trailer: &[u8], output: &[u8]) -> Result<()> {
+    let expected = read_u32_be(trailer, 0)?;
+    let found = adler32(output);
+    if expected != found {
+        return Err(InflateError::ChecksumMismatch { expected, found });
+    }
+    Ok(())
+}
+
+/// Read a zero-terminated field starting at the given position.
+/// Returns the field without its terminator.
+fn read_zero_terminated(data: &[u8], pos: usize) -> Result<&[u8]> {
+    let rest = data.get(pos..).ok_or(InflateError::UnexpectedEof)?;
+    let end = rest
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or(InflateError::UnexpectedEof)?;
+    Ok(&rest[..end])
+}
+
+/// Parse a gzip member header.
+/// Returns the header and its length in bytes.
+pub fn parse_gzip_header(data: &[u8]) -> Result<(GzipHeader, usize)> {
+    let fixed = data
+        .get(..GZIP_HEADER_LEN)
+        .ok_or(InflateError::UnexpectedEof)?;
+    if fixed[..2] != GZIP_ID {
+        return Err(InflateError::InvalidHeader("Invalid gzip magic"));
+    }
+    if fixed[2] != CM_DEFLATE {
+        return Err(InflateError::InvalidHeader(
+            "Unsupported compression method",
+        ));
+    }
+    let flg = fixed[3];
+    if flg & GZIP_FRESERVED != 0 {
+        return Err(InflateError::InvalidHeader("Reserved gzip flags set"));
+    }
+
+    let mut header = GzipHeader {
+        is_text: flg & GZIP_FTEXT != 0,
+        mtime: read_u32_le(data, 4)?,
+        extra_flags: fixed[8],
+        os: fixed[9],
+        ..Default::default()
+    };
+    let mut pos = GZIP_HEADER_LEN;
+    if flg & GZIP_FEXTRA != 0 {
+        let xlen = read_u16_le(data, pos)? as usize;
+        pos += 2;
+        let extra = data
+            .get(pos..pos + xlen)
+            .ok_or(InflateError::UnexpectedEof)?;
+        header.extra = Some(extra.to_vec());
+        pos += xlen;
+    }
+    if flg & GZIP_FNAME != 0 {
+        let name = read_zero_terminated(data, pos)?;
+        header.name = Some(name.to_vec());
+        pos += name.len() + 1;
+    }
+    if flg & GZIP_FCOMMENT != 0 {
+        let comment = read_zero_terminated(data, pos)?;
+        header.comment = Some(comment.to_vec());
+        pos += comment.len() + 1;
+    }
+    if flg & GZIP_FHCRC != 0 {
+        header.header_crc = Some(read_u16_le(data, pos)?);
+        pos += 2;
+    }
+    Ok((header, pos))
+}
+
+/// Check the gzip trailer against the decompressed data: its CRC-32, then ISIZE, the size of
+/// the data modulo 2^32.
+pub fn check_gzip_trailer(trailer: &[u8], output: &[u8]) -> Result<()> {
+    let expected = read_u32_le(trailer, 0)?;
+    let found = crc32(output);
+    if expected != found {
+        return Err(InflateError::ChecksumMismatch { expected, found });
+    }
+    if read_u32_le(trailer, 4)? != output.len() as u32 {
+        return Err(InflateError::InvalidHeader("gzip ISIZE mismatch"));
+    }
+    Ok(())
+}
+
+/// Get the ISIZE of a single-member gzip file, from its last 4 bytes: the size of the data
+/// modulo 2^32. It is only a hint, for [`InflateOptions::size_hint`], as nothing checks it
+/// before the end of decoding.
+///
+/// [`InflateOptions::size_hint`]: crate::inflate::InflateOptions::size_hint
+pub fn gzip_isize(data: &[u8]) -> Option<u32> {
+    read_u32_le(data, data.len().checked_sub(4)?).ok()
+}
+
+/// Wrap a DEFLATE stream compressing `data` in the given container.
+/// The zlib header declares a 32 KiB window and the default level; the gzip header has no name
+/// and no modification time.
+pub fn wrap(deflate: &[u8], data: &[u8], format: Format) -> Vec<u8> {
+    let mut wrapped = Vec::with_capacity(deflate.len() + GZIP_HEADER_LEN + GZIP_TRAILER_LEN);
+    match format {
+        Format::Raw => wrapped.extend_from_slice(deflate),
+        Format::Zlib => {
+            let cmf = (ZLIB_MAX_CINFO << 4) | CM_DEFLATE;
+            let flevel = 2 << 6;
+            // FCHECK makes the header, read as a big-endian u16, a multiple of 31.
+            let remainder = u16::from_be_bytes([cmf, flevel]) % ZLIB_FCHECK_MOD;
+            let fcheck = (ZLIB_FCHECK_MOD - remainder) % ZLIB_FCHECK_MOD;
+            wrapped.extend([cmf, flevel | fcheck as u8]);
+            wrapped.extend_from_slice(deflate);
+            wrapped.extend(adler32(data).to_be_bytes());
+        }
+        Format::Gzip => {
+            wrapped.extend(GZIP_ID);
+            wrapped.extend([CM_DEFLATE, 0, 0, 0, 0, 0, 0, GZIP_OS_UNKNOWN]);
+            wrapped.extend_from_slice(deflate);
+            wrapped.extend(crc32(data).to_le_bytes());
+            wrapped.extend((data.len() as u32).to_le_bytes());
+        }
+    }
+    wrapped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_parse_zlib_header() {
+        let (header, len) = parse_zlib_header(&[0x78, 0x9c]).unwrap();
+        assert_eq!(len, 2);
+        assert_eq!(header.window_bits, 15);
+        assert_eq!(header.level, 2);
+        assert_eq!(header.dictionary_id, None);
+
+        let (header, len) = parse_zlib_header(&[0x78, 0xbb, 0, 0, 0, 1]).unwrap();
+        assert_eq!(len, 6);
+        assert_eq!(header.dictionary_id, Some(1));
+
+        assert!(parse_zlib_header(&[0x78, 0x9d]).is_err());
+        assert!(parse_zlib_header(&[0x79, 0x9c]).is_err());
+        assert!(parse_zlib_header(&[0x78]).is_err());
+    }
+
+    #[test]
+    fn test_detect_format() {
+        assert_eq!(detect_format(&[0x1f, 0x8b, 8, 0]), Format::Gzip);
+        assert_eq!(detect_format(&[0x78, 0x01]), Format::Zlib);
+        assert_eq!(detect_format(&[0x78, 0x02]), Format::Raw);
+        assert_eq!(detect_format(&[]), Format::Raw);
+    }
+
+    #[test]
+    fn test_parse_gzip_header() {
+        let mut data = vec![0x1f, 0x8b, 8, GZIP_FNAME | GZIP_FEXTRA, 1, 0, 0, 0, 2, 3];
+        data.extend([2, 0, 0xaa, 0xbb]);
+        data.extend(b"file.txt\0");
+        let (header, len) = parse_gzip_header(&data).unwrap();
+        assert_eq!(len, data.len());
+        assert_eq!(header.mtime, 1);
+        assert_eq!(header.extra_flags, 2);
+        assert_eq!(header.os, 3);
+        assert_eq!(header.extra.as_deref(), Some(&[0xaa, 0xbb][..]));
+        assert_eq!(header.name.as_deref(), Some(&b"file.txt"[..]));
+        assert_eq!(header.comment, None);
+
+        assert!(parse_gzip_header(&data[..data.len() - 1]).is_err());
+        data[0] = 0;
+        assert!(parse_gzip_header(&data).is_err());
+    }
+
+    #[test]
+    fn test_check_gzip_trailer() {
+        let mut trailer = Vec::new();
+        trailer.extend(crc32(b"gzip").to_le_bytes());
+        trailer.extend(4u32.to_le_bytes());
+        assert_eq!(check_gzip_trailer(&trailer, b"gzip"), Ok(()));
+        assert_eq!(
+            check_gzip_trailer(&trailer, b"gzap"),
+            Err(InflateError::ChecksumMismatch {
+                expected: crc32(b"gzip"),
+                found: crc32(b"gzap"),
+            })
+        );
+        assert!(check_gzip_trailer(&trailer[..6], b"gzip").is_err());
+    }
+
+    #[test]
+    fn test_gzip_isize() {
+        let gzip = wrap(&[0x03, 0x00], &[], Format::Gzip);
+        assert_eq!(gzip_isize(&gzip), Some(0));
+        assert_eq!(gzip_isize(&[1, 0, 0, 0]), Some(1));
+        assert_eq!(gzip_isize(&[1, 0, 0]), None);
+    }
+
+    #[test]
+    fn test_wrap() {
+        let data = b"wrapped";
+        let deflate = crate::deflate::deflate_to_vec(data);
+        assert_eq!(wrap(&deflate, data, Format::Raw), deflate);
+
+        let zlib = wrap(&deflate, data, Format::Zlib);
+        assert_eq!(zlib[..2], [0x78, 0x9c]);
+        assert_eq!(detect_format(&zlib), Format::Zlib);
+        let end = zlib.len() - ZLIB_TRAILER_LEN;
+        assert_eq!(check_zlib_trailer(&zlib[end..], data), Ok(()));
+
+        let gzip = wrap(&deflate, data, Format::Gzip);
+        let (header, len) = parse_gzip_header(&gzip).unwrap();
+        assert_eq!(
+            (header.mtime, header.os, len),
+            (0, GZIP_OS_UNKNOWN, GZIP_HEADER_LEN)
+        );
+        let end = gzip.len() - GZIP_TRAILER_LEN;
+        assert_eq!(gzip[len..end], deflate);
+        assert_eq!(check_gzip_trailer(&gzip[end..], data), Ok(()));
+    }
+}
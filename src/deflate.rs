@@ -0,0 +1,475 @@
+//! Compress data into the DEFLATE format.
+//! This module is the counterpart of the `inflate` module, defined by the same RFC 1951.
+
+use crate::bit_stream::BitWriter;
+use crate::codes::{distance_to_code, length_to_code, END_BLOCK_CODE};
+use crate::huffman::{build_code_lengths, HuffmanEncodeTable};
+use crate::inflate::{
+    fixed_distance_code_lengths, fixed_literal_code_lengths, BFINAL_LEN, BFINAL_VALUE,
+    BTYPE_DYNAMIC_HUFFMAN, BTYPE_FIXED_HUFFMAN, BTYPE_LEN, BTYPE_NO_COMPRESSION,
+    DYNAMIC_HUFFMAN_TREE_ORDER, DYN_ALPHABET_CODE_LEN, DYN_ALPHABET_CODE_NUM,
+    DYN_ALPHABET_TABLE_MAX_BITS, DYN_TABLE_MAX_BITS, HCLEN_BASE, HCLEN_LEN, HDIST_BASE, HDIST_LEN,
+    HLIT_BASE, HLIT_LEN, MAX_DISTANCE,
+};
+use crate::lz77::{find_matches, MatchFinderOptions, MatchStrategy};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// The largest number of bytes a stored block can hold, as its LEN field is 16 bits.
+pub const MAX_STORED_BLOCK_LEN: usize = u16::MAX as usize;
+
+/// Compress data into a DEFLATE stream made of stored blocks, without any compression.
+/// The data is split into blocks of at most `MAX_STORED_BLOCK_LEN` bytes.
+/// Empty data gives a single empty final block.
+/// Defined in RFC 1951, section 3.2.4.
+pub fn deflate_stored_to_vec(data: &[u8]) -> Vec<u8> {
+    let n_blocks = data.len().div_ceil(MAX_STORED_BLOCK_LEN).max(1);
+    let mut output = Vec::with_capacity(data.len() + n_blocks * 5);
+    let mut chunks = data.chunks(MAX_STORED_BLOCK_LEN).peekable();
+    loop {
+        let chunk = chunks.next().unwrap_or_default();
+        let is_final = chunks.peek().is_none();
+        // The header starts on a byte boundary, so the padding after it fills the rest of the byte.
+        let b_final = if is_final { BFINAL_VALUE } else { 0 };
+        output.push((b_final | (BTYPE_NO_COMPRESSION << BFINAL_LEN)) as u8);
+        let len = chunk.len() as u16;
+        output.extend(len.to_le_bytes());
+        output.extend((!len).to_le_bytes());
+        output.extend(chunk);
+        if is_final {
+            break;
+        }
+    }
+    output
+}
+
+/// The shortest match DEFLATE can encode.
+pub const MIN_MATCH_LEN: usize = 3;
+/// The longest match DEFLATE can encode.
+pub const MAX_MATCH_LEN: usize = 258;
+
+/// An element of the LZ77 stream a compressed block encodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Token {
+    /// A byte copied as is.
+    Literal(u8),
+    /// A copy of `length` bytes starting `distance` bytes back in the output.
+    Match { length: usize, distance: usize },
+}
+
+/// Write the tokens of a block and its end-of-block symbol with the given codes.
+pub(crate) fn write_tokens(
+    writer: &mut BitWriter,
+    tokens: &[Token],
+    lit_codes: &HuffmanEncodeTable,
+    dis_codes: &HuffmanEncodeTable,
+) {
+    for &token in tokens {
+        match token {
+            Token::Literal(byte) => lit_codes.write_symbol(writer, byte as usize),
+            Token::Match { length, distance } => {
+                assert!((MIN_MATCH_LEN..=MAX_MATCH_LEN).contains(&length));
+                assert!((1..=MAX_DISTANCE).contains(&distance));
+                let (code, extra_bits, extra) = length_to_code(length).unwrap();
+                lit_codes.write_symbol(writer, code);
+                writer.write_bits(extra, extra_bits);
+                let (code, extra_bits, extra) = distance_to_code(distance).unwrap();
+                dis_codes.write_symbol(writer, code);
+                writer.write_bits(extra, extra_bits);
+            }
+        }
+    }
+    lit_codes.write_symbol(writer, END_BLOCK_CODE);
+}
+
+/// Encode tokens as a single final block with the fixed Huffman codes.
+/// Defined in RFC 1951, section 3.2.6.
+///
+/// # Panics
+///
+/// Panics if a match is longer than `MAX_MATCH_LEN`, shorter than `MIN_MATCH_LEN`,
+/// or reaches further back than `MAX_DISTANCE`.
+pub fn deflate_fixed_tokens_to_vec(tokens: &[Token]) -> Vec<u8> {
+    let mut writer = BitWriter::new();
+    write_fixed_block(&mut writer, tokens, true);
+    writer.into_vec()
+}
+
+/// Write a block header.
+pub(crate) fn write_block_header(writer: &mut BitWriter, b_type: usize, is_final: bool) {
+    let b_final = if is_final { BFINAL_VALUE } else { 0 };
+    writer.write_bits(b_final, BFINAL_LEN);
+    writer.write_bits(b_type, BTYPE_LEN);
+}
+
+/// Write a stored block holding `data`, of at most `MAX_STORED_BLOCK_LEN` bytes.
+pub(crate) fn write_stored_block(writer: &mut BitWriter, data: &[u8], is_final: bool) {
+    assert!(data.len() <= MAX_STORED_BLOCK_LEN);
+    write_block_header(writer, BTYPE_NO_COMPRESSION, is_final);
+    let len = data.len() as u16;
+    writer.write_bytes(&len.to_le_bytes());
+    writer.write_bytes(&(!len).to_le_bytes());
+    writer.write_bytes(data);
+}
+
+/// Write the tokens as a fixed Huffman block.
+pub(crate) fn write_fixed_block(writer: &mut BitWriter, tokens: &[Token], is_final: bool) {
+    let lit_codes = HuffmanEncodeTable::new(&fixed_literal_code_lengths());
+    let dis_codes = HuffmanEncodeTable::new(&fixed_distance_code_lengths());
+    write_block_header(writer, BTYPE_FIXED_HUFFMAN, is_final);
+    write_tokens(writer, tokens, &lit_codes, &dis_codes);
+}
+
+/// Compress data into a DEFLATE stream made of a single fixed Huffman block.
+pub fn deflate_fixed_to_vec(data: &[u8]) -> Vec<u8> {
+    deflate_fixed_tokens_to_vec(&find_matches(data, &MatchFinderOptions::default()))
+}
+
+/// Number of symbols of the literal/length alphabet, excluding the two unused length codes.
+const LITERAL_LENGTH_SYMBOLS: usize = 286;
+/// Number of symbols of the distance alphabet, excluding the two unused distance codes.
+const DISTANCE_SYMBOLS: usize = 30;
+
+/// Build Huffman code lengths of at most `max_bits` bits from symbol frequencies.
+/// At least two symbols get a code, so that the code is always complete: some decoders reject
+/// the single 1-bit code RFC 1951 allows.
+pub(crate) fn huffman_code_lengths(freqs: &[u64], max_bits: u8) -> Vec<u8> {
+    let mut freqs = freqs.to_vec();
+    let mut missing = 2usize.saturating_sub(freqs.iter().filter(|&&freq| freq > 0).count());
+    for freq in freqs.iter_mut().filter(|freq| **freq == 0) {
+        if missing == 0 {
+            break;
+        }
+        *freq = 1;
+        missing -= 1;
+    }
+    build_code_lengths(&freqs, max_bits)
+}
+
+/// A symbol of the code length alphabet with its extra bits: `(symbol, extra value, extra bits)`.
+pub(crate) type CodeLengthSymbol = (usize, usize, usize);
+
+/// Run-length encode code lengths with the code length alphabet.
+/// Defined in RFC 1951, section 3.2.7:
+/// 0-15 are code lengths, 16 repeats the previous length 3-6 times,
+/// 17 repeats a zero length 3-10 times and 18 repeats it 11-138 times.
+pub(crate) fn run_length_code_lengths(lengths: &[u8]) -> Vec<CodeLengthSymbol> {
+    let mut symbols = Vec::new();
+    let mut i = 0;
+    while i < lengths.len() {
+        let len = lengths[i];
+        let run = lengths[i..].iter().take_while(|&&l| l == len).count();
+        if len == 0 && run >= 11 {
+            let count = run.min(138);
+            symbols.push((18, count - 11, 7));
+            i += count;
+        } else if len == 0 && run >= 3 {
+            symbols.push((17, run - 3, 3));
+            i += run;
+        } else if len != 0 && run >= 4 {
+            // The length itself, then repeats of it.
+            symbols.push((len as usize, 0, 0));
+            let count = (run - 1).min(6);
+            symbols.push((16, count - 3, 2));
+            i += count + 1;
+        } else {
+            symbols.push((len as usize, 0, 0));
+            i += 1;
+        }
+    }
+    symbols
+}
+
+/// Write the HLIT, HDIST and HCLEN fields of a dynamic block header, the code length code
+/// and the code length symbols, built for the symbols they encode.
+pub(crate) fn write_dynamic_header(
+    writer: &mut BitWriter,
+    hlit: usize,
+    hdist: usize,
+    cl_symbols: &[CodeLengthSymbol],
+) {
+    let mut cl_freqs = vec![0; DYN_ALPHABET_CODE_NUM];
+    cl_symbols
+        .iter()
+        .for_each(|&(symbol, _, _)| cl_freqs[symbol] += 1);
+    let cl_lengths = huffman_code_lengths(&cl_freqs, DYN_ALPHABET_TABLE_MAX_BITS);
+    let hclen = HCLEN_BASE.max(
+        DYNAMIC_HUFFMAN_TREE_ORDER
+            .iter()
+            .rposition(|&symbol| cl_lengths[symbol] != 0)
+            .unwrap()
+            + 1,
+    );
+
+    writer.write_bits(hlit - HLIT_BASE, HLIT_LEN);
+    writer.write_bits(hdist - HDIST_BASE, HDIST_LEN);
+    writer.write_bits(hclen - HCLEN_BASE, HCLEN_LEN);
+    for &symbol in &DYNAMIC_HUFFMAN_TREE_ORDER[..hclen] {
+        writer.write_bits(cl_lengths[symbol] as usize, DYN_ALPHABET_CODE_LEN);
+    }
+    let cl_codes = HuffmanEncodeTable::new(&cl_lengths);
+    for &(symbol, extra, extra_bits) in cl_symbols {
+        cl_codes.write_symbol(writer, symbol);
+        writer.write_bits(extra, extra_bits);
+    }
+}
+
+/// Count the literal/length and distance symbols the tokens encode to,
+/// including the end-of-block symbol.
+pub(crate) fn symbol_frequencies(tokens: &[Token]) -> (Vec<u64>, Vec<u64>) {
+    let mut lit_freqs = vec![0; LITERAL_LENGTH_SYMBOLS];
+    let mut dis_freqs = vec![0; DISTANCE_SYMBOLS];
+    for &token in tokens {
+        match token {
+            Token::Literal(byte) => lit_freqs[byte as usize] += 1,
+            Token::Match { length, distance } => {
+                lit_freqs[length_to_code(length).unwrap().0] += 1;
+                dis_freqs[distance_to_code(distance).unwrap().0] += 1;
+            }
+        }
+    }
+    lit_freqs[END_BLOCK_CODE] += 1;
+    (lit_freqs, dis_freqs)
+}
+
+/// Encode tokens as a single final block with dynamic Huffman codes built for them.
+/// Defined in RFC 1951, section 3.2.7.
+///
+/// # Panics
+///
+/// Panics if a match is longer than `MAX_MATCH_LEN`, shorter than `MIN_MATCH_LEN`,
+/// or reaches further back than `MAX_DISTANCE`.
+pub fn deflate_dynamic_tokens_to_vec(tokens: &[Token]) -> Vec<u8> {
+    let mut writer = BitWriter::new();
+    write_dynamic_block(&mut writer, tokens, true);
+    writer.into_vec()
+}
+
+/// Write the tokens as a dynamic Huffman block with codes built for them.
+pub(crate) fn write_dynamic_block(writer: &mut BitWriter, tokens: &[Token], is_final: bool) {
+    let (lit_freqs, dis_freqs) = symbol_frequencies(tokens);
+    let lit_lengths = huffman_code_lengths(&lit_freqs, DYN_TABLE_MAX_BITS);
+    let dis_lengths = huffman_code_lengths(&dis_freqs, DYN_TABLE_MAX_BITS);
+
+    // Trailing unused symbols are left out, down to the minimum counts.
+    let hlit = HLIT_BASE.max(lit_lengths.iter().rposition(|&len| len != 0).unwrap() + 1);
+    let hdist = HDIST_BASE.max(dis_lengths.iter().rposition(|&len| len != 0).unwrap() + 1);
+    // Each alphabet is run-length encoded on its own, although RFC 1951 lets a run carry over
+    // from one into the other.
+    let mut cl_symbols = run_length_code_lengths(&lit_lengths[..hlit]);
+    cl_symbols.extend(run_length_code_lengths(&dis_lengths[..hdist]));
+
+    write_block_header(writer, BTYPE_DYNAMIC_HUFFMAN, is_final);
+    write_dynamic_header(writer, hlit, hdist, &cl_symbols);
+    let lit_codes = HuffmanEncodeTable::new(&lit_lengths);
+    let dis_codes = HuffmanEncodeTable::new(&dis_lengths);
+    write_tokens(writer, tokens, &lit_codes, &dis_codes);
+}
+
+/// Compress data into a DEFLATE stream made of a single dynamic Huffman block.
+pub fn deflate_dynamic_to_vec(data: &[u8]) -> Vec<u8> {
+    deflate_dynamic_tokens_to_vec(&find_matches(data, &MatchFinderOptions::default()))
+}
+
+/// The compression level used when none is given.
+pub const DEFAULT_LEVEL: u8 = 6;
+/// The highest compression level.
+pub const MAX_LEVEL: u8 = 9;
+
+/// Match finder settings for a compression level from 1 to `MAX_LEVEL`,
+/// trading speed for size like zlib's levels do.
+fn level_options(level: u8) -> MatchFinderOptions {
+    let (max_chain_len, strategy) = match level {
+        1 => (4, MatchStrategy::Greedy),
+        2 => (8, MatchStrategy::Greedy),
+        3 => (16, MatchStrategy::Greedy),
+        4 => (16, MatchStrategy::Lazy),
+        5 => (32, MatchStrategy::Lazy),
+        6 => (128, MatchStrategy::Lazy),
+        7 => (256, MatchStrategy::Lazy),
+        8 => (1024, MatchStrategy::Lazy),
+        _ => (4096, MatchStrategy::Lazy),
+    };
+    MatchFinderOptions {
+        max_chain_len,
+        strategy,
+        ..Default::default()
+    }
+}
+
+/// Compress data into a DEFLATE stream at the given level, from 0 to `MAX_LEVEL`.
+/// Level 0 only uses stored blocks; higher levels search longer for matches and keep the
+/// smallest of the stored, fixed Huffman and dynamic Huffman encodings.
+///
+/// # Panics
+///
+/// Panics if the level is above `MAX_LEVEL`.
+pub fn deflate_to_vec_with_level(data: &[u8], level: u8) -> Vec<u8> {
+    assert!(level <= MAX_LEVEL, "Invalid compression level {}", level);
+    if level == 0 {
+        return deflate_stored_to_vec(data);
+    }
+    let tokens = find_matches(data, &level_options(level));
+    [
+        deflate_stored_to_vec(data),
+        deflate_fixed_tokens_to_vec(&tokens),
+        deflate_dynamic_tokens_to_vec(&tokens),
+    ]
+    .into_iter()
+    .min_by_key(Vec::len)
+    .unwrap()
+}
+
+/// Compress data into a DEFLATE stream at the default level.
+pub fn deflate_to_vec(data: &[u8]) -> Vec<u8> {
+    deflate_to_vec_with_level(data, DEFAULT_LEVEL)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inflate::inflate_to_vec;
+
+    #[test]
+    fn test_deflate_stored_empty() {
+        let data = deflate_stored_to_vec(b"");
+        assert_eq!(data, [0x01, 0x00, 0x00, 0xff, 0xff]);
+        assert_eq!(inflate_to_vec(&data).unwrap(), b"");
+    }
+
+    #[test]
+    fn test_deflate_stored_round_trip() {
+        for len in [1, MAX_STORED_BLOCK_LEN, MAX_STORED_BLOCK_LEN + 1, 200_000] {
+            let raw: Vec<u8> = (0..len).map(|i| (i * 7 % 256) as u8).collect();
+            let data = deflate_stored_to_vec(&raw);
+            assert_eq!(data.len(), len + len.div_ceil(MAX_STORED_BLOCK_LEN) * 5);
+            assert_eq!(inflate_to_vec(&data).unwrap(), raw);
+            assert_eq!(miniz_oxide::inflate::decompress_to_vec(&data).unwrap(), raw);
+        }
+    }
+
+    #[test]
+    fn test_deflate_fixed_tokens() {
+        // 'a', then a match of length 3 repeating it.
+        let tokens = [
+            Token::Literal(b'a'),
+            Token::Match {
+                length: 3,
+                distance: 1,
+            },
+        ];
+        let data = deflate_fixed_tokens_to_vec(&tokens);
+        assert_eq!(inflate_to_vec(&data).unwrap(), b"aaaa");
+
+        let tokens = [
+            Token::Literal(b'x'),
+            Token::Literal(b'y'),
+            Token::Match {
+                length: MAX_MATCH_LEN,
+                distance: 2,
+            },
+            Token::Literal(0xff),
+        ];
+        let data = deflate_fixed_tokens_to_vec(&tokens);
+        let mut expected = b"xy".repeat(MAX_MATCH_LEN / 2 + 1);
+        expected.push(0xff);
+        assert_eq!(inflate_to_vec(&data).unwrap(), expected);
+        assert_eq!(
+            miniz_oxide::inflate::decompress_to_vec(&data).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_deflate_fixed_round_trip() {
+        let mut raw = b"abc".to_vec();
+        raw.extend([b'z'; 1000]);
+        raw.extend(0..=255);
+        raw.extend([0; 4]);
+        let data = deflate_fixed_to_vec(&raw);
+        assert!(data.len() < raw.len());
+        assert_eq!(inflate_to_vec(&data).unwrap(), raw);
+        assert_eq!(inflate_to_vec(&deflate_fixed_to_vec(b"")).unwrap(), b"");
+    }
+
+    #[test]
+    fn test_huffman_code_lengths() {
+        assert_eq!(huffman_code_lengths(&[1, 1, 2, 4], 15), [3, 3, 2, 1]);
+        // A single used symbol still gets a complete code.
+        assert_eq!(huffman_code_lengths(&[0, 0, 5], 15), [1, 0, 1]);
+        // Fibonacci frequencies make the deepest tree, which must be flattened to fit.
+        let mut freqs = vec![1, 1];
+        while freqs.len() < 30 {
+            freqs.push(freqs[freqs.len() - 1] + freqs[freqs.len() - 2]);
+        }
+        let lengths = huffman_code_lengths(&freqs, 15);
+        assert_eq!(lengths.iter().max(), Some(&15));
+        let kraft: f64 = lengths.iter().map(|&len| 0.5f64.powi(len as i32)).sum();
+        assert_eq!(kraft, 1.0);
+    }
+
+    #[test]
+    fn test_run_length_code_lengths() {
+        let mut lengths = vec![8; 5];
+        lengths.extend([0; 150]);
+        lengths.extend([5, 5, 0, 0, 0]);
+        assert_eq!(
+            run_length_code_lengths(&lengths),
+            [
+                (8, 0, 0),
+                (16, 1, 2),
+                (18, 127, 7),
+                (18, 1, 7),
+                (5, 0, 0),
+                (5, 0, 0),
+                (17, 0, 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_deflate_dynamic_round_trip() {
+        let text = b"The quick brown fox jumps over the lazy dog. Aaaaaaaaaaah!\n".repeat(20);
+        let mut seed = 0x2545_f491u32;
+        let binary: Vec<u8> = (0..5000)
+            .map(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                seed as u8
+            })
+            .collect();
+        for raw in [&b""[..], b"a", b"zzzzzzzzzzzz", &text, &binary] {
+            let data = deflate_dynamic_to_vec(raw);
+            assert_eq!(inflate_to_vec(&data).unwrap(), raw);
+            assert_eq!(miniz_oxide::inflate::decompress_to_vec(&data).unwrap(), raw);
+            assert_eq!(inflate_to_vec(&deflate_to_vec(raw)).unwrap(), raw);
+        }
+        // Data drawn from a few bytes is where dynamic codes beat the fixed ones.
+        let skewed: Vec<_> = binary.iter().map(|b| b"etaoin"[*b as usize % 6]).collect();
+        let data = deflate_dynamic_to_vec(&skewed);
+        assert_eq!(inflate_to_vec(&data).unwrap(), skewed);
+        assert!(data.len() < deflate_fixed_to_vec(&skewed).len());
+        assert_eq!(
+            deflate_to_vec(&binary).len(),
+            deflate_stored_to_vec(&binary).len()
+        );
+    }
+
+    #[test]
+    fn test_deflate_levels() {
+        let text = b"It was the best of times, it was the worst of times, it was the age of wisdom"
+            .repeat(40);
+        let sizes: Vec<_> = (0..=MAX_LEVEL)
+            .map(|level| {
+                let data = deflate_to_vec_with_level(&text, level);
+                assert_eq!(inflate_to_vec(&data).unwrap(), text);
+                data.len()
+            })
+            .collect();
+        assert_eq!(sizes[0], deflate_stored_to_vec(&text).len());
+        assert!(sizes[1] < sizes[0]);
+        assert!(sizes[MAX_LEVEL as usize] <= sizes[1]);
+        assert_eq!(deflate_to_vec(&text).len(), sizes[DEFAULT_LEVEL as usize]);
+    }
+}
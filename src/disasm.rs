@@ -0,0 +1,647 @@
+//! Disassemble a DEFLATE stream into the fields it is made of.
+//!
+//! Every bit of a stream belongs to some field: a block header, a code length, a Huffman-coded
+//! symbol, the extra bits of a match length... [`disassemble`] decodes a stream and records each
+//! field with its bit range, building an index that can tell what any given bit is, e.g. while
+//! reading a hex dump.
+
+use crate::bit_stream::BitReader;
+use crate::container::{self, Format};
+use crate::error::InflateError;
+use crate::inflate::{inflate_block, InflateState};
+use crate::text::TextOptions;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Range;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The two alphabets whose code lengths a dynamic block header describes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Alphabet {
+    LiteralLength,
+    Distance,
+}
+
+impl fmt::Display for Alphabet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Alphabet::LiteralLength => write!(f, "literal/length"),
+            Alphabet::Distance => write!(f, "distance"),
+        }
+    }
+}
+
+/// What a field of the stream holds.
+/// In exported traces, the variant name is the snake case `kind` and its data the `value`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(tag = "kind", content = "value", rename_all = "snake_case")
+)]
+pub enum FieldKind {
+    /// The zlib or gzip header before the DEFLATE data.
+    ContainerHeader(Format),
+    /// The zlib or gzip trailer after the DEFLATE data.
+    ContainerTrailer(Format),
+    /// The BFINAL bit of a block header.
+    BlockFinal(bool),
+    /// The BTYPE field of a block header.
+    BlockType(usize),
+    /// The bits skipped to reach a byte boundary before the LEN field of a stored block.
+    StoredPadding,
+    /// The LEN field of a stored block.
+    StoredLen(usize),
+    /// The NLEN field of a stored block.
+    StoredNlen(usize),
+    /// The data bytes of a stored block.
+    StoredData { len: usize },
+    /// The number of literal/length codes of a dynamic block.
+    Hlit(usize),
+    /// The number of distance codes of a dynamic block.
+    Hdist(usize),
+    /// The number of code length codes of a dynamic block.
+    Hclen(usize),
+    /// The code length of a symbol of the code length alphabet.
+    CodeLengthCodeLength { symbol: usize, len: usize },
+    /// The code length of a symbol, given directly.
+    CodeLength {
+        alphabet: Alphabet,
+        symbol: usize,
+        len: usize,
+    },
+    /// A code repeating a code length (16, 17 or 18), starting at the given symbol.
+    CodeLengthRepeat {
+        alphabet: Alphabet,
+        symbol: usize,
+        code: usize,
+    },
+    /// The extra bits giving how many times a repeat code applies.
+    CodeLengthRepeatCount {
+        alphabet: Alphabet,
+        symbol: usize,
+        count: usize,
+    },
+    /// A literal byte.
+    Literal(u8),
+    /// The end-of-block symbol.
+    EndOfBlock,
+    /// The length code of a match.
+    LengthCode { code: usize, length: usize },
+    /// The extra bits of the length of a match.
+    LengthExtra { length: usize },
+    /// The distance code of a match.
+    DistanceCode { code: usize, distance: usize },
+    /// The extra bits of the distance of a match.
+    DistanceExtra { distance: usize },
+    /// The bits after the final block, up to the byte boundary.
+    FinalPadding,
+}
+
+/// A field of the stream: what it holds and where it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Field {
+    /// Bit offset of the first bit of the field.
+    pub bit_offset: usize,
+    /// Number of bits in the field.
+    pub bit_len: usize,
+    /// Index of the block the field belongs to, if any.
+    pub block_index: Option<usize>,
+    /// Output offset at which the field's data goes, or the output size so far.
+    pub output_offset: usize,
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub kind: FieldKind,
+}
+
+pub(crate) fn format_name(format: Format) -> &'static str {
+    match format {
+        Format::Raw => "raw DEFLATE",
+        Format::Zlib => "zlib",
+        Format::Gzip => "gzip",
+    }
+}
+
+pub(crate) fn block_type_name(b_type: usize) -> &'static str {
+    match b_type {
+        0 => "stored",
+        1 => "fixed Huffman",
+        2 => "dynamic Huffman",
+        _ => "reserved",
+    }
+}
+
+impl Field {
+    /// Describe the field in a few words.
+    pub fn describe(&self) -> String {
+        let block = self.block_index.unwrap_or_default();
+        let offset = self.output_offset;
+        match self.kind {
+            FieldKind::ContainerHeader(format) => format!("the {} header", format_name(format)),
+            FieldKind::ContainerTrailer(format) => format!("the {} trailer", format_name(format)),
+            FieldKind::BlockFinal(is_final) => format!(
+                "the BFINAL flag of block {} ({})",
+                block,
+                if is_final { "final" } else { "not final" }
+            ),
+            FieldKind::BlockType(b_type) => format!(
+                "the BTYPE field of block {} ({})",
+                block,
+                block_type_name(b_type)
+            ),
+            FieldKind::StoredPadding => format!("the padding before LEN in stored block {}", block),
+            FieldKind::StoredLen(len) => {
+                format!("the LEN field of stored block {} ({})", block, len)
+            }
+            FieldKind::StoredNlen(nlen) => {
+                format!("the NLEN field of stored block {} ({:#06x})", block, nlen)
+            }
+            FieldKind::StoredData { len } => format!(
+                "the {} data bytes of stored block {} at output offset {}",
+                len, block, offset
+            ),
+            FieldKind::Hlit(n) => format!("the HLIT field of block {} ({} codes)", block, n),
+            FieldKind::Hdist(n) => format!("the HDIST field of block {} ({} codes)", block, n),
+            FieldKind::Hclen(n) => format!("the HCLEN field of block {} ({} codes)", block, n),
+            FieldKind::CodeLengthCodeLength { symbol, len } => format!(
+                "the length of code length symbol {} in block {} ({})",
+                symbol, block, len
+            ),
+            FieldKind::CodeLength {
+                alphabet,
+                symbol,
+                len,
+            } => format!(
+                "the code length of {} symbol {} in block {} ({})",
+                alphabet, symbol, block, len
+            ),
+            FieldKind::CodeLengthRepeat {
+                alphabet,
+                symbol,
+                code,
+            } => format!(
+                "the repeat code {} starting at {} symbol {} in block {}",
+                code, alphabet, symbol, block
+            ),
+            FieldKind::CodeLengthRepeatCount {
+                alphabet,
+                symbol,
+                count,
+            } => format!(
+                "the repeat count of the code lengths starting at {} symbol {} in block {} ({})",
+                alphabet, symbol, block, count
+            ),
+            FieldKind::Literal(byte) if byte.is_ascii_graphic() || byte == b' ' => {
+                format!("the literal '{}' at output offset {}", byte as char, offset)
+            }
+            FieldKind::Literal(byte) => {
+                format!("the literal {:#04x} at output offset {}", byte, offset)
+            }
+            FieldKind::EndOfBlock => format!("the end-of-block symbol of block {}", block),
+            FieldKind::LengthCode { code, length } => format!(
+                "the length code {} (length {}) of the match at output offset {}",
+                code, length, offset
+            ),
+            FieldKind::LengthExtra { length } => format!(
+                "the length extra bits (length {}) of the match at output offset {}",
+                length, offset
+            ),
+            FieldKind::DistanceCode { code, distance } => format!(
+                "the distance code {} (distance {}) of the match at output offset {}",
+                code, distance, offset
+            ),
+            FieldKind::DistanceExtra { distance } => format!(
+                "the distance extra bits (distance {}) of the match at output offset {}",
+                distance, offset
+            ),
+            FieldKind::FinalPadding => "the padding after the final block".to_string(),
+        }
+    }
+}
+
+/// The fields of a stream, in stream order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Disassembly {
+    /// The container the stream was decoded from.
+    pub format: Format,
+    pub fields: Vec<Field>,
+    /// The error decoding stopped at, if any.
+    pub error: Option<InflateError>,
+    /// Bit offset at which decoding stopped.
+    pub end_bit_offset: usize,
+    /// Number of bits in the data.
+    pub data_bits: usize,
+}
+
+impl Disassembly {
+    /// Find the field the given bit belongs to.
+    pub fn field_at(&self, bit_offset: usize) -> Option<&Field> {
+        let i = self
+            .fields
+            .partition_point(|f| f.bit_offset + f.bit_len <= bit_offset);
+        self.fields.get(i).filter(|f| f.bit_offset <= bit_offset)
+    }
+
+    /// Explain in a sentence what the given bit is.
+    pub fn explain(&self, bit_offset: usize) -> String {
+        self.explain_with(bit_offset, &TextOptions::default())
+    }
+
+    /// Explain in a sentence what the given bit is, written as the options say.
+    pub fn explain_with(&self, bit_offset: usize, options: &TextOptions) -> String {
+        let Some(field) = self.field_at(bit_offset) else {
+            return match self.error {
+                _ if bit_offset >= self.data_bits => {
+                    format!("bit {} is past the end of the data", bit_offset)
+                }
+                Some(error) if bit_offset >= self.end_bit_offset => format!(
+                    "bit {} comes after decoding failed at bit {}: {}",
+                    bit_offset,
+                    self.end_bit_offset,
+                    options.error(&error)
+                ),
+                _ => format!("bit {} is after the end of the stream", bit_offset),
+            };
+        };
+        let index = bit_offset - field.bit_offset;
+        match field.kind {
+            FieldKind::LengthExtra { .. } | FieldKind::DistanceExtra { .. } => format!(
+                "bit {} is extra bit {} of the {} for the match at output offset {}",
+                bit_offset,
+                index,
+                if matches!(field.kind, FieldKind::LengthExtra { .. }) {
+                    "length"
+                } else {
+                    "distance"
+                },
+                field.output_offset
+            ),
+            _ if field.bit_len == 1 => format!("bit {} is {}", bit_offset, field.describe()),
+            _ => format!(
+                "bit {} is bit {} of {}",
+                bit_offset,
+                index,
+                field.describe()
+            ),
+        }
+    }
+}
+
+/// Decode a stream inside the given container and record all of its fields.
+/// Bit offsets count from the start of `data`, container header included.
+/// Decoding stops at the first error, which is recorded along with the fields before it.
+pub fn disassemble(data: &[u8], format: Format) -> Disassembly {
+    let mut state = InflateState::new();
+    state.trace = Some(Vec::new());
+    let mut disassembly = Disassembly {
+        format,
+        fields: Vec::new(),
+        error: None,
+        end_bit_offset: 0,
+        data_bits: data.len() * 8,
+    };
+
+    if data.is_empty() {
+        disassembly.error = Some(InflateError::EmptyInput);
+        return disassembly;
+    }
+    let start = match container::header_len(data, format) {
+        Ok(len) => len,
+        Err(e) => {
+            disassembly.error = Some(e);
+            return disassembly;
+        }
+    };
+    state.record(0, start * 8, FieldKind::ContainerHeader(format));
+
+    let mut bit_reader = BitReader::new_strict(data);
+    bit_reader.advance(start * 8);
+    let result = loop {
+        match inflate_block(&mut bit_reader, &mut state) {
+            Ok(true) => break Ok(()),
+            Ok(false) => {}
+            Err(e) => break Err(e),
+        }
+    };
+    if result.is_ok() {
+        let end = bit_reader.bits_consumed();
+        bit_reader.advance_to_byte_boundary();
+        let aligned = bit_reader.bits_consumed();
+        state.record(end, aligned - end, FieldKind::FinalPadding);
+        let trailer_len = match format {
+            Format::Raw => 0,
+            Format::Zlib => container::ZLIB_TRAILER_LEN,
+            Format::Gzip => container::GZIP_TRAILER_LEN,
+        };
+        let trailer_len = (trailer_len * 8).min(disassembly.data_bits.saturating_sub(aligned));
+        state.record(aligned, trailer_len, FieldKind::ContainerTrailer(format));
+    }
+
+    disassembly.error = result.err();
+    disassembly.end_bit_offset = bit_reader.bits_consumed();
+    disassembly.fields = state.trace.take().unwrap_or_default();
+    disassembly
+}
+
+/// An entry of an annotated dump of a stream: a range of bits, what they are and their value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Annotation {
+    pub bit_range: Range<usize>,
+    pub label: String,
+    pub value: String,
+}
+
+impl fmt::Display for Annotation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "bits {}..{}: {} = {}",
+            self.bit_range.start, self.bit_range.end, self.label, self.value
+        )
+    }
+}
+
+/// Get the bits of a range in the order they are read, which is the order of the bits of a
+/// Huffman code, most significant first.
+fn stream_bits(data: &[u8], bit_range: Range<usize>) -> String {
+    bit_range
+        .map(|i| {
+            if (data[i / 8] >> (i % 8)) & 1 == 0 {
+                '0'
+            } else {
+                '1'
+            }
+        })
+        .collect()
+}
+
+/// Get the bits of a range as a number, written with its most significant bit first.
+/// The first bit read is the least significant one, as for every field but Huffman codes.
+fn value_bits(data: &[u8], bit_range: Range<usize>) -> String {
+    stream_bits(data, bit_range).chars().rev().collect()
+}
+
+/// Describe a Huffman code, e.g. `(code 10110, 5 bits)`.
+fn huffman_code(data: &[u8], field: &Field) -> String {
+    let bit_range = field.bit_offset..field.bit_offset + field.bit_len;
+    format!(
+        "(code {}, {} bits)",
+        stream_bits(data, bit_range),
+        field.bit_len
+    )
+}
+
+impl Field {
+    /// Annotate the field with its label and value, given the data the field was read from.
+    pub fn annotate(&self, data: &[u8]) -> Annotation {
+        let bit_range = self.bit_offset..self.bit_offset + self.bit_len;
+        let bits = value_bits(data, bit_range.clone());
+        let (label, value) = match self.kind {
+            FieldKind::ContainerHeader(format) => (
+                format!("{} header", format_name(format)),
+                format!("{} bytes", self.bit_len / 8),
+            ),
+            FieldKind::ContainerTrailer(format) => (
+                format!("{} trailer", format_name(format)),
+                format!("{} bytes", self.bit_len / 8),
+            ),
+            FieldKind::BlockFinal(is_final) => (
+                "BFINAL".to_string(),
+                format!(
+                    "{} ({})",
+                    bits,
+                    if is_final { "final" } else { "not final" }
+                ),
+            ),
+            FieldKind::BlockType(b_type) => (
+                "BTYPE".to_string(),
+                format!("{} ({})", bits, block_type_name(b_type)),
+            ),
+            FieldKind::StoredPadding | FieldKind::FinalPadding => ("padding".to_string(), bits),
+            FieldKind::StoredLen(len) => ("LEN".to_string(), len.to_string()),
+            FieldKind::StoredNlen(nlen) => ("NLEN".to_string(), format!("{:#06x}", nlen)),
+            FieldKind::StoredData { len } => ("stored data".to_string(), format!("{} bytes", len)),
+            FieldKind::Hlit(n) => ("HLIT".to_string(), format!("{} ({} codes)", bits, n)),
+            FieldKind::Hdist(n) => ("HDIST".to_string(), format!("{} ({} codes)", bits, n)),
+            FieldKind::Hclen(n) => ("HCLEN".to_string(), format!("{} ({} codes)", bits, n)),
+            FieldKind::CodeLengthCodeLength { symbol, len } => (
+                format!("code length code length of symbol {}", symbol),
+                len.to_string(),
+            ),
+            FieldKind::CodeLength {
+                alphabet,
+                symbol,
+                len,
+            } => (
+                format!("{} code length of symbol {}", alphabet, symbol),
+                format!("{} {}", len, huffman_code(data, self)),
+            ),
+            FieldKind::CodeLengthRepeat {
+                alphabet,
+                symbol,
+                code,
+            } => (
+                format!("{} code length repeat from symbol {}", alphabet, symbol),
+                format!("{} {}", code, huffman_code(data, self)),
+            ),
+            FieldKind::CodeLengthRepeatCount { count, .. } => {
+                ("repeat count".to_string(), format!("{} ({})", bits, count))
+            }
+            FieldKind::Literal(byte) if byte.is_ascii_graphic() || byte == b' ' => (
+                "lit/len symbol".to_string(),
+                format!("'{}' {}", byte as char, huffman_code(data, self)),
+            ),
+            FieldKind::Literal(byte) => (
+                "lit/len symbol".to_string(),
+                format!("{:#04x} {}", byte, huffman_code(data, self)),
+            ),
+            FieldKind::EndOfBlock => (
+                "lit/len symbol".to_string(),
+                format!("end of block {}", huffman_code(data, self)),
+            ),
+            FieldKind::LengthCode { code, length } => (
+                "lit/len symbol".to_string(),
+                format!("{}, length {} {}", code, length, huffman_code(data, self)),
+            ),
+            FieldKind::LengthExtra { length } => (
+                "length extra bits".to_string(),
+                format!("{} (length {})", bits, length),
+            ),
+            FieldKind::DistanceCode { code, distance } => (
+                "distance symbol".to_string(),
+                format!(
+                    "{}, distance {} {}",
+                    code,
+                    distance,
+                    huffman_code(data, self)
+                ),
+            ),
+            FieldKind::DistanceExtra { distance } => (
+                "distance extra bits".to_string(),
+                format!("{} (distance {})", bits, distance),
+            ),
+        };
+        Annotation {
+            bit_range,
+            label,
+            value,
+        }
+    }
+}
+
+/// Annotate every field of a stream inside the given container, in stream order.
+/// The annotations stop where decoding fails; [`disassemble`] tells why.
+pub fn annotate(data: &[u8], format: Format) -> Vec<Annotation> {
+    disassemble(data, format)
+        .fields
+        .iter()
+        .map(|field| field.annotate(data))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disassemble_fixed_block() {
+        // 'a', then a match of length 3 at distance 1, from the fixed block of the inflate tests:
+        // BFINAL, BTYPE, 'a' (8 bits), length 3 (7 bits), distance code 0 (5 bits), end of block.
+        let data = crate::deflate::deflate_fixed_tokens_to_vec(&[
+            crate::deflate::Token::Literal(b'a'),
+            crate::deflate::Token::Match {
+                length: 3,
+                distance: 1,
+            },
+        ]);
+        let disassembly = disassemble(&data, Format::Raw);
+        assert_eq!(disassembly.error, None);
+        let kinds: Vec<_> = disassembly.fields.iter().map(|f| f.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                FieldKind::BlockFinal(true),
+                FieldKind::BlockType(1),
+                FieldKind::Literal(b'a'),
+                FieldKind::LengthCode {
+                    code: 257,
+                    length: 3
+                },
+                FieldKind::DistanceCode {
+                    code: 0,
+                    distance: 1
+                },
+                FieldKind::EndOfBlock,
+                FieldKind::FinalPadding,
+            ]
+        );
+        assert_eq!(disassembly.fields[3].bit_offset, 11);
+        assert_eq!(disassembly.fields[3].output_offset, 1);
+        assert_eq!(
+            disassembly.explain(12),
+            "bit 12 is bit 1 of the length code 257 (length 3) of the match at output offset 1"
+        );
+        assert_eq!(
+            disassembly.explain(0),
+            "bit 0 is the BFINAL flag of block 0 (final)"
+        );
+        assert_eq!(
+            disassembly.explain(100),
+            "bit 100 is past the end of the data"
+        );
+    }
+
+    #[test]
+    fn test_disassemble_extra_bits() {
+        let raw = b"abcdefghij".repeat(30);
+        let data = miniz_oxide::deflate::compress_to_vec_zlib(&raw, 9);
+        let disassembly = disassemble(&data, Format::Zlib);
+        assert_eq!(disassembly.error, None);
+        assert_eq!(
+            disassembly.fields[0].kind,
+            FieldKind::ContainerHeader(Format::Zlib)
+        );
+        assert_eq!(
+            disassembly.fields.last().unwrap().kind,
+            FieldKind::ContainerTrailer(Format::Zlib)
+        );
+        // Fields tile the data without gaps or overlaps.
+        for pair in disassembly.fields.windows(2) {
+            assert_eq!(pair[0].bit_offset + pair[0].bit_len, pair[1].bit_offset);
+        }
+        let extra = disassembly
+            .fields
+            .iter()
+            .find(|f| matches!(f.kind, FieldKind::LengthExtra { .. }))
+            .unwrap();
+        assert!(disassembly.explain(extra.bit_offset).starts_with(&format!(
+            "bit {} is extra bit 0 of the length",
+            extra.bit_offset
+        )));
+    }
+
+    #[test]
+    fn test_disassemble_error() {
+        let disassembly = disassemble(&[0b111, 0xff], Format::Raw);
+        assert_eq!(disassembly.error, Some(InflateError::InvalidBlockType));
+        assert_eq!(
+            disassembly.explain(9),
+            "bit 9 comes after decoding failed at bit 3: Invalid block type"
+        );
+    }
+
+    #[test]
+    fn test_annotate() {
+        let mut tokens: Vec<_> = b"abcde"
+            .iter()
+            .map(|&byte| crate::deflate::Token::Literal(byte))
+            .collect();
+        tokens.push(crate::deflate::Token::Match {
+            length: 12,
+            distance: 5,
+        });
+        let data = crate::deflate::deflate_fixed_tokens_to_vec(&tokens);
+        let lines: Vec<_> = annotate(&data, Format::Raw)
+            .iter()
+            .map(Annotation::to_string)
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "bits 0..1: BFINAL = 1 (final)",
+                "bits 1..3: BTYPE = 01 (fixed Huffman)",
+                "bits 3..11: lit/len symbol = 'a' (code 10010001, 8 bits)",
+                "bits 11..19: lit/len symbol = 'b' (code 10010010, 8 bits)",
+                "bits 19..27: lit/len symbol = 'c' (code 10010011, 8 bits)",
+                "bits 27..35: lit/len symbol = 'd' (code 10010100, 8 bits)",
+                "bits 35..43: lit/len symbol = 'e' (code 10010101, 8 bits)",
+                "bits 43..50: lit/len symbol = 265, length 12 (code 0001001, 7 bits)",
+                "bits 50..51: length extra bits = 1 (length 12)",
+                "bits 51..56: distance symbol = 4, distance 5 (code 00100, 5 bits)",
+                "bits 56..57: distance extra bits = 0 (distance 5)",
+                "bits 57..64: lit/len symbol = end of block (code 0000000, 7 bits)",
+            ]
+        );
+
+        // Stored blocks, and the numbers of codes of a dynamic block.
+        let lines = annotate(&[0b001, 1, 0, !1, !0, b'x'], Format::Raw);
+        assert_eq!(lines[2].to_string(), "bits 3..8: padding = 00000");
+        assert_eq!(lines[3].to_string(), "bits 8..24: LEN = 1");
+        assert_eq!(lines[5].to_string(), "bits 40..48: stored data = 1 bytes");
+        let data = miniz_oxide::deflate::compress_to_vec(&b"annotate me ".repeat(10), 9);
+        let hlit = annotate(&data, Format::Raw)
+            .into_iter()
+            .find(|a| a.label == "HLIT")
+            .unwrap();
+        assert_eq!(hlit.bit_range, 3..8);
+    }
+}
@@ -0,0 +1,228 @@
+//! Estimate how close the Huffman codes of a DEFLATE stream are to optimal, block by block.
+//!
+//! For each alphabet of a block, [`estimate`] counts the symbols the block codes and compares
+//! three costs: the bits the block spends on them, the bits an optimal Huffman code built for
+//! the same counts would spend, and their zeroth-order entropy, the bound no code of whole
+//! bits per symbol goes below. The gap between the first two are the bits the compressor
+//! wasted on its choice of code; the gap between the last two is what Huffman coding itself
+//! costs. Extra bits, stored data and the bits describing the codes are left out of all three.
+
+use crate::codes::END_BLOCK_CODE;
+use crate::disasm::{block_type_name, Field, FieldKind};
+use crate::error::Result;
+use crate::inflate::{for_each_block, BlockInfo};
+use crate::text::{TableFormat, TextOptions};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap};
+
+/// The costs of the symbols a block codes with one of its alphabets.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AlphabetCost {
+    /// Number of symbols coded.
+    pub symbols: usize,
+    /// Number of bits the block spends on their codes.
+    pub actual_bits: usize,
+    /// Number of bits an optimal Huffman code for their counts would spend. A code needs at
+    /// least one bit per symbol, even with a single symbol to code.
+    pub huffman_bits: usize,
+    /// Their zeroth-order entropy times their number, in bits.
+    pub entropy_bits: f64,
+}
+
+impl AlphabetCost {
+    /// Work out the costs from the number of times each symbol is coded and the bits spent.
+    fn new(counts: &BTreeMap<usize, usize>, actual_bits: usize) -> Self {
+        let symbols: usize = counts.values().sum();
+        let entropy_bits = counts
+            .values()
+            .map(|&n| n as f64 * (symbols as f64 / n as f64).log2())
+            .sum();
+        Self {
+            symbols,
+            actual_bits,
+            huffman_bits: huffman_cost(counts.values().copied()),
+            entropy_bits,
+        }
+    }
+
+    /// Get the number of bits spent beyond an optimal Huffman code.
+    pub fn wasted_bits(&self) -> usize {
+        self.actual_bits - self.huffman_bits
+    }
+
+    /// Add the costs of another block.
+    fn merge(&mut self, other: &AlphabetCost) {
+        self.symbols += other.symbols;
+        self.actual_bits += other.actual_bits;
+        self.huffman_bits += other.huffman_bits;
+        self.entropy_bits += other.entropy_bits;
+    }
+}
+
+/// Get the number of bits an optimal Huffman code spends on symbols coded the given numbers of
+/// times: the sum of the weights of the inner nodes of its tree.
+fn huffman_cost(counts: impl Iterator<Item = usize>) -> usize {
+    let mut heap: BinaryHeap<Reverse<usize>> = counts.filter(|&n| n > 0).map(Reverse).collect();
+    if heap.len() == 1 {
+        return heap.pop().map_or(0, |Reverse(n)| n);
+    }
+    let mut cost = 0;
+    while let (Some(Reverse(a)), Some(Reverse(b))) = (heap.pop(), heap.pop()) {
+        cost += a + b;
+        heap.push(Reverse(a + b));
+    }
+    cost
+}
+
+/// The costs of the codes of a block. Both are empty for a stored block.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BlockEntropy {
+    pub info: BlockInfo,
+    /// Literals, lengths and the end of the block.
+    pub literal_length: AlphabetCost,
+    pub distance: AlphabetCost,
+}
+
+impl BlockEntropy {
+    /// Work out the costs of the Huffman-coded symbols of a block.
+    fn new(info: BlockInfo, fields: &[Field]) -> Self {
+        let mut literal_length = (BTreeMap::new(), 0);
+        let mut distance = (BTreeMap::new(), 0);
+        for field in fields {
+            let (alphabet, symbol) = match field.kind {
+                FieldKind::Literal(byte) => (&mut literal_length, byte as usize),
+                FieldKind::EndOfBlock => (&mut literal_length, END_BLOCK_CODE),
+                FieldKind::LengthCode { code, .. } => (&mut literal_length, code),
+                FieldKind::DistanceCode { code, .. } => (&mut distance, code),
+                _ => continue,
+            };
+            *alphabet.0.entry(symbol).or_default() += 1;
+            alphabet.1 += field.bit_len;
+        }
+        Self {
+            info,
+            literal_length: AlphabetCost::new(&literal_length.0, literal_length.1),
+            distance: AlphabetCost::new(&distance.0, distance.1),
+        }
+    }
+
+    /// Get the costs of both alphabets added up.
+    pub fn total(&self) -> AlphabetCost {
+        let mut total = self.literal_length.clone();
+        total.merge(&self.distance);
+        total
+    }
+}
+
+/// The columns of [`entropy_table`].
+const ENTROPY_TABLE_COLUMNS: [&str; 7] = [
+    "block",
+    "type",
+    "symbols",
+    "actual bits",
+    "huffman bits",
+    "entropy bits",
+    "wasted bits",
+];
+
+/// Write a table of the blocks, one row each and a last row of totals, with the costs of both
+/// alphabets added up.
+pub fn entropy_table(
+    blocks: &[BlockEntropy],
+    format: TableFormat,
+    options: &TextOptions,
+) -> String {
+    let row = |block: String, block_type: &str, cost: &AlphabetCost| {
+        Vec::from([
+            block,
+            block_type.to_string(),
+            cost.symbols.to_string(),
+            cost.actual_bits.to_string(),
+            cost.huffman_bits.to_string(),
+            options.float(cost.entropy_bits),
+            cost.wasted_bits().to_string(),
+        ])
+    };
+    let mut total = AlphabetCost::default();
+    let mut rows: Vec<_> = blocks
+        .iter()
+        .enumerate()
+        .map(|(index, block)| {
+            let cost = block.total();
+            total.merge(&cost);
+            row(
+                index.to_string(),
+                block_type_name(block.info.block_type),
+                &cost,
+            )
+        })
+        .collect();
+    rows.push(row("total".to_string(), "", &total));
+    format.table(&ENTROPY_TABLE_COLUMNS, &rows)
+}
+
+/// Decode a raw DEFLATE stream and estimate how close the codes of each block are to optimal.
+/// The whole stream is decoded, so the same errors as
+/// [`inflate_to_vec`](crate::inflate::inflate_to_vec) are reported.
+pub fn estimate(data: &[u8]) -> Result<Vec<BlockEntropy>> {
+    let mut blocks = Vec::new();
+    for_each_block(data, |info, fields| {
+        blocks.push(BlockEntropy::new(info, fields))
+    })?;
+    Ok(blocks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deflate::{deflate_dynamic_to_vec, deflate_fixed_to_vec, deflate_stored_to_vec};
+    use crate::error::InflateError;
+
+    #[test]
+    fn test_huffman_cost() {
+        assert_eq!(huffman_cost([].into_iter()), 0);
+        assert_eq!(huffman_cost([5, 0].into_iter()), 5);
+        // Codes of 1, 2 and 2 bits.
+        assert_eq!(huffman_cost([4, 1, 1].into_iter()), 4 + 2 + 2);
+        assert_eq!(huffman_cost([1, 1, 1, 1].into_iter()), 8);
+
+        let counts = BTreeMap::from([(0, 2), (1, 1), (2, 1)]);
+        let cost = AlphabetCost::new(&counts, 7);
+        assert_eq!(cost.symbols, 4);
+        assert_eq!(cost.huffman_bits, 6);
+        assert_eq!(cost.entropy_bits, 6.0);
+        assert_eq!(cost.wasted_bits(), 1);
+    }
+
+    #[test]
+    fn test_estimate() {
+        let raw = b"a rather skewed text: aaaaaaaaab aaaaaaab aaaaaab ".repeat(50);
+        let fixed = estimate(&deflate_fixed_to_vec(&raw)).unwrap();
+        let dynamic = estimate(&deflate_dynamic_to_vec(&raw)).unwrap();
+        for block in fixed.iter().chain(&dynamic) {
+            for cost in [&block.literal_length, &block.distance] {
+                assert!(cost.entropy_bits <= cost.huffman_bits as f64);
+                assert!(cost.huffman_bits <= cost.actual_bits);
+            }
+        }
+        // Both code the same symbols, but a dynamic code is built for them.
+        let fixed_total = fixed[0].total();
+        let dynamic_total = dynamic[0].total();
+        assert_eq!(fixed_total.symbols, dynamic_total.symbols);
+        assert_eq!(fixed_total.huffman_bits, dynamic_total.huffman_bits);
+        assert!(dynamic_total.wasted_bits() < fixed_total.wasted_bits());
+
+        let stored = estimate(&deflate_stored_to_vec(&raw)).unwrap();
+        assert_eq!(stored[0].total(), AlphabetCost::default());
+
+        let table = entropy_table(&dynamic, TableFormat::Csv, &TextOptions::stable());
+        assert!(table.starts_with("block,type,symbols,"));
+        assert_eq!(table.lines().count(), dynamic.len() + 2);
+
+        assert_eq!(estimate(&[]), Err(InflateError::EmptyInput));
+    }
+}
@@ -0,0 +1,292 @@
+//! The error type shared by the decoding modules.
+
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io;
+
+/// Everything that can go wrong while decoding a DEFLATE stream or its container.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InflateError {
+    /// There is no data at all, not even the header of a block.
+    EmptyInput,
+    /// The data ended before the stream did.
+    UnexpectedEof,
+    /// A block header with the reserved block type 11.
+    InvalidBlockType,
+    /// The LEN and NLEN fields of a stored block are not one's complements.
+    InvalidLenNlen,
+    /// The bits do not decode to a valid symbol of the current alphabet.
+    InvalidHuffmanSymbol,
+    /// A code length symbol of a dynamic block header repeats the previous length at the first
+    /// length, or runs past the last length of its alphabet. `position` is the index of the
+    /// length it stands at, counting the literal/length lengths, then the distance lengths.
+    InvalidCodeLengthEncoding { position: usize },
+    /// A code is longer than the Huffman table built for it supports.
+    CodeTooLong,
+    /// The code lengths describe more codes than the code space can hold.
+    OversubscribedTable,
+    /// The code lengths leave part of the code space unused.
+    IncompleteTable,
+    /// A back-reference reaches before the start of the output.
+    DistanceTooFar,
+    /// A back-reference reaches further back than the window size in effect.
+    DistanceOutsideWindow,
+    /// Data follows the end of the stream.
+    TrailingData,
+    /// The stream needs a preset dictionary that was not supplied.
+    DictionaryRequired,
+    /// The supplied preset dictionary is not the one the stream was compressed with.
+    DictionaryMismatch,
+    /// The zlib or gzip header is invalid.
+    InvalidHeader(&'static str),
+    /// The checksum in the trailer does not match the decompressed data.
+    ChecksumMismatch { expected: u32, found: u32 },
+    /// The output would grow beyond the configured maximum size.
+    OutputLimitExceeded,
+    /// The output does not fit in the buffer provided for it.
+    OutputTooSmall,
+    /// The stream has more blocks, or smaller ones, than the configured limits allow.
+    BlockLimitExceeded,
+    /// The options ask for something the decoder they were given to does not support.
+    UnsupportedOption(&'static str),
+}
+
+impl InflateError {
+    /// Check if the error was caused by the input being cut short rather than corrupted.
+    pub fn is_truncation(&self) -> bool {
+        matches!(self, InflateError::EmptyInput | InflateError::UnexpectedEof)
+    }
+
+    /// Get a short identifier of the error, in snake case.
+    /// Unlike the message, which may be reworded, the code never changes within a major version,
+    /// so it is what stable text output shows.
+    pub fn code(&self) -> &'static str {
+        match self {
+            InflateError::EmptyInput => "empty_input",
+            InflateError::UnexpectedEof => "unexpected_eof",
+            InflateError::InvalidBlockType => "invalid_block_type",
+            InflateError::InvalidLenNlen => "invalid_len_nlen",
+            InflateError::InvalidHuffmanSymbol => "invalid_huffman_symbol",
+            InflateError::InvalidCodeLengthEncoding { .. } => "invalid_code_length_encoding",
+            InflateError::CodeTooLong => "code_too_long",
+            InflateError::OversubscribedTable => "oversubscribed_table",
+            InflateError::IncompleteTable => "incomplete_table",
+            InflateError::DistanceTooFar => "distance_too_far",
+            InflateError::DistanceOutsideWindow => "distance_outside_window",
+            InflateError::TrailingData => "trailing_data",
+            InflateError::DictionaryRequired => "dictionary_required",
+            InflateError::DictionaryMismatch => "dictionary_mismatch",
+            InflateError::InvalidHeader(_) => "invalid_header",
+            InflateError::ChecksumMismatch { .. } => "checksum_mismatch",
+            InflateError::OutputLimitExceeded => "output_limit_exceeded",
+            InflateError::OutputTooSmall => "output_too_small",
+            InflateError::BlockLimitExceeded => "block_limit_exceeded",
+            InflateError::UnsupportedOption(_) => "unsupported_option",
+        }
+    }
+}
+
+impl fmt::Display for InflateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InflateError::EmptyInput => write!(f, "Empty input"),
+            InflateError::UnexpectedEof => write!(f, "Unexpected end of data"),
+            InflateError::InvalidBlockType => write!(f, "Invalid block type"),
+            InflateError::InvalidLenNlen => write!(f, "Invalid LEN and NLEN"),
+            InflateError::InvalidHuffmanSymbol => write!(f, "Invalid Huffman symbol"),
+            InflateError::InvalidCodeLengthEncoding { position } => {
+                write!(
+                    f,
+                    "Invalid code length encoding at code length {}",
+                    position
+                )
+            }
+            InflateError::CodeTooLong => write!(f, "Huffman code too long"),
+            InflateError::OversubscribedTable => write!(f, "Oversubscribed Huffman code lengths"),
+            InflateError::IncompleteTable => write!(f, "Incomplete Huffman code lengths"),
+            InflateError::DistanceTooFar => write!(f, "Distance too far back"),
+            InflateError::DistanceOutsideWindow => write!(f, "Distance outside the window"),
+            InflateError::TrailingData => write!(f, "Trailing data after the stream"),
+            InflateError::DictionaryRequired => write!(f, "A preset dictionary is required"),
+            InflateError::DictionaryMismatch => write!(f, "Wrong preset dictionary"),
+            InflateError::InvalidHeader(msg) => write!(f, "Invalid header: {}", msg),
+            InflateError::ChecksumMismatch { expected, found } => write!(
+                f,
+                "Checksum mismatch: expected {:08x}, found {:08x}",
+                expected, found
+            ),
+            InflateError::OutputLimitExceeded => write!(f, "Output size limit exceeded"),
+            InflateError::OutputTooSmall => write!(f, "Output buffer too small"),
+            InflateError::BlockLimitExceeded => write!(f, "Block limit exceeded"),
+            InflateError::UnsupportedOption(msg) => write!(f, "Unsupported option: {}", msg),
+        }
+    }
+}
+
+impl core::error::Error for InflateError {}
+
+#[cfg(feature = "std")]
+impl From<InflateError> for io::Error {
+    fn from(err: InflateError) -> Self {
+        let kind = if err.is_truncation() {
+            io::ErrorKind::UnexpectedEof
+        } else {
+            io::ErrorKind::InvalidData
+        };
+        io::Error::new(kind, err)
+    }
+}
+
+/// A result with [`InflateError`] as the error type.
+pub type Result<T> = core::result::Result<T, InflateError>;
+
+/// What the decoder was doing when it ran into an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeStage {
+    /// Reading the zlib or gzip header, or checking the preset dictionary it asks for.
+    ContainerHeader,
+    /// Reading the BFINAL and BTYPE fields of a block header.
+    BlockHeader,
+    /// Reading the LEN and NLEN fields of a stored block.
+    StoredHeader,
+    /// Copying the data of a stored block.
+    StoredData,
+    /// Reading the HLIT, HDIST and HCLEN fields of a dynamic block.
+    TableSizes,
+    /// Reading the code lengths of the code length alphabet.
+    CodeLengthCodeLengths,
+    /// Reading the literal/length and distance code lengths.
+    CodeLengths,
+    /// Building Huffman tables from code lengths.
+    BuildingTables,
+    /// Reading a literal/length symbol, or writing out its literal.
+    LiteralLengthSymbol,
+    /// Reading the extra bits of a length.
+    LengthExtraBits,
+    /// Reading a distance symbol.
+    DistanceSymbol,
+    /// Reading the extra bits of a distance.
+    DistanceExtraBits,
+    /// Copying the bytes a back-reference points to.
+    CopyingMatch,
+    /// Checking the zlib or gzip trailer.
+    ContainerTrailer,
+    /// Looking at the data after the end of the stream.
+    AfterStream,
+}
+
+impl fmt::Display for DecodeStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DecodeStage::ContainerHeader => "reading the container header",
+            DecodeStage::BlockHeader => "reading a block header",
+            DecodeStage::StoredHeader => "reading stored block LEN and NLEN",
+            DecodeStage::StoredData => "copying stored block data",
+            DecodeStage::TableSizes => "reading HLIT, HDIST and HCLEN",
+            DecodeStage::CodeLengthCodeLengths => "reading code length code lengths",
+            DecodeStage::CodeLengths => "reading code lengths",
+            DecodeStage::BuildingTables => "building Huffman tables",
+            DecodeStage::LiteralLengthSymbol => "reading a literal/length symbol",
+            DecodeStage::LengthExtraBits => "reading length extra bits",
+            DecodeStage::DistanceSymbol => "reading a distance symbol",
+            DecodeStage::DistanceExtraBits => "reading distance extra bits",
+            DecodeStage::CopyingMatch => "copying a match",
+            DecodeStage::ContainerTrailer => "checking the container trailer",
+            DecodeStage::AfterStream => "looking past the end of the stream",
+        })
+    }
+}
+
+/// An error along with where in the input the decoder ran into it and what it was doing,
+/// for debugging damaged data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CorruptionReport {
+    /// What went wrong.
+    pub error: InflateError,
+    /// Bit offset in the whole input, container included, of the field being decoded.
+    pub bit_offset: usize,
+    /// Index of the block being decoded, counting the blocks of every stream or gzip member
+    /// before it, or `None` outside of blocks.
+    pub block_index: Option<usize>,
+    /// Number of bytes decompressed before the error.
+    pub output_offset: usize,
+    /// What the decoder was doing.
+    pub stage: DecodeStage,
+}
+
+impl fmt::Display for CorruptionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at bit {} (byte {}, bit {})",
+            self.error,
+            self.bit_offset,
+            self.bit_offset / 8,
+            self.bit_offset % 8
+        )?;
+        if let Some(block_index) = self.block_index {
+            write!(f, " of block {}", block_index)?;
+        }
+        write!(
+            f,
+            ", while {}, after {} bytes of output",
+            self.stage, self.output_offset
+        )
+    }
+}
+
+impl core::error::Error for CorruptionReport {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl From<CorruptionReport> for InflateError {
+    fn from(report: CorruptionReport) -> Self {
+        report.error
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_corruption_report_display() {
+        let report = CorruptionReport {
+            error: InflateError::InvalidHuffmanSymbol,
+            bit_offset: 1234,
+            block_index: Some(3),
+            output_offset: 5678,
+            stage: DecodeStage::DistanceExtraBits,
+        };
+        assert_eq!(
+            report.to_string(),
+            "Invalid Huffman symbol at bit 1234 (byte 154, bit 2) of block 3, \
+             while reading distance extra bits, after 5678 bytes of output"
+        );
+        let report = CorruptionReport {
+            block_index: None,
+            stage: DecodeStage::ContainerTrailer,
+            ..report
+        };
+        assert!(report.to_string().contains("bit 2), while checking"));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_into_io_error() {
+        let err: io::Error = InflateError::UnexpectedEof.into();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        let err: io::Error = InflateError::InvalidBlockType.into();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "Invalid block type");
+        let inner = err
+            .into_inner()
+            .unwrap()
+            .downcast::<InflateError>()
+            .unwrap();
+        assert_eq!(*inner, InflateError::InvalidBlockType);
+    }
+}
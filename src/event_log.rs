@@ -0,0 +1,368 @@
+//! Save traces as compact binary event logs.
+//!
+//! A traced decode records one event per field of the stream, which for a large stream means
+//! millions of them: dozens of bytes each as JSON. An event log holds the same [`Trace`] in a few
+//! bytes per event, so an expensive decode can be captured once and replayed by a viewer, which
+//! can scrub through it without decoding the stream again.
+//!
+//! Version 1 layout, where every number is an unsigned LEB128 varint:
+//!
+//! ```text
+//! magic           "IFEL"
+//! version         1, as a single byte
+//! format          0 = raw, 1 = zlib, 2 = gzip
+//! data_bits
+//! end_bit_offset
+//! error           0 if none, otherwise 1 + the length of the UTF-8 message that follows
+//! event count
+//! events, each:
+//!   bit offset    zigzag-encoded difference with the end of the previous event
+//!   bit length
+//!   block index   0 if none, otherwise 1 + the index
+//!   output offset zigzag-encoded difference with the output offset of the previous event
+//!   kind          the index of the `FieldKind` variant, as a single byte
+//!   values        the data of the variant, in declaration order
+//! ```
+//!
+//! Booleans, formats and alphabets in values are encoded as the index of their variant.
+
+use crate::container::Format;
+use crate::disasm::{Alphabet, Field, FieldKind};
+use crate::trace::{Trace, TRACE_SCHEMA_VERSION};
+use std::io::{self, Read, Write};
+
+/// The bytes every event log starts with.
+pub const EVENT_LOG_MAGIC: [u8; 4] = *b"IFEL";
+/// The version of the layout written into event logs.
+pub const EVENT_LOG_VERSION: u8 = 1;
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Write the difference `value - base`, mapping small negative and positive differences
+/// to small numbers.
+fn write_delta(out: &mut Vec<u8>, value: usize, base: usize) {
+    let delta = value.wrapping_sub(base) as isize;
+    write_varint(out, ((delta << 1) ^ (delta >> (isize::BITS - 1))) as usize);
+}
+
+fn format_index(format: Format) -> usize {
+    match format {
+        Format::Raw => 0,
+        Format::Zlib => 1,
+        Format::Gzip => 2,
+    }
+}
+
+fn alphabet_index(alphabet: Alphabet) -> usize {
+    match alphabet {
+        Alphabet::LiteralLength => 0,
+        Alphabet::Distance => 1,
+    }
+}
+
+/// Get the tag of a field kind and its values.
+fn encode_kind(kind: FieldKind) -> (u8, Vec<usize>) {
+    match kind {
+        FieldKind::ContainerHeader(format) => (0, vec![format_index(format)]),
+        FieldKind::ContainerTrailer(format) => (1, vec![format_index(format)]),
+        FieldKind::BlockFinal(is_final) => (2, vec![is_final as usize]),
+        FieldKind::BlockType(b_type) => (3, vec![b_type]),
+        FieldKind::StoredPadding => (4, vec![]),
+        FieldKind::StoredLen(len) => (5, vec![len]),
+        FieldKind::StoredNlen(nlen) => (6, vec![nlen]),
+        FieldKind::StoredData { len } => (7, vec![len]),
+        FieldKind::Hlit(hlit) => (8, vec![hlit]),
+        FieldKind::Hdist(hdist) => (9, vec![hdist]),
+        FieldKind::Hclen(hclen) => (10, vec![hclen]),
+        FieldKind::CodeLengthCodeLength { symbol, len } => (11, vec![symbol, len]),
+        FieldKind::CodeLength {
+            alphabet,
+            symbol,
+            len,
+        } => (12, vec![alphabet_index(alphabet), symbol, len]),
+        FieldKind::CodeLengthRepeat {
+            alphabet,
+            symbol,
+            code,
+        } => (13, vec![alphabet_index(alphabet), symbol, code]),
+        FieldKind::CodeLengthRepeatCount {
+            alphabet,
+            symbol,
+            count,
+        } => (14, vec![alphabet_index(alphabet), symbol, count]),
+        FieldKind::Literal(byte) => (15, vec![byte as usize]),
+        FieldKind::EndOfBlock => (16, vec![]),
+        FieldKind::LengthCode { code, length } => (17, vec![code, length]),
+        FieldKind::LengthExtra { length } => (18, vec![length]),
+        FieldKind::DistanceCode { code, distance } => (19, vec![code, distance]),
+        FieldKind::DistanceExtra { distance } => (20, vec![distance]),
+        FieldKind::FinalPadding => (21, vec![]),
+    }
+}
+
+/// Write a trace as an event log.
+pub fn write_event_log<W: Write>(trace: &Trace, mut writer: W) -> io::Result<()> {
+    let mut out = EVENT_LOG_MAGIC.to_vec();
+    out.push(EVENT_LOG_VERSION);
+    write_varint(&mut out, format_index(trace.format));
+    write_varint(&mut out, trace.data_bits);
+    write_varint(&mut out, trace.end_bit_offset);
+    match &trace.error {
+        None => write_varint(&mut out, 0),
+        Some(message) => {
+            write_varint(&mut out, message.len() + 1);
+            out.extend_from_slice(message.as_bytes());
+        }
+    }
+    write_varint(&mut out, trace.fields.len());
+    let (mut bit_end, mut output_offset) = (0, 0);
+    for field in &trace.fields {
+        write_delta(&mut out, field.bit_offset, bit_end);
+        write_varint(&mut out, field.bit_len);
+        write_varint(&mut out, field.block_index.map_or(0, |index| index + 1));
+        write_delta(&mut out, field.output_offset, output_offset);
+        let (tag, values) = encode_kind(field.kind);
+        out.push(tag);
+        for value in values {
+            write_varint(&mut out, value);
+        }
+        bit_end = field.bit_offset + field.bit_len;
+        output_offset = field.output_offset;
+    }
+    writer.write_all(&out)
+}
+
+/// A position within the bytes of an event log being read.
+struct Cursor<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Cursor<'_> {
+    fn bytes(&mut self, n: usize) -> io::Result<&[u8]> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos.saturating_add(n))
+            .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> io::Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn varint(&mut self) -> io::Result<usize> {
+        let mut value = 0usize;
+        for shift in (0..usize::BITS).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as usize)
+                .checked_shl(shift)
+                .ok_or_else(|| invalid_data("varint too long"))?;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(invalid_data("varint too long"))
+    }
+
+    fn delta(&mut self, base: usize) -> io::Result<usize> {
+        let zigzag = self.varint()?;
+        let delta = (zigzag >> 1) as isize ^ -((zigzag & 1) as isize);
+        Ok(base.wrapping_add(delta as usize))
+    }
+
+    fn format(&mut self) -> io::Result<Format> {
+        match self.varint()? {
+            0 => Ok(Format::Raw),
+            1 => Ok(Format::Zlib),
+            2 => Ok(Format::Gzip),
+            _ => Err(invalid_data("unknown format")),
+        }
+    }
+
+    fn alphabet(&mut self) -> io::Result<Alphabet> {
+        match self.varint()? {
+            0 => Ok(Alphabet::LiteralLength),
+            1 => Ok(Alphabet::Distance),
+            _ => Err(invalid_data("unknown alphabet")),
+        }
+    }
+
+    fn kind(&mut self) -> io::Result<FieldKind> {
+        let kind = match self.byte()? {
+            0 => FieldKind::ContainerHeader(self.format()?),
+            1 => FieldKind::ContainerTrailer(self.format()?),
+            2 => FieldKind::BlockFinal(self.varint()? != 0),
+            3 => FieldKind::BlockType(self.varint()?),
+            4 => FieldKind::StoredPadding,
+            5 => FieldKind::StoredLen(self.varint()?),
+            6 => FieldKind::StoredNlen(self.varint()?),
+            7 => FieldKind::StoredData {
+                len: self.varint()?,
+            },
+            8 => FieldKind::Hlit(self.varint()?),
+            9 => FieldKind::Hdist(self.varint()?),
+            10 => FieldKind::Hclen(self.varint()?),
+            11 => FieldKind::CodeLengthCodeLength {
+                symbol: self.varint()?,
+                len: self.varint()?,
+            },
+            12 => FieldKind::CodeLength {
+                alphabet: self.alphabet()?,
+                symbol: self.varint()?,
+                len: self.varint()?,
+            },
+            13 => FieldKind::CodeLengthRepeat {
+                alphabet: self.alphabet()?,
+                symbol: self.varint()?,
+                code: self.varint()?,
+            },
+            14 => FieldKind::CodeLengthRepeatCount {
+                alphabet: self.alphabet()?,
+                symbol: self.varint()?,
+                count: self.varint()?,
+            },
+            15 => FieldKind::Literal(self.varint()? as u8),
+            16 => FieldKind::EndOfBlock,
+            17 => FieldKind::LengthCode {
+                code: self.varint()?,
+                length: self.varint()?,
+            },
+            18 => FieldKind::LengthExtra {
+                length: self.varint()?,
+            },
+            19 => FieldKind::DistanceCode {
+                code: self.varint()?,
+                distance: self.varint()?,
+            },
+            20 => FieldKind::DistanceExtra {
+                distance: self.varint()?,
+            },
+            21 => FieldKind::FinalPadding,
+            _ => return Err(invalid_data("unknown event kind")),
+        };
+        Ok(kind)
+    }
+}
+
+/// Read a trace back from an event log.
+/// Logs written with a newer version than `EVENT_LOG_VERSION` are rejected.
+pub fn read_event_log<R: Read>(mut reader: R) -> io::Result<Trace> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    let mut cursor = Cursor {
+        data: &data,
+        pos: 0,
+    };
+    if cursor.bytes(EVENT_LOG_MAGIC.len())? != EVENT_LOG_MAGIC {
+        return Err(invalid_data("not an event log"));
+    }
+    let version = cursor.byte()?;
+    if version > EVENT_LOG_VERSION {
+        return Err(invalid_data(&format!(
+            "unsupported event log version {}",
+            version
+        )));
+    }
+    let format = cursor.format()?;
+    let data_bits = cursor.varint()?;
+    let end_bit_offset = cursor.varint()?;
+    let error = match cursor.varint()? {
+        0 => None,
+        len => {
+            let message = cursor.bytes(len - 1)?.to_vec();
+            let message = String::from_utf8(message).map_err(|_| invalid_data("invalid error"))?;
+            Some(message)
+        }
+    };
+    let count = cursor.varint()?;
+    // Every event takes at least five bytes, which bounds the allocation for corrupt counts.
+    let mut fields = Vec::with_capacity(count.min(data.len() / 5));
+    let (mut bit_end, mut output_offset) = (0, 0);
+    for _ in 0..count {
+        let bit_offset = cursor.delta(bit_end)?;
+        let bit_len = cursor.varint()?;
+        let block_index = cursor.varint()?.checked_sub(1);
+        output_offset = cursor.delta(output_offset)?;
+        let kind = cursor.kind()?;
+        fields.push(Field {
+            bit_offset,
+            bit_len,
+            block_index,
+            output_offset,
+            kind,
+        });
+        bit_end = bit_offset.wrapping_add(bit_len);
+    }
+    if cursor.pos != data.len() {
+        return Err(invalid_data("trailing data after the events"));
+    }
+    Ok(Trace {
+        schema_version: TRACE_SCHEMA_VERSION,
+        format,
+        data_bits,
+        end_bit_offset,
+        error,
+        fields,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disasm::disassemble;
+
+    #[test]
+    fn test_event_log_round_trip() {
+        let raw = b"replay me, replay me, replay me once more".repeat(30);
+        for format in [Format::Raw, Format::Zlib] {
+            let data = match format {
+                Format::Zlib => miniz_oxide::deflate::compress_to_vec_zlib(&raw, 9),
+                _ => miniz_oxide::deflate::compress_to_vec(&raw, 9),
+            };
+            let trace = Trace::new(&disassemble(&data, format));
+            let mut log = Vec::new();
+            write_event_log(&trace, &mut log).unwrap();
+            assert!(log.starts_with(&EVENT_LOG_MAGIC));
+            assert_eq!(read_event_log(&log[..]).unwrap(), trace);
+            // Far smaller than JSON.
+            assert!(log.len() * 8 < trace.to_json().len());
+        }
+
+        // A trace of a stream cut short keeps its error.
+        let data = miniz_oxide::deflate::compress_to_vec(&raw, 9);
+        let trace = Trace::new(&disassemble(&data[..data.len() / 2], Format::Raw));
+        assert!(trace.error.is_some());
+        let mut log = Vec::new();
+        write_event_log(&trace, &mut log).unwrap();
+        assert_eq!(read_event_log(&log[..]).unwrap(), trace);
+    }
+
+    #[test]
+    fn test_event_log_rejects_invalid() {
+        let data = miniz_oxide::deflate::compress_to_vec(b"abc", 6);
+        let mut log = Vec::new();
+        write_event_log(&Trace::new(&disassemble(&data, Format::Raw)), &mut log).unwrap();
+
+        let kind = |log: &[u8]| read_event_log(log).unwrap_err().kind();
+        assert_eq!(kind(&log[..log.len() - 1]), io::ErrorKind::UnexpectedEof);
+        assert_eq!(kind(b"JSON"), io::ErrorKind::InvalidData);
+        let mut newer = log.clone();
+        newer[4] = EVENT_LOG_VERSION + 1;
+        assert_eq!(kind(&newer), io::ErrorKind::InvalidData);
+        let mut trailing = log.clone();
+        trailing.push(0);
+        assert_eq!(kind(&trailing), io::ErrorKind::InvalidData);
+    }
+}
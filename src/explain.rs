@@ -0,0 +1,370 @@
+//! Narrate the decoding of a DEFLATE stream in prose.
+//!
+//! [`explain`] tells what each part of a stream means, in the order the decoder reads it: the
+//! block headers, the size of the Huffman tables, every literal with the bits its code takes
+//! and every match with the bytes it copies, e.g.
+//! `Match of length 12 at distance 256 (21 bits) copies 'ipsum dolor '.`
+//! It is written for people learning the format, who get more out of reading it than out of
+//! a hex dump.
+
+use crate::container::Format;
+use crate::disasm::{block_type_name, disassemble, format_name, Field, FieldKind};
+use crate::inflate::repeat_with_overlap;
+use crate::text::TextOptions;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Most bytes of an excerpt quoted by default.
+pub const DEFAULT_MAX_EXCERPT: usize = 32;
+
+/// What [`explain`] tells, and how.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExplainOptions {
+    /// The container around the stream.
+    pub format: Format,
+    /// Only tell about the blocks, leaving out the line of each literal and match.
+    pub blocks_only: bool,
+    /// Most bytes quoted of what a match copies or a stored block holds. Longer excerpts are
+    /// cut short, with the number of bytes left out.
+    pub max_excerpt: usize,
+    /// How errors are written.
+    pub text: TextOptions,
+}
+
+impl Default for ExplainOptions {
+    fn default() -> Self {
+        Self {
+            format: Format::Raw,
+            blocks_only: false,
+            max_excerpt: DEFAULT_MAX_EXCERPT,
+            text: TextOptions::default(),
+        }
+    }
+}
+
+/// Count things in words, e.g. `1 bit` or `12 bits`.
+fn count(n: usize, one: &str, many: &str) -> String {
+    format!("{} {}", n, if n == 1 { one } else { many })
+}
+
+/// Quote bytes, escaping the ones that are not printable ASCII, and cut them short after
+/// `max` bytes, telling how many are left out.
+fn quote(bytes: &[u8], max: usize) -> String {
+    if bytes.len() > max {
+        let rest = count(bytes.len() - max, "more byte", "more bytes");
+        format!("'{}' and {}", bytes[..max].escape_ascii(), rest)
+    } else {
+        format!("'{}'", bytes.escape_ascii())
+    }
+}
+
+/// The narration being written, and what it remembers from one field to the next.
+#[derive(Debug)]
+struct Explainer<'a> {
+    data: &'a [u8],
+    options: &'a ExplainOptions,
+    text: String,
+    /// The output decoded so far, for the excerpts of matches.
+    output: Vec<u8>,
+    /// Number of blocks started so far.
+    blocks: usize,
+    is_final: bool,
+    /// Output size at the start of the current block.
+    block_start: usize,
+    literals: usize,
+    matches: usize,
+    hlit: usize,
+    hdist: usize,
+    /// Bits of code lengths read since the last time they were told about.
+    table_bits: usize,
+    /// Bit offset and length of the match being read.
+    match_start: usize,
+    length: usize,
+    /// The distance of the match being read, and the bit offset after it, until the fields
+    /// of the match are over: its distance extra bits are only there if it has some.
+    pending_match: Option<(usize, usize)>,
+}
+
+impl<'a> Explainer<'a> {
+    fn new(data: &'a [u8], options: &'a ExplainOptions) -> Self {
+        Self {
+            data,
+            options,
+            text: String::new(),
+            output: Vec::new(),
+            blocks: 0,
+            is_final: false,
+            block_start: 0,
+            literals: 0,
+            matches: 0,
+            hlit: 0,
+            hdist: 0,
+            table_bits: 0,
+            match_start: 0,
+            length: 0,
+            pending_match: None,
+        }
+    }
+
+    /// Add a line to the narration.
+    fn say(&mut self, line: String) {
+        self.text.push_str(&line);
+        self.text.push('\n');
+    }
+
+    /// Tell how many bits the code lengths of a dynamic block took, once they are all read.
+    fn tell_table_bits(&mut self) {
+        if self.table_bits > 0 {
+            let bits = count(self.table_bits, "bit", "bits");
+            self.say(format!(
+                "  The code lengths of its Huffman codes take {}.",
+                bits
+            ));
+            self.table_bits = 0;
+        }
+    }
+
+    /// Copy the match read last and tell about it, if it is not told about yet.
+    fn finish_match(&mut self) {
+        let Some((distance, end)) = self.pending_match.take() else {
+            return;
+        };
+        self.matches += 1;
+        // A distance reaching before the output fails right after the match.
+        if distance > self.output.len() {
+            return;
+        }
+        let start = self.output.len();
+        repeat_with_overlap(&mut self.output, distance, self.length);
+        if !self.options.blocks_only {
+            let excerpt = quote(&self.output[start..], self.options.max_excerpt);
+            self.say(format!(
+                "  Match of length {} at distance {} ({}) copies {}.",
+                self.length,
+                distance,
+                count(end - self.match_start, "bit", "bits"),
+                excerpt
+            ));
+        }
+    }
+
+    /// Tell about a field, or remember it for a later line.
+    fn field(&mut self, field: &Field) {
+        let bits = count(field.bit_len, "bit", "bits");
+        // The verb after a number of padding bits.
+        let s = if field.bit_len == 1 { "s" } else { "" };
+        let end = field.bit_offset + field.bit_len;
+        if !matches!(field.kind, FieldKind::DistanceExtra { .. }) {
+            self.finish_match();
+        }
+        match field.kind {
+            FieldKind::ContainerHeader(format) | FieldKind::ContainerTrailer(format)
+                if format == Format::Raw => {}
+            FieldKind::ContainerHeader(format) => self.say(format!(
+                "The data starts with a {} header of {}.",
+                format_name(format),
+                count(field.bit_len / 8, "byte", "bytes")
+            )),
+            FieldKind::ContainerTrailer(format) => self.say(format!(
+                "The {} trailer of {} ends the data.",
+                format_name(format),
+                count(field.bit_len / 8, "byte", "bytes")
+            )),
+            FieldKind::BlockFinal(is_final) => self.is_final = is_final,
+            FieldKind::BlockType(b_type) => {
+                self.blocks += 1;
+                self.block_start = self.output.len();
+                self.literals = 0;
+                self.matches = 0;
+                self.say(format!(
+                    "Block {} at bit {}: {}{}.",
+                    self.blocks,
+                    field.bit_offset - 1,
+                    block_type_name(b_type),
+                    if self.is_final {
+                        ", the last block"
+                    } else {
+                        ""
+                    }
+                ));
+                if b_type == 1 {
+                    self.say(
+                        "  Its codes are the fixed ones of RFC 1951: 7 to 9 bits for \
+                         literals and lengths, 5 bits for distances."
+                            .into(),
+                    );
+                }
+            }
+            FieldKind::StoredPadding if field.bit_len > 0 => {
+                self.say(format!("  {} of padding reach{} a byte boundary.", bits, s))
+            }
+            FieldKind::StoredPadding | FieldKind::StoredNlen(_) => {}
+            FieldKind::StoredLen(len) => self.say(format!(
+                "  LEN says {} follow, and NLEN repeats it with every bit flipped.",
+                count(len, "byte", "bytes")
+            )),
+            FieldKind::StoredData { len } => {
+                let start = field.bit_offset / 8;
+                let bytes = &self.data[start..start + len];
+                let excerpt = quote(bytes, self.options.max_excerpt);
+                self.output.extend_from_slice(bytes);
+                self.say(format!("  They are copied as they are: {}.", excerpt));
+            }
+            FieldKind::Hlit(n) => self.hlit = n,
+            FieldKind::Hdist(n) => self.hdist = n,
+            FieldKind::Hclen(n) => self.say(format!(
+                "  Its header declares {} lit/len codes, {} distance codes and {} code length \
+                 codes.",
+                self.hlit, self.hdist, n
+            )),
+            FieldKind::CodeLengthCodeLength { .. }
+            | FieldKind::CodeLength { .. }
+            | FieldKind::CodeLengthRepeat { .. }
+            | FieldKind::CodeLengthRepeatCount { .. } => self.table_bits += field.bit_len,
+            FieldKind::Literal(byte) => {
+                self.tell_table_bits();
+                self.literals += 1;
+                self.output.push(byte);
+                if !self.options.blocks_only {
+                    let excerpt = quote(&[byte], 1);
+                    self.say(format!("  Literal {} ({}).", excerpt, bits));
+                }
+            }
+            FieldKind::LengthCode { length, .. } => {
+                self.tell_table_bits();
+                self.match_start = field.bit_offset;
+                self.length = length;
+            }
+            FieldKind::LengthExtra { .. } => {}
+            FieldKind::DistanceCode { distance, .. } => self.pending_match = Some((distance, end)),
+            FieldKind::DistanceExtra { distance } => self.pending_match = Some((distance, end)),
+            FieldKind::EndOfBlock => {
+                self.tell_table_bits();
+                self.say(format!(
+                    "  The end-of-block symbol ({}) ends block {}: {} and {} made {}.",
+                    bits,
+                    self.blocks,
+                    count(self.literals, "literal", "literals"),
+                    count(self.matches, "match", "matches"),
+                    count(self.output.len() - self.block_start, "byte", "bytes")
+                ));
+            }
+            FieldKind::FinalPadding => self.say(format!(
+                "The stream ends at bit {} with {} of output{}.",
+                field.bit_offset,
+                count(self.output.len(), "byte", "bytes"),
+                if field.bit_len > 0 {
+                    format!(", and {} of padding fill{} its last byte", bits, s)
+                } else {
+                    String::new()
+                }
+            )),
+        }
+    }
+}
+
+/// Narrate the decoding of a stream inside the container the options give, one line per
+/// block header, literal, match and the like. The narration goes as far as the stream can be
+/// decoded, and then tells why it stops.
+pub fn explain(data: &[u8], options: &ExplainOptions) -> String {
+    let disassembly = disassemble(data, options.format);
+    let mut explainer = Explainer::new(data, options);
+    for field in &disassembly.fields {
+        explainer.field(field);
+    }
+    explainer.finish_match();
+    if let Some(error) = disassembly.error {
+        explainer.say(format!(
+            "Decoding fails at bit {}: {}.",
+            disassembly.end_bit_offset,
+            options.text.error(&error)
+        ));
+    }
+    explainer.text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::container;
+    use crate::deflate::{deflate_dynamic_tokens_to_vec, deflate_fixed_tokens_to_vec, Token};
+    use alloc::vec;
+
+    #[test]
+    fn test_explain() {
+        let mut data = vec![0b000, 3, 0, !3, !0, b'a', b'b', b'c'];
+        data.extend(deflate_fixed_tokens_to_vec(&[
+            Token::Literal(b'\n'),
+            Token::Match {
+                length: 5,
+                distance: 2,
+            },
+        ]));
+        assert_eq!(
+            explain(&data, &ExplainOptions::default()),
+            "Block 1 at bit 0: stored.\n\
+             \x20 5 bits of padding reach a byte boundary.\n\
+             \x20 LEN says 3 bytes follow, and NLEN repeats it with every bit flipped.\n\
+             \x20 They are copied as they are: 'abc'.\n\
+             Block 2 at bit 64: fixed Huffman, the last block.\n\
+             \x20 Its codes are the fixed ones of RFC 1951: 7 to 9 bits for literals and \
+             lengths, 5 bits for distances.\n\
+             \x20 Literal '\\n' (8 bits).\n\
+             \x20 Match of length 5 at distance 2 (12 bits) copies 'c\\nc\\nc'.\n\
+             \x20 The end-of-block symbol (7 bits) ends block 2: 1 literal and 1 match made \
+             6 bytes.\n\
+             The stream ends at bit 94 with 9 bytes of output, and 2 bits of padding fill its \
+             last byte.\n"
+        );
+
+        let options = ExplainOptions {
+            blocks_only: true,
+            ..Default::default()
+        };
+        let narration = explain(&data, &options);
+        assert!(!narration.contains("Literal"));
+        assert!(narration.contains("1 literal and 1 match made 6 bytes"));
+    }
+
+    #[test]
+    fn test_explain_dynamic() {
+        let raw = b"Lorem ipsum dolor sit amet, lorem ipsum dolor sit amet";
+        let tokens = crate::lz77::find_matches(raw, &Default::default());
+        let deflate = deflate_dynamic_tokens_to_vec(&tokens);
+        let zlib = container::wrap(&deflate, raw, Format::Zlib);
+        let options = ExplainOptions {
+            format: Format::Zlib,
+            max_excerpt: 8,
+            ..Default::default()
+        };
+        let narration = explain(&zlib, &options);
+        assert!(narration.starts_with("The data starts with a zlib header of 2 bytes.\n"));
+        assert!(narration.contains("Block 1 at bit 16: dynamic Huffman, the last block.\n"));
+        assert!(narration.contains("  Its header declares 271 lit/len codes, 10 distance codes"));
+        assert!(narration.contains("  The code lengths of its Huffman codes take "));
+        assert!(narration.contains("copies 'orem ips' and 17 more bytes.\n"));
+        assert!(narration.ends_with("The zlib trailer of 4 bytes ends the data.\n"));
+    }
+
+    #[test]
+    fn test_explain_error() {
+        let data = deflate_fixed_tokens_to_vec(&[Token::Literal(b'x'), Token::Literal(b'y')]);
+        let options = ExplainOptions {
+            text: TextOptions::stable(),
+            ..Default::default()
+        };
+        let narration = explain(&data[..1], &options);
+        assert!(narration.starts_with("Block 1 at bit 0: fixed Huffman, the last block.\n"));
+        assert!(narration.ends_with("Decoding fails at bit 8: unexpected_eof.\n"));
+
+        // A match reaching before the start of the output.
+        let data = deflate_fixed_tokens_to_vec(&[Token::Match {
+            length: 3,
+            distance: 1,
+        }]);
+        let narration = explain(&data, &ExplainOptions::default());
+        assert!(!narration.contains("Match"));
+        assert!(narration.contains("Decoding fails at bit"));
+    }
+}
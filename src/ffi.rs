@@ -0,0 +1,255 @@
+//! C bindings, to call the decoder from C teaching material or a zlib comparison harness.
+//!
+//! The declarations are in `include/inflate_toy.h`, which `cbindgen --config cbindgen.toml`
+//! regenerates from this module.
+
+use crate::error::InflateError;
+use crate::inflate::decompress_auto_into_slice;
+use core::ffi::{c_char, c_int, CStr};
+
+/// The outcome of a call to [`inflate_toy_decompress`]: success, or why it failed.
+/// Every [`InflateError`] has its own status.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InflateToyStatus {
+    Ok = 0,
+    /// A pointer argument is null while its length is not 0.
+    InvalidArgument = 1,
+    EmptyInput = 2,
+    UnexpectedEof = 3,
+    InvalidBlockType = 4,
+    InvalidLenNlen = 5,
+    InvalidHuffmanSymbol = 6,
+    OversubscribedTable = 7,
+    IncompleteTable = 8,
+    DistanceTooFar = 9,
+    DistanceOutsideWindow = 10,
+    TrailingData = 11,
+    DictionaryRequired = 12,
+    DictionaryMismatch = 13,
+    InvalidHeader = 14,
+    ChecksumMismatch = 15,
+    OutputLimitExceeded = 16,
+    /// The output buffer is too small for the decompressed data.
+    OutputTooSmall = 17,
+    BlockLimitExceeded = 18,
+    InvalidCodeLengthEncoding = 19,
+    CodeTooLong = 20,
+    UnsupportedOption = 21,
+}
+
+impl InflateToyStatus {
+    /// Every status, in the order of their values.
+    const ALL: [InflateToyStatus; 22] = [
+        Self::Ok,
+        Self::InvalidArgument,
+        Self::EmptyInput,
+        Self::UnexpectedEof,
+        Self::InvalidBlockType,
+        Self::InvalidLenNlen,
+        Self::InvalidHuffmanSymbol,
+        Self::OversubscribedTable,
+        Self::IncompleteTable,
+        Self::DistanceTooFar,
+        Self::DistanceOutsideWindow,
+        Self::TrailingData,
+        Self::DictionaryRequired,
+        Self::DictionaryMismatch,
+        Self::InvalidHeader,
+        Self::ChecksumMismatch,
+        Self::OutputLimitExceeded,
+        Self::OutputTooSmall,
+        Self::BlockLimitExceeded,
+        Self::InvalidCodeLengthEncoding,
+        Self::CodeTooLong,
+        Self::UnsupportedOption,
+    ];
+
+    /// Get the status with the given value, if any.
+    fn from_value(value: c_int) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|&status| status as c_int == value)
+    }
+}
+
+impl From<InflateError> for InflateToyStatus {
+    fn from(error: InflateError) -> Self {
+        match error {
+            InflateError::EmptyInput => Self::EmptyInput,
+            InflateError::UnexpectedEof => Self::UnexpectedEof,
+            InflateError::InvalidBlockType => Self::InvalidBlockType,
+            InflateError::InvalidLenNlen => Self::InvalidLenNlen,
+            InflateError::InvalidHuffmanSymbol => Self::InvalidHuffmanSymbol,
+            InflateError::InvalidCodeLengthEncoding { .. } => Self::InvalidCodeLengthEncoding,
+            InflateError::CodeTooLong => Self::CodeTooLong,
+            InflateError::OversubscribedTable => Self::OversubscribedTable,
+            InflateError::IncompleteTable => Self::IncompleteTable,
+            InflateError::DistanceTooFar => Self::DistanceTooFar,
+            InflateError::DistanceOutsideWindow => Self::DistanceOutsideWindow,
+            InflateError::TrailingData => Self::TrailingData,
+            InflateError::DictionaryRequired => Self::DictionaryRequired,
+            InflateError::DictionaryMismatch => Self::DictionaryMismatch,
+            InflateError::InvalidHeader(_) => Self::InvalidHeader,
+            InflateError::ChecksumMismatch { .. } => Self::ChecksumMismatch,
+            InflateError::OutputLimitExceeded => Self::OutputLimitExceeded,
+            InflateError::OutputTooSmall => Self::OutputTooSmall,
+            InflateError::BlockLimitExceeded => Self::BlockLimitExceeded,
+            InflateError::UnsupportedOption(_) => Self::UnsupportedOption,
+        }
+    }
+}
+
+/// Decompress the `in_len` bytes at `in_ptr`, a raw DEFLATE, zlib or gzip stream, into the
+/// `out_cap` bytes at `out_ptr`. On success, the number of bytes written is stored at `out_len`;
+/// on failure, 0 is, and the content of the output buffer is unspecified.
+///
+/// # Safety
+///
+/// `in_ptr` must point to `in_len` readable bytes and `out_ptr` to `out_cap` writable bytes,
+/// neither overlapping the other; both may be null if their length is 0. `out_len` must be
+/// null or point to a writable `size_t`.
+#[no_mangle]
+pub unsafe extern "C" fn inflate_toy_decompress(
+    in_ptr: *const u8,
+    in_len: usize,
+    out_ptr: *mut u8,
+    out_cap: usize,
+    out_len: *mut usize,
+) -> InflateToyStatus {
+    if !out_len.is_null() {
+        *out_len = 0;
+    }
+    if (in_ptr.is_null() && in_len > 0) || (out_ptr.is_null() && out_cap > 0) {
+        return InflateToyStatus::InvalidArgument;
+    }
+    let data = match in_len {
+        0 => &[][..],
+        _ => core::slice::from_raw_parts(in_ptr, in_len),
+    };
+    let out = match out_cap {
+        0 => &mut [][..],
+        _ => core::slice::from_raw_parts_mut(out_ptr, out_cap),
+    };
+    match decompress_auto_into_slice(data, out) {
+        Ok((len, _)) => {
+            if !out_len.is_null() {
+                *out_len = len;
+            }
+            InflateToyStatus::Ok
+        }
+        Err(error) => error.into(),
+    }
+}
+
+/// Get a static, NUL-terminated description of a status.
+/// The status is taken as a plain integer, so that any value is safe to pass: one that is not
+/// a status gets "Unknown status".
+#[no_mangle]
+pub extern "C" fn inflate_toy_status_message(status: c_int) -> *const c_char {
+    let Some(status) = InflateToyStatus::from_value(status) else {
+        return c"Unknown status".as_ptr();
+    };
+    let message: &CStr = match status {
+        InflateToyStatus::Ok => c"Success",
+        InflateToyStatus::InvalidArgument => c"Invalid argument",
+        InflateToyStatus::EmptyInput => c"Empty input",
+        InflateToyStatus::UnexpectedEof => c"Unexpected end of data",
+        InflateToyStatus::InvalidBlockType => c"Invalid block type",
+        InflateToyStatus::InvalidLenNlen => c"Invalid LEN and NLEN",
+        InflateToyStatus::InvalidHuffmanSymbol => c"Invalid Huffman symbol",
+        InflateToyStatus::OversubscribedTable => c"Oversubscribed Huffman code lengths",
+        InflateToyStatus::IncompleteTable => c"Incomplete Huffman code lengths",
+        InflateToyStatus::DistanceTooFar => c"Distance too far back",
+        InflateToyStatus::DistanceOutsideWindow => c"Distance outside the window",
+        InflateToyStatus::TrailingData => c"Trailing data after the stream",
+        InflateToyStatus::DictionaryRequired => c"A preset dictionary is required",
+        InflateToyStatus::DictionaryMismatch => c"Wrong preset dictionary",
+        InflateToyStatus::InvalidHeader => c"Invalid header",
+        InflateToyStatus::ChecksumMismatch => c"Checksum mismatch",
+        InflateToyStatus::OutputLimitExceeded => c"Output size limit exceeded",
+        InflateToyStatus::OutputTooSmall => c"Output buffer too small",
+        InflateToyStatus::BlockLimitExceeded => c"Block limit exceeded",
+        InflateToyStatus::InvalidCodeLengthEncoding => c"Invalid code length encoding",
+        InflateToyStatus::CodeTooLong => c"Huffman code too long",
+        InflateToyStatus::UnsupportedOption => c"Unsupported option",
+    };
+    message.as_ptr()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::container::{wrap, Format};
+    use crate::deflate::deflate_to_vec;
+    use core::ptr;
+
+    /// Call `inflate_toy_decompress` on slices.
+    fn decompress(data: &[u8], out: &mut [u8]) -> (InflateToyStatus, usize) {
+        let mut out_len = usize::MAX;
+        let status = unsafe {
+            inflate_toy_decompress(
+                data.as_ptr(),
+                data.len(),
+                out.as_mut_ptr(),
+                out.len(),
+                &mut out_len,
+            )
+        };
+        (status, out_len)
+    }
+
+    #[test]
+    fn test_inflate_toy_decompress() {
+        let raw = b"from C, from C, from C";
+        let mut out = [0; 64];
+        for format in [Format::Raw, Format::Zlib, Format::Gzip] {
+            let data = wrap(&deflate_to_vec(raw), raw, format);
+            assert_eq!(
+                decompress(&data, &mut out),
+                (InflateToyStatus::Ok, raw.len())
+            );
+            assert_eq!(out[..raw.len()], raw[..]);
+        }
+
+        let data = deflate_to_vec(raw);
+        assert_eq!(
+            decompress(&data, &mut out[..10]),
+            (InflateToyStatus::OutputTooSmall, 0)
+        );
+        assert_eq!(
+            decompress(&data[..3], &mut out),
+            (InflateToyStatus::UnexpectedEof, 0)
+        );
+        assert_eq!(decompress(&[], &mut out), (InflateToyStatus::EmptyInput, 0));
+        // A raw stream whose first bytes look like a zlib header.
+        let data = [
+            0x78, 0x01, 0x00, 0xfe, 0xff, b'x', 0x01, 0x00, 0x00, 0xff, 0xff,
+        ];
+        assert_eq!(decompress(&data, &mut out), (InflateToyStatus::Ok, 1));
+        assert_eq!(out[0], b'x');
+        let status = unsafe {
+            inflate_toy_decompress(ptr::null(), 3, out.as_mut_ptr(), 64, ptr::null_mut())
+        };
+        assert_eq!(status, InflateToyStatus::InvalidArgument);
+    }
+
+    #[test]
+    fn test_inflate_toy_status_message() {
+        let message = |status| unsafe { CStr::from_ptr(inflate_toy_status_message(status)) };
+        assert_eq!(
+            message(InflateToyStatus::DistanceTooFar as c_int).to_str(),
+            Ok(InflateError::DistanceTooFar.to_string().as_str())
+        );
+        for (value, status) in InflateToyStatus::ALL.into_iter().enumerate() {
+            assert_eq!(status as c_int, value as c_int);
+        }
+        assert_eq!(message(-1).to_str(), Ok("Unknown status"));
+        assert_eq!(message(1000).to_str(), Ok("Unknown status"));
+        let status = InflateToyStatus::from(InflateError::ChecksumMismatch {
+            expected: 1,
+            found: 2,
+        });
+        assert_eq!(status, InflateToyStatus::ChecksumMismatch);
+    }
+}
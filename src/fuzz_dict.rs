@@ -0,0 +1,189 @@
+//! Generate a fuzzing dictionary for the decoder.
+//!
+//! AFL and libFuzzer mutate inputs faster towards interesting code paths when they are given
+//! byte sequences the format cares about. The entries are derived from the constants the
+//! decoder itself uses, so the dictionary stays in sync with the code.
+//! Run `cargo fuzz-dict [path]` to write it out.
+
+use crate::bit_stream::BitWriter;
+use crate::container::{
+    CM_DEFLATE, GZIP_FCOMMENT, GZIP_FEXTRA, GZIP_FHCRC, GZIP_FNAME, GZIP_FTEXT, GZIP_ID,
+    ZLIB_FCHECK_MOD, ZLIB_FDICT, ZLIB_MAX_CINFO,
+};
+use crate::inflate::{
+    BFINAL_LEN, BFINAL_VALUE, BTYPE_DYNAMIC_HUFFMAN, BTYPE_FIXED_HUFFMAN, BTYPE_LEN,
+    BTYPE_NO_COMPRESSION, DYN_ALPHABET_CODE_NUM, HCLEN_BASE, HCLEN_LEN, HDIST_LEN, HLIT_LEN,
+    SYNC_FLUSH_MARKER,
+};
+use std::io::{Result, Write};
+
+/// A named byte sequence of the dictionary.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DictEntry {
+    /// Keyword name, only used to make the dictionary readable.
+    pub name: String,
+    /// The byte sequence itself.
+    pub bytes: Vec<u8>,
+}
+
+impl DictEntry {
+    fn new(name: impl Into<String>, bytes: Vec<u8>) -> Self {
+        Self {
+            name: name.into(),
+            bytes,
+        }
+    }
+}
+
+/// Pack `(value, bit count)` fields LSB first, the way DEFLATE stores block headers.
+/// The last byte is padded with zeros.
+fn pack_bits(fields: &[(usize, usize)]) -> Vec<u8> {
+    let mut writer = BitWriter::new();
+    for &(value, len) in fields {
+        writer.write_bits(value, len);
+    }
+    writer.into_vec()
+}
+
+/// Build a valid zlib header for the given window size and level.
+fn zlib_header(cinfo: u8, level: u8, fdict: bool) -> [u8; 2] {
+    let cmf = (cinfo << 4) | CM_DEFLATE;
+    let mut flg = (level << 6) | if fdict { ZLIB_FDICT } else { 0 };
+    let rem = u16::from_be_bytes([cmf, flg]) % ZLIB_FCHECK_MOD;
+    if rem != 0 {
+        flg += (ZLIB_FCHECK_MOD - rem) as u8;
+    }
+    [cmf, flg]
+}
+
+/// List the entries of the dictionary.
+pub fn dictionary_entries() -> Vec<DictEntry> {
+    let mut entries = Vec::new();
+
+    // zlib headers for every window size and level, plus a preset dictionary flag.
+    for cinfo in 0..=ZLIB_MAX_CINFO {
+        for level in 0..4 {
+            let header = zlib_header(cinfo, level, false);
+            entries.push(DictEntry::new(
+                format!("zlib_w{}_l{}", cinfo + 8, level),
+                header.to_vec(),
+            ));
+        }
+    }
+    let mut fdict = zlib_header(ZLIB_MAX_CINFO, 2, true).to_vec();
+    fdict.extend([0, 0, 0, 1]);
+    entries.push(DictEntry::new("zlib_fdict", fdict));
+
+    // gzip magic and headers with each optional field.
+    let mut gzip = GZIP_ID.to_vec();
+    gzip.push(CM_DEFLATE);
+    entries.push(DictEntry::new("gzip_magic", gzip.clone()));
+    for (name, flag) in [
+        ("none", 0),
+        ("ftext", GZIP_FTEXT),
+        ("fhcrc", GZIP_FHCRC),
+        ("fextra", GZIP_FEXTRA),
+        ("fname", GZIP_FNAME),
+        ("fcomment", GZIP_FCOMMENT),
+    ] {
+        let mut header = gzip.clone();
+        header.push(flag);
+        entries.push(DictEntry::new(format!("gzip_flg_{}", name), header));
+    }
+
+    // Block headers, final and not.
+    for (name, btype) in [
+        ("stored", BTYPE_NO_COMPRESSION),
+        ("fixed", BTYPE_FIXED_HUFFMAN),
+        ("dynamic", BTYPE_DYNAMIC_HUFFMAN),
+    ] {
+        for (prefix, bfinal) in [("block", 0), ("final", BFINAL_VALUE)] {
+            entries.push(DictEntry::new(
+                format!("{}_{}", prefix, name),
+                pack_bits(&[(bfinal, BFINAL_LEN), (btype, BTYPE_LEN)]),
+            ));
+        }
+    }
+
+    // Stored block lengths and the sync flush marker they end a flush with.
+    entries.push(DictEntry::new("sync_flush", SYNC_FLUSH_MARKER.to_vec()));
+    let mut empty_final = pack_bits(&[
+        (BFINAL_VALUE, BFINAL_LEN),
+        (BTYPE_NO_COMPRESSION, BTYPE_LEN),
+    ]);
+    empty_final.extend(SYNC_FLUSH_MARKER);
+    entries.push(DictEntry::new("empty_final_stored", empty_final));
+    entries.push(DictEntry::new(
+        "stored_len_max",
+        vec![0xff, 0xff, 0x00, 0x00],
+    ));
+
+    // Dynamic block headers with the largest HLIT and HDIST and every HCLEN.
+    for hclen in 0..=(DYN_ALPHABET_CODE_NUM - HCLEN_BASE) {
+        entries.push(DictEntry::new(
+            format!("dynamic_hclen_{}", hclen + HCLEN_BASE),
+            pack_bits(&[
+                (BFINAL_VALUE, BFINAL_LEN),
+                (BTYPE_DYNAMIC_HUFFMAN, BTYPE_LEN),
+                (29, HLIT_LEN),
+                (29, HDIST_LEN),
+                (hclen, HCLEN_LEN),
+            ]),
+        ));
+    }
+
+    entries
+}
+
+/// Write the dictionary in the AFL/libFuzzer format, one `name="\xNN..."` line per entry.
+pub fn write_dictionary<W: Write>(mut writer: W) -> Result<()> {
+    writeln!(
+        writer,
+        "# DEFLATE, zlib and gzip tokens generated by inflate-toy"
+    )?;
+    for entry in dictionary_entries() {
+        let escaped: String = entry
+            .bytes
+            .iter()
+            .map(|b| format!("\\x{:02x}", b))
+            .collect();
+        writeln!(writer, "{}=\"{}\"", entry.name, escaped)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::container::{parse_gzip_header, parse_zlib_header};
+
+    #[test]
+    fn test_entries_are_valid() {
+        let entries = dictionary_entries();
+        for entry in entries.iter().filter(|e| e.name.starts_with("zlib_")) {
+            assert!(parse_zlib_header(&entry.bytes).is_ok(), "{}", entry.name);
+        }
+        let mut names: Vec<_> = entries.iter().map(|e| &e.name).collect();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), entries.len());
+
+        let mut gzip = entries
+            .iter()
+            .find(|e| e.name == "gzip_flg_none")
+            .unwrap()
+            .bytes
+            .clone();
+        gzip.extend([0; 6]);
+        assert!(parse_gzip_header(&gzip).is_ok());
+    }
+
+    #[test]
+    fn test_write_dictionary() {
+        let mut out = Vec::new();
+        write_dictionary(&mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains("sync_flush=\"\\x00\\x00\\xff\\xff\"\n"));
+        assert!(text.contains("final_fixed=\"\\x03\"\n"));
+    }
+}
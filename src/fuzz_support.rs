@@ -0,0 +1,170 @@
+//! Entry points and a seed corpus for fuzzing the decoder.
+//!
+//! The `cargo fuzz` targets in `fuzz/` are thin wrappers around [`decode_no_panic`] and
+//! [`inspect_no_panic`]: any input is a valid argument, malformed data comes back as an error,
+//! and a panic below them is a bug. Both bound the output, so a small input expanding a
+//! thousand times costs a few milliseconds instead of the fuzzer's memory budget.
+//!
+//! [`seed_corpus`] builds small valid streams covering each block type and container, which
+//! mutate towards interesting inputs much faster than random bytes do.
+//! Run `cargo fuzz-corpus [dir]` to write them out.
+
+use crate::container::{self, detect_format, Format};
+use crate::deflate::{
+    deflate_dynamic_to_vec, deflate_fixed_to_vec, deflate_stored_to_vec, deflate_to_vec,
+};
+use crate::disasm::{annotate, disassemble};
+use crate::error::{InflateError, Result};
+use crate::inflate::{analyze, inflate_to_vec_with_options, InflateOptions, SYNC_FLUSH_MARKER};
+use crate::stepper::InflateStepper;
+use crate::tokens::TokenIter;
+use crate::validate::validate;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// The most output a fuzz input may decompress to.
+pub const FUZZ_MAX_OUTPUT_SIZE: usize = 1 << 20;
+
+/// The most blocks a fuzz input may have.
+pub const FUZZ_MAX_BLOCKS: usize = 1 << 12;
+
+/// Inflate options bounding the work spent on an input of the given format.
+fn fuzz_options(format: Format) -> InflateOptions {
+    InflateOptions::new()
+        .format(format)
+        .max_output(FUZZ_MAX_OUTPUT_SIZE)
+        .max_blocks(FUZZ_MAX_BLOCKS)
+}
+
+/// Decompress an arbitrary input, detecting its container, with bounded output.
+/// Never panics: malformed data is reported as an error.
+pub fn decode_no_panic(data: &[u8]) -> Result<(Vec<u8>, Format)> {
+    let format = detect_format(data);
+    let output = inflate_to_vec_with_options(data, &fuzz_options(format))?;
+    Ok((output, format))
+}
+
+/// Run an arbitrary input through the tools built on the decoder: the stepper, the token
+/// iterator, the disassembler, the annotator, the statistics and the validator.
+/// Never panics, whatever the input; the results are thrown away.
+///
+/// The tools don't take output or block limits, so they only run on inputs that
+/// [`decode_no_panic`] decodes, or that fail before going past [`FUZZ_MAX_OUTPUT_SIZE`] bytes
+/// or [`FUZZ_MAX_BLOCKS`] blocks.
+pub fn inspect_no_panic(data: &[u8]) {
+    let raw = inflate_to_vec_with_options(data, &fuzz_options(Format::Raw));
+    if let Err(InflateError::OutputLimitExceeded | InflateError::BlockLimitExceeded) = raw {
+        return;
+    }
+
+    if let Ok(mut stepper) = InflateStepper::with_options(data, &fuzz_options(Format::Raw)) {
+        while let Ok(false) = stepper.step() {}
+    }
+    for token in TokenIter::new(data) {
+        if token.is_err() {
+            break;
+        }
+    }
+    let _ = disassemble(data, Format::Raw);
+    let _ = annotate(data, detect_format(data));
+    let _ = analyze(data);
+    let _ = validate(data);
+}
+
+/// Build the seed corpus: small valid streams of every block type, in every container, plus
+/// a few edge cases. Each seed comes with a file name.
+pub fn seed_corpus() -> Vec<(String, Vec<u8>)> {
+    let text = b"Hello, fuzzer! Hello, fuzzer! Hello, fuzzer! Goodbye.";
+    let run = vec![b'a'; 300];
+    let mut seeds = Vec::new();
+
+    for (name, data) in [("text", &text[..]), ("run", &run[..])] {
+        for (block, deflate) in [
+            ("stored", deflate_stored_to_vec(data)),
+            ("fixed", deflate_fixed_to_vec(data)),
+            ("dynamic", deflate_dynamic_to_vec(data)),
+        ] {
+            seeds.push((format!("{}_{}.deflate", block, name), deflate));
+        }
+    }
+    let deflate = deflate_to_vec(text);
+    seeds.push((
+        "text.zlib".to_string(),
+        container::wrap(&deflate, text, Format::Zlib),
+    ));
+    seeds.push((
+        "text.gz".to_string(),
+        container::wrap(&deflate, text, Format::Gzip),
+    ));
+
+    // An empty final stored block, and a non-final one ended by a sync flush.
+    seeds.push(("empty.deflate".to_string(), deflate_stored_to_vec(b"")));
+    let mut flushed = vec![0];
+    flushed.extend(SYNC_FLUSH_MARKER);
+    flushed.extend(deflate_to_vec(text));
+    seeds.push(("sync_flush.deflate".to_string(), flushed));
+
+    seeds
+}
+
+/// Write the seed corpus into a directory, one file per seed.
+#[cfg(feature = "std")]
+pub fn write_corpus(dir: &std::path::Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    for (name, data) in seed_corpus() {
+        std::fs::write(dir.join(name), data)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seed_corpus_decodes() {
+        let seeds = seed_corpus();
+        for (name, data) in &seeds {
+            assert!(decode_no_panic(data).is_ok(), "{}", name);
+            inspect_no_panic(data);
+        }
+        let (output, format) = decode_no_panic(&seeds.last().unwrap().1).unwrap();
+        assert_eq!(format, Format::Raw);
+        assert!(output.starts_with(b"Hello, fuzzer!"));
+    }
+
+    #[test]
+    fn test_decode_no_panic_limits() {
+        // A fixed block of maximum-length matches expands about 1000 times.
+        let bomb = deflate_fixed_to_vec(&vec![0; 2 * FUZZ_MAX_OUTPUT_SIZE]);
+        assert_eq!(
+            decode_no_panic(&bomb),
+            Err(InflateError::OutputLimitExceeded)
+        );
+        inspect_no_panic(&bomb);
+        // Empty stored blocks, more than the block limit allows.
+        let mut blocks = [0x00, 0x00, 0x00, 0xff, 0xff].repeat(FUZZ_MAX_BLOCKS + 1);
+        blocks.extend([0x01, 0x00, 0x00, 0xff, 0xff]);
+        assert_eq!(
+            decode_no_panic(&blocks),
+            Err(InflateError::BlockLimitExceeded)
+        );
+        inspect_no_panic(&blocks);
+        assert_eq!(decode_no_panic(&[]), Err(InflateError::EmptyInput));
+    }
+
+    /// Inputs the fuzzer found panics with.
+    #[test]
+    fn test_regressions() {
+        // A stored block cut short: its data field was traced before being read, and the
+        // annotator indexed past the end of the input.
+        let truncated_stored = [0x01, 0x00, 0x02, 0xff, 0xfd, 0x1c, 0x70];
+        assert_eq!(
+            decode_no_panic(&truncated_stored),
+            Err(InflateError::UnexpectedEof)
+        );
+        inspect_no_panic(&truncated_stored);
+    }
+}
@@ -0,0 +1,297 @@
+//! Gather how the LZ77 window of a DEFLATE stream is used, as data for a heatmap.
+//!
+//! [`window_usage`] decodes a stream into its matches and gives two views of them: a 2-D
+//! histogram of match lengths by distance bucket, showing which parts of the window the
+//! compressor reaches into and for how much, and for every byte of the output the matches
+//! that copy it, showing which bytes are reused. Rendering is left to other tools, e.g. from
+//! the JSON of the `serde` feature.
+
+use crate::codes::{distance_to_code, DISTANCE_CODE_TABLE};
+use crate::deflate::{MAX_MATCH_LEN, MIN_MATCH_LEN};
+use crate::error::Result;
+use crate::tokens::{Token, TokenIter};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Range;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Number of distance buckets: one per distance code.
+pub const DISTANCE_BUCKETS: usize = 30;
+
+/// Get the range of distances of a bucket of the histogram, which is the range of its
+/// distance code.
+///
+/// # Panics
+///
+/// Panics if the bucket is not below `DISTANCE_BUCKETS`.
+pub fn distance_bucket(bucket: usize) -> Range<usize> {
+    let (_, base, extra_bits) = DISTANCE_CODE_TABLE[bucket];
+    base..base + (1 << extra_bits)
+}
+
+/// A match of the stream: `length` bytes written at `target`, copied from `distance` bytes
+/// before.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BackReference {
+    /// Output offset of the first byte the match writes.
+    pub target: usize,
+    /// Number of bytes the match copies.
+    pub length: usize,
+    /// How many bytes before `target` the copy starts.
+    pub distance: usize,
+}
+
+impl BackReference {
+    /// Get the output offsets of the bytes the match copies. They overlap the bytes it writes
+    /// when the distance is shorter than the length.
+    pub fn source(&self) -> Range<usize> {
+        let start = self.target - self.distance;
+        start..start + self.length
+    }
+}
+
+/// How the matches of a stream use its window.
+/// Only the public fields are serialized; the index of the matches copying each byte is
+/// rebuilt from them when deserializing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "WindowUsageFields")
+)]
+pub struct WindowUsage {
+    /// Number of matches of each length and distance bucket: the row is the length minus
+    /// `MIN_MATCH_LEN`, the column the bucket.
+    pub histogram: Vec<[usize; DISTANCE_BUCKETS]>,
+    /// The matches, in stream order.
+    pub references: Vec<BackReference>,
+    /// Number of bytes the stream decompresses to.
+    pub output_len: usize,
+    /// Where the references copying each output byte start in `incoming`: those of byte `i`
+    /// are at `incoming_start[i]..incoming_start[i + 1]`.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    incoming_start: Vec<usize>,
+    /// Indices in `references` of the matches copying each output byte, byte after byte.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    incoming: Vec<usize>,
+}
+
+/// The serialized fields of a [`WindowUsage`].
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct WindowUsageFields {
+    histogram: Vec<[usize; DISTANCE_BUCKETS]>,
+    references: Vec<BackReference>,
+    output_len: usize,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<WindowUsageFields> for WindowUsage {
+    type Error = &'static str;
+
+    fn try_from(fields: WindowUsageFields) -> core::result::Result<Self, Self::Error> {
+        let in_output = |reference: &BackReference| {
+            reference.distance <= reference.target
+                && reference
+                    .target
+                    .checked_add(reference.length)
+                    .is_some_and(|end| end <= fields.output_len)
+        };
+        if !fields.references.iter().all(in_output) {
+            return Err("a reference reaches outside the output");
+        }
+        Ok(Self::new(
+            fields.histogram,
+            fields.references,
+            fields.output_len,
+        ))
+    }
+}
+
+impl WindowUsage {
+    /// Gather the matches copying each output byte, as a compressed sparse row: count the
+    /// references to each byte, then place them.
+    fn new(
+        histogram: Vec<[usize; DISTANCE_BUCKETS]>,
+        references: Vec<BackReference>,
+        output_len: usize,
+    ) -> Self {
+        let mut incoming_start = vec![0; output_len + 1];
+        for reference in &references {
+            for offset in reference.source() {
+                incoming_start[offset + 1] += 1;
+            }
+        }
+        for i in 0..output_len {
+            incoming_start[i + 1] += incoming_start[i];
+        }
+        let mut next = incoming_start.clone();
+        let mut incoming = vec![0; incoming_start[output_len]];
+        for (index, reference) in references.iter().enumerate() {
+            for offset in reference.source() {
+                incoming[next[offset]] = index;
+                next[offset] += 1;
+            }
+        }
+        Self {
+            histogram,
+            references,
+            output_len,
+            incoming_start,
+            incoming,
+        }
+    }
+
+    /// Get the number of matches of the given length and distance bucket.
+    pub fn count(&self, length: usize, bucket: usize) -> usize {
+        self.histogram[length - MIN_MATCH_LEN][bucket]
+    }
+
+    /// Get the indices in `references` of the matches copying the byte at the given output
+    /// offset, in stream order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the offset is not below `output_len`.
+    pub fn incoming(&self, offset: usize) -> &[usize] {
+        &self.incoming[self.incoming_start[offset]..self.incoming_start[offset + 1]]
+    }
+
+    /// Get the number of times each byte of the output is copied by a match.
+    pub fn reference_counts(&self) -> Vec<usize> {
+        self.incoming_start
+            .windows(2)
+            .map(|pair| pair[1] - pair[0])
+            .collect()
+    }
+}
+
+/// Decode a raw DEFLATE stream and gather how its matches use the window.
+/// The whole stream is decoded, so the same errors as
+/// [`inflate_to_vec`](crate::inflate::inflate_to_vec) are reported.
+pub fn window_usage(data: &[u8]) -> Result<WindowUsage> {
+    let mut histogram = vec![[0; DISTANCE_BUCKETS]; MAX_MATCH_LEN - MIN_MATCH_LEN + 1];
+    let mut references = Vec::new();
+    let mut output_len = 0;
+    for token in TokenIter::new(data) {
+        match token? {
+            Token::Literal(_) => output_len += 1,
+            Token::Match { length, distance } => {
+                let (bucket, _, _) = distance_to_code(distance).expect("decoded distances fit");
+                histogram[length - MIN_MATCH_LEN][bucket] += 1;
+                references.push(BackReference {
+                    target: output_len,
+                    length,
+                    distance,
+                });
+                output_len += length;
+            }
+            Token::StoredBlock(bytes) => output_len += bytes.len(),
+            Token::EndOfBlock => {}
+        }
+    }
+    Ok(WindowUsage::new(histogram, references, output_len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deflate::{self, deflate_fixed_tokens_to_vec};
+    use crate::error::InflateError;
+
+    #[test]
+    fn test_window_usage() {
+        // "abcd", then "bcd" from 3 back, "dbcd" from 4 back, and "ddd" from 1 back, which
+        // overlaps itself.
+        let data = deflate_fixed_tokens_to_vec(&[
+            deflate::Token::Literal(b'a'),
+            deflate::Token::Literal(b'b'),
+            deflate::Token::Literal(b'c'),
+            deflate::Token::Literal(b'd'),
+            deflate::Token::Match {
+                length: 3,
+                distance: 3,
+            },
+            deflate::Token::Match {
+                length: 4,
+                distance: 4,
+            },
+            deflate::Token::Match {
+                length: 3,
+                distance: 1,
+            },
+        ]);
+        let usage = window_usage(&data).unwrap();
+        assert_eq!(usage.output_len, 14);
+        assert_eq!(
+            usage.references[1],
+            BackReference {
+                target: 7,
+                length: 4,
+                distance: 4
+            }
+        );
+        assert_eq!(usage.references[1].source(), 3..7);
+        assert_eq!(usage.references[2].source(), 10..13);
+        assert_eq!(usage.count(3, 2), 1);
+        assert_eq!(usage.count(4, 3), 1);
+        assert_eq!(usage.count(3, 0), 1);
+        assert_eq!(
+            usage.histogram.iter().flatten().sum::<usize>(),
+            usage.references.len()
+        );
+        assert_eq!(usage.incoming(0), &[] as &[usize]);
+        assert_eq!(usage.incoming(1), &[0]);
+        assert_eq!(usage.incoming(3), &[0, 1]);
+        assert_eq!(usage.incoming(12), &[2]);
+        assert_eq!(
+            usage.reference_counts(),
+            [0, 1, 1, 2, 1, 1, 1, 0, 0, 0, 1, 1, 1, 0]
+        );
+    }
+
+    #[test]
+    fn test_window_usage_buckets() {
+        assert_eq!(distance_bucket(0), 1..2);
+        assert_eq!(distance_bucket(4), 5..7);
+        assert_eq!(distance_bucket(DISTANCE_BUCKETS - 1), 24577..32769);
+
+        let raw = b"the window of a stream, the window of a stream ".repeat(500);
+        let data = miniz_oxide::deflate::compress_to_vec(&raw, 6);
+        let usage = window_usage(&data).unwrap();
+        assert_eq!(usage.output_len, raw.len());
+        for reference in &usage.references {
+            let (bucket, _, _) = distance_to_code(reference.distance).unwrap();
+            assert!(distance_bucket(bucket).contains(&reference.distance));
+            let source = reference.source();
+            assert_eq!(
+                raw[source.clone()],
+                raw[reference.target..reference.target + reference.length]
+            );
+        }
+        let counts = usage.reference_counts();
+        assert_eq!(
+            counts.iter().sum::<usize>(),
+            usage.references.iter().map(|r| r.length).sum::<usize>()
+        );
+
+        assert_eq!(window_usage(&[]), Err(InflateError::EmptyInput));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_window_usage_serde() {
+        let raw = b"abcabcabc, abcabcabc".repeat(20);
+        let usage = window_usage(&miniz_oxide::deflate::compress_to_vec(&raw, 6)).unwrap();
+        let json = serde_json::to_string(&usage).unwrap();
+        assert!(!json.contains("incoming"));
+        let parsed: WindowUsage = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, usage);
+
+        let mut json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        json["output_len"] = 3.into();
+        assert!(serde_json::from_value::<WindowUsage>(json).is_err());
+    }
+}
@@ -0,0 +1,165 @@
+//! Hex dumps of byte buffers, as `hexdump -C` writes them.
+//!
+//! Each line holds the offset of its first byte, the bytes in hex, then the bytes as ASCII
+//! between bars, with a dot for anything that is not printable.
+
+use alloc::format;
+use alloc::string::String;
+
+/// The base offsets are written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OffsetBase {
+    #[default]
+    Hex,
+    Decimal,
+    Octal,
+}
+
+/// How a hex dump is laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HexdumpOptions {
+    /// Number of bytes per line, at least 1.
+    pub width: usize,
+    /// Number of bytes per group, separated by an extra space. 0 for no groups.
+    pub group: usize,
+    /// Write hex digits in uppercase.
+    pub uppercase: bool,
+    pub offset_base: OffsetBase,
+    /// Color bytes by class with ANSI escape codes: zeros, printable ASCII, ASCII whitespace,
+    /// and everything else.
+    pub color: bool,
+}
+
+impl Default for HexdumpOptions {
+    fn default() -> Self {
+        Self {
+            width: 16,
+            group: 0,
+            uppercase: false,
+            offset_base: OffsetBase::Hex,
+            color: false,
+        }
+    }
+}
+
+const ANSI_RESET: &str = "\x1b[0m";
+
+/// Get the ANSI color of a byte.
+fn byte_color(byte: u8) -> &'static str {
+    match byte {
+        0 => "\x1b[90m",
+        _ if byte.is_ascii_graphic() => "\x1b[32m",
+        _ if byte.is_ascii_whitespace() => "\x1b[33m",
+        _ => "\x1b[31m",
+    }
+}
+
+/// Write a hex dump of the data, one line per `options.width` bytes.
+pub fn hexdump(data: &[u8], options: &HexdumpOptions) -> String {
+    let width = options.width.max(1);
+    let mut result = String::new();
+
+    for (i, chunk) in data.chunks(width).enumerate() {
+        let offset = i * width;
+        result.push_str(&match (options.offset_base, options.uppercase) {
+            (OffsetBase::Hex, false) => format!("{:08x}: ", offset),
+            (OffsetBase::Hex, true) => format!("{:08X}: ", offset),
+            (OffsetBase::Decimal, _) => format!("{:08}: ", offset),
+            (OffsetBase::Octal, _) => format!("{:08o}: ", offset),
+        });
+
+        for column in 0..width {
+            match chunk.get(column) {
+                Some(&byte) => {
+                    let hex = if options.uppercase {
+                        format!("{:02X} ", byte)
+                    } else {
+                        format!("{:02x} ", byte)
+                    };
+                    if options.color {
+                        result.push_str(byte_color(byte));
+                        result.push_str(&hex);
+                        result.push_str(ANSI_RESET);
+                    } else {
+                        result.push_str(&hex);
+                    }
+                }
+                // Fill the gap after the last byte, so that the ASCII column lines up.
+                None => result.push_str("   "),
+            }
+            let group_end = options.group > 0 && (column + 1) % options.group == 0;
+            if group_end && column + 1 < width {
+                result.push(' ');
+            }
+        }
+
+        result.push_str(" |");
+        for &byte in chunk {
+            let c = if byte.is_ascii_graphic() {
+                byte as char
+            } else {
+                '.'
+            };
+            if options.color {
+                result.push_str(byte_color(byte));
+                result.push(c);
+                result.push_str(ANSI_RESET);
+            } else {
+                result.push(c);
+            }
+        }
+        result.push_str("|\n");
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn test_hexdump_default() {
+        let data: Vec<u8> = (b'a'..=b'z').collect();
+        assert_eq!(
+            hexdump(&data, &HexdumpOptions::default()),
+            "00000000: 61 62 63 64 65 66 67 68 69 6a 6b 6c 6d 6e 6f 70  |abcdefghijklmnop|\n\
+             00000010: 71 72 73 74 75 76 77 78 79 7a                    |qrstuvwxyz|\n"
+        );
+        assert_eq!(hexdump(&[], &HexdumpOptions::default()), "");
+    }
+
+    #[test]
+    fn test_hexdump_options() {
+        let data = [0x00, 0x0a, 0xab, b'A', 0xff];
+        let options = HexdumpOptions {
+            width: 4,
+            group: 2,
+            uppercase: true,
+            offset_base: OffsetBase::Decimal,
+            color: false,
+        };
+        assert_eq!(
+            hexdump(&data, &options),
+            "00000000: 00 0A  AB 41  |...A|\n\
+             00000004: FF            |.|\n"
+        );
+
+        let options = HexdumpOptions {
+            width: 8,
+            offset_base: OffsetBase::Octal,
+            ..Default::default()
+        };
+        let dump = hexdump(&[0; 9], &options);
+        assert!(dump.ends_with("00000010: 00                       |.|\n"));
+
+        let options = HexdumpOptions {
+            color: true,
+            ..Default::default()
+        };
+        let dump = hexdump(b"A", &options);
+        assert!(dump.contains("\x1b[32m41 \x1b[0m"));
+        assert!(dump.contains("|\x1b[32mA\x1b[0m|"));
+    }
+}
@@ -2,6 +2,143 @@
 //! This mod focuses on the deflation-independent part of Huffman encoding and decoding.
 //!
 
+use crate::bit_stream::{BitReader, BitWriter};
+use crate::error::{InflateError, Result};
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use alloc::{format, vec};
+use core::fmt;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Assign the canonical Huffman code of every symbol from the code lengths alone.
+/// Returns `(code, len)` for each symbol, with the code in its natural (most significant bit
+/// first) order; symbols with a zero code length get `(0, 0)`.
+///
+/// This is the algorithm of RFC 1951, section 3.2.2:
+/// 1. Count the number of codes for each code length (`bl_count`).
+/// 2. Find the numerical value of the smallest code for each code length (`next_code`).
+/// 3. Assign consecutive values to the symbols of each length, in symbol order.
+pub fn assign_canonical_codes(code_len: &[u8]) -> Vec<(usize, u8)> {
+    let max_bits = code_len.iter().copied().max().unwrap_or(0) as usize;
+
+    // Step 1: count the number of codes for each code length.
+    let mut bl_count = vec![0usize; max_bits + 1];
+    code_len.iter().for_each(|&len| bl_count[len as usize] += 1);
+    bl_count[0] = 0;
+
+    // Step 2: find the numerical value of the smallest code for each code length.
+    let mut next_code = vec![0usize; max_bits + 1];
+    let mut code = 0;
+    for bits in 1..=max_bits {
+        code = (code + bl_count[bits - 1]) << 1;
+        next_code[bits] = code;
+    }
+
+    // Step 3: assign numerical values to all codes.
+    code_len
+        .iter()
+        .map(|&len| {
+            if len == 0 {
+                return (0, 0);
+            }
+            let code = next_code[len as usize];
+            next_code[len as usize] += 1;
+            (code, len)
+        })
+        .collect()
+}
+
+/// A node of the lists built by the package-merge algorithm.
+#[derive(Debug, Clone, Copy)]
+enum PackageNode {
+    /// A symbol.
+    Leaf(usize),
+    /// A package of two items, given by their indices in the list of the level below.
+    Package(usize, usize),
+}
+
+/// Build the code lengths of an optimal prefix code of at most `max_bits` bits from symbol
+/// frequencies. Symbols with a zero frequency get no code; a single used symbol gets a 1-bit code.
+///
+/// This is the package-merge algorithm of Larmore and Hirschberg. Finding optimal lengths
+/// limited to `max_bits` is the same as picking the cheapest set of "coins" from `max_bits`
+/// denominations, each symbol having one coin of each denomination with its frequency as value:
+/// 1. Start with the list of symbols sorted by frequency, for the smallest denomination.
+/// 2. For each larger denomination, pair up the items of the previous list into packages, and
+///    merge them with the symbols into a new sorted list.
+/// 3. Take the `2n - 2` cheapest items of the last list: the code length of a symbol is the
+///    number of times it appears in them, directly or inside packages.
+///
+/// # Panics
+///
+/// Panics if more symbols are used than `max_bits` bits can give codes to.
+pub fn build_code_lengths(freqs: &[u64], max_bits: u8) -> Vec<u8> {
+    let mut lengths = vec![0; freqs.len()];
+    let mut leaves: Vec<(u64, PackageNode)> = freqs
+        .iter()
+        .enumerate()
+        .filter(|&(_, &freq)| freq > 0)
+        .map(|(symbol, &freq)| (freq, PackageNode::Leaf(symbol)))
+        .collect();
+    let n = leaves.len();
+    assert!(
+        n as u128 <= 1 << max_bits,
+        "{} symbols do not fit in {} bits",
+        n,
+        max_bits
+    );
+    if n == 1 {
+        if let PackageNode::Leaf(symbol) = leaves[0].1 {
+            lengths[symbol] = 1;
+        }
+    }
+    if n < 2 {
+        return lengths;
+    }
+    leaves.sort_by_key(|&(freq, _)| freq);
+
+    // Step 1 and 2: one list per denomination, the smallest first.
+    let mut levels = vec![leaves.clone()];
+    for _ in 1..max_bits {
+        let previous = levels.last().unwrap();
+        let packages = (0..previous.len() / 2).map(|i| {
+            let weight = previous[2 * i].0.saturating_add(previous[2 * i + 1].0);
+            (weight, PackageNode::Package(2 * i, 2 * i + 1))
+        });
+        let mut merged: Vec<_> = leaves.iter().copied().chain(packages).collect();
+        // A stable sort keeps symbols before packages of the same weight.
+        merged.sort_by_key(|&(weight, _)| weight);
+        // Only the cheapest 2n - 2 items can ever be picked.
+        merged.truncate(2 * n - 2);
+        levels.push(merged);
+    }
+
+    // Step 3: count the appearances of each symbol in the picked items.
+    let mut stack: Vec<_> = (0..2 * n - 2).map(|i| (levels.len() - 1, i)).collect();
+    while let Some((level, index)) = stack.pop() {
+        match levels[level][index].1 {
+            PackageNode::Leaf(symbol) => lengths[symbol] += 1,
+            PackageNode::Package(a, b) => {
+                stack.push((level - 1, a));
+                stack.push((level - 1, b));
+            }
+        }
+    }
+    lengths
+}
+
+/// A way to decode the symbols of a Huffman code, so the decode loop can run over any of them
+/// and they can be compared on the same streams.
+pub trait HuffmanDecoder {
+    /// Decode one symbol, reading as many bits as its code takes.
+    ///
+    /// Bits that no code starts with are reported as [`InflateError::InvalidHuffmanSymbol`],
+    /// and a code running past the end of the data as [`InflateError::UnexpectedEof`].
+    fn decode_symbol(&self, bit_reader: &mut BitReader) -> Result<usize>;
+}
+
 /// Huffman tree lookup table.
 /// A lookup table is used to speed up the encoding and decoding process.
 /// In this table, each code is mapped to a symbol and a code length.
@@ -14,10 +151,134 @@
 /// will all be the same symbol that the code 0b101 represents.
 /// This will make the lookup process faster.
 ///
+/// A flat table for 15-bit codes has 32768 entries, most of them copies for the few long codes.
+/// A two-level table, built by [`HuffmanLookupTable::new_two_level`], only indexes the first
+/// `root_bits` bits in the root part of `table`. Codes longer than that go to sub-tables after
+/// it, indexed by the remaining bits: the root entry for their first bits is a link holding the
+/// start of the sub-table and, with `SUB_TABLE_LINK` set, its number of index bits.
+///
+/// Entries are packed into a [`TableEntry`] of 4 bytes, a quarter of a `(usize, u8)` tuple, so
+/// that more of a large table stays in the cache.
+///
 #[derive(Debug, Clone)]
 pub struct HuffmanLookupTable {
-    pub table: Vec<(usize, u8)>,
+    pub table: Vec<TableEntry>,
     pub max_bits: u8,
+    /// Number of bits indexing the root part of the table, `max_bits` for a flat table.
+    pub root_bits: u8,
+}
+
+/// The flag marking a root entry of a two-level table as a link to a sub-table.
+pub const SUB_TABLE_LINK: u8 = 0x80;
+
+/// An entry of a [`HuffmanLookupTable`]: a symbol and its code length, packed into a `u32` with
+/// the length in the low 8 bits and the symbol in the high 24.
+///
+/// A link to a sub-table holds its start in place of the symbol, and `SUB_TABLE_LINK` with its
+/// number of index bits in place of the length. An entry with a zero length has no code.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TableEntry(u32);
+
+impl TableEntry {
+    /// Pack a symbol and a code length.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the symbol does not fit in 24 bits.
+    pub const fn new(symbol: usize, len: u8) -> Self {
+        assert!(symbol < 1 << 24, "Symbol does not fit in 24 bits");
+        Self((symbol as u32) << 8 | len as u32)
+    }
+
+    /// Get the symbol, or the start of the sub-table of a link.
+    pub const fn symbol(self) -> usize {
+        (self.0 >> 8) as usize
+    }
+
+    /// Get the code length, or `SUB_TABLE_LINK` and the index bits of a link.
+    pub const fn code_len(self) -> u8 {
+        self.0 as u8
+    }
+
+    /// Check if the entry links to a sub-table.
+    pub const fn is_link(self) -> bool {
+        self.code_len() & SUB_TABLE_LINK != 0
+    }
+
+    /// Get the symbol and the code length as a tuple.
+    pub const fn unpack(self) -> (usize, u8) {
+        (self.symbol(), self.code_len())
+    }
+}
+
+/// The longest codes a lookup table supports. DEFLATE codes have at most 15 bits; the extra
+/// bit is for experimenting with other formats.
+pub const MAX_CODE_BITS: u8 = 16;
+
+/// How much of the code space a set of code lengths fills, by the Kraft inequality.
+///
+/// Shares of the code space are counted in units of 2^-`MAX_CODE_BITS`: a code of length `l`
+/// takes 2^(`MAX_CODE_BITS` - `l`) of the 2^`MAX_CODE_BITS` units there are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum CodeLengthStatus {
+    /// The codes fill the code space exactly.
+    Complete,
+    /// The codes leave `slack` units unused. Lengths that are all zero leave the whole space.
+    Incomplete { slack: u64 },
+    /// The codes take `excess` units more than the code space holds.
+    Oversubscribed { excess: u64 },
+}
+
+/// Compute how much of the code space the given code lengths fill, without building a table.
+/// A zero length means the symbol has no code.
+///
+/// This only measures the lengths: decoders still accept the incomplete codes RFC 1951 allows,
+/// no code at all or a single code of one bit, which are reported as
+/// [`CodeLengthStatus::Incomplete`] here.
+///
+/// # Panics
+///
+/// Panics if a length is above `MAX_CODE_BITS`.
+pub fn check_code_lengths(code_len: &[u8]) -> CodeLengthStatus {
+    assert!(
+        code_len.iter().all(|&len| len <= MAX_CODE_BITS),
+        "Code length above {} bits",
+        MAX_CODE_BITS
+    );
+    let used: u64 = code_len
+        .iter()
+        .filter(|&&len| len != 0)
+        .map(|&len| 1 << (MAX_CODE_BITS - len))
+        .sum();
+    let space = 1u64 << MAX_CODE_BITS;
+    match used.cmp(&space) {
+        core::cmp::Ordering::Equal => CodeLengthStatus::Complete,
+        core::cmp::Ordering::Less => CodeLengthStatus::Incomplete {
+            slack: space - used,
+        },
+        core::cmp::Ordering::Greater => CodeLengthStatus::Oversubscribed {
+            excess: used - space,
+        },
+    }
+}
+
+/// Check that a table of `max_bits` bits can hold the codes of the given lengths.
+fn check_max_bits(code_len: &[u8], max_bits: u8) -> Result<()> {
+    if max_bits > MAX_CODE_BITS || code_len.iter().any(|&len| len > max_bits) {
+        return Err(InflateError::CodeTooLong);
+    }
+    Ok(())
+}
+
+/// Reverse the `len` bits of a Huffman code, which is packed starting with its most significant
+/// bit, into the order the bits are read from the stream.
+const fn reverse_code(code: usize, len: u8) -> usize {
+    code.reverse_bits() >> (usize::BITS as u8 - len)
 }
 
 impl HuffmanLookupTable {
@@ -28,31 +289,19 @@ impl HuffmanLookupTable {
     /// * `max_bits` - The maximum code length in the Huffman tree.
     /// * `table` - The lookup table.
     ///
+    /// # Panics
+    ///
+    /// Panics if `max_bits` is above `MAX_CODE_BITS` or a code is longer than `max_bits`.
     pub fn new(code_len: &[u8], max_bits: u8) -> Self {
-        assert!(max_bits <= usize::BITS as u8);
-        let mut table = vec![(0, 0); 1 << max_bits];
-
-        // Count the number of codes for each code length.
-        let mut bl_count = vec![0; max_bits as usize + 1];
-        code_len.iter().for_each(|&len| bl_count[len as usize] += 1);
-
-        // Find the numerical value of the smallest code for each code length.
-        let mut next_code = vec![0usize; max_bits as usize + 2];
-        let mut code = 0;
-        bl_count.iter().enumerate().for_each(|(bits, &count)| {
-            code = (code + count) << 1;
-            next_code[bits + 1] = code;
-        });
+        check_max_bits(code_len, max_bits).expect("Code longer than the table supports");
+        let mut table = vec![TableEntry::default(); 1 << max_bits];
 
         // Fill the lookup table.
-        code_len
-            .iter()
+        assign_canonical_codes(code_len)
+            .into_iter()
             .enumerate()
-            .filter(|(_, &len)| len != 0)
-            .for_each(|(symbol, &len)| {
-                let code = next_code[len as usize];
-                next_code[len as usize] += 1;
-
+            .filter(|&(_, (_, len))| len != 0)
+            .for_each(|(symbol, (code, len))| {
                 // code is len bits long, so there are max_bits - len bits left.
                 let shift = max_bits - len;
                 let start = code << shift;
@@ -64,24 +313,824 @@ impl HuffmanLookupTable {
                     let rev = i.reverse_bits();
                     // Get the leftmost max_bits bits.
                     let rev_left = rev >> (usize::BITS as u8 - max_bits);
-                    table[rev_left] = (symbol, len);
+                    table[rev_left] = TableEntry::new(symbol, len);
                 }
             });
 
-        Self { table, max_bits }
+        Self {
+            table,
+            max_bits,
+            root_bits: max_bits,
+        }
+    }
+
+    /// Create a new two-level lookup table, whose root part is indexed by `root_bits` bits.
+    /// It decodes the same as the flat table of [`HuffmanLookupTable::new`], with far fewer
+    /// entries when a few codes are much longer than the others.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_bits` is above `MAX_CODE_BITS` or a code is longer than `max_bits`.
+    pub fn new_two_level(code_len: &[u8], max_bits: u8, root_bits: u8) -> Self {
+        check_max_bits(code_len, max_bits).expect("Code longer than the table supports");
+        let root_bits = root_bits.min(max_bits);
+        let root_mask = (1 << root_bits) - 1;
+        let codes = assign_canonical_codes(code_len);
+        let mut table = vec![TableEntry::default(); 1 << root_bits];
+
+        // Each sub-table is as large as its longest code requires.
+        let mut sub_bits = vec![0; 1 << root_bits];
+        for &(code, len) in codes.iter().filter(|&&(_, len)| len > root_bits) {
+            let prefix = reverse_code(code, len) & root_mask;
+            sub_bits[prefix] = sub_bits[prefix].max(len - root_bits);
+        }
+        for (prefix, &bits) in sub_bits.iter().enumerate().filter(|&(_, &bits)| bits > 0) {
+            table[prefix] = TableEntry::new(table.len(), SUB_TABLE_LINK | bits);
+            table.resize(table.len() + (1 << bits), TableEntry::default());
+        }
+
+        for (symbol, (code, len)) in codes.into_iter().enumerate() {
+            if len == 0 {
+                continue;
+            }
+            let reversed = reverse_code(code, len);
+            // Fill every entry whose index starts with the code, within the root or a sub-table.
+            let (start, index, index_bits, code_bits) = if len <= root_bits {
+                (0, reversed, root_bits, len)
+            } else {
+                let (start, link) = table[reversed & root_mask].unpack();
+                let bits = link & !SUB_TABLE_LINK;
+                (start, reversed >> root_bits, bits, len - root_bits)
+            };
+            for padding in 0..1 << (index_bits - code_bits) {
+                table[start + (index | padding << code_bits)] = TableEntry::new(symbol, len);
+            }
+        }
+
+        Self {
+            table,
+            max_bits,
+            root_bits,
+        }
+    }
+
+    /// Create a new lookup table, checking first that the code lengths describe a valid code.
+    ///
+    /// By the Kraft inequality, a code with lengths `l` fills the code space exactly when the sum
+    /// of `2^-l` is 1. Codes that go over it are rejected with
+    /// [`InflateError::OversubscribedTable`], and codes that leave part of it unused with
+    /// [`InflateError::IncompleteTable`], except for the two incomplete codes RFC 1951 allows
+    /// (section 3.2.7): no code at all, or a single code of one bit. Codes longer than
+    /// `max_bits`, or a `max_bits` above `MAX_CODE_BITS`, are rejected with
+    /// [`InflateError::CodeTooLong`].
+    pub fn try_new(code_len: &[u8], max_bits: u8) -> Result<Self> {
+        Self::validate_code_lengths(code_len, max_bits)?;
+        Ok(Self::new(code_len, max_bits))
+    }
+
+    /// Create a new two-level lookup table, checking the code lengths as
+    /// [`HuffmanLookupTable::try_new`] does.
+    pub fn try_new_two_level(code_len: &[u8], max_bits: u8, root_bits: u8) -> Result<Self> {
+        Self::validate_code_lengths(code_len, max_bits)?;
+        Ok(Self::new_two_level(code_len, max_bits, root_bits))
+    }
+
+    fn validate_code_lengths(code_len: &[u8], max_bits: u8) -> Result<()> {
+        check_max_bits(code_len, max_bits)?;
+        match check_code_lengths(code_len) {
+            CodeLengthStatus::Complete => Ok(()),
+            CodeLengthStatus::Oversubscribed { .. } => Err(InflateError::OversubscribedTable),
+            CodeLengthStatus::Incomplete { .. } => {
+                let n_codes = code_len.iter().filter(|&&len| len != 0).count();
+                let single_one_bit_code = n_codes == 1 && code_len.contains(&1);
+                if n_codes == 0 || single_one_bit_code {
+                    Ok(())
+                } else {
+                    Err(InflateError::IncompleteTable)
+                }
+            }
+        }
     }
 
+    /// Look up the symbol and code length of the code starting at the least significant bit of
+    /// `code`, which holds the next `max_bits` bits of the stream.
     pub fn get(&self, code: usize) -> Option<(usize, u8)> {
-        // Only use the least significant max_bits bits.
-        let mask = (1 << self.max_bits) - 1;
-        let code = code & mask;
-        self.table.get(code).cloned()
+        // Only use the least significant root_bits bits.
+        let mask = (1 << self.root_bits) - 1;
+        let entry = *self.table.get(code & mask)?;
+        if !entry.is_link() {
+            return Some(entry.unpack());
+        }
+        let (start, link) = entry.unpack();
+        let sub_mask = (1 << (link & !SUB_TABLE_LINK)) - 1;
+        self.table
+            .get(start + ((code >> self.root_bits) & sub_mask))
+            .map(|entry| entry.unpack())
+    }
+
+    /// Decode one symbol, reading as many bits as its code takes.
+    ///
+    /// Bits that no code starts with, which only incomplete codes leave, are reported as
+    /// [`InflateError::InvalidHuffmanSymbol`]. The code is looked up with zeros past the end of
+    /// the data, so a code running past the end is reported as [`InflateError::UnexpectedEof`].
+    pub fn decode_symbol(&self, bit_reader: &mut BitReader) -> Result<usize> {
+        decode_with_lookup(bit_reader, self.max_bits, |code| self.get(code))
+    }
+
+    /// Get the code lengths the table was built from, as long as its last used symbol.
+    pub fn code_lengths(&self) -> Vec<u8> {
+        let mut code_len = Vec::new();
+        for entry in &self.table {
+            let (symbol, len) = entry.unpack();
+            if len == 0 || entry.is_link() {
+                continue;
+            }
+            if symbol >= code_len.len() {
+                code_len.resize(symbol + 1, 0);
+            }
+            code_len[symbol] = len;
+        }
+        code_len
+    }
+
+    /// Get `(symbol, code, len)` for every symbol with a code, by symbol. The code is the
+    /// canonical one, most significant bit first, as [`assign_canonical_codes`] gives it.
+    pub fn codes(&self) -> Vec<(usize, usize, u8)> {
+        assign_canonical_codes(&self.code_lengths())
+            .into_iter()
+            .enumerate()
+            .filter(|&(_, (_, len))| len > 0)
+            .map(|(symbol, (code, len))| (symbol, code, len))
+            .collect()
+    }
+
+    /// Describe the Huffman tree the table flattens in the Graphviz DOT language,
+    /// as [`HuffmanTree::to_dot`] does.
+    pub fn to_dot(&self) -> String {
+        HuffmanTree::new_unchecked(&self.code_lengths()).to_dot()
+    }
+}
+
+/// Decode with one lookup of the next `max_bits` bits, or two for a long code of a two-level
+/// table.
+impl HuffmanDecoder for HuffmanLookupTable {
+    fn decode_symbol(&self, bit_reader: &mut BitReader) -> Result<usize> {
+        HuffmanLookupTable::decode_symbol(self, bit_reader)
+    }
+}
+
+/// Decode one symbol by looking up the next `max_bits` bits with `get`, for
+/// [`HuffmanLookupTable::decode_symbol`] and [`ConstLookupTable::decode_symbol`].
+fn decode_with_lookup(
+    bit_reader: &mut BitReader,
+    max_bits: u8,
+    get: impl Fn(usize) -> Option<(usize, u8)>,
+) -> Result<usize> {
+    let peek_code = bit_reader
+        .try_peek_bits(max_bits as usize)
+        .ok_or(InflateError::InvalidHuffmanSymbol)?;
+    let (symbol, len) = get(peek_code).ok_or(InflateError::InvalidHuffmanSymbol)?;
+    // A zero length means no code was assigned to these bits.
+    if len == 0 {
+        return Err(InflateError::InvalidHuffmanSymbol);
+    }
+    bit_reader
+        .try_advance(len as usize)
+        .ok_or(InflateError::UnexpectedEof)?;
+    Ok(symbol)
+}
+
+/// A flat lookup table of `N` entries held in an array rather than a `Vec`, so that it can be
+/// built in a `const` context: a table of a code known in advance, such as the fixed codes of
+/// DEFLATE, is then computed by the compiler and stored in the binary.
+///
+/// ```
+/// use inflate_toy::huffman::ConstLookupTable;
+///
+/// // The example of RFC 1951, section 3.2.2, built at compile time.
+/// static TABLE: ConstLookupTable<16> = ConstLookupTable::new(&[3, 3, 3, 3, 3, 2, 4, 4]);
+/// assert_eq!(TABLE.get(0b0_00), Some((5, 2)));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConstLookupTable<const N: usize> {
+    /// The entries, indexed by the next `max_bits` bits as in a flat [`HuffmanLookupTable`].
+    pub table: [TableEntry; N],
+    /// The maximum code length, such that `N` is 2^max_bits.
+    pub max_bits: u8,
+}
+
+impl<const N: usize> ConstLookupTable<N> {
+    /// Build the table of the canonical code with the given code lengths, as
+    /// [`HuffmanLookupTable::new`] does. A `const fn` can only loop with `while`, so this
+    /// repeats the steps of [`assign_canonical_codes`] without iterators or allocation.
+    ///
+    /// # Panics
+    ///
+    /// Panics, at compile time when evaluated in a constant, if `N` is not a power of two up to
+    /// 2^`MAX_CODE_BITS` or a code is longer than its logarithm.
+    pub const fn new(code_len: &[u8]) -> Self {
+        assert!(N.is_power_of_two(), "The table size must be a power of two");
+        let max_bits = N.ilog2() as u8;
+        assert!(
+            max_bits <= MAX_CODE_BITS,
+            "Code longer than the table supports"
+        );
+
+        // Step 1: count the number of codes for each code length.
+        let mut bl_count = [0usize; MAX_CODE_BITS as usize + 1];
+        let mut symbol = 0;
+        while symbol < code_len.len() {
+            let len = code_len[symbol];
+            assert!(len <= max_bits, "Code longer than the table supports");
+            if len != 0 {
+                bl_count[len as usize] += 1;
+            }
+            symbol += 1;
+        }
+
+        // Step 2: find the numerical value of the smallest code for each code length.
+        let mut next_code = [0usize; MAX_CODE_BITS as usize + 1];
+        let mut code = 0;
+        let mut bits = 1;
+        while bits <= max_bits as usize {
+            code = (code + bl_count[bits - 1]) << 1;
+            next_code[bits] = code;
+            bits += 1;
+        }
+
+        // Step 3: assign the codes, filling every entry whose index starts with one.
+        let mut table = [TableEntry(0); N];
+        let mut symbol = 0;
+        while symbol < code_len.len() {
+            let len = code_len[symbol];
+            if len != 0 {
+                let reversed = reverse_code(next_code[len as usize], len);
+                next_code[len as usize] += 1;
+                let mut padding = 0;
+                while padding < 1 << (max_bits - len) {
+                    table[reversed | padding << len] = TableEntry::new(symbol, len);
+                    padding += 1;
+                }
+            }
+            symbol += 1;
+        }
+        Self { table, max_bits }
+    }
+
+    /// Look up the symbol and code length of the code starting at the least significant bit of
+    /// `code`, as [`HuffmanLookupTable::get`] does.
+    pub const fn get(&self, code: usize) -> Option<(usize, u8)> {
+        Some(self.table[code & (N - 1)].unpack())
+    }
+
+    /// Decode one symbol, as [`HuffmanLookupTable::decode_symbol`] does.
+    pub fn decode_symbol(&self, bit_reader: &mut BitReader) -> Result<usize> {
+        decode_with_lookup(bit_reader, self.max_bits, |code| self.get(code))
+    }
+
+    /// Copy the table into a [`HuffmanLookupTable`], for the code that takes one.
+    pub fn to_lookup_table(&self) -> HuffmanLookupTable {
+        HuffmanLookupTable {
+            table: self.table.to_vec(),
+            max_bits: self.max_bits,
+            root_bits: self.max_bits,
+        }
+    }
+}
+
+impl<const N: usize> HuffmanDecoder for ConstLookupTable<N> {
+    fn decode_symbol(&self, bit_reader: &mut BitReader) -> Result<usize> {
+        ConstLookupTable::decode_symbol(self, bit_reader)
+    }
+}
+
+/// List the codes as the example of RFC 1951, section 3.2.2 does: one line per symbol with a
+/// code, giving its length and its bits.
+impl fmt::Display for HuffmanLookupTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Symbol Length   Code")?;
+        writeln!(f, "------ ------   ----")?;
+        for (symbol, code, len) in self.codes() {
+            writeln!(
+                f,
+                "{:>6} {:>6}   {:0width$b}",
+                symbol,
+                len,
+                code,
+                width = len as usize
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The code of every symbol, to write them: the reverse of a [`HuffmanLookupTable`].
+/// Both are built from the same canonical assignment, so a symbol written with the encode table
+/// is read back by the lookup table built from the same code lengths.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HuffmanEncodeTable {
+    /// `(code, len)` of every symbol, the code reversed into the order its bits are written.
+    codes: Vec<(usize, u8)>,
+}
+
+impl HuffmanEncodeTable {
+    /// Create the encode table of the canonical code with the given code lengths.
+    pub fn new(code_len: &[u8]) -> Self {
+        let codes = assign_canonical_codes(code_len)
+            .into_iter()
+            .map(|(code, len)| match len {
+                0 => (0, 0),
+                _ => (reverse_code(code, len), len),
+            })
+            .collect();
+        Self { codes }
+    }
+
+    /// Get the code of a symbol, most significant bit first as [`assign_canonical_codes`]
+    /// gives it, and its length. Symbols without a code give `None`.
+    pub fn get(&self, symbol: usize) -> Option<(usize, u8)> {
+        match *self.codes.get(symbol)? {
+            (_, 0) => None,
+            (reversed, len) => Some((reverse_code(reversed, len), len)),
+        }
+    }
+
+    /// Number of symbols of the alphabet, used or not.
+    pub fn len(&self) -> usize {
+        self.codes.len()
+    }
+
+    /// Check if the alphabet has no symbols.
+    pub fn is_empty(&self) -> bool {
+        self.codes.is_empty()
+    }
+
+    /// Write the code of a symbol.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the symbol has no code.
+    pub fn write_symbol(&self, writer: &mut BitWriter, symbol: usize) {
+        let (reversed, len) = self.codes[symbol];
+        assert!(len > 0, "Symbol {} has no code", symbol);
+        writer.write_bits(reversed, len as usize);
+    }
+}
+
+/// A node of a [`HuffmanTree`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HuffmanNode {
+    /// The end of a code, standing for this symbol.
+    Leaf(usize),
+    /// A prefix shared by longer codes, with the subtrees after a 0 bit and after a 1 bit.
+    /// A missing subtree means no code continues the prefix with that bit.
+    Internal {
+        zero: Option<Box<HuffmanNode>>,
+        one: Option<Box<HuffmanNode>>,
+    },
+}
+
+impl HuffmanNode {
+    /// Get the subtree after the given bit, if this is an internal node that has one.
+    pub fn child(&self, bit: usize) -> Option<&HuffmanNode> {
+        match self {
+            HuffmanNode::Leaf(_) => None,
+            HuffmanNode::Internal { zero, one } => match bit {
+                0 => zero.as_deref(),
+                _ => one.as_deref(),
+            },
+        }
+    }
+
+    /// Get the symbol of a leaf.
+    pub fn symbol(&self) -> Option<usize> {
+        match self {
+            HuffmanNode::Leaf(symbol) => Some(*symbol),
+            HuffmanNode::Internal { .. } => None,
+        }
+    }
+}
+
+/// A leaf of a [`HuffmanTree`], with the path leading to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct HuffmanLeaf {
+    pub symbol: usize,
+    /// The code of the symbol, with its first bit as the most significant one.
+    pub code: usize,
+    pub len: u8,
+}
+
+/// A Huffman code as an explicit binary tree.
+///
+/// Decoding walks down from the root one bit at a time, so it is much slower than a
+/// [`HuffmanLookupTable`], which finds the symbol with one or two lookups. The tree is there to
+/// be looked at: it is the structure the lookup table flattens.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HuffmanTree {
+    root: HuffmanNode,
+}
+
+impl HuffmanTree {
+    /// Build the tree of the canonical Huffman code with the given code lengths.
+    /// The code lengths must describe a complete code, as for [`HuffmanLookupTable::try_new`].
+    pub fn try_new(code_len: &[u8]) -> Result<Self> {
+        let max_bits = code_len.iter().copied().max().unwrap_or(0);
+        HuffmanLookupTable::validate_code_lengths(code_len, max_bits)?;
+        Ok(Self::new_unchecked(code_len))
+    }
+
+    /// Build the tree of the canonical Huffman code with the given code lengths, without checking
+    /// them. Codes of an oversubscribed code that collide with earlier ones are left out.
+    fn new_unchecked(code_len: &[u8]) -> Self {
+        let mut root = HuffmanNode::Internal {
+            zero: None,
+            one: None,
+        };
+        'symbols: for (symbol, (code, len)) in
+            assign_canonical_codes(code_len).into_iter().enumerate()
+        {
+            if len == 0 {
+                continue;
+            }
+            let mut node = &mut root;
+            for i in (0..len).rev() {
+                let HuffmanNode::Internal { zero, one } = node else {
+                    continue 'symbols;
+                };
+                let slot = if (code >> i) & 1 == 0 { zero } else { one };
+                node = slot.get_or_insert_with(|| {
+                    Box::new(if i == 0 {
+                        HuffmanNode::Leaf(symbol)
+                    } else {
+                        HuffmanNode::Internal {
+                            zero: None,
+                            one: None,
+                        }
+                    })
+                });
+            }
+        }
+        Self { root }
+    }
+
+    /// Get the root of the tree, an internal node even for a code with no symbols.
+    pub fn root(&self) -> &HuffmanNode {
+        &self.root
+    }
+
+    /// Decode one symbol, reading its code bit by bit.
+    pub fn decode_one(&self, bit_reader: &mut BitReader) -> Result<usize> {
+        let mut node = &self.root;
+        loop {
+            if let HuffmanNode::Leaf(symbol) = node {
+                return Ok(*symbol);
+            }
+            if bit_reader.eof() {
+                return Err(InflateError::UnexpectedEof);
+            }
+            let bit = bit_reader
+                .try_read_bits(1)
+                .ok_or(InflateError::UnexpectedEof)?;
+            node = node.child(bit).ok_or(InflateError::InvalidHuffmanSymbol)?;
+        }
+    }
+
+    /// Describe the tree in the Graphviz DOT language.
+    /// Each leaf is labeled with its symbol, its code bits and its length, and each edge with
+    /// the bit leading to its child.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph huffman {\n    node [shape=point];\n");
+        let mut next_id = 0;
+        write_dot_node(&self.root, 0, 0, &mut next_id, &mut dot);
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Iterate over the leaves of the tree, from left to right: the 0 side of a node comes
+    /// before its 1 side, so the leaves come in the order of their codes.
+    pub fn leaves(&self) -> Leaves<'_> {
+        Leaves {
+            stack: vec![(&self.root, 0, 0)],
+        }
+    }
+}
+
+/// Write the DOT statements of a node and its subtrees, given the code and length of the path
+/// to it. Nodes are numbered in depth-first order.
+fn write_dot_node(node: &HuffmanNode, code: usize, len: u8, next_id: &mut usize, dot: &mut String) {
+    let id = *next_id;
+    *next_id += 1;
+    match node {
+        HuffmanNode::Leaf(symbol) => {
+            let bits: String = (0..len)
+                .rev()
+                .map(|i| if (code >> i) & 1 == 0 { '0' } else { '1' })
+                .collect();
+            dot.push_str(&format!(
+                "    n{} [shape=box, label=\"{}\\n{}\\nlen {}\"];\n",
+                id, symbol, bits, len
+            ));
+        }
+        HuffmanNode::Internal { zero, one } => {
+            dot.push_str(&format!("    n{};\n", id));
+            for (bit, child) in [(0, zero), (1, one)] {
+                if let Some(child) = child {
+                    dot.push_str(&format!(
+                        "    n{} -> n{} [label=\"{}\"];\n",
+                        id, *next_id, bit
+                    ));
+                    write_dot_node(child, code << 1 | bit, len + 1, next_id, dot);
+                }
+            }
+        }
+    }
+}
+
+/// Decode by walking down the tree one bit at a time.
+impl HuffmanDecoder for HuffmanTree {
+    fn decode_symbol(&self, bit_reader: &mut BitReader) -> Result<usize> {
+        self.decode_one(bit_reader)
+    }
+}
+
+/// The iterator returned by [`HuffmanTree::leaves`].
+#[derive(Debug, Clone)]
+pub struct Leaves<'a> {
+    /// Subtrees still to visit, with the code and length of the path to them.
+    stack: Vec<(&'a HuffmanNode, usize, u8)>,
+}
+
+impl Iterator for Leaves<'_> {
+    type Item = HuffmanLeaf;
+
+    fn next(&mut self) -> Option<HuffmanLeaf> {
+        while let Some((node, code, len)) = self.stack.pop() {
+            match node {
+                HuffmanNode::Leaf(symbol) => {
+                    return Some(HuffmanLeaf {
+                        symbol: *symbol,
+                        code,
+                        len,
+                    })
+                }
+                HuffmanNode::Internal { zero, one } => {
+                    // Push the 1 side first so that the 0 side is visited first.
+                    if let Some(one) = one {
+                        self.stack.push((one, code << 1 | 1, len + 1));
+                    }
+                    if let Some(zero) = zero {
+                        self.stack.push((zero, code << 1, len + 1));
+                    }
+                }
+            }
+        }
+        None
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_assign_canonical_codes_rfc_example() {
+        // The example of RFC 1951, section 3.2.2: symbols A to H.
+        let code_lengths = [3, 3, 3, 3, 3, 2, 4, 4];
+        let codes = assign_canonical_codes(&code_lengths);
+        assert_eq!(
+            codes,
+            vec![
+                (0b010, 3),
+                (0b011, 3),
+                (0b100, 3),
+                (0b101, 3),
+                (0b110, 3),
+                (0b00, 2),
+                (0b1110, 4),
+                (0b1111, 4),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_assign_canonical_codes_unused_symbols() {
+        let codes = assign_canonical_codes(&[0, 1, 0, 2, 2]);
+        assert_eq!(codes, vec![(0, 0), (0b0, 1), (0, 0), (0b10, 2), (0b11, 2)]);
+        assert!(assign_canonical_codes(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_huffman_lookup_table_try_new() {
+        assert!(HuffmanLookupTable::try_new(&[3, 3, 3, 3, 3, 2, 4, 4], 4).is_ok());
+        assert_eq!(
+            HuffmanLookupTable::try_new(&[1, 1, 1], 4).unwrap_err(),
+            InflateError::OversubscribedTable
+        );
+        assert_eq!(
+            HuffmanLookupTable::try_new(&[1, 2, 3], 4).unwrap_err(),
+            InflateError::IncompleteTable
+        );
+        assert_eq!(
+            HuffmanLookupTable::try_new(&[0, 2, 0], 4).unwrap_err(),
+            InflateError::IncompleteTable
+        );
+        // The incomplete codes allowed by RFC 1951.
+        assert!(HuffmanLookupTable::try_new(&[0, 0, 0], 4).is_ok());
+        assert!(HuffmanLookupTable::try_new(&[0, 1, 0], 4).is_ok());
+        // Full 15-bit codes add up without overflow.
+        let mut code_len = vec![15u8; 1 << 15];
+        assert!(HuffmanLookupTable::try_new(&code_len, 15).is_ok());
+        code_len.push(15);
+        assert_eq!(
+            HuffmanLookupTable::try_new(&code_len, 15).unwrap_err(),
+            InflateError::OversubscribedTable
+        );
+    }
+
+    #[test]
+    fn test_two_level_lookup_table() {
+        // A skewed 15-bit code: lengths 1, 2, ..., 14, 15, 15.
+        let mut code_lengths: Vec<u8> = (1..=15).collect();
+        code_lengths.push(15);
+        let flat = HuffmanLookupTable::try_new(&code_lengths, 15).unwrap();
+        let two_level = HuffmanLookupTable::try_new_two_level(&code_lengths, 15, 9).unwrap();
+        assert_eq!(two_level.root_bits, 9);
+        assert!(two_level.table.len() < 1000);
+        assert_eq!(flat.table.len(), 1 << 15);
+        for code in 0..1 << 15 {
+            assert_eq!(two_level.get(code), flat.get(code), "code {:#b}", code);
+        }
+
+        // With every code within the root bits, there are no sub-tables.
+        let code_lengths = [3, 3, 3, 3, 3, 2, 4, 4];
+        let two_level = HuffmanLookupTable::new_two_level(&code_lengths, 4, 9);
+        assert_eq!(two_level.root_bits, 4);
+        assert_eq!(
+            two_level.table,
+            HuffmanLookupTable::new(&code_lengths, 4).table
+        );
+    }
+
+    /// The cost of a code: the number of bits it takes to encode every symbol occurrence.
+    fn code_cost(freqs: &[u64], lengths: &[u8]) -> u64 {
+        freqs.iter().zip(lengths).map(|(&f, &l)| f * l as u64).sum()
+    }
+
+    #[test]
+    fn test_build_code_lengths() {
+        assert_eq!(build_code_lengths(&[1, 1, 2, 4], 15), [3, 3, 2, 1]);
+        assert_eq!(build_code_lengths(&[0, 3, 0, 3], 15), [0, 1, 0, 1]);
+        assert_eq!(build_code_lengths(&[0, 5, 0], 15), [0, 1, 0]);
+        assert_eq!(build_code_lengths(&[0, 0], 15), [0, 0]);
+        // Unlimited, this would be [4, 4, 3, 2, 1]; the cheapest code within 3 bits.
+        let freqs = [1, 1, 2, 4, 8];
+        assert_eq!(build_code_lengths(&freqs, 4), [4, 4, 3, 2, 1]);
+        assert_eq!(build_code_lengths(&freqs, 3), [3, 3, 3, 3, 1]);
+        assert_eq!(code_cost(&freqs, &[3, 3, 3, 3, 1]), 32);
+        assert_eq!(build_code_lengths(&[1; 8], 3), [3; 8]);
+    }
+
+    #[test]
+    fn test_build_code_lengths_fibonacci() {
+        // Fibonacci frequencies make the deepest possible tree, one level per symbol.
+        let mut freqs = vec![1u64, 1];
+        while freqs.len() < 30 {
+            freqs.push(freqs[freqs.len() - 1] + freqs[freqs.len() - 2]);
+        }
+        let unlimited = build_code_lengths(&freqs, 29);
+        assert_eq!(unlimited.iter().max(), Some(&29));
+        for max_bits in [5, 7, 15] {
+            let lengths = build_code_lengths(&freqs, max_bits);
+            assert_eq!(lengths.iter().max(), Some(&max_bits));
+            // The code is complete, and costs more than the unlimited one.
+            let kraft: u64 = lengths.iter().map(|&len| 1 << (29 - len)).sum();
+            assert_eq!(kraft, 1 << 29);
+            assert!(code_cost(&freqs, &lengths) > code_cost(&freqs, &unlimited));
+            // Codes assigned from the lengths decode back through a lookup table.
+            let table = HuffmanLookupTable::try_new(&lengths, max_bits).unwrap();
+            for (symbol, (code, len)) in assign_canonical_codes(&lengths).into_iter().enumerate() {
+                assert_eq!(table.get(reverse_code(code, len)), Some((symbol, len)));
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_build_code_lengths_too_many_symbols() {
+        build_code_lengths(&[1; 9], 3);
+    }
+
+    #[test]
+    fn test_check_code_lengths() {
+        assert_eq!(
+            check_code_lengths(&[3, 3, 3, 3, 3, 2, 4, 4]),
+            CodeLengthStatus::Complete
+        );
+        assert_eq!(
+            check_code_lengths(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 16]),
+            CodeLengthStatus::Complete
+        );
+        assert_eq!(
+            check_code_lengths(&[]),
+            CodeLengthStatus::Incomplete { slack: 1 << 16 }
+        );
+        // A 1-bit code leaves half the space, as RFC 1951 allows for a single distance code.
+        assert_eq!(
+            check_code_lengths(&[0, 1]),
+            CodeLengthStatus::Incomplete { slack: 1 << 15 }
+        );
+        assert_eq!(
+            check_code_lengths(&[1, 2, 3, 3, 3]),
+            CodeLengthStatus::Oversubscribed { excess: 1 << 13 }
+        );
+        assert_eq!(
+            check_code_lengths(&[2, 2, 2, 3, 0, 15]),
+            CodeLengthStatus::Incomplete {
+                slack: (1 << 13) - 2
+            }
+        );
+    }
+
+    #[test]
+    fn test_huffman_lookup_table_max_bits() {
+        assert_eq!(
+            HuffmanLookupTable::try_new(&[1, 2, 3, 3], 2).unwrap_err(),
+            InflateError::CodeTooLong
+        );
+        assert_eq!(
+            HuffmanLookupTable::try_new_two_level(&[1, 1], 17, 9).unwrap_err(),
+            InflateError::CodeTooLong
+        );
+
+        // A complete code with 16-bit codes, beyond what DEFLATE uses.
+        let mut code_len: Vec<u8> = (1..16).collect();
+        code_len.extend([16, 16]);
+        let mut writer = BitWriter::new();
+        let encode = HuffmanEncodeTable::new(&code_len);
+        for symbol in (0..code_len.len()).rev() {
+            encode.write_symbol(&mut writer, symbol);
+        }
+        let data = writer.into_vec();
+        for table in [
+            HuffmanLookupTable::try_new(&code_len, MAX_CODE_BITS).unwrap(),
+            HuffmanLookupTable::try_new_two_level(&code_len, MAX_CODE_BITS, 8).unwrap(),
+        ] {
+            let mut bit_reader = BitReader::new_strict(&data);
+            for symbol in (0..code_len.len()).rev() {
+                assert_eq!(table.decode_symbol(&mut bit_reader), Ok(symbol));
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Code longer than the table supports")]
+    fn test_huffman_lookup_table_code_too_long() {
+        HuffmanLookupTable::new(&[1, 2, 3, 3], 2);
+    }
+
+    #[test]
+    fn test_table_entry() {
+        assert_eq!(core::mem::size_of::<TableEntry>(), 4);
+        let entry = TableEntry::new(285, 13);
+        assert_eq!(entry.unpack(), (285, 13));
+        assert!(!entry.is_link());
+        let link = TableEntry::new(512, SUB_TABLE_LINK | 6);
+        assert!(link.is_link());
+        assert_eq!(link.symbol(), 512);
+        assert_eq!(TableEntry::default().code_len(), 0);
+    }
+
+    #[test]
+    fn test_const_lookup_table() {
+        const TABLE: ConstLookupTable<16> = ConstLookupTable::new(&[3, 3, 3, 3, 3, 2, 4, 4]);
+        let table = HuffmanLookupTable::new(&[3, 3, 3, 3, 3, 2, 4, 4], 4);
+        assert_eq!(TABLE.max_bits, 4);
+        assert_eq!(TABLE.to_lookup_table().table, table.table);
+
+        let mut code_len: Vec<u8> = (1..16).collect();
+        code_len.extend([16, 16]);
+        let long = ConstLookupTable::<65536>::new(&code_len);
+        assert_eq!(
+            long.to_lookup_table().table,
+            HuffmanLookupTable::new(&code_len, 16).table
+        );
+
+        let mut writer = BitWriter::new();
+        HuffmanEncodeTable::new(&code_len).write_symbol(&mut writer, 16);
+        let data = writer.into_vec();
+        assert_eq!(
+            long.decode_symbol(&mut BitReader::new_strict(&data)),
+            Ok(16)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Code longer than the table supports")]
+    fn test_const_lookup_table_code_too_long() {
+        ConstLookupTable::<4>::new(&[1, 2, 3, 3]);
+    }
 
     #[test]
     fn test_huffman_lookup_table() {
@@ -89,8 +1138,187 @@ mod tests {
         let max_bits = 4;
         let huffman_table = HuffmanLookupTable::new(&code_lengths, max_bits);
 
-        huffman_table.table.iter().for_each(|&(symbol, len)| {
+        huffman_table.table.iter().for_each(|entry| {
+            let (symbol, len) = entry.unpack();
             assert_eq!(len, code_lengths[symbol]);
         });
     }
+
+    #[test]
+    fn test_huffman_lookup_table_codes() {
+        // The example of RFC 1951, section 3.2.2, with A to H as symbols 0 to 7.
+        let code_lengths = [3, 3, 3, 3, 3, 2, 4, 4, 0];
+        let table = HuffmanLookupTable::new_two_level(&code_lengths, 4, 2);
+        let codes = table.codes();
+        assert_eq!(codes.len(), 8);
+        assert_eq!(codes[0], (0, 0b010, 3));
+        assert_eq!(codes[5], (5, 0b00, 2));
+        assert_eq!(codes[7], (7, 0b1111, 4));
+        assert_eq!(
+            table.to_string(),
+            "Symbol Length   Code\n\
+             ------ ------   ----\n     \
+             0      3   010\n     \
+             1      3   011\n     \
+             2      3   100\n     \
+             3      3   101\n     \
+             4      3   110\n     \
+             5      2   00\n     \
+             6      4   1110\n     \
+             7      4   1111\n"
+        );
+        assert_eq!(HuffmanLookupTable::new(&[], 0).codes(), []);
+    }
+
+    #[test]
+    fn test_huffman_tree() {
+        // The example of RFC 1951, section 3.2.2.
+        let code_lengths = [3, 3, 3, 3, 3, 2, 4, 4];
+        let tree = HuffmanTree::try_new(&code_lengths).unwrap();
+        let codes = assign_canonical_codes(&code_lengths);
+        let leaves: Vec<_> = tree.leaves().collect();
+        assert_eq!(leaves.len(), 8);
+        assert!(leaves
+            .windows(2)
+            .all(|w| w[0].code << (4 - w[0].len) < w[1].code << (4 - w[1].len)));
+        for leaf in &leaves {
+            assert_eq!(codes[leaf.symbol], (leaf.code, leaf.len));
+        }
+        // Symbol 5 has the code 00.
+        let node = tree.root().child(0).and_then(|n| n.child(0));
+        assert_eq!(node.and_then(HuffmanNode::symbol), Some(5));
+
+        // Decoding agrees with the lookup table.
+        let mut writer = BitWriter::new();
+        let symbols = [0, 5, 7, 6, 1, 4];
+        for &symbol in &symbols {
+            let (code, len) = codes[symbol];
+            writer.write_bits(reverse_code(code, len), len as usize);
+        }
+        let data = writer.into_vec();
+        let mut bit_reader = BitReader::new_strict(&data);
+        for &symbol in &symbols {
+            assert_eq!(tree.decode_one(&mut bit_reader), Ok(symbol));
+        }
+        let table = HuffmanLookupTable::new(&code_lengths, 4);
+        let mut bit_reader = BitReader::new_strict(&data);
+        for &symbol in &symbols {
+            let (decoded, len) = table.get(bit_reader.peek_bits(4)).unwrap();
+            assert_eq!(decoded, symbol);
+            bit_reader.advance(len as usize);
+        }
+    }
+
+    #[test]
+    fn test_decode_symbol() {
+        // A single 1-bit code leaves the 1 side unassigned.
+        let table = HuffmanLookupTable::new(&[0, 1], 1);
+        let mut bit_reader = BitReader::new_strict(&[0b10]);
+        assert_eq!(table.decode_symbol(&mut bit_reader), Ok(1));
+        assert_eq!(
+            table.decode_symbol(&mut bit_reader),
+            Err(InflateError::InvalidHuffmanSymbol)
+        );
+
+        // Only the first 2 bits of a 3-bit code are left.
+        let table = HuffmanLookupTable::new_two_level(&[1, 2, 3, 3], 3, 2);
+        let mut bit_reader = BitReader::new_strict(&[0b1100_0000]);
+        bit_reader.advance(6);
+        assert_eq!(
+            table.decode_symbol(&mut bit_reader),
+            Err(InflateError::UnexpectedEof)
+        );
+    }
+
+    #[test]
+    fn test_encode_table_inverts_lookup_table() {
+        let freqs: Vec<u64> = (0..286u64).map(|i| (i * 7919) % 1000 + i % 3).collect();
+        let code_len = build_code_lengths(&freqs, 15);
+        let encode = HuffmanEncodeTable::new(&code_len);
+        assert_eq!(encode.len(), code_len.len());
+        let symbols: Vec<usize> = (0..5000)
+            .map(|i| i * 31 % 286)
+            .filter(|&symbol| code_len[symbol] > 0)
+            .collect();
+        let mut writer = BitWriter::new();
+        for &symbol in &symbols {
+            encode.write_symbol(&mut writer, symbol);
+        }
+        let data = writer.into_vec();
+
+        let tables = [
+            HuffmanLookupTable::new(&code_len, 15),
+            HuffmanLookupTable::new_two_level(&code_len, 15, 9),
+        ];
+        for table in tables {
+            let mut bit_reader = BitReader::new_strict(&data);
+            for &symbol in &symbols {
+                assert_eq!(table.decode_symbol(&mut bit_reader), Ok(symbol));
+            }
+        }
+        let codes = assign_canonical_codes(&code_len);
+        for (symbol, &(code, len)) in codes.iter().enumerate() {
+            let expected = (len > 0).then_some((code, len));
+            assert_eq!(encode.get(symbol), expected);
+        }
+        assert_eq!(encode.get(286), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Symbol 0 has no code")]
+    fn test_encode_table_unused_symbol() {
+        let encode = HuffmanEncodeTable::new(&[0, 1, 1]);
+        encode.write_symbol(&mut BitWriter::new(), 0);
+    }
+
+    #[test]
+    fn test_huffman_tree_errors() {
+        assert_eq!(
+            HuffmanTree::try_new(&[1, 1, 1]),
+            Err(InflateError::OversubscribedTable)
+        );
+        assert_eq!(
+            HuffmanTree::try_new(&[2, 2, 2]),
+            Err(InflateError::IncompleteTable)
+        );
+
+        // A single 1-bit code leaves the 1 side empty.
+        let tree = HuffmanTree::try_new(&[0, 1]).unwrap();
+        let mut bit_reader = BitReader::new_strict(&[0b10]);
+        assert_eq!(tree.decode_one(&mut bit_reader), Ok(1));
+        assert_eq!(
+            tree.decode_one(&mut bit_reader),
+            Err(InflateError::InvalidHuffmanSymbol)
+        );
+
+        let tree = HuffmanTree::try_new(&[1, 2, 2]).unwrap();
+        let mut bit_reader = BitReader::new_strict(&[0xff]);
+        bit_reader.advance(7);
+        assert_eq!(
+            tree.decode_one(&mut bit_reader),
+            Err(InflateError::UnexpectedEof)
+        );
+    }
+
+    #[test]
+    fn test_huffman_to_dot() {
+        let tree = HuffmanTree::try_new(&[2, 1, 2]).unwrap();
+        assert_eq!(
+            tree.to_dot(),
+            "digraph huffman {\n    node [shape=point];\n    n0;\n\
+             \x20   n0 -> n1 [label=\"0\"];\n    n1 [shape=box, label=\"1\\n0\\nlen 1\"];\n\
+             \x20   n0 -> n2 [label=\"1\"];\n    n2;\n\
+             \x20   n2 -> n3 [label=\"0\"];\n    n3 [shape=box, label=\"0\\n10\\nlen 2\"];\n\
+             \x20   n2 -> n4 [label=\"1\"];\n    n4 [shape=box, label=\"2\\n11\\nlen 2\"];\n}\n"
+        );
+
+        // Flat and two-level tables describe the tree they were built from.
+        let code_lengths = [3, 3, 3, 3, 3, 2, 4, 4, 0];
+        let dot = HuffmanTree::try_new(&code_lengths).unwrap().to_dot();
+        let flat = HuffmanLookupTable::new(&code_lengths, 4);
+        assert_eq!(flat.code_lengths(), &code_lengths[..8]);
+        assert_eq!(flat.to_dot(), dot);
+        let two_level = HuffmanLookupTable::new_two_level(&code_lengths, 4, 2);
+        assert_eq!(two_level.to_dot(), dot);
+    }
 }
@@ -0,0 +1,207 @@
+//! Random access into a DEFLATE stream, like zlib's `zran` example.
+//!
+//! A DEFLATE stream can only be decoded from its start: every back-reference may reach into the
+//! 32 KiB of output before it. A [`BlockIndex`] makes one pass over the stream and, at the first
+//! block boundary after every `span` bytes of output, keeps a checkpoint: where the block starts
+//! in the input and the window of output before it. [`BlockIndex::read_at`] then resumes
+//! decoding from the last checkpoint before the requested range, with the window as a preset
+//! dictionary, so reading from the middle of a large gzip log only decodes about `span` bytes
+//! more than asked for.
+//!
+//! Checkpoints sit at block boundaries, where no Huffman tables are in effect, so a stream of a
+//! single huge block gets no checkpoint past its start.
+
+use crate::bit_stream::BitReader;
+use crate::container::{self, Format};
+use crate::error::{InflateError, Result};
+use crate::inflate::{inflate_block, InflateState, MAX_DISTANCE};
+use alloc::vec::Vec;
+
+/// A point a stream can be decoded from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// Bit offset of the block header, from the start of the DEFLATE stream.
+    pub bit_offset: usize,
+    /// Output offset of the first byte of the block.
+    pub output_offset: usize,
+    /// The last `MAX_DISTANCE` bytes of output before the block, or all of them near the start.
+    pub window: Vec<u8>,
+}
+
+/// Checkpoints into a DEFLATE stream, to decode any range of its output without starting over.
+/// The index does not keep the compressed data: it is passed again to [`BlockIndex::read_at`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockIndex {
+    /// The container around the DEFLATE stream.
+    format: Format,
+    /// Byte offset of the DEFLATE stream within the data, after the container header.
+    stream_start: usize,
+    /// The checkpoints, by increasing output offset. The first is the start of the stream.
+    checkpoints: Vec<Checkpoint>,
+    /// Number of bytes the stream decompresses to.
+    total_out: usize,
+}
+
+impl BlockIndex {
+    /// Index a DEFLATE stream inside the given container, with a checkpoint at least every
+    /// `span` bytes of output. Only the first member of a gzip file is indexed, and the
+    /// container trailer is not checked.
+    pub fn build(data: &[u8], format: Format, span: usize) -> Result<Self> {
+        let stream_start = container::header_len(data, format)?;
+        let stream = &data[stream_start..];
+        if stream.is_empty() {
+            return Err(InflateError::EmptyInput);
+        }
+        let mut bit_reader = BitReader::new_strict(stream);
+        let mut state = InflateState::new();
+        let mut checkpoints = Vec::new();
+        loop {
+            let output_offset = state.total_out();
+            let due = checkpoints
+                .last()
+                .is_none_or(|last: &Checkpoint| output_offset - last.output_offset >= span);
+            if due {
+                checkpoints.push(Checkpoint {
+                    bit_offset: bit_reader.bits_consumed(),
+                    output_offset,
+                    window: state.output.clone(),
+                });
+            }
+            let is_final = inflate_block(&mut bit_reader, &mut state)?;
+            // The output never needs to hold more than a window.
+            state.discard_output(MAX_DISTANCE);
+            if is_final {
+                break;
+            }
+        }
+        Ok(Self {
+            format,
+            stream_start,
+            checkpoints,
+            total_out: state.total_out(),
+        })
+    }
+
+    /// Get the container of the indexed stream.
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Get the checkpoints, by increasing output offset.
+    pub fn checkpoints(&self) -> &[Checkpoint] {
+        &self.checkpoints
+    }
+
+    /// Number of bytes the stream decompresses to.
+    pub fn total_out(&self) -> usize {
+        self.total_out
+    }
+
+    /// Decode `len` bytes of output starting at `offset`, from the last checkpoint before them.
+    /// `data` must be the data the index was built from. Fewer bytes are returned when the
+    /// range goes past the end of the output, and none when it starts past it. Other data
+    /// fails to decode, or with [`InflateError::UnexpectedEof`] if it ends before the range.
+    pub fn read_at(&self, data: &[u8], offset: usize, len: usize) -> Result<Vec<u8>> {
+        let end = offset.saturating_add(len).min(self.total_out);
+        if offset >= end {
+            return Ok(Vec::new());
+        }
+        let checkpoint = self
+            .checkpoints
+            .iter()
+            .rev()
+            .find(|checkpoint| checkpoint.output_offset <= offset)
+            .expect("the first checkpoint is at the start of the output");
+
+        let stream = data
+            .get(self.stream_start..)
+            .ok_or(InflateError::UnexpectedEof)?;
+        let byte = checkpoint.bit_offset / 8;
+        let mut bit_reader = BitReader::new_strict(stream.get(byte..).unwrap_or_default());
+        bit_reader
+            .try_advance(checkpoint.bit_offset % 8)
+            .ok_or(InflateError::UnexpectedEof)?;
+        let mut state = InflateState::new();
+        state.set_dictionary(&checkpoint.window);
+
+        let skip = offset - checkpoint.output_offset;
+        while checkpoint.output_offset + state.total_out() < end {
+            if inflate_block(&mut bit_reader, &mut state)? {
+                break;
+            }
+        }
+        let output = state.decompressed();
+        output
+            .get(skip..output.len().min(end - checkpoint.output_offset))
+            .map(<[u8]>::to_vec)
+            .ok_or(InflateError::UnexpectedEof)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+
+    /// Lines of a made-up log, varied enough that the compressor emits many blocks.
+    fn log_lines(n: usize) -> Vec<u8> {
+        (0..n)
+            .flat_map(|i| {
+                format!("{i:08} level={} request {}\n", i % 7, i * 7919 % 10007).into_bytes()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_block_index_read_at() {
+        let text = log_lines(20_000);
+        let deflate = miniz_oxide::deflate::compress_to_vec(&text, 6);
+        let gzip = container::wrap(&deflate, &text, Format::Gzip);
+        let index = BlockIndex::build(&gzip, Format::Gzip, 64 * 1024).unwrap();
+        assert_eq!(index.total_out(), text.len());
+        assert!(index.checkpoints().len() > 2);
+        assert!(index
+            .checkpoints()
+            .windows(2)
+            .all(|pair| pair[1].output_offset - pair[0].output_offset >= 64 * 1024));
+
+        for offset in [0, 1, 70_000, 200_001, text.len() - 10] {
+            let read = index.read_at(&gzip, offset, 300).unwrap();
+            let end = (offset + 300).min(text.len());
+            assert_eq!(read, &text[offset..end], "offset {offset}");
+        }
+        assert!(index.read_at(&gzip, text.len(), 10).unwrap().is_empty());
+
+        // Data other than the indexed one fails instead of panicking.
+        assert_eq!(
+            index.read_at(&gzip[..5], 0, 10),
+            Err(InflateError::UnexpectedEof)
+        );
+        let mut short = gzip[..10].to_vec();
+        short.extend(miniz_oxide::deflate::compress_to_vec(b"short", 6));
+        assert_eq!(
+            index.read_at(&short, 100, 10),
+            Err(InflateError::UnexpectedEof)
+        );
+    }
+
+    #[test]
+    fn test_block_index_checkpoint_window() {
+        let text = log_lines(5_000);
+        let deflate = miniz_oxide::deflate::compress_to_vec(&text, 6);
+        let index = BlockIndex::build(&deflate, Format::Raw, 1).unwrap();
+        for checkpoint in index.checkpoints() {
+            let start = checkpoint.output_offset.saturating_sub(MAX_DISTANCE);
+            assert_eq!(checkpoint.window, &text[start..checkpoint.output_offset]);
+        }
+    }
+
+    #[test]
+    fn test_block_index_truncated() {
+        let deflate = miniz_oxide::deflate::compress_to_vec(&log_lines(1_000), 6);
+        assert_eq!(
+            BlockIndex::build(&deflate[..deflate.len() / 2], Format::Raw, 1024),
+            Err(InflateError::UnexpectedEof)
+        );
+    }
+}
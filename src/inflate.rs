@@ -5,389 +5,3498 @@
 //! The decompression process is the reverse of the compression process.
 
 use crate::bit_stream::BitReader;
-use crate::huffman::HuffmanLookupTable;
-use std::io::{Error, ErrorKind, Result};
+use crate::checksum::adler32;
+use crate::codes::{distance_code_entry, length_code_entry, Symbol};
+use crate::container::{self, Format, GzipHeader, ZlibHeader};
+use crate::disasm::{block_type_name, Alphabet, Field, FieldKind};
+use crate::error::{CorruptionReport, DecodeStage, InflateError, Result};
+use crate::huffman::{ConstLookupTable, HuffmanDecoder, HuffmanLookupTable, HuffmanTree};
+use crate::text::{TableFormat, TextOptions};
+use crate::window::Window;
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use alloc::{format, vec};
+use core::ops::Range;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The largest distance a back-reference can reach, and so the size of the window
+/// a decoder has to keep around.
+pub const MAX_DISTANCE: usize = 32 * 1024;
+
+/// The largest distance a back-reference of Deflate64, the variant of DEFLATE that ZIP method 9
+/// uses, can reach.
+pub const DEFLATE64_MAX_DISTANCE: usize = 64 * 1024;
 
 // constant values for the DEFLATE algorithm
 
-const BFINAL_LEN: usize = 1;
-const BFINAL_VALUE: usize = 1;
+pub(crate) const BFINAL_LEN: usize = 1;
+pub(crate) const BFINAL_VALUE: usize = 1;
 
-const BTYPE_LEN: usize = 2;
-const BTYPE_NO_COMPRESSION: usize = 0b00;
-const BTYPE_FIXED_HUFFMAN: usize = 0b01;
-const BTYPE_DYNAMIC_HUFFMAN: usize = 0b10;
+pub(crate) const BTYPE_LEN: usize = 2;
+pub(crate) const BTYPE_NO_COMPRESSION: usize = 0b00;
+pub(crate) const BTYPE_FIXED_HUFFMAN: usize = 0b01;
+pub(crate) const BTYPE_DYNAMIC_HUFFMAN: usize = 0b10;
+
+/// The empty stored block a sync flush ends with, as bytes.
+/// Protocols such as permessage-deflate strip it from every message.
+pub(crate) const SYNC_FLUSH_MARKER: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
 
 const LEN_LEN: usize = 16;
 const NLEN_LEN: usize = 16;
 
-const LITERAL_CODE_BASE: usize = 0;
-const LITERAL_CODE_MAX: usize = 255;
-const END_BLOCK_CODE: usize = 256;
-const LENGTH_CODE_BASE: usize = 257;
-const LENGTH_CODE_MAX: usize = 285;
-
-const HLIT_LEN: usize = 5;
-const HLIT_BASE: usize = 257;
-const HDIST_LEN: usize = 5;
-const HDIST_BASE: usize = 1;
-const HCLEN_LEN: usize = 4;
-const HCLEN_BASE: usize = 4;
-
-const DYN_ALPHABET_CODE_NUM: usize = 19;
-const DYN_ALPHABET_CODE_LEN: usize = 3;
-const DYN_ALPHABET_TABLE_MAX_BITS: u8 = 7;
-const DYN_TABLE_MAX_BITS: u8 = 15;
-
-/// Length code table for DEFLATE.
-/// length_code_table[i] = (length_code, length_base, extra_bits)
-const LENGTH_CODE_TABLE: &[(usize, usize, usize)] = &[
-    (257, 3, 0),
-    (258, 4, 0),
-    (259, 5, 0),
-    (260, 6, 0),
-    (261, 7, 0),
-    (262, 8, 0),
-    (263, 9, 0),
-    (264, 10, 0),
-    (265, 11, 1),
-    (266, 13, 1),
-    (267, 15, 1),
-    (268, 17, 1),
-    (269, 19, 2),
-    (270, 23, 2),
-    (271, 27, 2),
-    (272, 31, 2),
-    (273, 35, 3),
-    (274, 43, 3),
-    (275, 51, 3),
-    (276, 59, 3),
-    (277, 67, 4),
-    (278, 83, 4),
-    (279, 99, 4),
-    (280, 115, 4),
-    (281, 131, 5),
-    (282, 163, 5),
-    (283, 195, 5),
-    (284, 227, 5),
-    (285, 258, 0),
-];
+pub(crate) const HLIT_LEN: usize = 5;
+pub(crate) const HLIT_BASE: usize = 257;
+pub(crate) const HDIST_LEN: usize = 5;
+pub(crate) const HDIST_BASE: usize = 1;
+pub(crate) const HCLEN_LEN: usize = 4;
+pub(crate) const HCLEN_BASE: usize = 4;
+
+pub(crate) const DYN_ALPHABET_CODE_NUM: usize = 19;
+pub(crate) const DYN_ALPHABET_CODE_LEN: usize = 3;
+pub(crate) const DYN_ALPHABET_TABLE_MAX_BITS: u8 = 7;
+pub(crate) const DYN_TABLE_MAX_BITS: u8 = 15;
+/// Root bits of the two-level tables of dynamic blocks, as in zlib: most literal/length codes
+/// fit in 9 bits, and most distance codes in 6.
+const DYN_LITERAL_ROOT_BITS: u8 = 9;
+const DYN_DISTANCE_ROOT_BITS: u8 = 6;
+
+/// Read the given number of bits, failing if the data ends before them.
+fn read_bits(bit_reader: &mut BitReader, n_bits: usize) -> Result<usize> {
+    bit_reader
+        .try_read_bits(n_bits)
+        .ok_or(InflateError::UnexpectedEof)
+}
+
+/// The furthest a back-reference can reach in DEFLATE, or in Deflate64.
+fn max_distance(deflate64: bool) -> usize {
+    if deflate64 {
+        DEFLATE64_MAX_DISTANCE
+    } else {
+        MAX_DISTANCE
+    }
+}
 
 /// Get the length of the repeated data by the length code.
 /// This function reads the extra bits if needed.
-/// Returns None if the code is invalid.
-fn get_length_by_code(code: usize, bit_reader: &mut BitReader) -> Option<usize> {
+fn get_length_by_code(code: usize, deflate64: bool, bit_reader: &mut BitReader) -> Result<usize> {
     let (length_code, length_base, extra_bits) =
-        LENGTH_CODE_TABLE.get(code - LENGTH_CODE_BASE).cloned()?;
+        length_code_entry(code, deflate64).ok_or(InflateError::InvalidHuffmanSymbol)?;
     assert!(length_code == code);
-    Some(length_base + bit_reader.read_bits(extra_bits))
-}
-
-/// Distance code table for DEFLATE.
-/// distance_code_table[i] = (distance_code, distance_base, extra_bits)
-const DISTANCE_CODE_TABLE: &[(usize, usize, usize)] = &[
-    (0, 1, 0),
-    (1, 2, 0),
-    (2, 3, 0),
-    (3, 4, 0),
-    (4, 5, 1),
-    (5, 7, 1),
-    (6, 9, 2),
-    (7, 13, 2),
-    (8, 17, 3),
-    (9, 25, 3),
-    (10, 33, 4),
-    (11, 49, 4),
-    (12, 65, 5),
-    (13, 97, 5),
-    (14, 129, 6),
-    (15, 193, 6),
-    (16, 257, 7),
-    (17, 385, 7),
-    (18, 513, 8),
-    (19, 769, 8),
-    (20, 1025, 9),
-    (21, 1537, 9),
-    (22, 2049, 10),
-    (23, 3073, 10),
-    (24, 4097, 11),
-    (25, 6145, 11),
-    (26, 8193, 12),
-    (27, 12289, 12),
-    (28, 16385, 13),
-    (29, 24577, 13),
-];
+    Ok(length_base + read_bits(bit_reader, extra_bits)?)
+}
 
 /// Get the distance of the repeated data by the distance code.
 /// This function reads the extra bits if needed.
-/// Returns None if the code is invalid.
-fn get_distance_by_code(code: usize, bit_reader: &mut BitReader) -> Option<usize> {
-    let (distance_code, distance_base, extra_bits) = DISTANCE_CODE_TABLE.get(code).cloned()?;
+fn get_distance_by_code(code: usize, deflate64: bool, bit_reader: &mut BitReader) -> Result<usize> {
+    let (distance_code, distance_base, extra_bits) =
+        distance_code_entry(code, deflate64).ok_or(InflateError::InvalidHuffmanSymbol)?;
     assert!(distance_code == code);
-    Some(distance_base + bit_reader.read_bits(extra_bits))
+    Ok(distance_base + read_bits(bit_reader, extra_bits)?)
 }
 
-/// Create a fixed literal/length table.
+/// The code lengths of the fixed literal/length code, from the ranges of the RFC.
 /// Defined in RFC 1951, section 3.2.6.
-fn fixed_literal_table() -> HuffmanLookupTable {
-    let mut code_len = vec![0; 288];
-    (0..144).for_each(|i| code_len[i] = 8);
-    (144..256).for_each(|i| code_len[i] = 9);
-    (256..280).for_each(|i| code_len[i] = 7);
-    (280..288).for_each(|i| code_len[i] = 8);
-    HuffmanLookupTable::new(&code_len, 9)
+const FIXED_LITERAL_CODE_LENGTHS: [u8; 288] = {
+    let mut code_len = [0; 288];
+    let mut i = 0;
+    while i < FIXED_LITERAL_CODE_RANGES.len() {
+        let (first, last, len, _) = FIXED_LITERAL_CODE_RANGES[i];
+        let mut symbol = first;
+        while symbol <= last {
+            code_len[symbol] = len;
+            symbol += 1;
+        }
+        i += 1;
+    }
+    code_len
+};
+
+/// The code lengths of the fixed distance code.
+/// Defined in RFC 1951, section 3.2.6.
+const FIXED_DISTANCE_CODE_LENGTHS: [u8; 32] = [5; 32];
+
+/// The table of the fixed literal/length code, built at compile time.
+pub static FIXED_LITERAL_TABLE: ConstLookupTable<512> =
+    ConstLookupTable::new(&FIXED_LITERAL_CODE_LENGTHS);
+
+/// The table of the fixed distance code, built at compile time.
+pub static FIXED_DISTANCE_TABLE: ConstLookupTable<32> =
+    ConstLookupTable::new(&FIXED_DISTANCE_CODE_LENGTHS);
+
+/// Get the code lengths of the fixed literal/length code.
+/// Defined in RFC 1951, section 3.2.6.
+pub(crate) fn fixed_literal_code_lengths() -> Vec<u8> {
+    FIXED_LITERAL_CODE_LENGTHS.to_vec()
 }
 
-/// Create a fixed distance table.
+/// Get the code lengths of the fixed distance code.
 /// Defined in RFC 1951, section 3.2.6.
+pub(crate) fn fixed_distance_code_lengths() -> Vec<u8> {
+    FIXED_DISTANCE_CODE_LENGTHS.to_vec()
+}
+
+/// Create a fixed literal/length table, for the code that keeps a [`HuffmanLookupTable`].
+fn fixed_literal_table() -> HuffmanLookupTable {
+    FIXED_LITERAL_TABLE.to_lookup_table()
+}
+
+/// Create a fixed distance table, for the code that keeps a [`HuffmanLookupTable`].
 fn fixed_distance_table() -> HuffmanLookupTable {
-    let code_len = vec![5; 32];
-    HuffmanLookupTable::new(&code_len, 5)
+    FIXED_DISTANCE_TABLE.to_lookup_table()
+}
+
+/// Create the tree of a fixed code.
+fn fixed_tree(code_len: &[u8]) -> HuffmanTree {
+    HuffmanTree::try_new(code_len).expect("The fixed codes are complete")
+}
+
+/// The fixed literal/length code as RFC 1951 lists it in section 3.2.6:
+/// (first symbol, last symbol, code length, code of the first symbol).
+const FIXED_LITERAL_CODE_RANGES: [(usize, usize, u8, usize); 4] = [
+    (0, 143, 8, 0b0011_0000),
+    (144, 255, 9, 0b1_1001_0000),
+    (256, 279, 7, 0b000_0000),
+    (280, 287, 8, 0b1100_0000),
+];
+
+/// An entry of a fixed lookup table that does not decode to the code RFC 1951 assigns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedTableMismatch {
+    /// The alphabet of the table.
+    pub alphabet: Alphabet,
+    /// The symbol whose code was looked up.
+    pub symbol: usize,
+    /// The bits looked up: the code, first bit in the least significant bit, then padding.
+    pub index: usize,
+    /// What the table decoded the bits to, as (symbol, code length).
+    pub found: Option<(usize, u8)>,
+}
+
+/// Rebuild the `(symbol, code, len)` of every fixed literal/length code from the RFC ranges.
+fn fixed_literal_codes() -> impl Iterator<Item = (usize, usize, u8)> {
+    FIXED_LITERAL_CODE_RANGES
+        .iter()
+        .flat_map(|&(first, last, len, code)| {
+            (first..=last).map(move |symbol| (symbol, code + symbol - first, len))
+        })
+}
+
+/// Check the fixed lookup tables against the codes of RFC 1951, section 3.2.6.
+/// The code of every symbol is rebuilt from the ranges of the RFC, and must decode to that
+/// symbol and code length whatever bits follow it. Returns the first entry that does not.
+pub fn verify_fixed_tables() -> core::result::Result<(), FixedTableMismatch> {
+    verify_table(
+        &fixed_literal_table(),
+        Alphabet::LiteralLength,
+        fixed_literal_codes(),
+    )?;
+    // Distance codes are the 5-bit symbol values themselves.
+    let distance_codes = (0..32).map(|symbol| (symbol, symbol, 5));
+    verify_table(&fixed_distance_table(), Alphabet::Distance, distance_codes)
+}
+
+/// Check that every `(symbol, code, len)` decodes right with every padding after the code.
+fn verify_table(
+    table: &HuffmanLookupTable,
+    alphabet: Alphabet,
+    codes: impl Iterator<Item = (usize, usize, u8)>,
+) -> core::result::Result<(), FixedTableMismatch> {
+    for (symbol, code, len) in codes {
+        // Huffman codes are packed starting with their most significant bit.
+        let reversed = (0..len).fold(0, |acc, i| acc | ((code >> (len - 1 - i)) & 1) << i);
+        for padding in 0..1 << (table.max_bits - len) {
+            let index = reversed | padding << len;
+            let found = table.get(index);
+            if found != Some((symbol, len)) {
+                return Err(FixedTableMismatch {
+                    alphabet,
+                    symbol,
+                    index,
+                    found,
+                });
+            }
+        }
+    }
+    Ok(())
 }
 
 /// Dynamic Huffman Tree code lengths alphabet order.
 /// Defined in RFC 1951, section 3.2.7.
-const DYNAMIC_HUFFMAN_TREE_ORDER: [usize; DYN_ALPHABET_CODE_NUM] = [
+pub(crate) const DYNAMIC_HUFFMAN_TREE_ORDER: [usize; DYN_ALPHABET_CODE_NUM] = [
     16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
 ];
 
-/// Resolve one symbol from the Huffman table.
-fn resolve_symbol(bit_reader: &mut BitReader, huffman_table: &HuffmanLookupTable) -> Option<usize> {
-    let peek_code = bit_reader.try_peek_bits(huffman_table.max_bits as usize)?;
-    let (symbol, len) = huffman_table.get(peek_code)?;
-    bit_reader.try_advance(len as usize)?;
-    Some(symbol)
+/// Decode one symbol of a compressed block and append its output.
+/// Returns whether the symbol was the end of the block.
+fn inflate_symbol<O: OutputSink, L: HuffmanDecoder, D: HuffmanDecoder>(
+    bit_reader: &mut BitReader,
+    state: &mut InflateState<O>,
+    lit_tb: &L,
+    dis_tb: &D,
+) -> Result<bool> {
+    let start = bit_reader.bits_consumed();
+    // Every symbol takes at least one bit, so there must be data left.
+    if bit_reader.eof() {
+        let fail = state.fail(DecodeStage::LiteralLengthSymbol, start);
+        return Err(fail(InflateError::UnexpectedEof));
+    }
+    let symbol = lit_tb
+        .decode_symbol(bit_reader)
+        .and_then(Symbol::classify)
+        .map_err(state.fail(DecodeStage::LiteralLengthSymbol, start))?;
+    let symbol_len = bit_reader.bits_consumed() - start;
+    match symbol {
+        Symbol::EndOfBlock => {
+            state.record(start, symbol_len, FieldKind::EndOfBlock);
+            return Ok(true);
+        }
+        Symbol::Literal(byte) => {
+            state.record(start, symbol_len, FieldKind::Literal(byte));
+            state
+                .reserve_output(1)
+                .map_err(state.fail(DecodeStage::LiteralLengthSymbol, start))?;
+            state.output.push(byte);
+        }
+        Symbol::Length(code) => {
+            // get the length of the repeated data
+            let symbol = code as usize;
+            let len_extra_start = bit_reader.bits_consumed();
+            let deflate64 = state.deflate64;
+            let len = get_length_by_code(symbol, deflate64, bit_reader)
+                .map_err(state.fail(DecodeStage::LengthExtraBits, len_extra_start))?;
+            let len_extra_len = bit_reader.bits_consumed() - len_extra_start;
+            // the distance code
+            let dist_start = bit_reader.bits_consumed();
+            let dist_code = dis_tb
+                .decode_symbol(bit_reader)
+                .and_then(|code| {
+                    distance_code_entry(code, deflate64)
+                        .map(|_| code)
+                        .ok_or(InflateError::InvalidHuffmanSymbol)
+                })
+                .map_err(state.fail(DecodeStage::DistanceSymbol, dist_start))?;
+            let dist_extra_start = bit_reader.bits_consumed();
+            // get the distance of the repeated data
+            let dist = get_distance_by_code(dist_code, deflate64, bit_reader)
+                .map_err(state.fail(DecodeStage::DistanceExtraBits, dist_extra_start))?;
+            let dist_extra_len = bit_reader.bits_consumed() - dist_extra_start;
+            if state.trace.is_some() {
+                let (code, length, distance) = (symbol, len, dist);
+                state.record(start, symbol_len, FieldKind::LengthCode { code, length });
+                state.record(
+                    len_extra_start,
+                    len_extra_len,
+                    FieldKind::LengthExtra { length },
+                );
+                let code = dist_code;
+                let dist_len = dist_extra_start - dist_start;
+                state.record(
+                    dist_start,
+                    dist_len,
+                    FieldKind::DistanceCode { code, distance },
+                );
+                state.record(
+                    dist_extra_start,
+                    dist_extra_len,
+                    FieldKind::DistanceExtra { distance },
+                );
+            }
+            // repeat the data
+            state
+                .copy_match(dist, len, bit_reader.bits_consumed())
+                .map_err(state.fail(DecodeStage::CopyingMatch, start))?;
+        }
+    }
+    Ok(false)
 }
 
 /// Inflate block with literal and distance huffman tables.
 /// Because a duplicated string reference may refer to a string in a previous block,
 /// we need the whole output to be able to resolve the references.
 /// Returns the number of bytes outputted.
-fn inflate_compressed_block(
+fn inflate_compressed_block<O: OutputSink, L: HuffmanDecoder, D: HuffmanDecoder>(
     bit_reader: &mut BitReader,
-    output: &mut Vec<u8>,
-    lit_tb: &HuffmanLookupTable,
-    dis_tb: &HuffmanLookupTable,
+    state: &mut InflateState<O>,
+    lit_tb: &L,
+    dis_tb: &D,
 ) -> Result<usize> {
-    let mut bytes_outputted = 0;
-    loop {
-        let symbol = resolve_symbol(bit_reader, lit_tb).ok_or_else(invalid_huffman_symbol)?;
-        match symbol {
-            END_BLOCK_CODE => {
-                // End of block
-                break;
-            }
-            LITERAL_CODE_BASE..=LITERAL_CODE_MAX => {
-                // Literal
-                output.push(symbol as u8);
-                bytes_outputted += 1;
-            }
-            LENGTH_CODE_BASE..=LENGTH_CODE_MAX => {
-                // Length
-                // get the length of the repeated data
-                let len =
-                    get_length_by_code(symbol, bit_reader).ok_or_else(invalid_huffman_symbol)?;
-                // the distance code
-                let dist_code =
-                    resolve_symbol(bit_reader, dis_tb).ok_or_else(invalid_huffman_symbol)?;
-                // get the distance of the repeated data
-                let dist = get_distance_by_code(dist_code, bit_reader)
-                    .ok_or_else(invalid_huffman_symbol)?;
-                // repeat the data
-                bytes_outputted +=
-                    repeat_with_overlap(output, dist, len).ok_or_else(invalid_huffman_symbol)?;
-            }
-            _ => Err(invalid_huffman_symbol())?,
-        }
-    }
-    Ok(bytes_outputted)
+    let start = state.total_out();
+    while !inflate_symbol(bit_reader, state, lit_tb, dis_tb)? {}
+    Ok(state.total_out() - start)
 }
 
 /// Deal with reapeted data in the output.
-fn repeat_with_overlap(output: &mut Vec<u8>, dist: usize, len: usize) -> Option<usize> {
-    let mut bytes_out = 0usize;
-    for _ in 0..len {
-        let read_pos = output.len() - dist;
-        if let Some(byte) = output.get(read_pos) {
-            output.push(*byte);
-            bytes_out += 1;
-        } else {
-            break;
+/// The caller is responsible for checking that `dist` does not exceed the output length,
+/// unless `len` is 0.
+pub(crate) fn repeat_with_overlap<O: OutputSink>(output: &mut O, dist: usize, len: usize) -> usize {
+    if len == 0 {
+        return 0;
+    }
+    let start = output.written().len() - dist;
+    if dist == 1 {
+        // A run of a single byte.
+        let byte = output.written()[start];
+        output.fill(byte, len);
+    } else if dist >= len {
+        output.extend_from_within(start..start + len);
+    } else {
+        // The copy overlaps itself, so the bytes from `start` on repeat with period `dist`:
+        // each copy of everything from `start` doubles the length of the repetition.
+        let end = output.written().len() + len;
+        while output.written().len() < end {
+            let n = (end - output.written().len()).min(output.written().len() - start);
+            output.extend_from_within(start..start + n);
         }
     }
-    Some(bytes_out)
-}
-
-/// For the sake of simplicity, we use the io::Error type for all errors.
-/// Invalid Huffman symbol error.
-fn invalid_huffman_symbol() -> Error {
-    Error::new(ErrorKind::InvalidData, "Invalid Huffman symbol")
-}
-
-/// For the sake of simplicity, we use the io::Error type for all errors.
-/// Invalid LEN and NLEN error.
-fn invalid_len_nlen() -> Error {
-    Error::new(ErrorKind::InvalidData, "Invalid LEN and NLEN")
+    len
 }
 
-/// For the sake of simplicity, we use the io::Error type for all errors.
-/// Invalid block type error.
-fn invalid_block_type() -> Error {
-    Error::new(ErrorKind::InvalidData, "Invalid block type")
+/// Read dynamic Huffman tables into lookup tables, flat or two-level as the state selects.
+/// Returns a tuple of (literal table, distance table).
+fn read_dynamic_huffman_tables<O: OutputSink>(
+    bit_reader: &mut BitReader,
+    state: &mut InflateState<O>,
+) -> Result<(HuffmanLookupTable, HuffmanLookupTable)> {
+    let flat = state.decoder == DecodeStrategy::Flat;
+    read_dynamic_decoders(bit_reader, state, |code_len, root_bits| {
+        if flat {
+            HuffmanLookupTable::try_new(code_len, DYN_TABLE_MAX_BITS)
+        } else {
+            HuffmanLookupTable::try_new_two_level(code_len, DYN_TABLE_MAX_BITS, root_bits)
+        }
+    })
 }
 
-/// Read dynamic Huffman tables.
-/// Returns a tuple of (literal table, distance table).
+/// Read dynamic Huffman tables, building the decoder of each alphabet with `build` from its
+/// code lengths and the root bits suited to it.
+/// Returns a tuple of (literal decoder, distance decoder).
 /// Defined in RFC 1951, section 3.2.7.
-fn read_dynamic_huffman_tables(
+fn read_dynamic_decoders<O: OutputSink, D>(
     bit_reader: &mut BitReader,
-) -> Result<(HuffmanLookupTable, HuffmanLookupTable)> {
-    let hlit = bit_reader.read_bits(HLIT_LEN) + HLIT_BASE;
-    let hdist = bit_reader.read_bits(HDIST_LEN) + HDIST_BASE;
-    let hclen = bit_reader.read_bits(HCLEN_LEN) + HCLEN_BASE;
+    state: &mut InflateState<O>,
+    build: impl Fn(&[u8], u8) -> Result<D>,
+) -> Result<(D, D)> {
+    let start = bit_reader.bits_consumed();
+    let sizes = read_bits(bit_reader, HLIT_LEN + HDIST_LEN + HCLEN_LEN)
+        .map_err(state.fail(DecodeStage::TableSizes, start))?;
+    let hlit = (sizes & ((1 << HLIT_LEN) - 1)) + HLIT_BASE;
+    let hdist = ((sizes >> HLIT_LEN) & ((1 << HDIST_LEN) - 1)) + HDIST_BASE;
+    let hclen = (sizes >> (HLIT_LEN + HDIST_LEN)) + HCLEN_BASE;
+    state.record(start, HLIT_LEN, FieldKind::Hlit(hlit));
+    state.record(start + HLIT_LEN, HDIST_LEN, FieldKind::Hdist(hdist));
+    state.record(
+        start + HLIT_LEN + HDIST_LEN,
+        HCLEN_LEN,
+        FieldKind::Hclen(hclen),
+    );
     let mut alphabet_code_len = vec![0; DYN_ALPHABET_CODE_NUM];
-    (0..hclen).for_each(|i| {
-        alphabet_code_len[DYNAMIC_HUFFMAN_TREE_ORDER[i]] =
-            bit_reader.read_bits(DYN_ALPHABET_CODE_LEN) as u8;
-    });
+    let alphabet_start = bit_reader.bits_consumed();
+    for &symbol in &DYNAMIC_HUFFMAN_TREE_ORDER[..hclen] {
+        let start = bit_reader.bits_consumed();
+        let len = read_bits(bit_reader, DYN_ALPHABET_CODE_LEN)
+            .map_err(state.fail(DecodeStage::CodeLengthCodeLengths, start))?;
+        alphabet_code_len[symbol] = len as u8;
+        let kind = FieldKind::CodeLengthCodeLength { symbol, len };
+        state.record(start, DYN_ALPHABET_CODE_LEN, kind);
+    }
     let alphabet_code_len_table =
-        HuffmanLookupTable::new(&alphabet_code_len, DYN_ALPHABET_TABLE_MAX_BITS);
+        HuffmanLookupTable::try_new(&alphabet_code_len, DYN_ALPHABET_TABLE_MAX_BITS)
+            .map_err(state.fail(DecodeStage::BuildingTables, alphabet_start))?;
 
-    let lit_code_len = read_code_lengths(bit_reader, &alphabet_code_len_table, hlit)?;
-    let dis_code_len = read_code_lengths(bit_reader, &alphabet_code_len_table, hdist)?;
+    let code_lengths_start = bit_reader.bits_consumed();
+    let mut lit_code_len =
+        read_code_lengths(bit_reader, state, &alphabet_code_len_table, hlit, hdist)?;
+    let dis_code_len = lit_code_len.split_off(hlit);
 
-    let lit_tb = HuffmanLookupTable::new(&lit_code_len, DYN_TABLE_MAX_BITS);
-    let dis_tb = HuffmanLookupTable::new(&dis_code_len, DYN_TABLE_MAX_BITS);
+    let lit_tb = build(&lit_code_len, DYN_LITERAL_ROOT_BITS)
+        .map_err(state.fail(DecodeStage::BuildingTables, code_lengths_start))?;
+    let dis_tb = build(&dis_code_len, DYN_DISTANCE_ROOT_BITS)
+        .map_err(state.fail(DecodeStage::BuildingTables, code_lengths_start))?;
+    #[cfg(feature = "tracing")]
+    tracing::trace!(
+        hlit,
+        hdist,
+        hclen,
+        header_bits = bit_reader.bits_consumed() - start,
+        "dynamic tables built"
+    );
 
     Ok((lit_tb, dis_tb))
 }
 
 /// Read code lengths using the alphabet code length table.
-/// Returns a vector of code lengths.
-/// Defined in RFC 1951, section 3.2.7.
+/// Returns the `hlit` literal/length code lengths followed by the `hdist` distance code lengths.
+/// Defined in RFC 1951, section 3.2.7: the two alphabets are run-length encoded as a single
+/// sequence, so a run may carry over from the literal/length lengths into the distance lengths.
 /// The code lengths are used to create the dynamic Huffman tables.
-fn read_code_lengths(
+fn read_code_lengths<O: OutputSink>(
     bit_reader: &mut BitReader,
+    state: &mut InflateState<O>,
     alphabet_code_len_table: &HuffmanLookupTable,
-    num: usize,
+    hlit: usize,
+    hdist: usize,
 ) -> Result<Vec<u8>> {
-    let mut code_lengths = vec![0; num];
+    let mut code_lengths = vec![0; hlit + hdist];
     let mut i = 0;
-    while i < num {
-        let symbol = resolve_symbol(bit_reader, alphabet_code_len_table)
-            .ok_or_else(invalid_huffman_symbol)?;
-        match symbol {
-            0..=15 => {
-                // 0-15: represent code lengths of 0-15
-                code_lengths[i] = symbol as u8;
-                i += 1;
-            }
-            16 => {
-                // 16: copy the previous code length 3-6 times
-                let repeat_len = 3 + bit_reader.read_bits(2);
-                let prev_len = *code_lengths
-                    .get(i.wrapping_sub(1))
-                    .ok_or_else(invalid_huffman_symbol)?;
-                for _ in 0..repeat_len {
-                    code_lengths[i] = prev_len;
-                    i += 1;
-                }
+    while i < code_lengths.len() {
+        let start = bit_reader.bits_consumed();
+        i = read_code_length(
+            bit_reader,
+            state,
+            alphabet_code_len_table,
+            &mut code_lengths,
+            i,
+            hlit,
+        )
+        .map_err(state.fail(DecodeStage::CodeLengths, start))?;
+    }
+    Ok(code_lengths)
+}
+
+/// Get the alphabet the `i`th code length of a block belongs to, and its symbol in it.
+fn code_length_symbol(i: usize, hlit: usize) -> (Alphabet, usize) {
+    if i < hlit {
+        (Alphabet::LiteralLength, i)
+    } else {
+        (Alphabet::Distance, i - hlit)
+    }
+}
+
+/// Read the code length symbol for the `i`th code length, along with its extra bits, and set
+/// the code lengths it gives. Returns the index of the next code length to read.
+///
+/// A repeat of the previous length at the first code length, and a run going past the last
+/// one, fail with [`InflateError::InvalidCodeLengthEncoding`] at `i`.
+fn read_code_length<O: OutputSink>(
+    bit_reader: &mut BitReader,
+    state: &mut InflateState<O>,
+    alphabet_code_len_table: &HuffmanLookupTable,
+    code_lengths: &mut [u8],
+    i: usize,
+    hlit: usize,
+) -> Result<usize> {
+    if bit_reader.eof() {
+        return Err(InflateError::UnexpectedEof);
+    }
+    let start = bit_reader.bits_consumed();
+    let symbol = alphabet_code_len_table.decode_symbol(bit_reader)?;
+    let extra_start = bit_reader.bits_consumed();
+    let (alphabet, alphabet_symbol) = code_length_symbol(i, hlit);
+    let kind = match symbol {
+        0..=15 => FieldKind::CodeLength {
+            alphabet,
+            symbol: alphabet_symbol,
+            len: symbol,
+        },
+        _ => FieldKind::CodeLengthRepeat {
+            alphabet,
+            symbol: alphabet_symbol,
+            code: symbol,
+        },
+    };
+    state.record(start, extra_start - start, kind);
+    // 0-15: a code length of 0-15; 16: the previous code length 3-6 times;
+    // 17: a code length of 0 3-10 times; 18: a code length of 0 11-138 times.
+    let (extra_bits, min_count) = match symbol {
+        0..=15 => {
+            code_lengths[i] = symbol as u8;
+            return Ok(i + 1);
+        }
+        16 => (2, 3),
+        17 => (3, 3),
+        18 => (7, 11),
+        _ => return Err(InflateError::InvalidHuffmanSymbol),
+    };
+    let count = min_count + read_bits(bit_reader, extra_bits)?;
+    let kind = FieldKind::CodeLengthRepeatCount {
+        alphabet,
+        symbol: alphabet_symbol,
+        count,
+    };
+    state.record(extra_start, extra_bits, kind);
+
+    let invalid = InflateError::InvalidCodeLengthEncoding { position: i };
+    let len = match symbol {
+        16 => *i
+            .checked_sub(1)
+            .map(|prev| &code_lengths[prev])
+            .ok_or(invalid)?,
+        _ => 0,
+    };
+    code_lengths.get_mut(i..i + count).ok_or(invalid)?.fill(len);
+    Ok(i + count)
+}
+
+/// How back-references that reach outside the usable window are handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MatchValidation {
+    /// Fail with an error.
+    #[default]
+    Strict,
+    /// Keep decoding and record a [`MatchWarning`].
+    /// Bytes before the start of the output are taken as zeros.
+    Permissive,
+}
+
+/// Why a back-reference was flagged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchWarningKind {
+    /// The match reaches before the start of the output, where only a preset dictionary could be.
+    BeforeStart,
+    /// The match reaches further back than the window size in effect.
+    OutsideWindow,
+}
+
+/// A back-reference that was accepted in permissive mode although it reaches outside the window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MatchWarning {
+    /// Bit offset in the input just after the match.
+    pub bit_offset: usize,
+    /// Output offset of the first byte produced by the match.
+    pub output_offset: usize,
+    /// Distance of the match.
+    pub distance: usize,
+    /// Why the match was flagged.
+    pub kind: MatchWarningKind,
+}
+
+/// Streams with at most this many blocks are never rejected for their average block output.
+const BLOCK_RATIO_GRACE: usize = 16;
+
+/// Counters about the blocks of a stream.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BlockStats {
+    /// Number of blocks started.
+    pub blocks: usize,
+    /// Number of those blocks with dynamic Huffman tables, each of which had tables built.
+    pub dynamic_blocks: usize,
+    /// Whether decoding stopped because a block limit was exceeded.
+    pub limit_exceeded: bool,
+}
+
+/// Where the decoder writes the decompressed data: a `Vec` growing as needed, or a slice
+/// of the caller's that cannot grow.
+pub(crate) trait OutputSink {
+    /// The bytes written so far.
+    fn written(&self) -> &[u8];
+    /// Make room for `n` more bytes, growing as `growth` says by at most `limit` bytes.
+    /// Fails with [`InflateError::OutputTooSmall`] if the sink cannot hold them.
+    fn reserve(&mut self, n: usize, growth: OutputGrowth, limit: usize) -> Result<()>;
+    /// Append a byte, which there must be room for.
+    fn push(&mut self, byte: u8);
+    /// Append `n` copies of `byte`.
+    fn fill(&mut self, byte: u8, n: usize);
+    /// Append a copy of the bytes written at `range`.
+    fn extend_from_within(&mut self, range: Range<usize>);
+    /// Append `n` zeros and get them, to be overwritten.
+    fn append_zeroed(&mut self, n: usize) -> &mut [u8];
+    /// Drop the bytes written past `len`.
+    fn truncate(&mut self, len: usize);
+}
+
+impl OutputSink for Vec<u8> {
+    fn written(&self) -> &[u8] {
+        self
+    }
+
+    fn reserve(&mut self, n: usize, growth: OutputGrowth, limit: usize) -> Result<()> {
+        let free = self.capacity() - self.len();
+        if free >= n {
+            return Ok(());
+        }
+        match growth {
+            OutputGrowth::Doubling => Vec::reserve(self, n),
+            OutputGrowth::Exact => self.reserve_exact(n),
+            OutputGrowth::Chunked(chunk) => {
+                // Whole chunks past the current capacity, but not past the limit.
+                let chunk = chunk.max(1);
+                let grown = free.saturating_add((n - free).div_ceil(chunk).saturating_mul(chunk));
+                self.reserve_exact(grown.min(limit).max(n));
             }
-            17 => {
-                // 17: repeat code length of 0 for 3-10 times
-                let repeat_len = 3 + bit_reader.read_bits(3);
-                for _ in 0..repeat_len {
-                    code_lengths[i] = 0;
-                    i += 1;
-                }
+        }
+        Ok(())
+    }
+
+    fn push(&mut self, byte: u8) {
+        Vec::push(self, byte);
+    }
+
+    fn fill(&mut self, byte: u8, n: usize) {
+        self.resize(self.len() + n, byte);
+    }
+
+    fn extend_from_within(&mut self, range: Range<usize>) {
+        Vec::extend_from_within(self, range);
+    }
+
+    fn append_zeroed(&mut self, n: usize) -> &mut [u8] {
+        let end = self.len();
+        self.resize(end + n, 0);
+        &mut self[end..]
+    }
+
+    fn truncate(&mut self, len: usize) {
+        Vec::truncate(self, len);
+    }
+}
+
+/// A slice of the caller's that the decoder writes into in place.
+#[derive(Debug)]
+pub(crate) struct SliceSink<'a> {
+    buf: &'a mut [u8],
+    /// Number of bytes written to the front of `buf`.
+    len: usize,
+}
+
+impl<'a> SliceSink<'a> {
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        Self { buf, len: 0 }
+    }
+
+    /// Insert `bytes` in front of the bytes written so far.
+    fn prepend(&mut self, bytes: &[u8]) -> Result<()> {
+        self.reserve(bytes.len(), OutputGrowth::Exact, bytes.len())?;
+        self.buf.copy_within(..self.len, bytes.len());
+        self.buf[..bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+        Ok(())
+    }
+}
+
+impl OutputSink for SliceSink<'_> {
+    fn written(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+
+    fn reserve(&mut self, n: usize, _growth: OutputGrowth, _limit: usize) -> Result<()> {
+        if self.buf.len() - self.len < n {
+            return Err(InflateError::OutputTooSmall);
+        }
+        Ok(())
+    }
+
+    fn push(&mut self, byte: u8) {
+        self.buf[self.len] = byte;
+        self.len += 1;
+    }
+
+    fn fill(&mut self, byte: u8, n: usize) {
+        self.buf[self.len..self.len + n].fill(byte);
+        self.len += n;
+    }
+
+    fn extend_from_within(&mut self, range: Range<usize>) {
+        let n = range.len();
+        self.buf.copy_within(range, self.len);
+        self.len += n;
+    }
+
+    fn append_zeroed(&mut self, n: usize) -> &mut [u8] {
+        let end = self.len;
+        self.len += n;
+        let appended = &mut self.buf[end..self.len];
+        appended.fill(0);
+        appended
+    }
+
+    fn truncate(&mut self, len: usize) {
+        self.len = self.len.min(len);
+    }
+}
+
+/// State shared by the decoding functions while inflating one stream.
+#[derive(Debug)]
+pub(crate) struct InflateState<O = Vec<u8>> {
+    /// Decompressed data, or at least the part of it that back-references can reach.
+    pub output: O,
+    /// Number of bytes dropped from the front of `output`.
+    pub discarded: usize,
+    /// The last of the dropped bytes, which back-references can still reach.
+    pub window: Window,
+    /// Number of preset dictionary bytes placed in front of the decompressed data.
+    pub dictionary: usize,
+    /// Number of bytes, discarded ones and the dictionary included, before the start of the
+    /// current stream, which back-references cannot reach.
+    pub history_start: usize,
+    pub validation: MatchValidation,
+    /// The furthest distance a back-reference may reach.
+    pub window_size: usize,
+    pub warnings: Vec<MatchWarning>,
+    /// The largest number of bytes the stream may decompress to.
+    pub max_output: Option<usize>,
+    pub block_stats: BlockStats,
+    /// The largest number of blocks the stream may have.
+    pub max_blocks: Option<usize>,
+    /// The smallest average number of bytes a block must decompress to.
+    pub min_block_output: Option<usize>,
+    /// How the output grows once it is full.
+    pub growth: OutputGrowth,
+    /// How the symbols of compressed blocks are decoded.
+    pub decoder: DecodeStrategy,
+    /// Whether the stream is Deflate64 rather than DEFLATE.
+    pub deflate64: bool,
+    /// The fields read so far, when disassembling.
+    pub trace: Option<Vec<Field>>,
+    /// Byte offset within the whole input of the data being decoded, for error reports.
+    pub input_offset: usize,
+    /// Where the last error was found and what the decoder was doing.
+    pub failure: Option<CorruptionReport>,
+}
+
+impl InflateState {
+    /// Create a state for a stream using the whole DEFLATE window.
+    pub fn new() -> Self {
+        Self::with_validation(MatchValidation::Strict, MAX_DISTANCE)
+    }
+
+    /// Create a state with the given match validation and window size.
+    pub fn with_validation(validation: MatchValidation, window_size: usize) -> Self {
+        Self::with_output(Vec::new(), validation, window_size)
+    }
+
+    /// Create a state configured by the given options.
+    pub fn from_options(options: &InflateOptions) -> Self {
+        Self::configured(options, reserved_output(options))
+    }
+
+    /// Place a preset dictionary in front of the output, so back-references can reach into it.
+    /// Only the last `MAX_DISTANCE` bytes of the dictionary are reachable, or
+    /// `DEFLATE64_MAX_DISTANCE` for Deflate64.
+    pub fn set_dictionary(&mut self, dictionary: &[u8]) {
+        let reachable = self.reachable(dictionary);
+        self.output.splice(0..0, reachable.iter().copied());
+        self.dictionary += reachable.len();
+    }
+
+    /// Drop all but the last `keep` bytes of the output.
+    /// Back-references still reach the dropped bytes through the window.
+    pub fn discard_output(&mut self, keep: usize) {
+        if self.output.len() > keep {
+            let n = self.output.len() - keep;
+            self.window.push(&self.output[..n]);
+            self.output.drain(..n);
+            self.discarded += n;
+        }
+    }
+}
+
+impl InflateState<SliceSink<'_>> {
+    /// Place a preset dictionary in front of the output, as [`InflateState::set_dictionary`]
+    /// does. Fails with [`InflateError::OutputTooSmall`] if the slice cannot hold it.
+    pub fn set_dictionary(&mut self, dictionary: &[u8]) -> Result<()> {
+        let reachable = self.reachable(dictionary);
+        self.output.prepend(reachable)?;
+        self.dictionary += reachable.len();
+        Ok(())
+    }
+}
+
+impl<O: OutputSink> InflateState<O> {
+    /// Create a state writing to `output`, with the given match validation and window size.
+    pub fn with_output(output: O, validation: MatchValidation, window_size: usize) -> Self {
+        Self {
+            output,
+            discarded: 0,
+            window: Window::new(),
+            dictionary: 0,
+            history_start: 0,
+            validation,
+            window_size: window_size.min(MAX_DISTANCE),
+            warnings: Vec::new(),
+            max_output: None,
+            block_stats: BlockStats::default(),
+            max_blocks: None,
+            min_block_output: None,
+            growth: OutputGrowth::Doubling,
+            decoder: DecodeStrategy::TwoLevel,
+            deflate64: false,
+            trace: None,
+            input_offset: 0,
+            failure: None,
+        }
+    }
+
+    /// Create a state configured by the given options, writing to `output`.
+    pub fn configured(options: &InflateOptions, output: O) -> Self {
+        Self {
+            max_output: options.max_output_size,
+            max_blocks: options.max_blocks,
+            min_block_output: options.min_block_output,
+            growth: options.growth,
+            decoder: options.decoder,
+            deflate64: options.deflate64,
+            window: Window::with_capacity(max_distance(options.deflate64)),
+            window_size: options.window_size.min(max_distance(options.deflate64)),
+            ..Self::with_output(output, options.validation, options.window_size)
+        }
+    }
+
+    /// Check that `n` more bytes of output stay within the output limit, and make room for
+    /// them as the growth strategy says.
+    pub fn reserve_output(&mut self, n: usize) -> Result<()> {
+        let mut limit = usize::MAX;
+        if let Some(max) = self.max_output {
+            if self.total_out() + n > max {
+                return Err(InflateError::OutputLimitExceeded);
             }
-            18 => {
-                // 18: repeat code length of 0 for 11-138 times
-                let repeat_len = 11 + bit_reader.read_bits(7);
-                for _ in 0..repeat_len {
-                    code_lengths[i] = 0;
-                    i += 1;
-                }
+            limit = max - self.total_out();
+        }
+        self.output.reserve(n, self.growth, limit)
+    }
+
+    /// Count a new block and check the block limits before decoding it.
+    pub fn start_block(&mut self, dynamic: bool) -> Result<()> {
+        self.block_stats.blocks += 1;
+        if dynamic {
+            self.block_stats.dynamic_blocks += 1;
+        }
+        let too_many = self
+            .max_blocks
+            .is_some_and(|max| self.block_stats.blocks > max);
+        let finished = self.block_stats.blocks - 1;
+        let too_small = self.min_block_output.is_some_and(|min| {
+            finished > BLOCK_RATIO_GRACE && self.total_out() < finished.saturating_mul(min)
+        });
+        if too_many || too_small {
+            self.block_stats.limit_exceeded = true;
+            return Err(InflateError::BlockLimitExceeded);
+        }
+        Ok(())
+    }
+
+    /// Get a function passing an error on after noting, for its report, that the decoder ran
+    /// into it at `stage`, at `bit_offset` within the data being decoded.
+    pub fn fail(
+        &mut self,
+        stage: DecodeStage,
+        bit_offset: usize,
+    ) -> impl FnOnce(InflateError) -> InflateError + '_ {
+        let block_index = match stage {
+            DecodeStage::ContainerHeader
+            | DecodeStage::ContainerTrailer
+            | DecodeStage::AfterStream => None,
+            _ => self.block_stats.blocks.checked_sub(1),
+        };
+        self.fail_in_block(stage, bit_offset, block_index)
+    }
+
+    /// Like [`InflateState::fail`], for an error in the given block rather than the current one.
+    pub fn fail_in_block(
+        &mut self,
+        stage: DecodeStage,
+        bit_offset: usize,
+        block_index: Option<usize>,
+    ) -> impl FnOnce(InflateError) -> InflateError + '_ {
+        move |error| {
+            self.note_failure(error, stage, bit_offset, block_index);
+            error
+        }
+    }
+
+    /// Keep the report of an error. Kept out of line: the decoding loops only get here once.
+    #[cold]
+    #[inline(never)]
+    fn note_failure(
+        &mut self,
+        error: InflateError,
+        stage: DecodeStage,
+        bit_offset: usize,
+        block_index: Option<usize>,
+    ) {
+        self.failure = Some(CorruptionReport {
+            error,
+            bit_offset: self.input_offset * 8 + bit_offset,
+            block_index,
+            output_offset: self.total_out(),
+            stage,
+        });
+    }
+
+    /// Pass on an error that reaches the caller of the decoder, logging its report with the
+    /// `tracing` feature. Errors the decoder recovers from, such as running out of input
+    /// before more of it arrives, do not go through here.
+    pub fn surface(&self, error: InflateError) -> InflateError {
+        #[cfg(feature = "tracing")]
+        {
+            let report = self.report(error);
+            tracing::warn!(
+                error = %error,
+                code = error.code(),
+                stage = ?report.stage,
+                bit_offset = report.bit_offset,
+                block_index = ?report.block_index,
+                output_offset = report.output_offset,
+                "decoding failed"
+            );
+        }
+        error
+    }
+
+    /// Get the report of an error returned by the decoding functions.
+    pub fn report(&self, error: InflateError) -> CorruptionReport {
+        match self.failure {
+            Some(report) if report.error == error => report,
+            // Every error is noted where it is found; this is only a fallback.
+            _ => CorruptionReport {
+                error,
+                bit_offset: self.input_offset * 8,
+                block_index: self.block_stats.blocks.checked_sub(1),
+                output_offset: self.total_out(),
+                stage: DecodeStage::AfterStream,
+            },
+        }
+    }
+
+    /// Record a field of `bit_len` bits at `bit_offset` when disassembling.
+    /// Empty fields, such as the extra bits of codes without any, are skipped.
+    pub fn record(&mut self, bit_offset: usize, bit_len: usize, kind: FieldKind) {
+        self.record_at(bit_offset, bit_len, self.total_out(), kind);
+    }
+
+    /// Record a field whose output starts at `output_offset`, for fields recorded once their
+    /// output is written.
+    fn record_at(
+        &mut self,
+        bit_offset: usize,
+        bit_len: usize,
+        output_offset: usize,
+        kind: FieldKind,
+    ) {
+        let block_index = self.block_stats.blocks.checked_sub(1);
+        if let Some(trace) = &mut self.trace {
+            if bit_len > 0 {
+                trace.push(Field {
+                    bit_offset,
+                    bit_len,
+                    block_index,
+                    output_offset,
+                    kind,
+                });
             }
-            _ => Err(invalid_huffman_symbol())?,
         }
     }
-    Ok(code_lengths)
+
+    /// Total number of bytes outputted, including the discarded ones.
+    pub fn total_out(&self) -> usize {
+        self.discarded + self.output.written().len() - self.dictionary
+    }
+
+    /// Get the part of a preset dictionary back-references can reach.
+    fn reachable<'d>(&self, dictionary: &'d [u8]) -> &'d [u8] {
+        let max_distance = max_distance(self.deflate64);
+        &dictionary[dictionary.len().saturating_sub(max_distance)..]
+    }
+
+    /// Start a new stream after the output so far, e.g. another gzip member: its
+    /// back-references cannot reach the bytes of the streams before it.
+    pub fn start_history(&mut self) {
+        self.history_start = self.discarded + self.output.written().len();
+    }
+
+    /// Number of bytes back-references of the current stream can reach, the dictionary
+    /// included.
+    fn history_len(&self) -> usize {
+        self.discarded + self.output.written().len() - self.history_start
+    }
+
+    /// Get the decompressed data, without the preset dictionary.
+    /// Only meaningful while no output has been discarded.
+    pub fn decompressed(&self) -> &[u8] {
+        &self.output.written()[self.dictionary..]
+    }
+
+    /// Copy `len` bytes from `dist` bytes back, checking the distance against the window.
+    /// `bit_offset` is where the match ends in the input, for the warnings.
+    /// Returns the number of bytes outputted.
+    fn copy_match(&mut self, dist: usize, len: usize, bit_offset: usize) -> Result<usize> {
+        self.reserve_output(len)?;
+        let kind = if dist > self.history_len() {
+            MatchWarningKind::BeforeStart
+        } else if dist > self.window_size {
+            MatchWarningKind::OutsideWindow
+        } else {
+            return Ok(self.repeat(dist, len));
+        };
+
+        if self.validation == MatchValidation::Strict {
+            return Err(match kind {
+                MatchWarningKind::BeforeStart => InflateError::DistanceTooFar,
+                MatchWarningKind::OutsideWindow => InflateError::DistanceOutsideWindow,
+            });
+        }
+        self.warnings.push(MatchWarning {
+            bit_offset,
+            output_offset: self.total_out(),
+            distance: dist,
+            kind,
+        });
+        if kind == MatchWarningKind::BeforeStart {
+            // Pretend the output of the stream, including the dictionary, is preceded by zeros.
+            let missing = (dist - self.history_len()).min(len);
+            self.output.fill(0, missing);
+            return Ok(missing + self.repeat(dist, len - missing));
+        }
+        Ok(self.repeat(dist, len))
+    }
+
+    /// Copy `len` bytes from `dist` bytes back, which the window and output must reach.
+    /// The bytes behind the output come from the window; the rest from the output itself.
+    fn repeat(&mut self, dist: usize, len: usize) -> usize {
+        let behind = dist.saturating_sub(self.output.written().len());
+        let copied = behind.min(len);
+        if copied > 0 {
+            self.window
+                .copy_into(behind, self.output.append_zeroed(copied));
+        }
+        copied + repeat_with_overlap(&mut self.output, dist, len - copied)
+    }
 }
 
-/// Inflate a DEFLATE file into a Vec<u8>.
-/// This function decompresses the DEFLATE data and returns the decompressed data as a Vec<u8>.
-/// The input data should be the compressed DEFLATE data.
-pub fn inflate_to_vec(data: &[u8]) -> Result<Vec<u8>> {
-    let mut bit_reader = BitReader::new(data);
-    let mut output = Vec::new();
-    loop {
-        let b_final = bit_reader.read_bits(BFINAL_LEN);
-        let b_type = bit_reader.read_bits(BTYPE_LEN);
-        match b_type {
-            BTYPE_NO_COMPRESSION => {
-                // No compression
-                bit_reader.advance_to_byte_boundary();
-                let len = bit_reader.read_bits(LEN_LEN) as u16;
-                let nlen = bit_reader.read_bits(NLEN_LEN) as u16;
-                if len != !nlen {
-                    return Err(invalid_len_nlen());
-                }
-                let mut literal_data = vec![0; len as usize];
-                bit_reader.read_bytes_to_slice(len as usize, &mut literal_data);
-                output.extend(literal_data);
-            }
-            BTYPE_FIXED_HUFFMAN => {
-                // Fixed Huffman block
-                let lit_tb = fixed_literal_table();
-                let dis_tb = fixed_distance_table();
-                inflate_compressed_block(&mut bit_reader, &mut output, &lit_tb, &dis_tb)?;
+/// Inflate a single block, starting at its header, and append the result to the output.
+/// Returns whether the block was marked as the final block of the stream.
+pub(crate) fn inflate_block<O: OutputSink>(
+    bit_reader: &mut BitReader,
+    state: &mut InflateState<O>,
+) -> Result<bool> {
+    let start = bit_reader.bits_consumed();
+    let (is_final, b_type) = read_block_header(bit_reader, state)?;
+    match b_type {
+        BTYPE_NO_COMPRESSION => {
+            // No compression
+            let len = read_stored_header(bit_reader, state)?;
+            copy_stored(bit_reader, state, len, is_final, len)?;
+        }
+        BTYPE_FIXED_HUFFMAN => {
+            // Fixed Huffman block
+            if state.decoder == DecodeStrategy::Tree {
+                let lit_tree = fixed_tree(&fixed_literal_code_lengths());
+                let dis_tree = fixed_tree(&fixed_distance_code_lengths());
+                inflate_compressed_block(bit_reader, state, &lit_tree, &dis_tree)?;
+            } else {
+                // The fixed codes are short enough for flat tables with either strategy.
+                inflate_compressed_block(
+                    bit_reader,
+                    state,
+                    &FIXED_LITERAL_TABLE,
+                    &FIXED_DISTANCE_TABLE,
+                )?;
             }
-            BTYPE_DYNAMIC_HUFFMAN => {
-                // Dynamic Huffman block
-                let (lit_tb, dis_tb) = read_dynamic_huffman_tables(&mut bit_reader)?;
-                inflate_compressed_block(&mut bit_reader, &mut output, &lit_tb, &dis_tb)?;
+        }
+        BTYPE_DYNAMIC_HUFFMAN => {
+            // Dynamic Huffman block
+            if state.decoder == DecodeStrategy::Tree {
+                let (lit_tree, dis_tree) =
+                    read_dynamic_decoders(bit_reader, state, |code_len, _| {
+                        HuffmanTree::try_new(code_len)
+                    })?;
+                inflate_compressed_block(bit_reader, state, &lit_tree, &dis_tree)?;
+            } else {
+                let (lit_tb, dis_tb) = read_dynamic_huffman_tables(bit_reader, state)?;
+                inflate_compressed_block(bit_reader, state, &lit_tb, &dis_tb)?;
             }
-            _ => return Err(invalid_block_type()),
         }
-        if b_final == BFINAL_VALUE {
-            break;
+        _ => {
+            let fail = state.fail(DecodeStage::BlockHeader, start);
+            return Err(fail(InflateError::InvalidBlockType));
         }
     }
-    Ok(output)
+    Ok(is_final)
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Read the BFINAL and BTYPE fields of a block header, counting the block.
+/// Returns whether the block is the final one, and its type.
+fn read_block_header<O: OutputSink>(
+    bit_reader: &mut BitReader,
+    state: &mut InflateState<O>,
+) -> Result<(bool, usize)> {
+    let start = bit_reader.bits_consumed();
+    // The block is only counted once its header is read.
+    let block_index = Some(state.block_stats.blocks);
+    let header = read_bits(bit_reader, BFINAL_LEN + BTYPE_LEN).map_err(state.fail_in_block(
+        DecodeStage::BlockHeader,
+        start,
+        block_index,
+    ))?;
+    let is_final = header & 1 == BFINAL_VALUE;
+    let b_type = header >> BFINAL_LEN;
+    state
+        .start_block(b_type == BTYPE_DYNAMIC_HUFFMAN)
+        .map_err(state.fail(DecodeStage::BlockHeader, start))?;
+    #[cfg(feature = "tracing")]
+    tracing::debug!(
+        block_index = state.block_stats.blocks - 1,
+        block_type = b_type,
+        is_final,
+        bit_offset = state.input_offset * 8 + start,
+        output_offset = state.total_out(),
+        "block header"
+    );
+    state.record(start, BFINAL_LEN, FieldKind::BlockFinal(is_final));
+    state.record(start + BFINAL_LEN, BTYPE_LEN, FieldKind::BlockType(b_type));
+    Ok((is_final, b_type))
+}
 
-    #[test]
-    fn test_huffman_lookup_table() {
-        let code_lengths = vec![3, 3, 3, 3, 3, 2, 4, 4];
-        let max_bits = 4;
-        let huffman_table = HuffmanLookupTable::new(&code_lengths, max_bits);
+/// Read the rest of a stored block header: the padding up to the byte boundary, LEN and NLEN.
+/// Returns the number of bytes in the block.
+/// Defined in RFC 1951, section 3.2.4.
+fn read_stored_header<O: OutputSink>(
+    bit_reader: &mut BitReader,
+    state: &mut InflateState<O>,
+) -> Result<usize> {
+    let start = bit_reader.bits_consumed();
+    bit_reader.advance_to_byte_boundary();
+    let len_start = bit_reader.bits_consumed();
+    state.record(start, len_start - start, FieldKind::StoredPadding);
+    let (len, nlen) = bit_reader
+        .try_read_u16_le()
+        .zip(bit_reader.try_read_u16_le())
+        .ok_or(InflateError::UnexpectedEof)
+        .map_err(state.fail(DecodeStage::StoredHeader, len_start))?;
+    state.record(len_start, LEN_LEN, FieldKind::StoredLen(len as usize));
+    state.record(
+        len_start + LEN_LEN,
+        NLEN_LEN,
+        FieldKind::StoredNlen(nlen as usize),
+    );
+    if len != !nlen {
+        let fail = state.fail(DecodeStage::StoredHeader, len_start);
+        return Err(fail(InflateError::InvalidLenNlen));
+    }
+    Ok(len as usize)
+}
 
-        huffman_table.table.iter().for_each(|&(symbol, len)| {
-            assert_eq!(len, code_lengths[symbol]);
-        });
+/// Where a resumable decoder stands within a stream.
+#[derive(Debug)]
+pub(crate) enum BlockState {
+    /// Before a block header.
+    Header,
+    /// Inside a stored block, with the number of bytes left to copy.
+    Stored { remaining: usize, is_final: bool },
+    /// Inside a Huffman-coded block.
+    Compressed {
+        lit_tb: HuffmanLookupTable,
+        dis_tb: HuffmanLookupTable,
+        is_final: bool,
+    },
+    /// After the end of the final block.
+    Done,
+}
+
+impl BlockState {
+    /// The state after a block ends.
+    fn after_block(is_final: bool) -> Self {
+        if is_final {
+            BlockState::Done
+        } else {
+            BlockState::Header
+        }
     }
-    #[test]
-    fn test_fixed_literal_table() {
-        let huffman_table = fixed_literal_table();
-        assert_eq!(huffman_table.max_bits, 9);
-        assert_eq!(huffman_table.table[0b0_01111111], (252, 9));
-        assert_eq!(huffman_table.table[0b1_10000000], (256, 7));
-        assert_eq!(huffman_table.table[0b1_00000011], (280, 8));
-        assert_eq!(huffman_table.table[0b0_00001100], (0, 8));
-        assert_eq!(huffman_table.table[0b1_11111111], (255, 9));
+
+    /// The state inside a stored block, moving on right away when nothing is left to copy.
+    fn stored(remaining: usize, is_final: bool) -> Self {
+        if remaining == 0 {
+            Self::after_block(is_final)
+        } else {
+            BlockState::Stored {
+                remaining,
+                is_final,
+            }
+        }
     }
+}
 
-    #[test]
-    fn test_fixed_distance_table() {
-        let huffman_table = fixed_distance_table();
-        assert_eq!(huffman_table.max_bits, 5);
-        assert_eq!(huffman_table.table[0b00000], (0, 5));
-        assert_eq!(huffman_table.table[0b11100], (7, 5));
-        assert_eq!(huffman_table.table[0b11111], (31, 5));
+/// Decode the next piece of a stream: a block header along with its Huffman tables,
+/// as much of a stored block as the input holds, or one symbol of a compressed block.
+/// Each piece either completes or leaves `block` untouched, so a caller running out of input
+/// can roll back to the start of the piece and retry it once more input arrives.
+/// Returns the state to move to, if it changes.
+pub(crate) fn inflate_step<O: OutputSink>(
+    bit_reader: &mut BitReader,
+    state: &mut InflateState<O>,
+    block: &BlockState,
+) -> Result<Option<BlockState>> {
+    match block {
+        BlockState::Header => {
+            let start = bit_reader.bits_consumed();
+            let (is_final, b_type) = read_block_header(bit_reader, state)?;
+            let next = match b_type {
+                BTYPE_NO_COMPRESSION => {
+                    let len = read_stored_header(bit_reader, state)?;
+                    BlockState::stored(len, is_final)
+                }
+                BTYPE_FIXED_HUFFMAN => BlockState::Compressed {
+                    lit_tb: fixed_literal_table(),
+                    dis_tb: fixed_distance_table(),
+                    is_final,
+                },
+                BTYPE_DYNAMIC_HUFFMAN => {
+                    let (lit_tb, dis_tb) = read_dynamic_huffman_tables(bit_reader, state)?;
+                    BlockState::Compressed {
+                        lit_tb,
+                        dis_tb,
+                        is_final,
+                    }
+                }
+                _ => {
+                    let fail = state.fail(DecodeStage::BlockHeader, start);
+                    return Err(fail(InflateError::InvalidBlockType));
+                }
+            };
+            Ok(Some(next))
+        }
+        &BlockState::Stored {
+            remaining,
+            is_final,
+        } => {
+            // Hold back the last input byte: a piece reaching the end of the input gets
+            // retried once more input arrives, which would copy all of it again.
+            let n = remaining
+                .min(bit_reader.bytes_remaining().saturating_sub(1))
+                .max(1);
+            copy_stored(bit_reader, state, remaining, is_final, n).map(Some)
+        }
+        BlockState::Compressed {
+            lit_tb,
+            dis_tb,
+            is_final,
+        } => {
+            if inflate_symbol(bit_reader, state, lit_tb, dis_tb)? {
+                return Ok(Some(BlockState::after_block(*is_final)));
+            }
+            Ok(None)
+        }
+        BlockState::Done => Ok(None),
+    }
+}
+
+/// Copy the next `n` bytes of a stored block with `remaining` bytes left to the output.
+/// Returns the state after them.
+pub(crate) fn copy_stored<O: OutputSink>(
+    bit_reader: &mut BitReader,
+    state: &mut InflateState<O>,
+    remaining: usize,
+    is_final: bool,
+    n: usize,
+) -> Result<BlockState> {
+    let bit_offset = bit_reader.bits_consumed();
+    state
+        .reserve_output(n)
+        .map_err(state.fail(DecodeStage::StoredData, bit_offset))?;
+    let end = state.output.written().len();
+    if bit_reader
+        .try_read_bytes_to_slice(n, state.output.append_zeroed(n))
+        .is_none()
+    {
+        state.output.truncate(end);
+        let fail = state.fail(DecodeStage::StoredData, bit_offset);
+        return Err(fail(InflateError::UnexpectedEof));
+    }
+    let output_offset = state.total_out() - n;
+    state.record_at(
+        bit_offset,
+        n * 8,
+        output_offset,
+        FieldKind::StoredData { len: n },
+    );
+    Ok(BlockState::stored(remaining - n, is_final))
+}
+
+/// What to do with the input after the end of the stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TailHandling {
+    /// Stop at the end of the stream and ignore anything after it.
+    #[default]
+    Ignore,
+    /// Fail if anything follows the end of the stream.
+    Reject,
+    /// The stream was cut at a sync flush, whose trailing `00 00 FF FF` was stripped.
+    /// Decoding stops when the input runs out instead of at a final block.
+    /// Only applies to raw streams.
+    SyncFlush,
+    /// Decode what follows the end of the stream as another stream in the same container,
+    /// until the input runs out, and concatenate their outputs. Each stream is decoded on its
+    /// own: back-references cannot reach the output of the streams before it, nor the preset
+    /// dictionary, which only precedes the first one.
+    Concatenated,
+}
+
+/// The most output bytes [`InflateOptions::size_hint`] reserves up front, whatever the hint.
+pub const MAX_SIZE_HINT: usize = 16 * 1024 * 1024;
+
+/// Create the output of a stream, reserving the size hint of the options, capped by
+/// `MAX_SIZE_HINT` and the output limit. The hint is left out if the allocation fails.
+fn reserved_output(options: &InflateOptions) -> Vec<u8> {
+    let len = options
+        .size_hint
+        .unwrap_or(0)
+        .min(MAX_SIZE_HINT)
+        .min(options.max_output_size.unwrap_or(usize::MAX));
+    let mut output = Vec::new();
+    let _ = output.try_reserve_exact(len);
+    output
+}
+
+/// Options controlling how a stream is inflated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InflateOptions {
+    /// The container around the DEFLATE data.
+    pub format: Format,
+    /// How back-references outside the window are handled.
+    pub validation: MatchValidation,
+    /// The furthest distance a back-reference may reach, at most `MAX_DISTANCE`, or
+    /// `DEFLATE64_MAX_DISTANCE` for Deflate64.
+    /// zlib streams are further held to the window size their header declares.
+    pub window_size: usize,
+    /// What to do with the input after the end of the stream.
+    pub tail: TailHandling,
+    /// Abort with [`InflateError::OutputLimitExceeded`] once the output would grow beyond this
+    /// many bytes. Use it when inflating untrusted data, which can expand over a thousand times.
+    pub max_output_size: Option<usize>,
+    /// Preset dictionary that back-references may reach into before the start of the output.
+    /// zlib streams declaring a dictionary require it, and it must match their DICTID.
+    pub dictionary: Option<Vec<u8>>,
+    /// Abort with [`InflateError::BlockLimitExceeded`] once the stream has more blocks than this.
+    /// Every dynamic block costs a table build, so streams of tiny blocks are slow to decode.
+    pub max_blocks: Option<usize>,
+    /// Abort with [`InflateError::BlockLimitExceeded`] once the blocks decoded so far average
+    /// less output than this many bytes. The first few blocks are exempt.
+    pub min_block_output: Option<usize>,
+    /// The expected size of the output, such as the gzip ISIZE or the size in a ZIP directory,
+    /// reserved up front. It is only a hint: the output still grows past it, and at most
+    /// `MAX_SIZE_HINT` bytes, or `max_output_size` if lower, are reserved, so an untrusted size
+    /// reserves no more than that.
+    pub size_hint: Option<usize>,
+    /// How the output grows once it is full.
+    pub growth: OutputGrowth,
+    /// How the symbols of compressed blocks are decoded.
+    pub decoder: DecodeStrategy,
+    /// Decode Deflate64, the variant of DEFLATE that ZIP method 9 uses, instead of DEFLATE.
+    /// Its window is 64 KiB, length code 285 takes 16 extra bits for lengths of 3 to 65538,
+    /// and distance codes 30 and 31 reach up to 64 KiB back.
+    pub deflate64: bool,
+}
+
+/// How the symbols of compressed blocks are decoded. Every strategy gives the same output and
+/// the same errors; they differ in speed and in the memory their tables take, so they can be
+/// compared on the same streams.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DecodeStrategy {
+    /// One lookup of the next 15 bits, in a table of 32768 entries for a dynamic block.
+    Flat,
+    /// One lookup of the first 9 bits, 6 for distances, and a second one in a sub-table for
+    /// longer codes, as zlib does.
+    #[default]
+    TwoLevel,
+    /// A walk down the canonical Huffman tree, one bit at a time.
+    /// The resumable decoders, such as [`Inflater`](crate::stream::Inflater), keep lookup tables
+    /// and decode with two-level tables instead.
+    Tree,
+}
+
+/// How the output buffer grows when a block needs more room than it has.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputGrowth {
+    /// At least double the capacity, as `Vec` does, for few reallocations on any size.
+    #[default]
+    Doubling,
+    /// Grow by just the bytes needed, for the least memory when the size hint is accurate.
+    Exact,
+    /// Grow by whole chunks of this many bytes, so the memory wasted is at most one chunk.
+    Chunked(usize),
+}
+
+impl Default for InflateOptions {
+    fn default() -> Self {
+        Self {
+            format: Format::Raw,
+            validation: MatchValidation::Strict,
+            window_size: MAX_DISTANCE,
+            tail: TailHandling::Ignore,
+            max_output_size: None,
+            dictionary: None,
+            max_blocks: None,
+            min_block_output: None,
+            size_hint: None,
+            growth: OutputGrowth::Doubling,
+            decoder: DecodeStrategy::TwoLevel,
+            deflate64: false,
+        }
+    }
+}
+
+/// Builder methods, to set only the options that differ from the defaults:
+///
+/// ```
+/// # use inflate_toy::container::Format;
+/// # use inflate_toy::inflate::InflateOptions;
+/// let options = InflateOptions::new()
+///     .format(Format::Zlib)
+///     .max_output(1 << 20)
+///     .window_bits(15)
+///     .strict(true);
+/// ```
+impl InflateOptions {
+    /// Create the default options: a raw stream, strictly validated against a 32 KiB window,
+    /// with no limits.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the container around the DEFLATE data.
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Set how back-references outside the window are handled.
+    pub fn validation(mut self, validation: MatchValidation) -> Self {
+        self.validation = validation;
+        self
+    }
+
+    /// Fail on back-references outside the window, or with `false`, accept them.
+    pub fn strict(self, strict: bool) -> Self {
+        self.validation(match strict {
+            true => MatchValidation::Strict,
+            false => MatchValidation::Permissive,
+        })
+    }
+
+    /// Set the furthest distance a back-reference may reach, at most `MAX_DISTANCE`, or
+    /// `DEFLATE64_MAX_DISTANCE` for Deflate64.
+    pub fn window_size(mut self, window_size: usize) -> Self {
+        self.window_size = window_size.min(max_distance(self.deflate64));
+        self
+    }
+
+    /// Set the window size as a base-two logarithm, as zlib's `windowBits` does.
+    /// Values outside 8 to 15 are clamped. This is how raw streams, which don't declare their
+    /// window, get a smaller one than 32 KiB.
+    pub fn window_bits(self, bits: u8) -> Self {
+        self.window_size(1 << bits.clamp(8, 15))
+    }
+
+    /// Set what to do with the input after the end of the stream.
+    pub fn tail(mut self, tail: TailHandling) -> Self {
+        self.tail = tail;
+        self
+    }
+
+    /// Abort once the output would grow beyond this many bytes.
+    pub fn max_output(mut self, max_output_size: usize) -> Self {
+        self.max_output_size = Some(max_output_size);
+        self
+    }
+
+    /// Set the preset dictionary back-references may reach into.
+    pub fn dictionary(mut self, dictionary: impl Into<Vec<u8>>) -> Self {
+        self.dictionary = Some(dictionary.into());
+        self
+    }
+
+    /// Abort once the stream has more blocks than this.
+    pub fn max_blocks(mut self, max_blocks: usize) -> Self {
+        self.max_blocks = Some(max_blocks);
+        self
+    }
+
+    /// Abort once the blocks decoded so far average less output than this many bytes.
+    pub fn min_block_output(mut self, min_block_output: usize) -> Self {
+        self.min_block_output = Some(min_block_output);
+        self
+    }
+
+    /// Reserve this many bytes of output up front, when the size is known from elsewhere.
+    pub fn size_hint(mut self, size_hint: usize) -> Self {
+        self.size_hint = Some(size_hint);
+        self
+    }
+
+    /// Set how the output grows once it is full.
+    pub fn growth(mut self, growth: OutputGrowth) -> Self {
+        self.growth = growth;
+        self
+    }
+
+    /// Set how the symbols of compressed blocks are decoded.
+    pub fn decoder(mut self, decoder: DecodeStrategy) -> Self {
+        self.decoder = decoder;
+        self
+    }
+
+    /// Decode Deflate64 instead of DEFLATE. This sets the window size to the largest the
+    /// format allows, so a smaller window is set after it.
+    pub fn deflate64(mut self, deflate64: bool) -> Self {
+        self.deflate64 = deflate64;
+        self.window_size = max_distance(deflate64);
+        self
+    }
+
+    /// Check that options given to a decoder of raw DEFLATE only ask for no container, and
+    /// for one of the tail handling modes the decoder supports.
+    pub(crate) fn check_raw(&self, tails: &[TailHandling]) -> Result<()> {
+        if self.format != Format::Raw {
+            return Err(InflateError::UnsupportedOption(
+                "Only raw DEFLATE streams are supported",
+            ));
+        }
+        if !tails.contains(&self.tail) {
+            return Err(InflateError::UnsupportedOption(
+                "Tail handling mode not supported",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Inflate a DEFLATE file into a Vec<u8>.
+/// This function decompresses the DEFLATE data and returns the decompressed data as a Vec<u8>.
+/// The input data should be the compressed DEFLATE data.
+/// It is [`inflate_to_vec_with_options`] with the default options.
+pub fn inflate_to_vec(data: &[u8]) -> Result<Vec<u8>> {
+    inflate_to_vec_with_options(data, &InflateOptions::new())
+}
+
+/// Inflate a DEFLATE file, checking every back-reference against a window of `window_size` bytes
+/// (at most `MAX_DISTANCE`).
+/// Returns the decompressed data and, in permissive mode, the matches that reached outside the window.
+pub fn inflate_with_validation(
+    data: &[u8],
+    validation: MatchValidation,
+    window_size: usize,
+) -> Result<(Vec<u8>, Vec<MatchWarning>)> {
+    if data.is_empty() {
+        return Err(InflateError::EmptyInput);
+    }
+    let mut bit_reader = BitReader::new_strict(data);
+    let mut state = InflateState::with_validation(validation, window_size);
+    while !inflate_block(&mut bit_reader, &mut state).map_err(|e| state.surface(e))? {}
+    Ok((state.output, state.warnings))
+}
+
+/// Inflate a raw DEFLATE stream split into chunks, such as network buffers, reading them
+/// one after the other without copying them into one buffer first.
+pub fn inflate_chunks_to_vec(chunks: &[&[u8]]) -> Result<Vec<u8>> {
+    if chunks.iter().all(|chunk| chunk.is_empty()) {
+        return Err(InflateError::EmptyInput);
+    }
+    let mut bit_reader = BitReader::from_chunks_strict(chunks);
+    let mut state = InflateState::new();
+    while !inflate_block(&mut bit_reader, &mut state).map_err(|e| state.surface(e))? {}
+    Ok(state.output)
+}
+
+/// Inflate the raw DEFLATE stream at the start of `data`.
+/// Returns the number of bytes the stream took, including the padding bits of its last byte.
+fn inflate_raw<O: OutputSink>(
+    data: &[u8],
+    state: &mut InflateState<O>,
+    tail: TailHandling,
+) -> Result<usize> {
+    if tail == TailHandling::SyncFlush {
+        let mut flushed = data.to_vec();
+        flushed.extend(SYNC_FLUSH_MARKER);
+        let mut bit_reader = BitReader::new_strict(&flushed);
+        while !bit_reader.eof() && !inflate_block(&mut bit_reader, state)? {}
+        return Ok(data.len());
+    }
+
+    let mut bit_reader = BitReader::new_strict(data);
+    while !inflate_block(&mut bit_reader, state)? {}
+    bit_reader.advance_to_byte_boundary();
+    Ok(bit_reader.bits_consumed() / 8)
+}
+
+/// Inflate a DEFLATE stream inside the container given by the options.
+/// This is the configurable counterpart of [`inflate_to_vec`].
+pub fn inflate_to_vec_with_options(data: &[u8], options: &InflateOptions) -> Result<Vec<u8>> {
+    let mut output = Vec::new();
+    inflate_into_vec_with_options(data, options, &mut output)?;
+    Ok(output)
+}
+
+/// Inflate a DEFLATE file into the given vector, which is cleared first.
+/// The capacity of the vector is kept, so decoding many messages in a loop with the same
+/// vector avoids allocating a new one each time.
+/// Returns the number of bytes decompressed. On error, the vector holds the data decompressed
+/// before the error.
+pub fn inflate_into_vec(data: &[u8], out: &mut Vec<u8>) -> Result<usize> {
+    inflate_into_vec_with_options(data, &InflateOptions::default(), out)
+}
+
+/// Inflate a DEFLATE file into the given slice, decoding in place.
+/// Fails with [`InflateError::OutputTooSmall`] as soon as the output would overflow the slice.
+/// Returns the number of bytes written to the slice.
+pub fn inflate_into_slice(data: &[u8], out: &mut [u8]) -> Result<usize> {
+    inflate_into_slice_with_options(data, &InflateOptions::default(), out)
+}
+
+/// Inflate a DEFLATE stream inside the container given by the options into the given slice,
+/// as [`inflate_into_slice`] does. A preset dictionary takes room at the front of the slice
+/// while decoding, and the output is moved over it once the stream ends.
+pub fn inflate_into_slice_with_options(
+    data: &[u8],
+    options: &InflateOptions,
+    out: &mut [u8],
+) -> Result<usize> {
+    let (dictionary, len) = {
+        let mut state = InflateState::configured(options, SliceSink::new(out));
+        if let Some(dictionary) = &options.dictionary {
+            state
+                .set_dictionary(dictionary)
+                .map_err(|e| state.surface(e))?;
+        }
+        inflate_stream(data, options, &mut state).map_err(|e| state.surface(e))?;
+        (state.dictionary, state.output.written().len())
+    };
+    out.copy_within(dictionary..len, 0);
+    Ok(len - dictionary)
+}
+
+/// Inflate a DEFLATE stream inside the container given by the options into the given vector,
+/// reusing it as [`inflate_into_vec`] does.
+pub fn inflate_into_vec_with_options(
+    data: &[u8],
+    options: &InflateOptions,
+    out: &mut Vec<u8>,
+) -> Result<usize> {
+    inflate_into_vec_with_stats(data, options, out).0?;
+    Ok(out.len())
+}
+
+/// The decompressed data of a stream, and where the stream ends in the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InflateResult {
+    /// The decompressed data.
+    pub output: Vec<u8>,
+    /// Number of input bytes the stream takes, container included, up to the byte holding the
+    /// end of its final block. Whatever follows the stream, such as the next entry of a ZIP
+    /// archive or the next chunk of a PNG file, starts at this offset.
+    pub bytes_consumed: usize,
+    /// Number of input bytes after the stream, left unread. Always 0 when the options reject
+    /// trailing data or decode it as more streams.
+    pub trailing_bytes: usize,
+}
+
+/// Inflate a DEFLATE stream inside the container given by the options, reporting how much of
+/// the input it takes, for formats embedding DEFLATE data without recording its length.
+pub fn inflate(data: &[u8], options: &InflateOptions) -> Result<InflateResult> {
+    let mut output = Vec::new();
+    let bytes_consumed = inflate_into_vec_with_stats(data, options, &mut output).0?;
+    Ok(InflateResult {
+        output,
+        bytes_consumed,
+        trailing_bytes: data.len().saturating_sub(bytes_consumed),
+    })
+}
+
+/// The start of the output of a stream, decoded by [`inflate_prefix`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InflatePrefix {
+    /// The first bytes of the output: as many as asked for, or fewer if the stream ends first.
+    pub output: Vec<u8>,
+    /// Bit offset in the input, container header included, after the last field decoded.
+    /// A match ending past the requested length was decoded whole, so the bits up to this
+    /// offset can stand for a few more bytes of output than were kept.
+    pub bit_offset: usize,
+    /// Whether the end of the final block was reached, so the output is all of it.
+    pub complete: bool,
+}
+
+/// Inflate only the first `len` bytes of the output of a DEFLATE stream inside the container
+/// given by the options, then stop, for sniffers and thumbnailers that only need the head of
+/// the data. The rest of the input is never read, so the container trailer is not checked.
+pub fn inflate_prefix(data: &[u8], options: &InflateOptions, len: usize) -> Result<InflatePrefix> {
+    let start = container::header_len(data, options.format)?;
+    let stream = &data[start..];
+    if stream.is_empty() {
+        return Err(InflateError::EmptyInput);
+    }
+    let mut bit_reader = BitReader::new_strict(stream);
+    let mut state = InflateState::from_options(options);
+    if let Some(dictionary) = &options.dictionary {
+        state.set_dictionary(dictionary);
+    }
+    let mut block = BlockState::Header;
+    while state.total_out() < len {
+        let wanted = len - state.total_out();
+        match block {
+            // Copy no more of a stored block than needed: it may be most of the input.
+            BlockState::Stored {
+                remaining,
+                is_final,
+            } if remaining > wanted => {
+                block = copy_stored(&mut bit_reader, &mut state, remaining, is_final, wanted)
+                    .map_err(|e| state.surface(e))?;
+            }
+            BlockState::Done => break,
+            _ => {
+                if let Some(next) = inflate_step(&mut bit_reader, &mut state, &block)
+                    .map_err(|e| state.surface(e))?
+                {
+                    block = next;
+                }
+            }
+        }
+    }
+    let mut output = state.output.split_off(state.dictionary);
+    output.truncate(len);
+    Ok(InflatePrefix {
+        output,
+        bit_offset: start * 8 + bit_reader.bits_consumed(),
+        complete: matches!(block, BlockState::Done),
+    })
+}
+
+/// Inflate a DEFLATE stream inside the container given by the options, like [`inflate`], but
+/// on error report where in the input decoding failed: the bit offset, the block and what the
+/// decoder was reading at the time.
+pub fn inflate_with_report(
+    data: &[u8],
+    options: &InflateOptions,
+) -> core::result::Result<InflateResult, CorruptionReport> {
+    let mut state = InflateState::from_options(options);
+    if let Some(dictionary) = &options.dictionary {
+        state.set_dictionary(dictionary);
+    }
+    match inflate_stream(data, options, &mut state) {
+        Ok(bytes_consumed) => {
+            let mut output = state.output;
+            output.drain(..state.dictionary);
+            Ok(InflateResult {
+                output,
+                bytes_consumed,
+                trailing_bytes: data.len().saturating_sub(bytes_consumed),
+            })
+        }
+        Err(error) => Err(state.report(state.surface(error))),
+    }
+}
+
+/// Inflate a DEFLATE stream inside the container given by the options.
+/// Returns the result along with the block counters, which are available even on error,
+/// e.g. to see how far a stream got before a block limit stopped it.
+pub fn inflate_with_block_stats(
+    data: &[u8],
+    options: &InflateOptions,
+) -> (Result<Vec<u8>>, BlockStats) {
+    let mut output = Vec::new();
+    let (result, stats) = inflate_into_vec_with_stats(data, options, &mut output);
+    (result.map(|_| output), stats)
+}
+
+/// Inflate into the given vector, reusing it.
+/// Returns the number of input bytes consumed along with the block counters.
+fn inflate_into_vec_with_stats(
+    data: &[u8],
+    options: &InflateOptions,
+    out: &mut Vec<u8>,
+) -> (Result<usize>, BlockStats) {
+    let mut state = InflateState::from_options(options);
+    out.clear();
+    state.output = core::mem::take(out);
+    if let Some(dictionary) = &options.dictionary {
+        state.set_dictionary(dictionary);
+    }
+    let result = inflate_stream(data, options, &mut state).map_err(|e| state.surface(e));
+    *out = state.output;
+    out.drain(..state.dictionary);
+    (result, state.block_stats)
+}
+
+/// Inflate a DEFLATE stream with its container into the state's output, along with the
+/// streams following it if the options ask for them.
+/// Empty data is not a stream: the smallest one is an empty final block, which takes a byte.
+/// Returns the number of bytes the streams take.
+fn inflate_stream<O: OutputSink>(
+    data: &[u8],
+    options: &InflateOptions,
+    state: &mut InflateState<O>,
+) -> Result<usize> {
+    if data.is_empty() {
+        let fail = state.fail_in_block(DecodeStage::BlockHeader, 0, Some(0));
+        return Err(fail(InflateError::EmptyInput));
+    }
+    #[cfg(feature = "tracing")]
+    let _span =
+        tracing::debug_span!("inflate", format = ?options.format, input_len = data.len()).entered();
+    state.input_offset = 0;
+    let mut end = inflate_one_stream(data, options, state)?;
+    state.input_offset = end;
+    match options.tail {
+        TailHandling::Reject if end < data.len() => {
+            let fail = state.fail(DecodeStage::AfterStream, 0);
+            return Err(fail(InflateError::TrailingData));
+        }
+        // The next stream starts on the byte after the end of the previous one, and its
+        // back-references cannot reach the output of the streams before it.
+        TailHandling::Concatenated => {
+            while end < data.len() {
+                state.start_history();
+                end += inflate_one_stream(&data[end..], options, state)?;
+                state.input_offset = end;
+            }
+        }
+        _ => {}
+    }
+    Ok(end)
+}
+
+/// Inflate the DEFLATE stream with its container at the start of `data` into the state's
+/// output. `data` starts at the input offset of the state.
+/// Returns the number of bytes the stream takes.
+fn inflate_one_stream<O: OutputSink>(
+    data: &[u8],
+    options: &InflateOptions,
+    state: &mut InflateState<O>,
+) -> Result<usize> {
+    match options.format {
+        Format::Raw => inflate_raw(data, state, options.tail),
+        Format::Zlib => {
+            let base = state.input_offset;
+            let (header, start) = container::parse_zlib_header(data)
+                .and_then(|(header, start)| {
+                    check_dictionary(&header, options)?;
+                    Ok((header, start))
+                })
+                .map_err(state.fail(DecodeStage::ContainerHeader, 0))?;
+            let output_start = state.decompressed().len();
+            state.input_offset = base + start;
+            // Back-references may not reach further than the window the header declares.
+            let window_size = state.window_size;
+            state.window_size = window_size.min(1 << header.window_bits);
+            let result = inflate_raw(&data[start..], state, TailHandling::Ignore);
+            state.window_size = window_size;
+            let end = start + result?;
+            state.input_offset = base + end;
+            let trailer = data.get(end..).unwrap_or_default();
+            container::check_zlib_trailer(trailer, &state.decompressed()[output_start..])
+                .map_err(state.fail(DecodeStage::ContainerTrailer, 0))?;
+            state.input_offset = base;
+            Ok(end + container::ZLIB_TRAILER_LEN)
+        }
+        Format::Gzip => Ok(inflate_gzip(data, state)?.1),
+    }
+}
+
+/// Check that the options supply the preset dictionary a zlib header asks for, if any.
+fn check_dictionary(header: &ZlibHeader, options: &InflateOptions) -> Result<()> {
+    let Some(id) = header.dictionary_id else {
+        return Ok(());
+    };
+    let dictionary = options
+        .dictionary
+        .as_ref()
+        .ok_or(InflateError::DictionaryRequired)?;
+    if adler32(dictionary) != id {
+        return Err(InflateError::DictionaryMismatch);
+    }
+    Ok(())
+}
+
+/// A member of a gzip file decoded into the output of a state: its header, the range of the
+/// output it decoded to, and the CRC-32 of its trailer.
+type DecodedMember = (GzipHeader, Range<usize>, u32);
+
+/// Inflate the gzip members at the start of `data` into the state's output, checking the
+/// trailer of each. A gzip file can hold several members one after the other, e.g. made by
+/// `cat a.gz b.gz`: another member follows as long as the data after one starts with the gzip
+/// magic. Each member is a stream of its own, so its back-references cannot reach the data
+/// of the members before it. Returns the members and the number of bytes they take.
+/// Defined in RFC 1952, section 2.2.
+fn inflate_gzip<O: OutputSink>(
+    data: &[u8],
+    state: &mut InflateState<O>,
+) -> Result<(Vec<DecodedMember>, usize)> {
+    let base = state.input_offset;
+    let mut members = Vec::new();
+    let mut end = 0;
+    loop {
+        let member = &data[end..];
+        state.input_offset = base + end;
+        let (header, start) = container::parse_gzip_header(member)
+            .map_err(state.fail(DecodeStage::ContainerHeader, 0))?;
+        let output_start = state.decompressed().len();
+        if end > 0 {
+            state.start_history();
+        }
+        state.input_offset = base + end + start;
+        let member_end = start + inflate_raw(&member[start..], state, TailHandling::Ignore)?;
+        state.input_offset = base + end + member_end;
+        let trailer = member.get(member_end..).unwrap_or_default();
+        let output = output_start..state.decompressed().len();
+        container::check_gzip_trailer(trailer, &state.decompressed()[output.clone()])
+            .map_err(state.fail(DecodeStage::ContainerTrailer, 0))?;
+        let crc = u32::from_le_bytes(trailer[..4].try_into().unwrap());
+        members.push((header, output, crc));
+        end += member_end + container::GZIP_TRAILER_LEN;
+        if !data[end..].starts_with(&container::GZIP_ID) {
+            state.input_offset = base;
+            return Ok((members, end));
+        }
+    }
+}
+
+/// A member of a gzip file, with its own header, data and checksum.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GzipMember {
+    /// The member header, with the file name and modification time of its data.
+    pub header: GzipHeader,
+    /// The decompressed data of the member.
+    pub data: Vec<u8>,
+    /// The CRC-32 of the data, from the member trailer.
+    pub crc32: u32,
+}
+
+/// Inflate a gzip file member by member, keeping the header and checksum of each instead of
+/// only concatenating their data as [`inflate_to_vec_with_options`] does.
+/// The format of the options is ignored; their limits apply to the data of all members.
+pub fn inflate_gzip_members(data: &[u8], options: &InflateOptions) -> Result<Vec<GzipMember>> {
+    if data.is_empty() {
+        return Err(InflateError::EmptyInput);
+    }
+    let mut state = InflateState::from_options(options);
+    let (members, end) = inflate_gzip(data, &mut state).map_err(|e| state.surface(e))?;
+    if options.tail == TailHandling::Reject && end < data.len() {
+        return Err(InflateError::TrailingData);
+    }
+    let output = state.decompressed();
+    Ok(members
+        .into_iter()
+        .map(|(header, range, crc32)| GzipMember {
+            header,
+            data: output[range].to_vec(),
+            crc32,
+        })
+        .collect())
+}
+
+/// Inflate a raw DEFLATE stream that was compressed with a preset dictionary.
+/// Back-references may reach into the last `MAX_DISTANCE` bytes of the dictionary.
+pub fn inflate_with_dictionary(data: &[u8], dictionary: &[u8]) -> Result<Vec<u8>> {
+    inflate_to_vec_with_options(data, &InflateOptions::new().dictionary(dictionary))
+}
+
+/// Inflate data that may be raw DEFLATE, zlib or gzip, detecting the container from its first bytes.
+/// Returns the decompressed data and the detected format.
+pub fn decompress_auto(data: &[u8]) -> Result<(Vec<u8>, Format)> {
+    with_detected_format(data, |options| inflate_to_vec_with_options(data, options))
+}
+
+/// Inflate data that may be raw DEFLATE, zlib or gzip into the given slice, detecting the
+/// container as [`decompress_auto`] does and decoding in place as [`inflate_into_slice`] does.
+/// Returns the number of bytes written to the slice and the detected format.
+pub fn decompress_auto_into_slice(data: &[u8], out: &mut [u8]) -> Result<(usize, Format)> {
+    with_detected_format(data, |options| {
+        inflate_into_slice_with_options(data, options, out)
+    })
+}
+
+/// Decode `data` with the options for the container detected from its first bytes.
+/// Returns the result along with the format it was decoded as.
+fn with_detected_format<T>(
+    data: &[u8],
+    mut decode: impl FnMut(&InflateOptions) -> Result<T>,
+) -> Result<(T, Format)> {
+    let format = container::detect_format(data);
+    match decode(&InflateOptions::new().format(format)) {
+        Ok(output) => Ok((output, format)),
+        // A raw stream can start with bytes that happen to form a valid zlib header.
+        Err(e) if format == Format::Zlib => match decode(&InflateOptions::new()) {
+            Ok(output) => Ok((output, Format::Raw)),
+            Err(_) => Err(e),
+        },
+        Err(e) => Err(e),
+    }
+}
+
+/// The HLIT, HDIST and HCLEN fields of a dynamic block header, as numbers of codes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DynamicHeader {
+    pub hlit: usize,
+    pub hdist: usize,
+    pub hclen: usize,
+}
+
+/// Where a block of a stream is and what it holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BlockInfo {
+    /// The BTYPE field: 0 for stored, 1 for fixed Huffman, 2 for dynamic Huffman.
+    pub block_type: usize,
+    /// The BFINAL bit.
+    pub is_final: bool,
+    /// Bit offset of the block header.
+    pub header_bit_offset: usize,
+    /// Bit offset of the block body: the stored data, or the first Huffman-coded symbol.
+    pub body_bit_offset: usize,
+    /// Number of bits of the whole block, header included.
+    pub bit_len: usize,
+    /// Output offset of the first byte of the block.
+    pub output_offset: usize,
+    /// Number of bytes the block decompresses to.
+    pub output_len: usize,
+    /// The header fields of a dynamic block.
+    pub dynamic: Option<DynamicHeader>,
+}
+
+/// Check if a field is part of the body of a block rather than of its header.
+fn is_block_body(kind: &FieldKind) -> bool {
+    matches!(
+        kind,
+        FieldKind::Literal(_)
+            | FieldKind::LengthCode { .. }
+            | FieldKind::LengthExtra { .. }
+            | FieldKind::DistanceCode { .. }
+            | FieldKind::DistanceExtra { .. }
+            | FieldKind::EndOfBlock
+            | FieldKind::StoredData { .. }
+    )
+}
+
+/// Decode a raw DEFLATE stream block by block, calling `f` with the description of each block
+/// and the fields it is made of.
+pub(crate) fn for_each_block(data: &[u8], mut f: impl FnMut(BlockInfo, &[Field])) -> Result<()> {
+    if data.is_empty() {
+        return Err(InflateError::EmptyInput);
+    }
+    let mut bit_reader = BitReader::new_strict(data);
+    let mut state = InflateState::new();
+    loop {
+        let header_bit_offset = bit_reader.bits_consumed();
+        let output_offset = state.total_out();
+        state.trace = Some(Vec::new());
+        let is_final = inflate_block(&mut bit_reader, &mut state)?;
+        let fields = state.trace.take().unwrap_or_default();
+        // Only the window is needed for the blocks after this one.
+        state.discard_output(0);
+
+        let mut info = BlockInfo {
+            block_type: 0,
+            is_final,
+            header_bit_offset,
+            body_bit_offset: header_bit_offset,
+            bit_len: bit_reader.bits_consumed() - header_bit_offset,
+            output_offset,
+            output_len: state.total_out() - output_offset,
+            dynamic: None,
+        };
+        let mut dynamic = DynamicHeader {
+            hlit: 0,
+            hdist: 0,
+            hclen: 0,
+        };
+        for field in fields.iter().filter(|field| !is_block_body(&field.kind)) {
+            match field.kind {
+                FieldKind::BlockType(block_type) => info.block_type = block_type,
+                FieldKind::Hlit(n) => dynamic.hlit = n,
+                FieldKind::Hdist(n) => dynamic.hdist = n,
+                FieldKind::Hclen(n) => dynamic.hclen = n,
+                _ => {}
+            }
+            info.body_bit_offset = field.bit_offset + field.bit_len;
+        }
+        if info.block_type == BTYPE_DYNAMIC_HUFFMAN {
+            info.dynamic = Some(dynamic);
+        }
+        f(info, &fields);
+        if is_final {
+            return Ok(());
+        }
+    }
+}
+
+/// List the blocks of a raw DEFLATE stream.
+/// The whole stream is decoded, so the same errors as [`inflate_to_vec`] are reported.
+pub fn inspect(data: &[u8]) -> Result<Vec<BlockInfo>> {
+    let mut blocks = Vec::new();
+    for_each_block(data, |info, _| blocks.push(info))?;
+    Ok(blocks)
+}
+
+/// Figures about how a block, or a whole stream, is coded.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CodingStats {
+    /// Number of compressed bits.
+    pub bit_len: usize,
+    /// Number of bits of block headers: block types, stored lengths and Huffman code lengths.
+    pub header_bits: usize,
+    /// Number of bits of block bodies: Huffman-coded symbols, their extra bits and stored data.
+    pub payload_bits: usize,
+    /// Number of bytes decompressed.
+    pub output_len: usize,
+    pub literals: usize,
+    pub matches: usize,
+    /// Number of literal/length symbols, end-of-block symbols included.
+    pub literal_length_symbols: usize,
+    /// Number of bits of literal/length codes, without extra bits.
+    pub literal_length_code_bits: usize,
+    /// Number of bits of distance codes, without extra bits.
+    pub distance_code_bits: usize,
+    /// Number of matches of each length.
+    pub match_lengths: BTreeMap<usize, usize>,
+    /// Number of matches at each distance.
+    pub match_distances: BTreeMap<usize, usize>,
+}
+
+impl CodingStats {
+    /// Count the fields of a block.
+    fn add_block(&mut self, info: &BlockInfo, fields: &[Field]) {
+        self.bit_len += info.bit_len;
+        self.output_len += info.output_len;
+        let mut length = 0;
+        for field in fields {
+            if is_block_body(&field.kind) {
+                self.payload_bits += field.bit_len;
+            } else {
+                self.header_bits += field.bit_len;
+            }
+            match field.kind {
+                FieldKind::Literal(_) => {
+                    self.literals += 1;
+                    self.literal_length_symbols += 1;
+                    self.literal_length_code_bits += field.bit_len;
+                }
+                FieldKind::EndOfBlock => {
+                    self.literal_length_symbols += 1;
+                    self.literal_length_code_bits += field.bit_len;
+                }
+                FieldKind::LengthCode { length: n, .. } => {
+                    length = n;
+                    self.literal_length_symbols += 1;
+                    self.literal_length_code_bits += field.bit_len;
+                }
+                FieldKind::DistanceCode { distance, .. } => {
+                    self.matches += 1;
+                    self.distance_code_bits += field.bit_len;
+                    *self.match_lengths.entry(length).or_default() += 1;
+                    *self.match_distances.entry(distance).or_default() += 1;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Add the figures of another block.
+    fn merge(&mut self, other: &CodingStats) {
+        self.bit_len += other.bit_len;
+        self.header_bits += other.header_bits;
+        self.payload_bits += other.payload_bits;
+        self.output_len += other.output_len;
+        self.literals += other.literals;
+        self.matches += other.matches;
+        self.literal_length_symbols += other.literal_length_symbols;
+        self.literal_length_code_bits += other.literal_length_code_bits;
+        self.distance_code_bits += other.distance_code_bits;
+        for (&length, &n) in &other.match_lengths {
+            *self.match_lengths.entry(length).or_default() += n;
+        }
+        for (&distance, &n) in &other.match_distances {
+            *self.match_distances.entry(distance).or_default() += n;
+        }
+    }
+
+    /// Get the average length of the literal/length codes, 0 without any.
+    pub fn average_literal_length_code_len(&self) -> f64 {
+        ratio(self.literal_length_code_bits, self.literal_length_symbols)
+    }
+
+    /// Get the average length of the distance codes, 0 without any.
+    pub fn average_distance_code_len(&self) -> f64 {
+        ratio(self.distance_code_bits, self.matches)
+    }
+
+    /// Get the number of compressed bits per decompressed byte, 0 without any output.
+    pub fn bits_per_byte(&self) -> f64 {
+        ratio(self.bit_len, self.output_len)
+    }
+
+    /// Get the number of bytes copied by matches.
+    pub fn matched_bytes(&self) -> usize {
+        self.match_lengths
+            .iter()
+            .map(|(length, n)| length * n)
+            .sum()
+    }
+}
+
+/// Divide two counts, giving 0 for an empty denominator.
+fn ratio(numerator: usize, denominator: usize) -> f64 {
+    if denominator == 0 {
+        0.0
+    } else {
+        numerator as f64 / denominator as f64
+    }
+}
+
+/// The statistics of a block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BlockAnalysis {
+    pub info: BlockInfo,
+    pub stats: CodingStats,
+}
+
+/// Statistics about how a stream is compressed, block by block and as a whole.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct StreamStats {
+    pub blocks: Vec<BlockAnalysis>,
+    /// The figures of all blocks added up. Padding after the final block is not counted.
+    pub total: CodingStats,
+}
+
+/// The columns of [`StreamStats::block_table`].
+const BLOCK_TABLE_COLUMNS: [&str; 9] = [
+    "block",
+    "type",
+    "final",
+    "compressed bits",
+    "output bytes",
+    "ratio",
+    "literals",
+    "matches",
+    "matched bytes",
+];
+
+impl StreamStats {
+    /// Write the statistics as a text report.
+    /// With stable output, the report is the same on every platform and release.
+    pub fn report(&self, options: &TextOptions) -> String {
+        let mut report = format!("blocks: {}\n", self.blocks.len());
+        write_coding_stats(&mut report, &self.total, options);
+        report.push_str("match lengths:\n");
+        for (length, n) in &self.total.match_lengths {
+            report.push_str(&format!("  {}: {}\n", length, n));
+        }
+        report.push_str("match distances:\n");
+        for (distance, n) in &self.total.match_distances {
+            report.push_str(&format!("  {}: {}\n", distance, n));
+        }
+        for (index, block) in self.blocks.iter().enumerate() {
+            report.push_str(&format!(
+                "block {}: type {}, final {}, header at bit {}\n",
+                index, block.info.block_type, block.info.is_final, block.info.header_bit_offset
+            ));
+            write_coding_stats(&mut report, &block.stats, options);
+        }
+        report
+    }
+
+    /// Write a table of the blocks, one row each and a last row of totals: type, compressed
+    /// bits, output bytes, ratio, and the split between literals and matches. The ratio is the
+    /// number of output bytes per compressed byte.
+    pub fn block_table(&self, format: TableFormat, options: &TextOptions) -> String {
+        let row = |block: String, block_type: &str, is_final: &str, stats: &CodingStats| {
+            let compression = ratio(stats.output_len * 8, stats.bit_len);
+            Vec::from([
+                block,
+                block_type.to_string(),
+                is_final.to_string(),
+                stats.bit_len.to_string(),
+                stats.output_len.to_string(),
+                options.float(compression),
+                stats.literals.to_string(),
+                stats.matches.to_string(),
+                stats.matched_bytes().to_string(),
+            ])
+        };
+        let mut rows: Vec<_> = self
+            .blocks
+            .iter()
+            .enumerate()
+            .map(|(index, block)| {
+                let block_type = block_type_name(block.info.block_type);
+                let is_final = if block.info.is_final { "yes" } else { "no" };
+                row(index.to_string(), block_type, is_final, &block.stats)
+            })
+            .collect();
+        rows.push(row("total".to_string(), "", "", &self.total));
+        format.table(&BLOCK_TABLE_COLUMNS, &rows)
+    }
+}
+
+/// Write the figures shared by blocks and whole streams, one per line.
+fn write_coding_stats(report: &mut String, stats: &CodingStats, options: &TextOptions) {
+    let lines = [
+        format!(
+            "compressed bits: {} (header {}, payload {})",
+            stats.bit_len, stats.header_bits, stats.payload_bits
+        ),
+        format!("decompressed bytes: {}", stats.output_len),
+        format!("bits per byte: {}", options.float(stats.bits_per_byte())),
+        format!("literals: {}", stats.literals),
+        format!("matches: {}", stats.matches),
+        format!(
+            "average literal/length code length: {}",
+            options.float(stats.average_literal_length_code_len())
+        ),
+        format!(
+            "average distance code length: {}",
+            options.float(stats.average_distance_code_len())
+        ),
+    ];
+    for line in lines {
+        report.push_str("  ");
+        report.push_str(&line);
+        report.push('\n');
+    }
+}
+
+/// Gather statistics about how a raw DEFLATE stream is compressed.
+/// The whole stream is decoded, so the same errors as [`inflate_to_vec`] are reported.
+pub fn analyze(data: &[u8]) -> Result<StreamStats> {
+    let mut stream_stats = StreamStats::default();
+    for_each_block(data, |info, fields| {
+        let mut stats = CodingStats::default();
+        stats.add_block(&info, fields);
+        stream_stats.total.merge(&stats);
+        stream_stats.blocks.push(BlockAnalysis { info, stats });
+    })?;
+    Ok(stream_stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::crc32;
+    use crate::deflate::{self, deflate_dynamic_to_vec, deflate_stored_to_vec, deflate_to_vec};
+    use crate::huffman::TableEntry;
+
+    #[test]
+    fn test_huffman_lookup_table() {
+        let code_lengths = vec![3, 3, 3, 3, 3, 2, 4, 4];
+        let max_bits = 4;
+        let huffman_table = HuffmanLookupTable::new(&code_lengths, max_bits);
+
+        huffman_table.table.iter().for_each(|entry| {
+            let (symbol, len) = entry.unpack();
+            assert_eq!(len, code_lengths[symbol]);
+        });
+    }
+    #[test]
+    fn test_fixed_literal_table() {
+        let huffman_table = fixed_literal_table();
+        assert_eq!(huffman_table.max_bits, 9);
+        assert_eq!(huffman_table.table[0b0_01111111], TableEntry::new(252, 9));
+        assert_eq!(huffman_table.table[0b1_10000000], TableEntry::new(256, 7));
+        assert_eq!(huffman_table.table[0b1_00000011], TableEntry::new(280, 8));
+        assert_eq!(huffman_table.table[0b0_00001100], TableEntry::new(0, 8));
+        assert_eq!(huffman_table.table[0b1_11111111], TableEntry::new(255, 9));
+
+        // The table built at compile time is the one built at run time.
+        let code_len = fixed_literal_code_lengths();
+        assert_eq!(code_len.iter().filter(|&&len| len == 9).count(), 112);
+        assert_eq!(
+            huffman_table.table,
+            HuffmanLookupTable::new(&code_len, 9).table
+        );
+    }
+
+    /// A fixed Huffman block with the literal 'a' followed by a match of length 3 using `dist_code`.
+    fn fixed_block_with_match(dist_code: u8) -> Vec<u8> {
+        // BFINAL = 1, BTYPE = 01, then the codes, all packed LSB first.
+        // 'a' = 0b10010001 (8 bits), length 3 = 0b0000001 (7 bits),
+        // distance code is 5 bits, end of block = 0b0000000 (7 bits).
+        let mut bits: Vec<u8> = vec![1, 1, 0];
+        bits.extend([1, 0, 0, 1, 0, 0, 0, 1]);
+        bits.extend([0, 0, 0, 0, 0, 0, 1]);
+        bits.extend((0..5).rev().map(|i| (dist_code >> i) & 1));
+        bits.extend([0; 7]);
+        bits.chunks(8)
+            .map(|c| c.iter().enumerate().fold(0, |acc, (i, &b)| acc | (b << i)))
+            .collect()
+    }
+
+    #[test]
+    fn test_match_validation_before_start() {
+        // Distance 2 reaches one byte before the start of the output.
+        let data = fixed_block_with_match(1);
+        assert_eq!(inflate_to_vec(&data), Err(InflateError::DistanceTooFar));
+        let (output, warnings) =
+            inflate_with_validation(&data, MatchValidation::Permissive, MAX_DISTANCE).unwrap();
+        assert_eq!(output, b"a\0a\0");
+        assert_eq!(
+            warnings,
+            vec![MatchWarning {
+                bit_offset: 23,
+                output_offset: 1,
+                distance: 2,
+                kind: MatchWarningKind::BeforeStart,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_repeat_with_overlap() {
+        let history: Vec<u8> = (0..300u32).map(|i| (i * 7 % 256) as u8).collect();
+        for dist in [1, 2, 3, 7, 64, 100, 258, 300] {
+            for len in [0, 1, 2, 3, 5, 64, 100, 257, 258] {
+                let mut expected = history.clone();
+                for _ in 0..len {
+                    expected.push(expected[expected.len() - dist]);
+                }
+                let mut output = history.clone();
+                assert_eq!(repeat_with_overlap(&mut output, dist, len), len);
+                assert_eq!(output, expected, "distance {}, length {}", dist, len);
+            }
+        }
+    }
+
+    #[test]
+    fn test_copy_match_from_window() {
+        let mut state = InflateState::new();
+        state.output.extend_from_slice(b"abcdefgh");
+        state.discard_output(3);
+        assert_eq!(state.window.len(), 5);
+        // Starts in the window, carries on into the output and then over itself.
+        assert_eq!(state.copy_match(6, 8, 0), Ok(8));
+        assert_eq!(state.output, b"fghcdefghcd");
+        assert_eq!(state.total_out(), 16);
+        state.discard_output(0);
+        assert_eq!(
+            state.copy_match(17, 3, 0),
+            Err(InflateError::DistanceTooFar)
+        );
+        assert_eq!(state.copy_match(16, 3, 0), Ok(3));
+        assert_eq!(state.output, b"abc");
+
+        // Zeros before the start, then the window.
+        let mut state = InflateState::with_validation(MatchValidation::Permissive, MAX_DISTANCE);
+        state.output.extend_from_slice(b"ab");
+        state.discard_output(0);
+        assert_eq!(state.copy_match(4, 4, 0), Ok(4));
+        assert_eq!(state.output, b"\0\0ab");
+    }
+
+    #[test]
+    fn test_options_builder() {
+        let options = InflateOptions::new()
+            .format(Format::Zlib)
+            .max_output(1000)
+            .window_bits(10)
+            .strict(false)
+            .tail(TailHandling::Reject)
+            .dictionary(&b"dict"[..])
+            .max_blocks(5)
+            .min_block_output(64)
+            .size_hint(4096)
+            .growth(OutputGrowth::Chunked(512))
+            .decoder(DecodeStrategy::Tree);
+        assert_eq!(
+            options,
+            InflateOptions {
+                format: Format::Zlib,
+                validation: MatchValidation::Permissive,
+                window_size: 1024,
+                tail: TailHandling::Reject,
+                max_output_size: Some(1000),
+                dictionary: Some(b"dict".to_vec()),
+                max_blocks: Some(5),
+                min_block_output: Some(64),
+                size_hint: Some(4096),
+                growth: OutputGrowth::Chunked(512),
+                decoder: DecodeStrategy::Tree,
+                deflate64: false,
+            }
+        );
+        assert_eq!(InflateOptions::new(), InflateOptions::default());
+        assert_eq!(
+            InflateOptions::new().window_bits(20).window_size,
+            MAX_DISTANCE
+        );
+        assert_eq!(
+            InflateOptions::new().window_size(1 << 20).window_size,
+            MAX_DISTANCE
+        );
+        assert_eq!(
+            InflateOptions::new().strict(false).strict(true).validation,
+            MatchValidation::Strict
+        );
+    }
+
+    #[test]
+    fn test_inflate_with_report() {
+        let raw = InflateOptions::default();
+        let report = |data: &[u8], options| inflate_with_report(data, options).unwrap_err();
+
+        // The match after the literal 'a' reaches before the start of the output.
+        let data = fixed_block_with_match(1);
+        assert_eq!(
+            report(&data, &raw),
+            CorruptionReport {
+                error: InflateError::DistanceTooFar,
+                bit_offset: 11,
+                block_index: Some(0),
+                output_offset: 1,
+                stage: DecodeStage::CopyingMatch,
+            }
+        );
+        let data = fixed_block_with_match(30);
+        let error = report(&data, &raw);
+        assert_eq!(error.stage, DecodeStage::DistanceSymbol);
+        assert_eq!(error.bit_offset, 18);
+
+        // A stored block, then a block with the reserved type.
+        let data = [0x00, 0x01, 0x00, 0xfe, 0xff, b'x', 0x07];
+        let error = report(&data, &raw);
+        assert_eq!(error.error, InflateError::InvalidBlockType);
+        assert_eq!(
+            (error.bit_offset, error.block_index, error.stage),
+            (48, Some(1), DecodeStage::BlockHeader)
+        );
+
+        // Offsets count the container header and earlier gzip members.
+        let text = b"hello hello hello hello";
+        let member = container::wrap(&deflate_to_vec(text), text, Format::Gzip);
+        let mut data = member.repeat(2);
+        let last = data.len() - 1;
+        data[last] ^= 1;
+        let gzip = InflateOptions {
+            format: Format::Gzip,
+            ..Default::default()
+        };
+        let error = report(&data, &gzip);
+        assert!(matches!(error.error, InflateError::InvalidHeader(_)));
+        assert_eq!(
+            (error.bit_offset, error.block_index, error.stage),
+            (8 * (data.len() - 8), None, DecodeStage::ContainerTrailer)
+        );
+        assert_eq!(error.output_offset, 2 * text.len());
+
+        let compressed = deflate_to_vec(text);
+        let zlib = container::wrap(&compressed, text, Format::Zlib);
+        let options = InflateOptions {
+            format: Format::Zlib,
+            ..Default::default()
+        };
+        let error = report(&zlib[..zlib.len() - 6], &options);
+        assert_eq!(error.error, InflateError::UnexpectedEof);
+        assert!(error.bit_offset > 8 * (zlib.len() - 10));
+        assert!(inflate_with_report(&zlib, &options).is_ok());
+        assert_eq!(report(&[], &raw).stage, DecodeStage::BlockHeader);
+    }
+
+    #[test]
+    fn test_every_error_is_located() {
+        // Every truncation and bit flip of a dynamic stream that fails is reported from the
+        // place it was found, never from the fallback.
+        let text = b"Located errors are easier to debug. Located errors are easier to fix.";
+        let data = deflate_dynamic_to_vec(text);
+        let mut inputs: Vec<Vec<u8>> = (0..data.len()).map(|n| data[..n].to_vec()).collect();
+        for bit in 0..data.len() * 8 {
+            let mut flipped = data.clone();
+            flipped[bit / 8] ^= 1 << (bit % 8);
+            inputs.push(flipped);
+        }
+        let options = InflateOptions::default();
+        for input in inputs {
+            let Err(report) = inflate_with_report(&input, &options) else {
+                continue;
+            };
+            assert_ne!(report.stage, DecodeStage::AfterStream, "{:?}", input);
+            assert!(report.bit_offset <= input.len() * 8, "{:?}", input);
+            assert_eq!(inflate_to_vec(&input), Err(report.error));
+        }
+    }
+
+    #[test]
+    fn test_match_validation_outside_window() {
+        let raw = b"abcdefgh".repeat(64);
+        let data = miniz_oxide::deflate::compress_to_vec(&raw, 6);
+        assert!(inflate_with_validation(&data, MatchValidation::Strict, 4).is_err());
+        let (output, warnings) =
+            inflate_with_validation(&data, MatchValidation::Permissive, 4).unwrap();
+        assert_eq!(output, raw);
+        assert!(warnings
+            .iter()
+            .all(|w| w.kind == MatchWarningKind::OutsideWindow && w.distance > 4));
+        assert!(!warnings.is_empty());
+        let (_, warnings) = inflate_with_validation(&data, MatchValidation::Strict, 8).unwrap();
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_inflate_with_options_zlib() {
+        let raw = b"hello hello hello zlib".repeat(10);
+        let mut data = miniz_oxide::deflate::compress_to_vec_zlib(&raw, 6);
+        let options = InflateOptions {
+            format: Format::Zlib,
+            ..Default::default()
+        };
+        assert_eq!(inflate_to_vec_with_options(&data, &options).unwrap(), raw);
+
+        data.push(0);
+        assert_eq!(inflate_to_vec_with_options(&data, &options).unwrap(), raw);
+        let reject = InflateOptions {
+            tail: TailHandling::Reject,
+            ..options.clone()
+        };
+        assert!(inflate_to_vec_with_options(&data, &reject).is_err());
+
+        data.pop();
+        *data.last_mut().unwrap() ^= 1;
+        assert!(inflate_to_vec_with_options(&data, &options).is_err());
+    }
+
+    #[test]
+    fn test_inflate_zlib_declared_window() {
+        // 300 bytes repeated: the second half is a match at distance 300.
+        let raw: Vec<u8> = (0..300u32)
+            .map(|i| (i * 7 % 251) as u8)
+            .collect::<Vec<_>>()
+            .repeat(2);
+        let deflate = deflate_to_vec(&raw);
+        let zlib_with_window = |window_bits: u8| {
+            let cmf = ((window_bits - 8) << 4) | 8;
+            let flg = (31 - (cmf as u16 * 256) % 31) % 31;
+            let mut data = vec![cmf, flg as u8];
+            data.extend(&deflate);
+            data.extend(adler32(&raw).to_be_bytes());
+            data
+        };
+        let options = InflateOptions::new().format(Format::Zlib);
+        assert_eq!(
+            inflate_to_vec_with_options(&zlib_with_window(9), &options),
+            Ok(raw.clone())
+        );
+        assert_eq!(
+            inflate_to_vec_with_options(&zlib_with_window(8), &options),
+            Err(InflateError::DistanceOutsideWindow)
+        );
+        // The options can only narrow the window further.
+        let narrow = options.clone().window_bits(8);
+        assert_eq!(
+            inflate_to_vec_with_options(&zlib_with_window(15), &narrow),
+            Err(InflateError::DistanceOutsideWindow)
+        );
+        let permissive = options.strict(false);
+        assert_eq!(
+            inflate_to_vec_with_options(&zlib_with_window(8), &permissive),
+            Ok(raw.clone())
+        );
+        // Raw streams only go by the options.
+        assert_eq!(
+            inflate_to_vec_with_options(&deflate, &InflateOptions::new().window_bits(8)),
+            Err(InflateError::DistanceOutsideWindow)
+        );
+        assert_eq!(inflate_to_vec(&deflate), Ok(raw));
+    }
+
+    #[test]
+    fn test_inflate_errors() {
+        let raw = b"truncated or corrupted".repeat(8);
+        let data = miniz_oxide::deflate::compress_to_vec(&raw, 6);
+        let err = inflate_to_vec(&data[..data.len() / 2]).unwrap_err();
+        assert_eq!(err, InflateError::UnexpectedEof);
+        assert!(err.is_truncation());
+        // BFINAL = 1, BTYPE = 11
+        assert_eq!(
+            inflate_to_vec(&[0b111]),
+            Err(InflateError::InvalidBlockType)
+        );
+        // Stored block with LEN = 1 and NLEN = 1
+        assert_eq!(
+            inflate_to_vec(&[0b001, 1, 0, 1, 0]),
+            Err(InflateError::InvalidLenNlen)
+        );
+    }
+
+    #[test]
+    fn test_inflate_invalid_code_lengths() {
+        // A final dynamic block header giving the code length codes 16, 17, 18 and 0 the lengths
+        // of `code_len_code_len`.
+        let header = |code_len_code_len: [usize; 4]| {
+            let mut writer = crate::bit_stream::BitWriter::new();
+            writer.write_bits(1, BFINAL_LEN);
+            writer.write_bits(BTYPE_DYNAMIC_HUFFMAN, BTYPE_LEN);
+            writer.write_bits(0, HLIT_LEN + HDIST_LEN + HCLEN_LEN);
+            for len in code_len_code_len {
+                writer.write_bits(len, DYN_ALPHABET_CODE_LEN);
+            }
+            writer.write_bits(0, 16);
+            writer.into_vec()
+        };
+        assert_eq!(
+            inflate_to_vec(&header([1, 1, 1, 1])),
+            Err(InflateError::OversubscribedTable)
+        );
+        assert_eq!(
+            inflate_to_vec(&header([1, 2, 0, 0])),
+            Err(InflateError::IncompleteTable)
+        );
+    }
+
+    #[test]
+    fn test_inflate_invalid_literal_length_symbol() {
+        // Symbols 286 and 287 have codes in the fixed code but stand for nothing.
+        let lit_codes = crate::huffman::HuffmanEncodeTable::new(&fixed_literal_code_lengths());
+        for symbol in [286, 287] {
+            let mut writer = crate::bit_stream::BitWriter::new();
+            writer.write_bits(1, BFINAL_LEN);
+            writer.write_bits(BTYPE_FIXED_HUFFMAN, BTYPE_LEN);
+            lit_codes.write_symbol(&mut writer, b'a' as usize);
+            lit_codes.write_symbol(&mut writer, symbol);
+            assert_eq!(
+                inflate_to_vec(&writer.into_vec()),
+                Err(InflateError::InvalidHuffmanSymbol)
+            );
+        }
+    }
+
+    #[test]
+    fn test_inflate_empty_input() {
+        assert_eq!(inflate_to_vec(&[]), Err(InflateError::EmptyInput));
+        assert_eq!(decompress_auto(&[]), Err(InflateError::EmptyInput));
+        for format in [Format::Raw, Format::Zlib, Format::Gzip] {
+            let options = InflateOptions {
+                format,
+                ..Default::default()
+            };
+            assert_eq!(
+                inflate_to_vec_with_options(&[], &options),
+                Err(InflateError::EmptyInput)
+            );
+        }
+        assert!(InflateError::EmptyInput.is_truncation());
+
+        // An empty final stored block: BFINAL = 1, BTYPE = 00, LEN = 0, NLEN = 0xffff.
+        assert_eq!(inflate_to_vec(&[0x01, 0x00, 0x00, 0xff, 0xff]), Ok(vec![]));
+        // An empty final fixed block: BFINAL = 1, BTYPE = 01, end of block 0000000.
+        assert_eq!(inflate_to_vec(&[0x03, 0x00]), Ok(vec![]));
+        assert_eq!(
+            inflate_to_vec(&miniz_oxide::deflate::compress_to_vec(b"", 6)),
+            Ok(vec![])
+        );
+        // A stored block header cut before its LEN field.
+        assert_eq!(inflate_to_vec(&[0x01]), Err(InflateError::UnexpectedEof));
+    }
+
+    #[test]
+    fn test_inflate_truncated_never_zero_padded() {
+        // The end of block code of an empty fixed block, cut after its first five bits.
+        assert_eq!(inflate_to_vec(&[0x03]), Err(InflateError::UnexpectedEof));
+        // A stored block missing the last of its three bytes.
+        assert_eq!(
+            inflate_to_vec(&[0x01, 0x03, 0x00, 0xfc, 0xff, b'a', b'b']),
+            Err(InflateError::UnexpectedEof)
+        );
+        // Every cut of a stream is reported as a truncation, never decoded with zeros.
+        let raw = b"truncated streams must not decode".repeat(10);
+        for level in [1, 6, 9] {
+            let data = miniz_oxide::deflate::compress_to_vec(&raw, level);
+            for len in 1..data.len() {
+                let result = inflate_to_vec(&data[..len]);
+                assert!(
+                    result.is_err_and(|e| e.is_truncation()),
+                    "level {} cut at {}",
+                    level,
+                    len
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_inflate_with_options_max_output() {
+        let raw = vec![0u8; 100_000];
+        let data = miniz_oxide::deflate::compress_to_vec(&raw, 6);
+        let mut options = InflateOptions {
+            max_output_size: Some(raw.len() - 1),
+            ..Default::default()
+        };
+        assert_eq!(
+            inflate_to_vec_with_options(&data, &options),
+            Err(InflateError::OutputLimitExceeded)
+        );
+        options.max_output_size = Some(raw.len());
+        assert_eq!(inflate_to_vec_with_options(&data, &options).unwrap(), raw);
+
+        let data = miniz_oxide::deflate::compress_to_vec(&raw[..1000], 0);
+        options.max_output_size = Some(999);
+        assert_eq!(
+            inflate_to_vec_with_options(&data, &options),
+            Err(InflateError::OutputLimitExceeded)
+        );
+    }
+
+    #[test]
+    fn test_inflate_deflate64() {
+        let (data, expected) = crate::testgen::deflate64_stream();
+        let options = InflateOptions::new().deflate64(true);
+        assert_eq!(options.window_size, DEFLATE64_MAX_DISTANCE);
+        assert_eq!(
+            inflate_to_vec_with_options(&data, &options).unwrap(),
+            expected
+        );
+        assert_ne!(
+            inflate_to_vec_with_options(&data, &InflateOptions::new()),
+            Ok(expected.clone())
+        );
+        // The window can still be narrowed after choosing Deflate64.
+        assert_eq!(
+            inflate_to_vec_with_options(&data, &options.clone().window_size(MAX_DISTANCE)),
+            Err(InflateError::DistanceOutsideWindow)
+        );
+
+        // Streaming keeps a window of 64 KiB once the output is taken.
+        #[cfg(feature = "std")]
+        {
+            let mut inflater = crate::stream::Inflater::with_options(&options).unwrap();
+            let mut output = Vec::new();
+            for chunk in data.chunks(1000) {
+                inflater.feed(chunk).unwrap();
+                output.extend(inflater.take_output());
+            }
+            inflater.finish().unwrap();
+            output.extend(inflater.take_output());
+            assert_eq!(output, expected);
+        }
+    }
+
+    #[test]
+    fn test_inflate_decode_strategies() {
+        let raw = b"one line\nanother line\n".repeat(3000);
+        let mut vectors = crate::testgen::test_vectors();
+        vectors.push(crate::testgen::TestVector {
+            name: "miniz".into(),
+            deflate: miniz_oxide::deflate::compress_to_vec(&raw, 6),
+            expected: Ok(raw),
+        });
+        for decoder in [
+            DecodeStrategy::Flat,
+            DecodeStrategy::TwoLevel,
+            DecodeStrategy::Tree,
+        ] {
+            let options = InflateOptions::new().decoder(decoder);
+            for vector in &vectors {
+                assert_eq!(
+                    inflate_to_vec_with_options(&vector.deflate, &options),
+                    vector.expected,
+                    "{} with {:?}",
+                    vector.name,
+                    decoder
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_inflate_output_growth() {
+        let raw = b"capacity hints, capacity hints".repeat(1000);
+        let data = miniz_oxide::deflate::compress_to_vec(&raw, 6);
+        let decode = |options: &InflateOptions| {
+            let mut bit_reader = BitReader::new_strict(&data);
+            let mut state = InflateState::from_options(options);
+            while !inflate_block(&mut bit_reader, &mut state).unwrap() {}
+            assert_eq!(state.output, raw);
+            state.output.capacity()
+        };
+
+        let exact = InflateOptions::new().growth(OutputGrowth::Exact);
+        assert_eq!(decode(&exact.clone().size_hint(raw.len())), raw.len());
+        // A hint too small is only a start.
+        assert!(decode(&exact.clone().size_hint(10)) >= raw.len());
+        let chunked = InflateOptions::new().growth(OutputGrowth::Chunked(4096));
+        let capacity = decode(&chunked);
+        assert!(capacity.is_multiple_of(4096) && capacity - raw.len() < 4096);
+        // Neither the hint nor the chunks go past the output limit.
+        let limited = chunked.max_output(raw.len()).size_hint(usize::MAX);
+        assert_eq!(decode(&limited), raw.len());
+        assert!(decode(&InflateOptions::new()) >= raw.len());
+        // Nor does an untrusted hint reserve more than the ceiling.
+        let forged = InflateOptions::new().size_hint(usize::MAX);
+        assert_eq!(
+            InflateState::from_options(&forged).output.capacity(),
+            MAX_SIZE_HINT
+        );
+        assert_eq!(decode(&forged), MAX_SIZE_HINT);
+    }
+
+    #[test]
+    fn test_inflate_prefix() {
+        let raw = b"%PDF-1.7 then a long body of text, text and more text".repeat(500);
+        let data = miniz_oxide::deflate::compress_to_vec_zlib(&raw, 6);
+        let options = InflateOptions::new().format(Format::Zlib);
+        let prefix = inflate_prefix(&data, &options, 8).unwrap();
+        assert_eq!(prefix.output, b"%PDF-1.7");
+        assert!(!prefix.complete);
+        assert!(prefix.bit_offset > 16 && prefix.bit_offset < data.len() * 8 / 2);
+        // Only the bytes up to the offset reached are needed.
+        let head = &data[..prefix.bit_offset.div_ceil(8)];
+        assert_eq!(inflate_prefix(head, &options, 8).unwrap(), prefix);
+
+        let whole = inflate_prefix(&data, &options, usize::MAX).unwrap();
+        assert_eq!(whole.output, raw);
+        assert!(whole.complete);
+        assert_eq!(inflate_prefix(&data, &options, 0).unwrap().output, b"");
+
+        // A stored block is only copied as far as asked for.
+        let stored = deflate_stored_to_vec(&raw);
+        let prefix = inflate_prefix(&stored, &InflateOptions::new(), 100).unwrap();
+        assert_eq!(prefix.output, &raw[..100]);
+        assert_eq!(prefix.bit_offset, (5 + 100) * 8);
+        assert_eq!(
+            inflate_prefix(&stored[..50], &InflateOptions::new(), 100),
+            Err(InflateError::UnexpectedEof)
+        );
+    }
+
+    #[test]
+    fn test_inflate_into_vec_reuse() {
+        let mut out = Vec::with_capacity(4096);
+        for message in [&b"first message"[..], b"second", b""] {
+            let data = miniz_oxide::deflate::compress_to_vec(message, 6);
+            let ptr = out.as_ptr();
+            assert_eq!(inflate_into_vec(&data, &mut out).unwrap(), message.len());
+            assert_eq!(out, message);
+            assert_eq!(out.as_ptr(), ptr);
+            assert!(out.capacity() >= 4096);
+        }
+    }
+
+    #[test]
+    fn test_inflate_with_dictionary() {
+        // 'a' then a match of length 3 at distance 2, one byte before the start of the output.
+        let data = fixed_block_with_match(1);
+        assert_eq!(inflate_with_dictionary(&data, b"xyz").unwrap(), b"azaz");
+        assert_eq!(
+            inflate_with_dictionary(&data, b"").unwrap_err(),
+            InflateError::DistanceTooFar
+        );
+    }
+
+    #[test]
+    fn test_inflate_zlib_with_dictionary() {
+        let dictionary = b"hello world";
+        // Wrap the raw stream in a zlib header declaring the dictionary and an Adler-32 trailer.
+        let mut data = vec![0x78, 0xbb];
+        data.extend(adler32(dictionary).to_be_bytes());
+        data.extend(fixed_block_with_match(1));
+        let output = inflate_with_dictionary(&fixed_block_with_match(1), dictionary).unwrap();
+        data.extend(adler32(&output).to_be_bytes());
+
+        let mut options = InflateOptions {
+            format: Format::Zlib,
+            ..Default::default()
+        };
+        assert_eq!(
+            inflate_to_vec_with_options(&data, &options),
+            Err(InflateError::DictionaryRequired)
+        );
+        options.dictionary = Some(b"other".to_vec());
+        assert_eq!(
+            inflate_to_vec_with_options(&data, &options),
+            Err(InflateError::DictionaryMismatch)
+        );
+        options.dictionary = Some(dictionary.to_vec());
+        assert_eq!(
+            inflate_to_vec_with_options(&data, &options).unwrap(),
+            b"adad"
+        );
+    }
+
+    #[test]
+    fn test_block_limits() {
+        // Many tiny non-final stored blocks, then the data itself.
+        let mut data = [0b000, 0, 0, 0xff, 0xff].repeat(100);
+        data.extend([0b001, 2, 0, !2, !0, b'o', b'k']);
+        let (output, stats) = inflate_with_block_stats(&data, &InflateOptions::default());
+        assert_eq!(output.unwrap(), b"ok");
+        assert_eq!(stats.blocks, 101);
+        assert!(!stats.limit_exceeded);
+
+        let options = InflateOptions {
+            max_blocks: Some(50),
+            ..Default::default()
+        };
+        let (output, stats) = inflate_with_block_stats(&data, &options);
+        assert_eq!(output, Err(InflateError::BlockLimitExceeded));
+        assert_eq!(stats.blocks, 51);
+        assert!(stats.limit_exceeded);
+
+        let options = InflateOptions {
+            min_block_output: Some(1),
+            ..Default::default()
+        };
+        let (output, stats) = inflate_with_block_stats(&data, &options);
+        assert_eq!(output, Err(InflateError::BlockLimitExceeded));
+        assert_eq!(stats.blocks, BLOCK_RATIO_GRACE + 2);
+        // A minimum too large to multiply by the number of blocks rejects them all the same.
+        let options = InflateOptions {
+            min_block_output: Some(usize::MAX / 2),
+            ..Default::default()
+        };
+        let (output, stats) = inflate_with_block_stats(&data, &options);
+        assert_eq!(output, Err(InflateError::BlockLimitExceeded));
+        assert_eq!(stats.blocks, BLOCK_RATIO_GRACE + 2);
+    }
+
+    #[test]
+    fn test_inflate_into_slice() {
+        let raw = b"slice slice slice slice".to_vec();
+        let data = miniz_oxide::deflate::compress_to_vec(&raw, 6);
+        let mut out = [0; 64];
+        assert_eq!(inflate_into_slice(&data, &mut out), Ok(raw.len()));
+        assert_eq!(&out[..raw.len()], &raw[..]);
+        let mut exact = vec![0; raw.len()];
+        assert_eq!(inflate_into_slice(&data, &mut exact), Ok(raw.len()));
+        let mut short = vec![0; raw.len() - 1];
+        assert_eq!(
+            inflate_into_slice(&data, &mut short),
+            Err(InflateError::OutputTooSmall)
+        );
+    }
+
+    #[test]
+    fn test_inflate_into_slice_with_options() {
+        let raw = b"in place in place in place".to_vec();
+        let data = miniz_oxide::deflate::compress_to_vec_zlib(&raw, 6);
+        let options = InflateOptions::new().format(Format::Zlib);
+        let mut out = [0; 64];
+        assert_eq!(
+            inflate_into_slice_with_options(&data, &options, &mut out),
+            Ok(raw.len())
+        );
+        assert_eq!(&out[..raw.len()], &raw[..]);
+        // The output limit stays an error of its own.
+        let limited = options.max_output(raw.len() - 1);
+        assert_eq!(
+            inflate_into_slice_with_options(&data, &limited, &mut out),
+            Err(InflateError::OutputLimitExceeded)
+        );
+
+        // The dictionary is moved out of the way once decoded, but needs room meanwhile.
+        let data = fixed_block_with_match(1);
+        let options = InflateOptions::new().dictionary(b"xyz");
+        let mut out = [0; 7];
+        assert_eq!(
+            inflate_into_slice_with_options(&data, &options, &mut out),
+            Ok(4)
+        );
+        assert_eq!(&out[..4], b"azaz");
+        assert_eq!(
+            inflate_into_slice_with_options(&data, &options, &mut [0; 6]),
+            Err(InflateError::OutputTooSmall)
+        );
+    }
+
+    #[test]
+    fn test_decompress_auto() {
+        let raw = b"auto detect auto detect".repeat(5);
+        let data = miniz_oxide::deflate::compress_to_vec_zlib(&raw, 6);
+        assert_eq!(decompress_auto(&data).unwrap(), (raw.clone(), Format::Zlib));
+        let data = miniz_oxide::deflate::compress_to_vec(&raw, 6);
+        assert_eq!(decompress_auto(&data).unwrap(), (raw.clone(), Format::Raw));
+        // A raw stream starting with a stored block whose first bytes form a valid zlib header.
+        let data = [
+            0x78, 0x01, 0x00, 0xfe, 0xff, b'x', 0x01, 0x00, 0x00, 0xff, 0xff,
+        ];
+        assert_eq!(
+            decompress_auto(&data).unwrap(),
+            (b"x".to_vec(), Format::Raw)
+        );
+        let mut out = [0; 4];
+        assert_eq!(
+            decompress_auto_into_slice(&data, &mut out),
+            Ok((1, Format::Raw))
+        );
+        assert_eq!(out[0], b'x');
+    }
+
+    #[test]
+    fn test_inflate_gzip_multi_member() {
+        let gzip = |raw: &[u8]| container::wrap(&deflate_to_vec(raw), raw, Format::Gzip);
+        let mut data = gzip(b"first member, ");
+        data.extend(gzip(b""));
+        data.extend(gzip(b"second member"));
+        let options = InflateOptions {
+            format: Format::Gzip,
+            tail: TailHandling::Reject,
+            ..Default::default()
+        };
+        assert_eq!(
+            inflate_to_vec_with_options(&data, &options).unwrap(),
+            b"first member, second member"
+        );
+
+        let members = inflate_gzip_members(&data, &options).unwrap();
+        assert_eq!(members.len(), 3);
+        assert_eq!(members[0].data, b"first member, ");
+        assert_eq!(members[1].data, b"");
+        assert_eq!(members[2].data, b"second member");
+        assert_eq!(members[2].crc32, crc32(b"second member"));
+
+        // Trailing zeros are not a member: ignored by default, rejected on demand.
+        data.extend([0; 4]);
+        assert_eq!(
+            inflate_to_vec_with_options(&data, &options),
+            Err(InflateError::TrailingData)
+        );
+        let options = InflateOptions {
+            format: Format::Gzip,
+            ..Default::default()
+        };
+        assert_eq!(inflate_gzip_members(&data, &options).unwrap(), members);
+
+        // The trailer of each member is checked against that member's data only.
+        let mut corrupt = gzip(b"ok");
+        let mut second = gzip(b"corrupt");
+        let n = second.len();
+        second[n - 8] ^= 1;
+        corrupt.extend(second);
+        assert!(matches!(
+            inflate_to_vec_with_options(&corrupt, &options),
+            Err(InflateError::ChecksumMismatch { .. })
+        ));
+
+        // A member cannot refer back to the data of the member before it.
+        let mut data = gzip(b"abc");
+        let tokens = [deflate::Token::Match {
+            length: 3,
+            distance: 3,
+        }];
+        data.extend(container::wrap(
+            &deflate::deflate_fixed_tokens_to_vec(&tokens),
+            b"abc",
+            Format::Gzip,
+        ));
+        assert_eq!(
+            inflate_to_vec_with_options(&data, &options),
+            Err(InflateError::DistanceTooFar)
+        );
+        assert_eq!(
+            inflate_gzip_members(&data, &options),
+            Err(InflateError::DistanceTooFar)
+        );
+    }
+
+    #[test]
+    fn test_inflate_bytes_consumed() {
+        let raw = b"where does it end? where does it end?";
+        for format in [Format::Raw, Format::Zlib, Format::Gzip] {
+            let stream = container::wrap(&deflate_to_vec(raw), raw, format);
+            let mut data = stream.clone();
+            data.extend(b"next entry");
+            let options = InflateOptions {
+                format,
+                ..Default::default()
+            };
+            let result = inflate(&data, &options).unwrap();
+            assert_eq!(result.output, raw);
+            assert_eq!(result.bytes_consumed, stream.len(), "{:?}", format);
+            assert_eq!(result.trailing_bytes, b"next entry".len());
+            assert_eq!(inflate_with_report(&data, &options), Ok(result));
+            assert_eq!(inflate(&stream, &options).unwrap().trailing_bytes, 0);
+            let options = options.tail(TailHandling::Reject);
+            assert_eq!(inflate(&data, &options), Err(InflateError::TrailingData));
+        }
+    }
+
+    #[test]
+    fn test_inflate_concatenated() {
+        for format in [Format::Raw, Format::Zlib] {
+            let mut data = Vec::new();
+            for raw in [&b"one, "[..], b"two, ", b"three"] {
+                data.extend(container::wrap(&deflate_to_vec(raw), raw, format));
+            }
+            let options = InflateOptions {
+                format,
+                tail: TailHandling::Concatenated,
+                ..Default::default()
+            };
+            let result = inflate(&data, &options).unwrap();
+            assert_eq!(result.output, b"one, two, three");
+            assert_eq!(result.bytes_consumed, data.len());
+            assert_eq!(result.trailing_bytes, 0);
+            // Only the first stream without the option.
+            let options = InflateOptions {
+                format,
+                ..Default::default()
+            };
+            assert_eq!(
+                inflate_to_vec_with_options(&data, &options).unwrap(),
+                b"one, "
+            );
+            // Anything after the last stream has to be a stream too.
+            data.push(0xff);
+            let options = InflateOptions {
+                format,
+                tail: TailHandling::Concatenated,
+                ..Default::default()
+            };
+            assert!(inflate(&data, &options).is_err());
+
+            // A stream cannot refer back to the output of the stream before it.
+            let tokens = [deflate::Token::Match {
+                length: 3,
+                distance: 3,
+            }];
+            let mut data = container::wrap(&deflate_to_vec(b"abc"), b"abc", format);
+            data.extend(container::wrap(
+                &deflate::deflate_fixed_tokens_to_vec(&tokens),
+                b"abc",
+                format,
+            ));
+            assert_eq!(inflate(&data, &options), Err(InflateError::DistanceTooFar));
+        }
+    }
+
+    #[test]
+    fn test_inflate_with_options_sync_flush() {
+        // Make the single block non-final and end it with a sync flush,
+        // then strip the marker again as permessage-deflate does.
+        let raw = b"abcabc";
+        let mut data = miniz_oxide::deflate::compress_to_vec(raw, 6);
+        data[0] &= !1;
+        data.extend(SYNC_FLUSH_MARKER);
+        let stripped = &data[..data.len() - SYNC_FLUSH_MARKER.len()];
+        let options = InflateOptions {
+            tail: TailHandling::SyncFlush,
+            ..Default::default()
+        };
+        assert_eq!(
+            inflate_to_vec_with_options(stripped, &options).unwrap(),
+            raw
+        );
+    }
+
+    #[test]
+    fn test_inflate_chunks() {
+        let text = b"chunked input, chunked input, chunked input".repeat(20);
+        // A stored stream ends byte-aligned, so a second stream can follow its non-final block.
+        let mut data = deflate_stored_to_vec(b"stored");
+        data[0] &= !1;
+        data.extend(deflate_dynamic_to_vec(&text));
+        let chunks: Vec<&[u8]> = data.chunks(3).collect();
+        let mut expected = b"stored".to_vec();
+        expected.extend(&text);
+        assert_eq!(inflate_chunks_to_vec(&chunks), Ok(expected));
+        assert_eq!(
+            inflate_chunks_to_vec(&chunks[..chunks.len() - 1]),
+            Err(InflateError::UnexpectedEof)
+        );
+        assert_eq!(inflate_chunks_to_vec(&[&[]]), Err(InflateError::EmptyInput));
+    }
+
+    #[test]
+    fn test_verify_fixed_tables() {
+        assert_eq!(verify_fixed_tables(), Ok(()));
+
+        // 'a' = 0b10010001, whose first bit is the least significant of the index.
+        let mut table = fixed_literal_table();
+        table.table[0b1_10001001] = TableEntry::new(b'b' as usize, 8);
+        assert_eq!(
+            verify_table(&table, Alphabet::LiteralLength, fixed_literal_codes()),
+            Err(FixedTableMismatch {
+                alphabet: Alphabet::LiteralLength,
+                symbol: b'a' as usize,
+                index: 0b1_10001001,
+                found: Some((b'b' as usize, 8)),
+            })
+        );
+    }
+
+    #[test]
+    fn test_fixed_distance_table() {
+        let huffman_table = fixed_distance_table();
+        assert_eq!(huffman_table.max_bits, 5);
+        assert_eq!(huffman_table.table[0b00000], TableEntry::new(0, 5));
+        assert_eq!(huffman_table.table[0b11100], TableEntry::new(7, 5));
+        assert_eq!(huffman_table.table[0b11111], TableEntry::new(31, 5));
+    }
+
+    #[test]
+    fn test_inspect() {
+        // A stored block holding "abc", then a final fixed block.
+        let mut data = vec![0b000, 3, 0, !3, !0, b'a', b'b', b'c'];
+        data.extend(crate::deflate::deflate_fixed_tokens_to_vec(&[
+            crate::deflate::Token::Literal(b'd'),
+        ]));
+        let blocks = inspect(&data).unwrap();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].block_type, BTYPE_NO_COMPRESSION);
+        assert!(!blocks[0].is_final);
+        assert_eq!(blocks[0].body_bit_offset, 40);
+        assert_eq!(blocks[0].bit_len, 64);
+        assert_eq!(blocks[0].output_len, 3);
+        assert_eq!(blocks[1].block_type, BTYPE_FIXED_HUFFMAN);
+        assert!(blocks[1].is_final);
+        assert_eq!(blocks[1].header_bit_offset, 64);
+        assert_eq!(blocks[1].body_bit_offset, 67);
+        assert_eq!(blocks[1].output_offset, 3);
+        assert_eq!(blocks[1].output_len, 1);
+        assert_eq!(blocks[1].dynamic, None);
+
+        // Large compressible data takes several dynamic blocks, laid out back to back.
+        let mut x = 1u32;
+        let raw: Vec<u8> = (0..200_000)
+            .map(|_| {
+                x = x.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                b'a' + (x >> 16) as u8 % 16
+            })
+            .collect();
+        let data = miniz_oxide::deflate::compress_to_vec(&raw, 9);
+        let blocks = inspect(&data).unwrap();
+        assert!(blocks.len() > 1);
+        for pair in blocks.windows(2) {
+            let end = pair[0].header_bit_offset + pair[0].bit_len;
+            assert_eq!(pair[1].header_bit_offset, end);
+            assert_eq!(
+                pair[1].output_offset,
+                pair[0].output_offset + pair[0].output_len
+            );
+        }
+        let last = blocks.last().unwrap();
+        assert!(last.is_final);
+        assert_eq!(last.output_offset + last.output_len, raw.len());
+        let dynamic = blocks[0].dynamic.unwrap();
+        assert_eq!(blocks[0].block_type, BTYPE_DYNAMIC_HUFFMAN);
+        assert!(dynamic.hlit >= 257 && dynamic.hdist >= 1 && dynamic.hclen >= 4);
+        assert!(blocks[0].body_bit_offset > blocks[0].header_bit_offset + 17);
+
+        assert_eq!(inspect(&[0b111]), Err(InflateError::InvalidBlockType));
+    }
+
+    #[test]
+    fn test_analyze() {
+        // A stored block holding "abc", then a final fixed block with "d" and a match.
+        let mut data = vec![0b000, 3, 0, !3, !0, b'a', b'b', b'c'];
+        data.extend(crate::deflate::deflate_fixed_tokens_to_vec(&[
+            crate::deflate::Token::Literal(b'd'),
+            crate::deflate::Token::Match {
+                length: 4,
+                distance: 2,
+            },
+        ]));
+        let stats = analyze(&data).unwrap();
+        assert_eq!(stats.blocks.len(), 2);
+        let stored = &stats.blocks[0].stats;
+        assert_eq!((stored.header_bits, stored.payload_bits), (40, 24));
+        assert_eq!(stored.literals, 0);
+        let fixed = &stats.blocks[1].stats;
+        // "d" takes 8 bits, length 4 takes 7 bits, distance 2 takes 5 bits, end of block 7 bits.
+        assert_eq!((fixed.header_bits, fixed.payload_bits), (3, 27));
+        assert_eq!((fixed.literals, fixed.matches), (1, 1));
+        assert_eq!(fixed.literal_length_symbols, 3);
+        assert_eq!(fixed.average_distance_code_len(), 5.0);
+        assert_eq!(stats.total.output_len, 8);
+        assert_eq!(stats.total.bit_len, 94);
+        assert_eq!(stats.total.match_lengths, BTreeMap::from([(4, 1)]));
+        assert_eq!(stats.total.match_distances, BTreeMap::from([(2, 1)]));
+
+        let report = stats.report(&TextOptions::stable());
+        assert!(report.starts_with("blocks: 2\n  compressed bits: 94 (header 43, payload 51)\n"));
+        assert!(report.contains("  average literal/length code length: 7.333333\n"));
+        assert!(report.contains("match lengths:\n  4: 1\nmatch distances:\n  2: 1\n"));
+        assert!(stats
+            .report(&TextOptions::default())
+            .contains("  average literal/length code length: 7.33\n"));
+
+        assert_eq!(analyze(&[]), Err(InflateError::EmptyInput));
+    }
+
+    #[test]
+    fn test_block_table() {
+        let mut data = deflate_stored_to_vec(b"abc");
+        data[0] = 0b000;
+        data.extend(crate::deflate::deflate_fixed_tokens_to_vec(&[
+            crate::deflate::Token::Literal(b'd'),
+            crate::deflate::Token::Match {
+                length: 4,
+                distance: 2,
+            },
+        ]));
+        let stats = analyze(&data).unwrap();
+        assert_eq!(stats.total.matched_bytes(), 4);
+        let options = TextOptions::stable();
+        assert_eq!(
+            stats.block_table(TableFormat::Markdown, &options),
+            "| block | type | final | compressed bits | output bytes | ratio | literals | matches \
+             | matched bytes |\n\
+             | --- | --- | --- | --- | --- | --- | --- | --- | --- |\n\
+             | 0 | stored | no | 64 | 3 | 0.375000 | 0 | 0 | 0 |\n\
+             | 1 | fixed Huffman | yes | 30 | 5 | 1.333333 | 1 | 1 | 4 |\n\
+             | total |  |  | 94 | 8 | 0.680851 | 1 | 1 | 4 |\n"
+        );
+        let csv = stats.block_table(TableFormat::Csv, &options);
+        assert!(csv.starts_with("block,type,final,compressed bits,"));
+        assert!(csv.ends_with("\r\ntotal,,,94,8,0.680851,1,1,4\r\n"));
+    }
+
+    /// A subscriber keeping the message of every event.
+    #[cfg(all(feature = "tracing", feature = "std"))]
+    #[derive(Default)]
+    struct MessageCollector(std::sync::Mutex<Vec<String>>);
+
+    #[cfg(all(feature = "tracing", feature = "std"))]
+    impl tracing::Subscriber for MessageCollector {
+        fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+        fn new_span(&self, _: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            tracing::span::Id::from_u64(1)
+        }
+        fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record<'_>) {}
+        fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+        fn event(&self, event: &tracing::Event<'_>) {
+            struct Message<'a>(&'a mut String);
+            impl tracing::field::Visit for Message<'_> {
+                fn record_debug(
+                    &mut self,
+                    field: &tracing::field::Field,
+                    value: &dyn core::fmt::Debug,
+                ) {
+                    if field.name() == "message" {
+                        *self.0 = format!("{value:?}");
+                    }
+                }
+            }
+            let mut message = String::new();
+            event.record(&mut Message(&mut message));
+            self.0.lock().unwrap().push(message);
+        }
+        fn enter(&self, _: &tracing::span::Id) {}
+        fn exit(&self, _: &tracing::span::Id) {}
+    }
+
+    #[cfg(all(feature = "tracing", feature = "std"))]
+    #[test]
+    fn test_tracing_events() {
+        let collector = std::sync::Arc::new(MessageCollector::default());
+        let mut data = deflate_dynamic_to_vec(b"traced, traced, traced");
+        let _ = tracing::subscriber::with_default(collector.clone(), || {
+            inflate_to_vec(&data).unwrap();
+            data.truncate(data.len() - 1);
+            inflate_to_vec(&data)
+        });
+        let messages = collector.0.lock().unwrap();
+        assert_eq!(
+            *messages,
+            [
+                "block header",
+                "dynamic tables built",
+                "block header",
+                "dynamic tables built",
+                "decoding failed"
+            ]
+        );
+
+        // Running out of input while more may arrive is not a failure.
+        let collector = std::sync::Arc::new(MessageCollector::default());
+        let data = deflate_dynamic_to_vec(&b"chunked, chunked".repeat(20));
+        tracing::subscriber::with_default(collector.clone(), || {
+            let mut inflater = crate::stream::Inflater::new();
+            for chunk in data.chunks(3) {
+                inflater.feed(chunk).unwrap();
+            }
+            inflater.finish().unwrap();
+        });
+        let messages = collector.0.lock().unwrap();
+        assert!(!messages.iter().any(|message| message == "decoding failed"));
     }
 }
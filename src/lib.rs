@@ -3,8 +3,93 @@
 //! It is primarily intended for educational purposes, facilitating a deeper understanding of the DEFLATE compression algorithm as outlined in RFC 1951.
 //! By implementing this library, the goal is to gain practical experience with the intricacies of compression and decompression processes, as well as to enhance Rust programming skills.
 //! It is not designed for production use but serves as a hands-on learning tool to explore the fundamentals of data compression.
+//!
+//! Without the default `std` feature, the crate is `no_std` and only needs `alloc`: the decoder
+//! runs on bare metal, while the modules built on `std::io` and the file system are left out.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
 pub mod bit_stream;
 
 pub mod huffman;
 
 pub mod inflate;
+
+pub mod codes;
+
+pub mod window;
+
+#[cfg(feature = "std")]
+pub mod stream;
+
+pub mod checksum;
+
+pub mod container;
+
+pub mod preset;
+
+pub mod error;
+
+pub mod validate;
+
+#[cfg(feature = "std")]
+pub mod spill;
+
+#[cfg(feature = "std")]
+pub mod fuzz_dict;
+
+pub mod fuzz_support;
+
+pub mod deflate;
+
+#[cfg(feature = "std")]
+pub mod zip;
+
+pub mod png;
+
+pub mod index;
+
+pub mod disasm;
+
+pub mod explain;
+
+pub mod lz77;
+
+#[cfg(all(feature = "std", feature = "serde"))]
+pub mod trace;
+
+pub mod stepper;
+
+pub mod byte_iter;
+
+#[cfg(all(feature = "std", feature = "serde"))]
+pub mod event_log;
+
+pub mod text;
+
+pub mod observer;
+
+pub mod tokens;
+
+pub mod heatmap;
+
+#[cfg(feature = "std")]
+pub mod entropy;
+
+pub mod transcode;
+
+pub mod hexdump;
+
+pub mod testgen;
+
+pub mod roundtrip;
+
+#[cfg(feature = "compare")]
+pub mod compare;
+
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+#[cfg(feature = "ffi")]
+pub mod ffi;
@@ -0,0 +1,251 @@
+//! Find repeated strings in data, turning it into the LZ77 tokens a compressed block encodes.
+//!
+//! This is the mirror image of the decoder's back-references: where the decoder copies `length`
+//! bytes from `distance` bytes back, the match finder looks back for the longest string equal to
+//! the upcoming bytes. Candidate positions are found through hash chains: the positions of every
+//! 3-byte prefix seen so far are linked from the most recent to the oldest.
+
+use crate::deflate::{Token, MAX_MATCH_LEN, MIN_MATCH_LEN};
+use crate::inflate::MAX_DISTANCE;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Number of bits of the hash of a 3-byte prefix.
+const HASH_BITS: u32 = 15;
+/// Marks the end of a hash chain.
+const NO_POS: usize = usize::MAX;
+
+/// How the match finder picks between the matches it finds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MatchStrategy {
+    /// Take the longest match at the current position right away.
+    Greedy,
+    /// Before taking a match, check whether the next position has a longer one,
+    /// in which case the current byte is emitted as a literal.
+    #[default]
+    Lazy,
+}
+
+/// Settings of the match finder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MatchFinderOptions {
+    /// How far back matches may reach, at most `MAX_DISTANCE`.
+    pub window_size: usize,
+    /// How many candidate positions are compared at most for each match.
+    pub max_chain_len: usize,
+    pub strategy: MatchStrategy,
+}
+
+impl Default for MatchFinderOptions {
+    fn default() -> Self {
+        Self {
+            window_size: MAX_DISTANCE,
+            max_chain_len: 128,
+            strategy: MatchStrategy::default(),
+        }
+    }
+}
+
+/// Hash the 3 bytes starting at `pos`.
+fn hash(data: &[u8], pos: usize) -> usize {
+    let prefix = u32::from_le_bytes([data[pos], data[pos + 1], data[pos + 2], 0]);
+    (prefix.wrapping_mul(2_654_435_761) >> (u32::BITS - HASH_BITS)) as usize
+}
+
+/// Hash chains over the positions of the data.
+struct MatchFinder<'a> {
+    data: &'a [u8],
+    options: MatchFinderOptions,
+    /// Most recent position of each hash.
+    head: Vec<usize>,
+    /// Previous position with the same hash, for each position.
+    prev: Vec<usize>,
+}
+
+impl<'a> MatchFinder<'a> {
+    fn new(data: &'a [u8], options: MatchFinderOptions) -> Self {
+        Self {
+            data,
+            options,
+            head: vec![NO_POS; 1 << HASH_BITS],
+            prev: vec![NO_POS; data.len()],
+        }
+    }
+
+    /// Link `pos` at the head of its hash chain.
+    fn insert(&mut self, pos: usize) {
+        if pos + MIN_MATCH_LEN > self.data.len() {
+            return;
+        }
+        let h = hash(self.data, pos);
+        self.prev[pos] = self.head[h];
+        self.head[h] = pos;
+    }
+
+    /// Find the longest match for the bytes at `pos` among the positions inserted so far.
+    /// Returns `(length, distance)`, with a zero length if there is no match.
+    fn longest_match(&self, pos: usize) -> (usize, usize) {
+        let max_len = MAX_MATCH_LEN.min(self.data.len() - pos);
+        if max_len < MIN_MATCH_LEN {
+            return (0, 0);
+        }
+        let (mut best_len, mut best_dist) = (0, 0);
+        let mut candidate = self.head[hash(self.data, pos)];
+        let mut chain_len = 0;
+        while candidate != NO_POS && chain_len < self.options.max_chain_len {
+            let distance = pos - candidate;
+            if distance > self.options.window_size {
+                break;
+            }
+            let len = self.data[candidate..]
+                .iter()
+                .zip(&self.data[pos..pos + max_len])
+                .take_while(|(a, b)| a == b)
+                .count();
+            if len > best_len {
+                (best_len, best_dist) = (len, distance);
+                if len == max_len {
+                    break;
+                }
+            }
+            candidate = self.prev[candidate];
+            chain_len += 1;
+        }
+        if best_len < MIN_MATCH_LEN {
+            return (0, 0);
+        }
+        (best_len, best_dist)
+    }
+}
+
+/// Turn data into a stream of literals and matches.
+///
+/// # Panics
+///
+/// Panics if the window size is 0 or larger than `MAX_DISTANCE`.
+pub fn find_matches(data: &[u8], options: &MatchFinderOptions) -> Vec<Token> {
+    assert!((1..=MAX_DISTANCE).contains(&options.window_size));
+    let mut finder = MatchFinder::new(data, *options);
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        let (length, distance) = finder.longest_match(pos);
+        finder.insert(pos);
+        if length == 0 {
+            tokens.push(Token::Literal(data[pos]));
+            pos += 1;
+            continue;
+        }
+        if options.strategy == MatchStrategy::Lazy && length < MAX_MATCH_LEN {
+            let (next_length, _) = finder.longest_match(pos + 1);
+            if next_length > length {
+                tokens.push(Token::Literal(data[pos]));
+                pos += 1;
+                continue;
+            }
+        }
+        tokens.push(Token::Match { length, distance });
+        (pos + 1..pos + length).for_each(|p| finder.insert(p));
+        pos += length;
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Expand tokens back into data, the way the decoder does.
+    fn expand(tokens: &[Token]) -> Vec<u8> {
+        let mut output = Vec::new();
+        for &token in tokens {
+            match token {
+                Token::Literal(byte) => output.push(byte),
+                Token::Match { length, distance } => {
+                    for _ in 0..length {
+                        output.push(output[output.len() - distance]);
+                    }
+                }
+            }
+        }
+        output
+    }
+
+    #[test]
+    fn test_find_matches() {
+        let tokens = find_matches(b"abcabcabcabcx", &MatchFinderOptions::default());
+        assert_eq!(
+            tokens,
+            [
+                Token::Literal(b'a'),
+                Token::Literal(b'b'),
+                Token::Literal(b'c'),
+                Token::Match {
+                    length: 9,
+                    distance: 3
+                },
+                Token::Literal(b'x'),
+            ]
+        );
+        assert_eq!(find_matches(b"", &MatchFinderOptions::default()), []);
+    }
+
+    #[test]
+    fn test_lazy_matching() {
+        // At the second 'b', greedy takes "bcd" while lazy finds "cdefg" one byte later.
+        let data = b"bcd-cdefg-bcdefg";
+        let greedy = MatchFinderOptions {
+            strategy: MatchStrategy::Greedy,
+            ..Default::default()
+        };
+        let greedy_tokens = find_matches(data, &greedy);
+        let lazy_tokens = find_matches(data, &MatchFinderOptions::default());
+        assert_eq!(expand(&greedy_tokens), data);
+        assert_eq!(expand(&lazy_tokens), data);
+        assert!(greedy_tokens.contains(&Token::Match {
+            length: 3,
+            distance: 10
+        }));
+        assert_eq!(
+            lazy_tokens[10..],
+            [
+                Token::Literal(b'b'),
+                Token::Match {
+                    length: 5,
+                    distance: 7
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_window_and_chain_limits() {
+        let mut data = b"0123456789".to_vec();
+        data.extend((0..100u8).map(|i| i.wrapping_mul(97) | 0x80));
+        data.extend(b"0123456789");
+        let small_window = MatchFinderOptions {
+            window_size: 100,
+            ..Default::default()
+        };
+        let tokens = find_matches(&data, &small_window);
+        assert!(tokens.iter().all(|t| matches!(t, Token::Literal(_))));
+        let tokens = find_matches(&data, &MatchFinderOptions::default());
+        assert_eq!(expand(&tokens), data);
+        assert!(tokens.contains(&Token::Match {
+            length: 10,
+            distance: 110
+        }));
+
+        let text = b"the cat sat on the mat with the hat".repeat(100);
+        let no_chain = MatchFinderOptions {
+            max_chain_len: 0,
+            ..Default::default()
+        };
+        let tokens = find_matches(&text, &no_chain);
+        assert_eq!(tokens.len(), text.len());
+        assert_eq!(
+            expand(&find_matches(&text, &MatchFinderOptions::default())),
+            text
+        );
+    }
+}
@@ -1,58 +1,904 @@
-use inflate_toy::inflate::inflate_to_vec;
-
-const DATA_COMPRESSED: &[u8] = &[
-    0xed, 0x90, 0xcb, 0x51, 0x04, 0x31, 0x0c, 0x44, 0xef, 0x44, 0xd1, 0x01, 0x6c, 0x11, 0x05, 0xc7,
-    0xbd, 0x12, 0x80, 0xb0, 0xc5, 0xd0, 0x55, 0xb6, 0x3c, 0x6b, 0x4b, 0x5b, 0x84, 0x8f, 0x66, 0xa6,
-    0x08, 0x81, 0x1b, 0x3e, 0xc9, 0x87, 0x7e, 0xfd, 0xb9, 0x8f, 0xa9, 0x1d, 0xdc, 0x57, 0x74, 0xd4,
-    0xd1, 0xc6, 0xc4, 0xa2, 0x43, 0xba, 0xfa, 0x0d, 0x65, 0xd8, 0xd2, 0xe2, 0xea, 0x31, 0x21, 0x95,
-    0x3b, 0x57, 0xa1, 0x6d, 0xd0, 0x46, 0x7f, 0x41, 0xbe, 0xa5, 0x35, 0x35, 0x50, 0xc6, 0xea, 0xa3,
-    0xc2, 0xb5, 0xef, 0xa9, 0xa7, 0x15, 0x56, 0xd6, 0x30, 0x47, 0x38, 0x9a, 0x7c, 0xa4, 0x03, 0xd4,
-    0x2f, 0xba, 0xa2, 0xcb, 0x66, 0x02, 0x69, 0x7c, 0x84, 0x9c, 0x94, 0x77, 0x87, 0x1a, 0x7b, 0x3a,
-    0xa0, 0xf3, 0x38, 0x9e, 0xf9, 0x95, 0x7e, 0xc3, 0x23, 0xb8, 0x60, 0x63, 0xf9, 0x8c, 0x0a, 0xfd,
-    0xd6, 0x59, 0xe8, 0xe2, 0x1c, 0x86, 0x68, 0x4d, 0x7a, 0x19, 0x17, 0x9c, 0xeb, 0xc4, 0x18, 0x17,
-    0x0f, 0xc3, 0x93, 0xcc, 0x3d, 0x05, 0x50, 0xc9, 0x0a, 0x3d, 0xa3, 0x8d, 0xab, 0x4a, 0x3a, 0xfa,
-    0x2b, 0xde, 0x0e, 0xac, 0x84, 0x2b, 0x38, 0x23, 0x03, 0x5d, 0xad, 0x69, 0x27, 0x65, 0xea, 0x3e,
-    0xf5, 0x4b, 0xad, 0xea, 0xcc, 0x19, 0x68, 0x78, 0x8e, 0x16, 0x7b, 0xda, 0x6a, 0xc6, 0xca, 0xde,
-    0xd0, 0xb5, 0x14, 0x85, 0xad, 0xfd, 0xee, 0x95, 0xdd, 0x02, 0x9f, 0xb1, 0x51, 0x1c, 0x76, 0x04,
-    0x3b, 0x39, 0xf7, 0xff, 0x59, 0xff, 0x62, 0xd6, 0x1f,
+use inflate_toy::container::{detect_format, header_len, parse_gzip_header, wrap, Format};
+use inflate_toy::deflate::{deflate_to_vec_with_level, DEFAULT_LEVEL, MAX_LEVEL};
+use inflate_toy::disasm::{disassemble, Alphabet};
+use inflate_toy::entropy::{entropy_table, estimate};
+use inflate_toy::error::InflateError;
+use inflate_toy::explain::ExplainOptions;
+use inflate_toy::hexdump::{hexdump, HexdumpOptions, OffsetBase};
+use inflate_toy::huffman::{HuffmanLeaf, HuffmanLookupTable};
+use inflate_toy::inflate::{
+    analyze, decompress_auto, inflate_to_vec_with_options, inflate_with_report, inspect,
+    InflateOptions, TailHandling,
+};
+use inflate_toy::observer::{inflate_with_observer, DecodeObserver, Narrator, Verbosity};
+use inflate_toy::text::{TableFormat, TextOptions};
+use inflate_toy::trace::Trace;
+use serde::Serialize;
+use std::io::{Read, Write};
+use std::process::exit;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+const USAGE: &str = "Usage: inflate-toy COMMAND [ARGS...]
+Commands:
+  inflate    Decompress a raw DEFLATE, zlib or gzip file; the default command
+  gunzip     Decompress gzip files next to them, like gunzip (or -d)
+  deflate    Compress a file into raw DEFLATE, zlib or gzip
+  inspect    List the blocks of a compressed file
+  huffman    Print the Huffman codes of the blocks of a compressed file
+  bench      Time the decompression of a file
+  explain    Narrate the decoding of a compressed file, or say what a bit encodes
+  trace      Print the JSON trace of a compressed file
+  stats      Print compression statistics of a compressed file
+  entropy    Compare the Huffman codes of the blocks of a compressed file with optimal ones
+  annotate   Print every field of a compressed file
+Run inflate-toy COMMAND --help for the arguments of a command.";
+const INFLATE_USAGE: &str =
+    "Usage: inflate-toy [inflate] INPUT [-o OUTPUT] [--hex [HEX OPTIONS]] [-v | --trace] [--compare]
+Decompress a raw DEFLATE, zlib or gzip file. Use - as INPUT to read from stdin.
+Without -o, the decompressed bytes go to stdout; --hex writes a hex dump of them instead.
+--compare decompresses with miniz_oxide too and reports where the outputs first differ
+(requires the compare feature).
+Hex options: --width N (bytes per line, default 16), --group N (bytes per group),
+--uppercase, --offset-base hex|dec|oct, --color.
+-v narrates the decoding block by block on stderr, --trace symbol by symbol.";
+const GUNZIP_USAGE: &str = "Usage: inflate-toy gunzip [-k] [-f] FILE.gz...
+Decompress each file next to it without its .gz suffix, keeping its modification time,
+then remove it. -k keeps the compressed files, -f overwrites existing files.";
+const DEFLATE_USAGE: &str =
+    "Usage: inflate-toy deflate INPUT [-o OUTPUT] [--level N] [--zlib | --gzip]
+Compress a file, or stdin for -, at level 0 (stored) to 9 (default 6).
+Without -o, the compressed bytes go to stdout.";
+const INSPECT_USAGE: &str = "Usage: inflate-toy inspect FILE [--stable-output] [--json]";
+const HUFFMAN_USAGE: &str = "Usage: inflate-toy huffman FILE [--block N] [--dot | --json]
+Print the code of every symbol of the Huffman tables of each block, or of block N only.
+--dot prints the code trees as Graphviz graphs instead, --json the codes as JSON.";
+const BENCH_USAGE: &str = "Usage: inflate-toy bench FILE [--iterations N]
+Decompress a file N times (default 10) and print the fastest and average times.";
+const EXPLAIN_USAGE: &str = "Usage: inflate-toy explain FILE [--bit-offset N] [--stable-output]
+Say what the bit at offset N encodes, or without --bit-offset, narrate the decoding of the
+whole file in prose: every block header, literal and match.";
+const TRACE_USAGE: &str = "Usage: inflate-toy trace FILE [--stable-output]";
+const STATS_USAGE: &str = "Usage: inflate-toy stats FILE [--stable-output] [--json] [--report FILE]
+--report also writes a table of the blocks to FILE: type, compressed bits, output bytes, ratio,
+literals and matches, as Markdown for a .md file or CSV for a .csv file.";
+const ENTROPY_USAGE: &str = "Usage: inflate-toy entropy FILE [--stable-output] [--json]
+Print a Markdown table of the blocks: the bits spent on Huffman codes, what an optimal Huffman
+code would spend, the entropy of the symbols, and the bits wasted.";
+const ANNOTATE_USAGE: &str = "Usage: inflate-toy annotate FILE [--stable-output] [--json]";
+
+/// A subcommand of the binary.
+struct Command {
+    name: &'static str,
+    run: fn(&[String]),
+    usage: &'static str,
+}
+
+const COMMANDS: &[Command] = &[
+    Command {
+        name: "inflate",
+        run: decompress,
+        usage: INFLATE_USAGE,
+    },
+    Command {
+        name: "gunzip",
+        run: gunzip,
+        usage: GUNZIP_USAGE,
+    },
+    Command {
+        name: "deflate",
+        run: compress,
+        usage: DEFLATE_USAGE,
+    },
+    Command {
+        name: "inspect",
+        run: inspect_file,
+        usage: INSPECT_USAGE,
+    },
+    Command {
+        name: "huffman",
+        run: huffman,
+        usage: HUFFMAN_USAGE,
+    },
+    Command {
+        name: "bench",
+        run: bench,
+        usage: BENCH_USAGE,
+    },
+    Command {
+        name: "explain",
+        run: explain,
+        usage: EXPLAIN_USAGE,
+    },
+    Command {
+        name: "trace",
+        run: trace,
+        usage: TRACE_USAGE,
+    },
+    Command {
+        name: "stats",
+        run: stats,
+        usage: STATS_USAGE,
+    },
+    Command {
+        name: "entropy",
+        run: entropy,
+        usage: ENTROPY_USAGE,
+    },
+    Command {
+        name: "annotate",
+        run: annotate_file,
+        usage: ANNOTATE_USAGE,
+    },
 ];
 
 fn main() {
-    let data = inflate_to_vec(DATA_COMPRESSED).unwrap();
-    println!("{}", String::from_utf8_lossy(&data));
-    println!("Data: \n{}", display_data(&data));
+    let args: Vec<String> = std::env::args().collect();
+    let name = match args.get(1).map(String::as_str) {
+        None => {
+            eprintln!("{}", USAGE);
+            exit(2);
+        }
+        Some("help" | "-h" | "--help") => {
+            println!("{}", USAGE);
+            return;
+        }
+        Some("-d") => "gunzip",
+        Some(name) => name,
+    };
+    // Without a command, the arguments are those of inflate.
+    let (command, args) = match COMMANDS.iter().find(|command| command.name == name) {
+        Some(command) => (command, &args[2..]),
+        None => (&COMMANDS[0], &args[1..]),
+    };
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", command.usage);
+        return;
+    }
+    (command.run)(args);
+}
+
+/// The arguments of the decompression:
+/// `INPUT [-o OUTPUT] [--hex [HEX OPTIONS]] [-v | --trace] [--compare]`.
+struct DecompressArgs<'a> {
+    input: &'a str,
+    output: Option<&'a str>,
+    /// The layout of the hex dump, if one is written instead of the decompressed bytes.
+    hex: Option<HexdumpOptions>,
+    verbosity: Option<Verbosity>,
+    /// Compare the output with the reference inflater instead of writing it.
+    compare: bool,
+}
+
+/// Parse the arguments of the decompression.
+fn parse_decompress_args(args: &[String]) -> Option<DecompressArgs<'_>> {
+    let mut input = None;
+    let mut output = None;
+    let mut hex = false;
+    let mut layout = HexdumpOptions::default();
+    let mut verbosity = None;
+    let mut compare = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "-o" || arg == "--output" {
+            output = Some(args.next()?.as_str());
+        } else if let Some(value) = arg.strip_prefix("--output=") {
+            output = Some(value);
+        } else if arg == "--hex" {
+            hex = true;
+        } else if arg == "--width" {
+            layout.width = args.next()?.parse().ok().filter(|&width| width > 0)?;
+        } else if arg == "--group" {
+            layout.group = args.next()?.parse().ok()?;
+        } else if arg == "--uppercase" {
+            layout.uppercase = true;
+        } else if arg == "--offset-base" {
+            layout.offset_base = match args.next()?.as_str() {
+                "hex" => OffsetBase::Hex,
+                "dec" => OffsetBase::Decimal,
+                "oct" => OffsetBase::Octal,
+                _ => return None,
+            };
+        } else if arg == "--color" {
+            layout.color = true;
+        } else if arg == "-v" || arg == "--verbose" {
+            verbosity = verbosity.or(Some(Verbosity::Blocks));
+        } else if arg == "--trace" {
+            verbosity = Some(Verbosity::Symbols);
+        } else if arg == "--compare" {
+            compare = true;
+        } else if input.is_none() && (arg == "-" || !arg.starts_with('-')) {
+            input = Some(arg.as_str());
+        } else {
+            return None;
+        }
+    }
+    Some(DecompressArgs {
+        input: input?,
+        output,
+        hex: hex.then_some(layout),
+        verbosity,
+        compare,
+    })
+}
+
+/// Decompress a file, or stdin, into a file or stdout.
+fn decompress(args: &[String]) {
+    let Some(DecompressArgs {
+        input,
+        output,
+        hex,
+        verbosity,
+        compare,
+    }) = parse_decompress_args(args)
+    else {
+        eprintln!("{}", INFLATE_USAGE);
+        exit(2);
+    };
+    let data = read_file(input);
+    if let Some(verbosity) = verbosity {
+        // The narration stops where decoding fails, which the decompression below reports.
+        if let Ok(start) = deflate_start(&data) {
+            let mut narrator = Narrator::new(std::io::stderr().lock(), verbosity);
+            let _ = inflate_with_observer(&data[start..], &mut narrator);
+        }
+    }
+    if compare {
+        compare_with_reference(input, &data);
+    }
+    let decompressed = match decompress_auto(&data) {
+        Ok((decompressed, _)) => decompressed,
+        Err(e) => {
+            eprintln!("Cannot decompress {}: {}", input, locate_error(&data, e));
+            exit(1);
+        }
+    };
+    let bytes = match hex {
+        Some(options) => hexdump(&decompressed, &options).into_bytes(),
+        None => decompressed,
+    };
+    let result = match output {
+        Some(path) => std::fs::write(path, &bytes),
+        None => std::io::stdout().lock().write_all(&bytes),
+    };
+    if let Err(e) = result {
+        eprintln!("Cannot write {}: {}", output.unwrap_or("to stdout"), e);
+        exit(1);
+    }
+}
+
+/// Describe a decompression error along with where in the data it was found, when decoding
+/// in the detected container runs into the same error.
+fn locate_error(data: &[u8], error: InflateError) -> String {
+    let options = InflateOptions::new().format(detect_format(data));
+    match inflate_with_report(data, &options) {
+        Err(report) if report.error == error => report.to_string(),
+        _ => error.to_string(),
+    }
+}
+
+/// Decompress a file with both inflate-toy and miniz_oxide and report where they disagree.
+/// Exits with status 1 if they do.
+#[cfg(feature = "compare")]
+fn compare_with_reference(input: &str, data: &[u8]) -> ! {
+    match inflate_toy::compare::compare_with_reference(data) {
+        Ok(comparison) => {
+            print!("{}", comparison.report());
+            exit(i32::from(comparison.divergence.is_some()));
+        }
+        Err(e) => {
+            eprintln!("Cannot decompress {}: {}", input, e);
+            exit(1);
+        }
+    }
+}
+
+#[cfg(not(feature = "compare"))]
+fn compare_with_reference(_input: &str, _data: &[u8]) -> ! {
+    eprintln!("--compare requires inflate-toy to be built with the compare feature");
+    exit(2);
+}
+
+/// Find where the DEFLATE stream of a file starts, after its container header if any.
+fn deflate_start(data: &[u8]) -> inflate_toy::error::Result<usize> {
+    header_len(data, detect_format(data))
+}
+
+/// Get the name of the file a gzip file decompresses to, as `gunzip` does.
+fn gunzip_output_path(path: &str) -> Option<String> {
+    if let Some(stem) = path.strip_suffix(".tgz") {
+        return Some(format!("{}.tar", stem));
+    }
+    path.strip_suffix(".gz")
+        .filter(|stem| !stem.is_empty() && !stem.ends_with('/'))
+        .map(str::to_string)
+}
+
+/// Decompress a gzip file next to it, checking its CRC and restoring its modification time.
+fn gunzip_file(path: &str, keep: bool, force: bool) -> Result<(), String> {
+    let output_path = gunzip_output_path(path).ok_or("unknown suffix -- ignored")?;
+    if !force && std::path::Path::new(&output_path).exists() {
+        return Err(format!("{} already exists", output_path));
+    }
+    let data = std::fs::read(path).map_err(|e| e.to_string())?;
+    let (header, _) = parse_gzip_header(&data).map_err(|e| e.to_string())?;
+    let options = InflateOptions::new()
+        .format(Format::Gzip)
+        .tail(TailHandling::Reject);
+    let decompressed = inflate_to_vec_with_options(&data, &options).map_err(|e| e.to_string())?;
+    std::fs::write(&output_path, decompressed).map_err(|e| e.to_string())?;
+    // An mtime of 0 means the original modification time is unknown.
+    if header.mtime != 0 {
+        let mtime = UNIX_EPOCH + Duration::from_secs(header.mtime.into());
+        std::fs::File::options()
+            .write(true)
+            .open(&output_path)
+            .and_then(|file| file.set_modified(mtime))
+            .map_err(|e| e.to_string())?;
+    }
+    if !keep {
+        std::fs::remove_file(path).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Decompress gzip files like the system `gunzip`.
+fn gunzip(args: &[String]) {
+    let mut keep = false;
+    let mut force = false;
+    let mut paths = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "-k" | "--keep" => keep = true,
+            "-f" | "--force" => force = true,
+            _ if arg.starts_with('-') => {
+                eprintln!("{}", GUNZIP_USAGE);
+                exit(2);
+            }
+            _ => paths.push(arg.as_str()),
+        }
+    }
+    if paths.is_empty() {
+        eprintln!("{}", GUNZIP_USAGE);
+        exit(2);
+    }
+    let mut failed = false;
+    for path in paths {
+        if let Err(e) = gunzip_file(path, keep, force) {
+            eprintln!("inflate-toy: {}: {}", path, e);
+            failed = true;
+        }
+    }
+    if failed {
+        exit(1);
+    }
+}
+
+/// The arguments of a subcommand.
+struct CommandArgs<'a> {
+    file: &'a str,
+    bit_offset: Option<usize>,
+    options: TextOptions,
+    /// Print the result as JSON rather than text.
+    json: bool,
+    /// A file to write a table to as well, as Markdown or CSV by its extension.
+    report: Option<&'a str>,
+}
+
+/// Parse the arguments of a subcommand: a file, then `--bit-offset N`, `--stable-output`,
+/// `--json` and `--report FILE`.
+fn parse_command_args(args: &[String]) -> Option<CommandArgs<'_>> {
+    let mut file = None;
+    let mut bit_offset = None;
+    let mut options = TextOptions::default();
+    let mut json = false;
+    let mut report = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--bit-offset" {
+            bit_offset = Some(args.next()?.parse().ok()?);
+        } else if let Some(value) = arg.strip_prefix("--bit-offset=") {
+            bit_offset = Some(value.parse().ok()?);
+        } else if arg == "--report" {
+            report = Some(args.next()?.as_str());
+        } else if let Some(value) = arg.strip_prefix("--report=") {
+            report = Some(value);
+        } else if arg == "--stable-output" {
+            options.stable_output = true;
+        } else if arg == "--json" {
+            json = true;
+        } else if file.is_none() && !arg.starts_with("--") {
+            file = Some(arg.as_str());
+        } else {
+            return None;
+        }
+    }
+    Some(CommandArgs {
+        file: file?,
+        bit_offset,
+        options,
+        json,
+        report,
+    })
+}
+
+/// Print a value as pretty JSON.
+fn print_json(value: &impl Serialize) {
+    println!(
+        "{}",
+        serde_json::to_string_pretty(value).expect("introspection types always serialize")
+    );
+}
+
+/// Read a file, or stdin for `-`, or exit with an error message.
+fn read_file(path: &str) -> Vec<u8> {
+    let result = if path == "-" {
+        let mut data = Vec::new();
+        std::io::stdin().lock().read_to_end(&mut data).map(|_| data)
+    } else {
+        std::fs::read(path)
+    };
+    match result {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("Cannot read {}: {}", path, e);
+            exit(1);
+        }
+    }
+}
+
+/// Say what field or symbol the given bit of a compressed file belongs to,
+/// or narrate the decoding of all of it.
+fn explain(args: &[String]) {
+    let Some(CommandArgs {
+        file,
+        bit_offset,
+        options,
+        json: false,
+        report: None,
+    }) = parse_command_args(args)
+    else {
+        eprintln!("{}", EXPLAIN_USAGE);
+        exit(2);
+    };
+    let data = read_file(file);
+    let format = detect_format(&data);
+    match bit_offset {
+        Some(bit_offset) => {
+            let disassembly = disassemble(&data, format);
+            println!("{}", disassembly.explain_with(bit_offset, &options));
+        }
+        None => {
+            let options = ExplainOptions {
+                format,
+                text: options,
+                ..Default::default()
+            };
+            print!("{}", inflate_toy::explain::explain(&data, &options));
+        }
+    }
+}
+
+/// Print the JSON trace of a compressed file.
+fn trace(args: &[String]) {
+    let Some(CommandArgs {
+        file,
+        bit_offset: None,
+        options,
+        json: false,
+        report: None,
+    }) = parse_command_args(args)
+    else {
+        eprintln!("{}", TRACE_USAGE);
+        exit(2);
+    };
+    let data = read_file(file);
+    let disassembly = disassemble(&data, detect_format(&data));
+    println!("{}", Trace::with_options(&disassembly, &options).to_json());
+}
+
+/// Print statistics about how a compressed file is compressed.
+fn stats(args: &[String]) {
+    let Some(CommandArgs {
+        file,
+        bit_offset: None,
+        options,
+        json,
+        report,
+    }) = parse_command_args(args)
+    else {
+        eprintln!("{}", STATS_USAGE);
+        exit(2);
+    };
+    let report = report.map(|path| match table_format(path) {
+        Some(format) => (path, format),
+        None => {
+            eprintln!("{}", STATS_USAGE);
+            exit(2);
+        }
+    });
+    let data = read_file(file);
+    let stats = match deflate_start(&data).and_then(|start| analyze(&data[start..])) {
+        Ok(stats) => stats,
+        Err(e) => {
+            eprintln!("Cannot analyze {}: {}", file, options.error(&e));
+            exit(1);
+        }
+    };
+    if let Some((path, format)) = report {
+        if let Err(e) = std::fs::write(path, stats.block_table(format, &options)) {
+            eprintln!("Cannot write {}: {}", path, e);
+            exit(1);
+        }
+    }
+    if json {
+        print_json(&stats);
+    } else {
+        print!("{}", stats.report(&options));
+    }
 }
 
+/// Print how close the Huffman codes of each block of a compressed file are to optimal.
+fn entropy(args: &[String]) {
+    let Some(CommandArgs {
+        file,
+        bit_offset: None,
+        options,
+        json,
+        report: None,
+    }) = parse_command_args(args)
+    else {
+        eprintln!("{}", ENTROPY_USAGE);
+        exit(2);
+    };
+    let data = read_file(file);
+    let blocks = match deflate_start(&data).and_then(|start| estimate(&data[start..])) {
+        Ok(blocks) => blocks,
+        Err(e) => {
+            eprintln!("Cannot analyze {}: {}", file, options.error(&e));
+            exit(1);
+        }
+    };
+    if json {
+        print_json(&blocks);
+    } else {
+        print!(
+            "{}",
+            entropy_table(&blocks, TableFormat::Markdown, &options)
+        );
+    }
+}
+
+/// Get the format of a table file by its extension: `.md` for Markdown, `.csv` for CSV.
+fn table_format(path: &str) -> Option<TableFormat> {
+    match std::path::Path::new(path).extension()?.to_str()? {
+        "md" | "markdown" => Some(TableFormat::Markdown),
+        "csv" => Some(TableFormat::Csv),
+        _ => None,
+    }
+}
 
-/// Display the data in hex format.
-fn display_data(data: &[u8]) -> String {
-    let mut result = String::new();
+/// Print every field of a compressed file with its bit range and value.
+fn annotate_file(args: &[String]) {
+    let Some(CommandArgs {
+        file,
+        bit_offset: None,
+        options,
+        json,
+        report: None,
+    }) = parse_command_args(args)
+    else {
+        eprintln!("{}", ANNOTATE_USAGE);
+        exit(2);
+    };
+    let data = read_file(file);
+    let disassembly = disassemble(&data, detect_format(&data));
+    if json {
+        let annotations: Vec<_> = disassembly
+            .fields
+            .iter()
+            .map(|field| field.annotate(&data))
+            .collect();
+        print_json(&annotations);
+    } else {
+        for field in &disassembly.fields {
+            println!("{}", field.annotate(&data));
+        }
+    }
+    // The fields decoded before an error are still printed, followed by the error, which
+    // stays out of the JSON on stdout.
+    if let Some(error) = disassembly.error {
+        let error = format!(
+            "bit {}: error = {}",
+            disassembly.end_bit_offset,
+            options.error(&error)
+        );
+        if json {
+            eprintln!("Cannot annotate {}: {}", file, error);
+        } else {
+            println!("{}", error);
+        }
+        exit(1);
+    }
+}
+
+/// The arguments of the compression: `INPUT [-o OUTPUT] [--level N] [--zlib | --gzip]`.
+struct DeflateArgs<'a> {
+    input: &'a str,
+    output: Option<&'a str>,
+    level: u8,
+    format: Format,
+}
+
+/// Parse the arguments of the compression.
+fn parse_deflate_args(args: &[String]) -> Option<DeflateArgs<'_>> {
+    let mut input = None;
+    let mut output = None;
+    let mut level = DEFAULT_LEVEL;
+    let mut format = Format::Raw;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "-o" || arg == "--output" {
+            output = Some(args.next()?.as_str());
+        } else if arg == "--level" {
+            level = args
+                .next()?
+                .parse()
+                .ok()
+                .filter(|&level| level <= MAX_LEVEL)?;
+        } else if arg == "--zlib" {
+            format = Format::Zlib;
+        } else if arg == "--gzip" {
+            format = Format::Gzip;
+        } else if input.is_none() && (arg == "-" || !arg.starts_with('-')) {
+            input = Some(arg.as_str());
+        } else {
+            return None;
+        }
+    }
+    Some(DeflateArgs {
+        input: input?,
+        output,
+        level,
+        format,
+    })
+}
 
-    for (i, chunk) in data.chunks(16).enumerate() {
-        // Print the offset
-        result.push_str(&format!("{:08x}: ", i * 16));
+/// Compress a file, or stdin, into a file or stdout.
+fn compress(args: &[String]) {
+    let Some(DeflateArgs {
+        input,
+        output,
+        level,
+        format,
+    }) = parse_deflate_args(args)
+    else {
+        eprintln!("{}", DEFLATE_USAGE);
+        exit(2);
+    };
+    let data = read_file(input);
+    let compressed = wrap(&deflate_to_vec_with_level(&data, level), &data, format);
+    let result = match output {
+        Some(path) => std::fs::write(path, &compressed),
+        None => std::io::stdout().lock().write_all(&compressed),
+    };
+    if let Err(e) = result {
+        eprintln!("Cannot write {}: {}", output.unwrap_or("to stdout"), e);
+        exit(1);
+    }
+}
 
-        // Print the byte values in hex
-        for byte in chunk {
-            result.push_str(&format!("{:02x} ", byte));
+/// List the blocks of a compressed file: their type, bit range and output range.
+fn inspect_file(args: &[String]) {
+    let Some(CommandArgs {
+        file,
+        bit_offset: None,
+        options,
+        json,
+        report: None,
+    }) = parse_command_args(args)
+    else {
+        eprintln!("{}", INSPECT_USAGE);
+        exit(2);
+    };
+    let data = read_file(file);
+    let blocks = match deflate_start(&data).and_then(|start| inspect(&data[start..])) {
+        Ok(blocks) => blocks,
+        Err(e) => {
+            eprintln!("Cannot inspect {}: {}", file, options.error(&e));
+            exit(1);
         }
+    };
+    if json {
+        print_json(&blocks);
+        return;
+    }
+    for (index, block) in blocks.iter().enumerate() {
+        let name = match block.block_type {
+            0 => "stored",
+            1 => "fixed Huffman",
+            _ => "dynamic Huffman",
+        };
+        let end = block.header_bit_offset + block.bit_len;
+        print!(
+            "block {}: {}{}, bits {}..{} (body at {}), output {}..{}",
+            index,
+            name,
+            if block.is_final { ", final" } else { "" },
+            block.header_bit_offset,
+            end,
+            block.body_bit_offset,
+            block.output_offset,
+            block.output_offset + block.output_len
+        );
+        match block.dynamic {
+            Some(header) => println!(
+                ", HLIT {}, HDIST {}, HCLEN {}",
+                header.hlit, header.hdist, header.hclen
+            ),
+            None => println!(),
+        }
+    }
+}
+
+/// Collects the Huffman tables of every block as they are built.
+#[derive(Default)]
+struct TableCollector {
+    blocks: usize,
+    tables: Vec<(usize, Alphabet, HuffmanLookupTable)>,
+}
+
+impl DecodeObserver for TableCollector {
+    fn on_block_start(&mut self, _block_type: usize, _is_final: bool, _bit_offset: usize) {
+        self.blocks += 1;
+    }
+
+    fn on_table_built(&mut self, alphabet: Alphabet, table: &HuffmanLookupTable) {
+        self.tables.push((self.blocks - 1, alphabet, table.clone()));
+    }
+}
 
-        // If the chunk is less than 16 bytes, fill the gap
-        for _ in 0..(16 - chunk.len()) {
-            result.push_str("   ");
+/// The codes of a Huffman table, as the `huffman` subcommand prints them with `--json`.
+#[derive(Serialize)]
+struct TableListing {
+    block: usize,
+    alphabet: Alphabet,
+    codes: Vec<HuffmanLeaf>,
+}
+
+/// The arguments of the `huffman` subcommand: `FILE [--block N] [--dot | --json]`.
+struct HuffmanArgs<'a> {
+    file: &'a str,
+    block: Option<usize>,
+    dot: bool,
+    json: bool,
+}
+
+/// Parse the arguments of the `huffman` subcommand.
+fn parse_huffman_args(args: &[String]) -> Option<HuffmanArgs<'_>> {
+    let mut file = None;
+    let mut block = None;
+    let mut dot = false;
+    let mut json = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--block" {
+            block = Some(args.next()?.parse().ok()?);
+        } else if arg == "--dot" && !json {
+            dot = true;
+        } else if arg == "--json" && !dot {
+            json = true;
+        } else if file.is_none() && !arg.starts_with('-') {
+            file = Some(arg.as_str());
+        } else {
+            return None;
         }
+    }
+    Some(HuffmanArgs {
+        file: file?,
+        block,
+        dot,
+        json,
+    })
+}
 
-        // Print the ASCII representation
-        result.push_str(" |");
-        for byte in chunk {
-            if byte.is_ascii_graphic() {
-                result.push(*byte as char);
-            } else {
-                result.push('.');
+/// Print the Huffman codes of the compressed blocks of a file.
+fn huffman(args: &[String]) {
+    let Some(HuffmanArgs {
+        file,
+        block,
+        dot,
+        json,
+    }) = parse_huffman_args(args)
+    else {
+        eprintln!("{}", HUFFMAN_USAGE);
+        exit(2);
+    };
+    let data = read_file(file);
+    let mut collector = TableCollector::default();
+    // The tables built before an error are still printed, followed by the error.
+    let result = deflate_start(&data)
+        .and_then(|start| inflate_with_observer(&data[start..], &mut collector));
+    let tables: Vec<_> = collector
+        .tables
+        .iter()
+        .filter(|(index, _, _)| block.is_none_or(|block| block == *index))
+        .collect();
+    if json {
+        let listings: Vec<_> = tables
+            .iter()
+            .map(|(index, alphabet, table)| TableListing {
+                block: *index,
+                alphabet: *alphabet,
+                codes: table
+                    .codes()
+                    .into_iter()
+                    .map(|(symbol, code, len)| HuffmanLeaf { symbol, code, len })
+                    .collect(),
+            })
+            .collect();
+        print_json(&listings);
+    } else {
+        for (index, alphabet, table) in tables {
+            if dot {
+                print!("{}", table.to_dot());
+                continue;
             }
+            println!("block {} {} codes:", index, alphabet);
+            print!("{}", table);
         }
-        result.push_str("|\n");
     }
+    if let Err(e) = result {
+        eprintln!("Cannot decompress {}: {}", file, e);
+        exit(1);
+    }
+}
 
-    result
+/// Parse the arguments of the `bench` subcommand: `FILE [--iterations N]`.
+fn parse_bench_args(args: &[String]) -> Option<(&str, usize)> {
+    let mut file = None;
+    let mut iterations = 10;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--iterations" {
+            iterations = args.next()?.parse().ok().filter(|&n| n > 0)?;
+        } else if file.is_none() && !arg.starts_with('-') {
+            file = Some(arg.as_str());
+        } else {
+            return None;
+        }
+    }
+    Some((file?, iterations))
+}
+
+/// Time the decompression of a file.
+fn bench(args: &[String]) {
+    let Some((file, iterations)) = parse_bench_args(args) else {
+        eprintln!("{}", BENCH_USAGE);
+        exit(2);
+    };
+    let data = read_file(file);
+    let mut times = Vec::with_capacity(iterations);
+    let mut output_len = 0;
+    for _ in 0..iterations {
+        let start = Instant::now();
+        match decompress_auto(&data) {
+            Ok((decompressed, _)) => output_len = decompressed.len(),
+            Err(e) => {
+                eprintln!("Cannot decompress {}: {}", file, e);
+                exit(1);
+            }
+        }
+        times.push(start.elapsed());
+    }
+    let fastest = times.iter().min().copied().unwrap_or_default();
+    let average = times.iter().sum::<Duration>() / iterations as u32;
+    let throughput = |time: Duration| output_len as f64 / time.as_secs_f64() / 1e6;
+    println!(
+        "{} bytes into {} bytes, {} iterations",
+        data.len(),
+        output_len,
+        iterations
+    );
+    println!("fastest: {:?} ({:.1} MB/s)", fastest, throughput(fastest));
+    println!("average: {:?} ({:.1} MB/s)", average, throughput(average));
 }
@@ -0,0 +1,335 @@
+//! Watch a DEFLATE stream being decoded through callbacks.
+//!
+//! A [`DecodeObserver`] is told about each block header, Huffman table, literal, match and
+//! piece of stored data as [`inflate_with_observer`] decodes them, in stream order. Tracing,
+//! statistics and visualizers can be built on it without a decode loop of their own.
+
+use crate::bit_stream::BitReader;
+use crate::disasm::{Alphabet, Field, FieldKind};
+use crate::error::{InflateError, Result};
+use crate::huffman::HuffmanLookupTable;
+use crate::inflate::{inflate_step, BlockState, InflateState};
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io::Write;
+
+/// Callbacks for the events of a decoding. Every method does nothing by default, so an observer
+/// only implements the ones it cares about.
+pub trait DecodeObserver {
+    /// Called for every field of the stream, before the more specific callback for it if any.
+    fn on_field(&mut self, _field: &Field) {}
+
+    /// Called once the header of a block is read: its BTYPE, its BFINAL bit, and the bit offset
+    /// of the header.
+    fn on_block_start(&mut self, _block_type: usize, _is_final: bool, _bit_offset: usize) {}
+
+    /// Called once the Huffman table of an alphabet is ready, for fixed and dynamic blocks.
+    fn on_table_built(&mut self, _alphabet: Alphabet, _table: &HuffmanLookupTable) {}
+
+    /// Called for each literal byte.
+    fn on_literal(&mut self, _byte: u8) {}
+
+    /// Called for each back-reference, before its bytes are copied.
+    fn on_match(&mut self, _length: usize, _distance: usize) {}
+
+    /// Called with the data of a stored block, which may come in several pieces.
+    fn on_stored(&mut self, _data: &[u8]) {}
+
+    /// Called at the end-of-block symbol of a compressed block.
+    fn on_end_of_block(&mut self) {}
+}
+
+/// Inflate a raw DEFLATE stream, telling the observer about everything decoded.
+/// On error, the observer has seen everything decoded before it.
+pub fn inflate_with_observer(data: &[u8], observer: &mut dyn DecodeObserver) -> Result<Vec<u8>> {
+    if data.is_empty() {
+        return Err(InflateError::EmptyInput);
+    }
+    let mut bit_reader = BitReader::new_strict(data);
+    let mut state = InflateState::new();
+    state.trace = Some(Vec::new());
+    let mut block = BlockState::Header;
+    let mut is_final = false;
+    let mut length = 0;
+
+    while !matches!(block, BlockState::Done) {
+        let result = inflate_step(&mut bit_reader, &mut state, &block);
+        // Only the fields of this step are kept, so the trace never grows.
+        let fields = state
+            .trace
+            .as_mut()
+            .map(core::mem::take)
+            .unwrap_or_default();
+        for field in &fields {
+            observer.on_field(field);
+            match field.kind {
+                FieldKind::BlockFinal(bit) => is_final = bit,
+                FieldKind::BlockType(block_type) => {
+                    // BTYPE follows BFINAL, which starts the header.
+                    observer.on_block_start(block_type, is_final, field.bit_offset - 1)
+                }
+                FieldKind::Literal(byte) => observer.on_literal(byte),
+                FieldKind::LengthCode { length: n, .. } => length = n,
+                FieldKind::DistanceCode { distance, .. } => observer.on_match(length, distance),
+                FieldKind::StoredData { len } => {
+                    let start = field.output_offset;
+                    observer.on_stored(&state.decompressed()[start..start + len]);
+                }
+                FieldKind::EndOfBlock => observer.on_end_of_block(),
+                _ => {}
+            }
+        }
+        if let Some(next) = result? {
+            if let BlockState::Compressed { lit_tb, dis_tb, .. } = &next {
+                if matches!(block, BlockState::Header) {
+                    observer.on_table_built(Alphabet::LiteralLength, lit_tb);
+                    observer.on_table_built(Alphabet::Distance, dis_tb);
+                }
+            }
+            block = next;
+        }
+    }
+    Ok(state.output)
+}
+
+/// How much a [`Narrator`] tells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verbosity {
+    /// One line per block header and Huffman table, and a summary at the end of each block.
+    Blocks,
+    /// Also one line per literal and match.
+    Symbols,
+}
+
+/// An observer writing a narration of the decoding, one line per event, e.g. to debug a
+/// corrupted stream. The narration is best effort: write errors are ignored.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct Narrator<W: Write> {
+    out: W,
+    verbosity: Verbosity,
+    blocks: usize,
+    literals: usize,
+    matches: usize,
+    output_offset: usize,
+}
+
+#[cfg(feature = "std")]
+impl<W: Write> Narrator<W> {
+    /// Create a narrator writing to `out`.
+    pub fn new(out: W, verbosity: Verbosity) -> Self {
+        Self {
+            out,
+            verbosity,
+            blocks: 0,
+            literals: 0,
+            matches: 0,
+            output_offset: 0,
+        }
+    }
+
+    /// Get the writer back.
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: Write> DecodeObserver for Narrator<W> {
+    fn on_block_start(&mut self, block_type: usize, is_final: bool, bit_offset: usize) {
+        let name = match block_type {
+            0 => "stored",
+            1 => "fixed Huffman",
+            2 => "dynamic Huffman",
+            _ => "reserved",
+        };
+        let index = self.blocks;
+        self.blocks += 1;
+        let _ = writeln!(
+            self.out,
+            "block {} at bit {}: {}{}",
+            index,
+            bit_offset,
+            name,
+            if is_final { ", final" } else { "" }
+        );
+    }
+
+    fn on_table_built(&mut self, alphabet: Alphabet, table: &HuffmanLookupTable) {
+        let code_lengths = table.code_lengths();
+        let n_codes = code_lengths.iter().filter(|&&len| len > 0).count();
+        let longest = code_lengths.iter().max().copied().unwrap_or(0);
+        let _ = writeln!(
+            self.out,
+            "  {} table: {} codes, longest {} bits",
+            alphabet, n_codes, longest
+        );
+    }
+
+    fn on_literal(&mut self, byte: u8) {
+        self.literals += 1;
+        if self.verbosity == Verbosity::Symbols {
+            let offset = self.output_offset;
+            let _ = if byte.is_ascii_graphic() || byte == b' ' {
+                writeln!(self.out, "  {}: literal '{}'", offset, byte as char)
+            } else {
+                writeln!(self.out, "  {}: literal {:#04x}", offset, byte)
+            };
+        }
+        self.output_offset += 1;
+    }
+
+    fn on_match(&mut self, length: usize, distance: usize) {
+        self.matches += 1;
+        if self.verbosity == Verbosity::Symbols {
+            let _ = writeln!(
+                self.out,
+                "  {}: match length {} distance {}",
+                self.output_offset, length, distance
+            );
+        }
+        self.output_offset += length;
+    }
+
+    fn on_stored(&mut self, data: &[u8]) {
+        self.output_offset += data.len();
+        let _ = writeln!(self.out, "  stored {} bytes", data.len());
+    }
+
+    fn on_end_of_block(&mut self) {
+        let _ = writeln!(
+            self.out,
+            "  end of block: {} literals, {} matches, output now {} bytes",
+            self.literals, self.matches, self.output_offset
+        );
+        self.literals = 0;
+        self.matches = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deflate::{deflate_fixed_tokens_to_vec, Token};
+    use alloc::string::{String, ToString};
+    use alloc::{format, vec};
+
+    /// Writes every event down as a line of text.
+    #[derive(Default)]
+    struct Recorder {
+        events: Vec<String>,
+        fields: usize,
+    }
+
+    impl DecodeObserver for Recorder {
+        fn on_field(&mut self, _field: &Field) {
+            self.fields += 1;
+        }
+
+        fn on_block_start(&mut self, block_type: usize, is_final: bool, bit_offset: usize) {
+            self.events.push(format!(
+                "block {} {} at {}",
+                block_type, is_final, bit_offset
+            ));
+        }
+
+        fn on_table_built(&mut self, alphabet: Alphabet, table: &HuffmanLookupTable) {
+            let n_codes = table.code_lengths().iter().filter(|&&len| len > 0).count();
+            self.events
+                .push(format!("table {} with {} codes", alphabet, n_codes));
+        }
+
+        fn on_literal(&mut self, byte: u8) {
+            self.events.push(format!("literal {}", byte as char));
+        }
+
+        fn on_match(&mut self, length: usize, distance: usize) {
+            self.events.push(format!("match {} {}", length, distance));
+        }
+
+        fn on_stored(&mut self, data: &[u8]) {
+            self.events
+                .push(format!("stored {}", String::from_utf8_lossy(data)));
+        }
+
+        fn on_end_of_block(&mut self) {
+            self.events.push("end of block".to_string());
+        }
+    }
+
+    #[test]
+    fn test_inflate_with_observer() {
+        let mut data = vec![0b000, 3, 0, !3, !0, b'a', b'b', b'c'];
+        data.extend(deflate_fixed_tokens_to_vec(&[
+            Token::Literal(b'd'),
+            Token::Match {
+                length: 4,
+                distance: 2,
+            },
+        ]));
+        let mut recorder = Recorder::default();
+        let output = inflate_with_observer(&data, &mut recorder).unwrap();
+        assert_eq!(output, b"abcdcdcd");
+        assert_eq!(
+            recorder.events,
+            [
+                "block 0 false at 0",
+                "stored abc",
+                "block 1 true at 64",
+                "table literal/length with 288 codes",
+                "table distance with 32 codes",
+                "literal d",
+                "match 4 2",
+                "end of block",
+            ]
+        );
+        let mut stepper = crate::stepper::InflateStepper::new(&data);
+        while !stepper.step().unwrap() {}
+        assert_eq!(recorder.fields, stepper.fields().len());
+    }
+
+    #[test]
+    fn test_inflate_with_observer_error() {
+        let data = deflate_fixed_tokens_to_vec(&[Token::Literal(b'x'), Token::Literal(b'y')]);
+        let mut recorder = Recorder::default();
+        assert_eq!(
+            inflate_with_observer(&data[..1], &mut recorder),
+            Err(InflateError::UnexpectedEof)
+        );
+        assert_eq!(recorder.events[0], "block 1 true at 0");
+        assert_eq!(
+            inflate_with_observer(&[], &mut recorder),
+            Err(InflateError::EmptyInput)
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_narrator() {
+        let mut data = vec![0b000, 3, 0, !3, !0, b'a', b'b', b'c'];
+        data.extend(deflate_fixed_tokens_to_vec(&[
+            Token::Literal(b'd'),
+            Token::Match {
+                length: 4,
+                distance: 2,
+            },
+        ]));
+        let mut narrator = Narrator::new(Vec::new(), Verbosity::Symbols);
+        inflate_with_observer(&data, &mut narrator).unwrap();
+        assert_eq!(
+            String::from_utf8(narrator.into_inner()).unwrap(),
+            "block 0 at bit 0: stored\n  stored 3 bytes\n\
+             block 1 at bit 64: fixed Huffman, final\n\
+             \x20 literal/length table: 288 codes, longest 9 bits\n\
+             \x20 distance table: 32 codes, longest 5 bits\n\
+             \x20 3: literal 'd'\n  4: match length 4 distance 2\n\
+             \x20 end of block: 1 literals, 1 matches, output now 8 bytes\n"
+        );
+
+        let mut narrator = Narrator::new(Vec::new(), Verbosity::Blocks);
+        inflate_with_observer(&data, &mut narrator).unwrap();
+        let narration = String::from_utf8(narrator.into_inner()).unwrap();
+        assert!(!narration.contains("literal 'd'"));
+        assert!(narration.ends_with("end of block: 1 literals, 1 matches, output now 8 bytes\n"));
+    }
+}
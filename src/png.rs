@@ -0,0 +1,450 @@
+//! Get at the DEFLATE data inside PNG images.
+//!
+//! A PNG file is a signature followed by chunks, each a length, a four-letter type, the data
+//! and a CRC-32. The pixels are compressed as a single zlib stream, split over any number of
+//! IDAT chunks, and before compression every scanline was run through one of five filters,
+//! whose type is the first byte of the scanline. [`scanlines`] concatenates the IDAT chunks
+//! and inflates them into the filtered scanlines; [`unfilter`] undoes the filters.
+//! The format is defined in the PNG specification, <https://www.w3.org/TR/png/>.
+
+use crate::checksum::Crc32;
+use crate::container::Format;
+use crate::error::{InflateError, Result};
+use crate::inflate::{inflate_to_vec_with_options, InflateOptions};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// The eight bytes every PNG file starts with.
+pub const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+const IHDR: [u8; 4] = *b"IHDR";
+const IDAT: [u8; 4] = *b"IDAT";
+const IEND: [u8; 4] = *b"IEND";
+const IHDR_LEN: usize = 13;
+
+/// The starting column, starting row, column step and row step of the seven Adam7 passes.
+pub const ADAM7_PASSES: [(u32, u32, u32, u32); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+/// The fields of the IHDR chunk that determine the layout of the scanlines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PngHeader {
+    /// Width of the image in pixels.
+    pub width: u32,
+    /// Height of the image in pixels.
+    pub height: u32,
+    /// Bits per sample, or per palette index.
+    pub bit_depth: u8,
+    /// 0 = greyscale, 2 = RGB, 3 = palette, 4 = greyscale with alpha, 6 = RGB with alpha.
+    pub color_type: u8,
+    /// Whether the image is stored as the seven reduced images of Adam7 interlacing.
+    pub interlaced: bool,
+}
+
+impl PngHeader {
+    /// Number of samples per pixel.
+    pub fn channels(&self) -> usize {
+        match self.color_type {
+            2 => 3,
+            4 => 2,
+            6 => 4,
+            _ => 1,
+        }
+    }
+
+    /// Number of bits per pixel.
+    pub fn bits_per_pixel(&self) -> usize {
+        self.channels() * self.bit_depth as usize
+    }
+
+    /// Number of bytes in a scanline `width` pixels wide, without its filter type byte.
+    pub fn row_len(&self, width: u32) -> usize {
+        (width as usize * self.bits_per_pixel()).div_ceil(8)
+    }
+
+    /// Get the width and height of each reduced image the scanlines are stored as:
+    /// the whole image, or the seven Adam7 passes when interlaced. Passes can be empty.
+    pub fn passes(&self) -> Vec<(u32, u32)> {
+        if !self.interlaced {
+            return vec![(self.width, self.height)];
+        }
+        let reduce =
+            |size: u32, start: u32, step: u32| (size + step - 1).saturating_sub(start) / step;
+        ADAM7_PASSES
+            .iter()
+            .map(|&(x, y, dx, dy)| (reduce(self.width, x, dx), reduce(self.height, y, dy)))
+            .collect()
+    }
+
+    /// Number of bytes of filtered scanlines, filter type bytes included.
+    /// Empty passes have no scanlines at all.
+    pub fn filtered_len(&self) -> Result<usize> {
+        self.passes()
+            .into_iter()
+            .filter(|&(width, _)| width > 0)
+            .try_fold(0usize, |total, (width, height)| {
+                (1 + self.row_len(width))
+                    .checked_mul(height as usize)
+                    .and_then(|len| total.checked_add(len))
+            })
+            .ok_or(InflateError::InvalidHeader("PNG image too large"))
+    }
+
+    /// Number of bytes between a byte and the one it is filtered against in the scanline
+    /// before, or the corresponding byte of the pixel before: at least one.
+    fn filter_distance(&self) -> usize {
+        self.bits_per_pixel().div_ceil(8)
+    }
+}
+
+/// A chunk of a PNG file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PngChunk<'a> {
+    /// The four-letter type, e.g. `IDAT`.
+    pub kind: [u8; 4],
+    /// The data, without the length, type and CRC.
+    pub data: &'a [u8],
+}
+
+/// Split a PNG file into its chunks, up to and including IEND, checking their CRCs.
+pub fn chunks(png: &[u8]) -> Result<Vec<PngChunk<'_>>> {
+    if png.len() < PNG_SIGNATURE.len() {
+        return Err(InflateError::UnexpectedEof);
+    }
+    if png[..PNG_SIGNATURE.len()] != PNG_SIGNATURE {
+        return Err(InflateError::InvalidHeader("Invalid PNG signature"));
+    }
+    let mut chunks = Vec::new();
+    let mut pos = PNG_SIGNATURE.len();
+    loop {
+        let header = png.get(pos..pos + 8).ok_or(InflateError::UnexpectedEof)?;
+        let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let kind = [header[4], header[5], header[6], header[7]];
+        let data = png
+            .get(pos + 8..pos + 8 + len)
+            .ok_or(InflateError::UnexpectedEof)?;
+        let crc = png
+            .get(pos + 8 + len..pos + 12 + len)
+            .ok_or(InflateError::UnexpectedEof)?;
+        let expected = u32::from_be_bytes([crc[0], crc[1], crc[2], crc[3]]);
+        let mut found = Crc32::new();
+        found.update(&kind);
+        found.update(data);
+        let found = found.finish();
+        if found != expected {
+            return Err(InflateError::ChecksumMismatch { expected, found });
+        }
+        chunks.push(PngChunk { kind, data });
+        if kind == IEND {
+            return Ok(chunks);
+        }
+        pos += 12 + len;
+    }
+}
+
+/// Parse the IHDR chunk, which must be the first chunk.
+fn parse_header(chunks: &[PngChunk<'_>]) -> Result<PngHeader> {
+    let ihdr = match chunks.first() {
+        Some(chunk) if chunk.kind == IHDR && chunk.data.len() == IHDR_LEN => chunk.data,
+        _ => return Err(InflateError::InvalidHeader("Missing PNG IHDR chunk")),
+    };
+    let header = PngHeader {
+        width: u32::from_be_bytes([ihdr[0], ihdr[1], ihdr[2], ihdr[3]]),
+        height: u32::from_be_bytes([ihdr[4], ihdr[5], ihdr[6], ihdr[7]]),
+        bit_depth: ihdr[8],
+        color_type: ihdr[9],
+        interlaced: ihdr[12] == 1,
+    };
+    let depth_allowed = match header.color_type {
+        0 => matches!(header.bit_depth, 1 | 2 | 4 | 8 | 16),
+        3 => matches!(header.bit_depth, 1 | 2 | 4 | 8),
+        2 | 4 | 6 => matches!(header.bit_depth, 8 | 16),
+        _ => false,
+    };
+    if !depth_allowed {
+        return Err(InflateError::InvalidHeader(
+            "Invalid PNG color type and bit depth",
+        ));
+    }
+    if header.width == 0 || header.height == 0 {
+        return Err(InflateError::InvalidHeader("Empty PNG image"));
+    }
+    // Compression method 0 is zlib and filter method 0 the five filters; there are no others.
+    if ihdr[10] != 0 || ihdr[11] != 0 || ihdr[12] > 1 {
+        return Err(InflateError::InvalidHeader(
+            "Unsupported PNG compression, filter or interlace method",
+        ));
+    }
+    Ok(header)
+}
+
+/// Get the header of a PNG file and its zlib stream: the data of its IDAT chunks, concatenated.
+pub fn idat_stream(png: &[u8]) -> Result<(PngHeader, Vec<u8>)> {
+    let chunks = chunks(png)?;
+    let header = parse_header(&chunks)?;
+    let stream: Vec<u8> = chunks
+        .iter()
+        .filter(|chunk| chunk.kind == IDAT)
+        .flat_map(|chunk| chunk.data.iter().copied())
+        .collect();
+    if stream.is_empty() {
+        return Err(InflateError::InvalidHeader("PNG without IDAT chunks"));
+    }
+    Ok((header, stream))
+}
+
+/// Get the header of a PNG file and its filtered scanlines, each starting with its filter type.
+/// Image data longer than the header says fails with [`InflateError::OutputLimitExceeded`].
+pub fn scanlines(png: &[u8]) -> Result<(PngHeader, Vec<u8>)> {
+    let (header, stream) = idat_stream(png)?;
+    let len = header.filtered_len()?;
+    let options = InflateOptions::new()
+        .format(Format::Zlib)
+        .max_output(len)
+        .size_hint(len);
+    let data = inflate_to_vec_with_options(&stream, &options)?;
+    if data.len() < len {
+        return Err(InflateError::UnexpectedEof);
+    }
+    Ok((header, data))
+}
+
+/// Predict a byte from its left, upper and upper-left neighbours, whichever is closest to
+/// `left + up - up_left`. Defined in the PNG specification, section 9.4.
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let p = left as i16 + up as i16 - up_left as i16;
+    let (pa, pb, pc) = (
+        (p - left as i16).abs(),
+        (p - up as i16).abs(),
+        (p - up_left as i16).abs(),
+    );
+    if pa <= pb && pa <= pc {
+        left
+    } else if pb <= pc {
+        up
+    } else {
+        up_left
+    }
+}
+
+/// Undo the filters of the scanlines returned by [`scanlines`], dropping the filter type bytes.
+/// An interlaced image comes out as its reduced images one after the other, in the order of
+/// [`PngHeader::passes`].
+pub fn unfilter(header: &PngHeader, filtered: &[u8]) -> Result<Vec<u8>> {
+    let bpp = header.filter_distance();
+    let mut out = Vec::with_capacity(filtered.len());
+    let mut rows = filtered;
+    for (width, height) in header.passes() {
+        if width == 0 {
+            continue;
+        }
+        let row_len = header.row_len(width);
+        let pass_start = out.len();
+        for _ in 0..height {
+            let (&filter, rest) = rows.split_first().ok_or(InflateError::UnexpectedEof)?;
+            let row = rest.get(..row_len).ok_or(InflateError::UnexpectedEof)?;
+            rows = &rest[row_len..];
+            let start = out.len();
+            // The scanline before, or none for the first one of a pass.
+            let up_start = (start > pass_start).then(|| start - row_len);
+            for (i, &byte) in row.iter().enumerate() {
+                let left = if i >= bpp { out[start + i - bpp] } else { 0 };
+                let up = up_start.map_or(0, |up| out[up + i]);
+                let up_left = match up_start {
+                    Some(up) if i >= bpp => out[up + i - bpp],
+                    _ => 0,
+                };
+                let prediction = match filter {
+                    0 => 0,
+                    1 => left,
+                    2 => up,
+                    3 => ((left as u16 + up as u16) / 2) as u8,
+                    4 => paeth(left, up, up_left),
+                    _ => return Err(InflateError::InvalidHeader("Invalid PNG filter type")),
+                };
+                out.push(byte.wrapping_add(prediction));
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Get the header of a PNG file and its pixel data: the scanlines with their filters undone,
+/// packed as the header describes.
+pub fn decode(png: &[u8]) -> Result<(PngHeader, Vec<u8>)> {
+    let (header, filtered) = scanlines(png)?;
+    let pixels = unfilter(&header, &filtered)?;
+    Ok((header, pixels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::crc32;
+    use crate::container::wrap;
+    use crate::deflate::deflate_to_vec;
+
+    fn push_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+        png.extend((data.len() as u32).to_be_bytes());
+        png.extend(kind);
+        png.extend(data);
+        png.extend(crc32(&[&kind[..], data].concat()).to_be_bytes());
+    }
+
+    /// Build a PNG of the given filtered scanlines, splitting the zlib stream over IDAT chunks
+    /// of `idat_len` bytes.
+    fn build_png(header: &PngHeader, filtered: &[u8], idat_len: usize) -> Vec<u8> {
+        let mut ihdr = Vec::new();
+        ihdr.extend(header.width.to_be_bytes());
+        ihdr.extend(header.height.to_be_bytes());
+        ihdr.extend([
+            header.bit_depth,
+            header.color_type,
+            0,
+            0,
+            header.interlaced as u8,
+        ]);
+        let mut png = PNG_SIGNATURE.to_vec();
+        push_chunk(&mut png, &IHDR, &ihdr);
+        push_chunk(&mut png, b"tEXt", b"Comment\0not image data");
+        let stream = wrap(&deflate_to_vec(filtered), filtered, Format::Zlib);
+        for piece in stream.chunks(idat_len) {
+            push_chunk(&mut png, &IDAT, piece);
+        }
+        push_chunk(&mut png, &IEND, &[]);
+        png
+    }
+
+    const RGB: PngHeader = PngHeader {
+        width: 3,
+        height: 2,
+        bit_depth: 8,
+        color_type: 2,
+        interlaced: false,
+    };
+
+    #[test]
+    fn test_scanlines() {
+        let filtered = [
+            [0, 1, 2, 3, 4, 5, 6, 7, 8, 9].as_slice(),
+            &[2, 1, 1, 1, 1, 1, 1, 1, 1, 1],
+        ]
+        .concat();
+        let png = build_png(&RGB, &filtered, 5);
+        let (header, data) = scanlines(&png).unwrap();
+        assert_eq!(header, RGB);
+        assert_eq!(data, filtered);
+        let (_, pixels) = decode(&png).unwrap();
+        assert_eq!(
+            pixels,
+            [1, 2, 3, 4, 5, 6, 7, 8, 9, 2, 3, 4, 5, 6, 7, 8, 9, 10]
+        );
+    }
+
+    #[test]
+    fn test_unfilter_every_filter() {
+        let header = PngHeader {
+            width: 2,
+            height: 5,
+            ..RGB
+        };
+        // Row 0 is raw; the others encode the same pixels with each filter.
+        let pixels = [10u8, 20, 30, 200, 100, 50];
+        let mut filtered = vec![0];
+        filtered.extend(pixels);
+        let mut prev = pixels;
+        for filter in 1..=4u8 {
+            filtered.push(filter);
+            for i in 0..pixels.len() {
+                let left = if i >= 3 { pixels[i - 3] } else { 0 };
+                let up_left = if i >= 3 { prev[i - 3] } else { 0 };
+                let prediction = match filter {
+                    1 => left,
+                    2 => prev[i],
+                    3 => ((left as u16 + prev[i] as u16) / 2) as u8,
+                    _ => paeth(left, prev[i], up_left),
+                };
+                filtered.push(pixels[i].wrapping_sub(prediction));
+            }
+            prev = pixels;
+        }
+        assert_eq!(unfilter(&header, &filtered).unwrap(), pixels.repeat(5));
+
+        filtered[0] = 5;
+        assert_eq!(
+            unfilter(&header, &filtered),
+            Err(InflateError::InvalidHeader("Invalid PNG filter type"))
+        );
+    }
+
+    #[test]
+    fn test_paeth() {
+        assert_eq!(paeth(10, 20, 10), 20);
+        assert_eq!(paeth(20, 10, 10), 20);
+        assert_eq!(paeth(10, 10, 20), 10);
+        assert_eq!(paeth(0, 0, 0), 0);
+    }
+
+    #[test]
+    fn test_interlaced_passes() {
+        let header = PngHeader {
+            width: 5,
+            height: 3,
+            bit_depth: 1,
+            color_type: 0,
+            interlaced: true,
+        };
+        assert_eq!(
+            header.passes(),
+            [(1, 1), (1, 1), (2, 0), (1, 1), (3, 1), (2, 2), (5, 1)]
+        );
+        // One byte per scanline of up to 8 one-bit pixels, plus the filter type.
+        assert_eq!(header.filtered_len(), Ok(14));
+        let filtered: Vec<u8> = (0..14).map(|i| if i % 2 == 0 { 0 } else { i }).collect();
+        let png = build_png(&header, &filtered, 100);
+        let (_, pixels) = decode(&png).unwrap();
+        assert_eq!(pixels, [1, 3, 5, 7, 9, 11, 13]);
+    }
+
+    #[test]
+    fn test_png_errors() {
+        let filtered = [0u8; 20];
+        let png = build_png(&RGB, &filtered, 100);
+        assert_eq!(
+            scanlines(&png[1..]),
+            Err(InflateError::InvalidHeader("Invalid PNG signature"))
+        );
+        assert_eq!(
+            scanlines(&png[..png.len() - 1]),
+            Err(InflateError::UnexpectedEof)
+        );
+        let mut corrupted = png.clone();
+        corrupted[PNG_SIGNATURE.len() + 9] ^= 1;
+        assert!(matches!(
+            scanlines(&corrupted),
+            Err(InflateError::ChecksumMismatch { .. })
+        ));
+
+        let short = build_png(&RGB, &filtered[..19], 100);
+        assert_eq!(scanlines(&short), Err(InflateError::UnexpectedEof));
+        let long = build_png(&RGB, &[0; 21], 100);
+        assert_eq!(scanlines(&long), Err(InflateError::OutputLimitExceeded));
+
+        let paletted_rgb = PngHeader {
+            color_type: 3,
+            bit_depth: 16,
+            ..RGB
+        };
+        assert!(matches!(
+            scanlines(&build_png(&paletted_rgb, &filtered, 100)),
+            Err(InflateError::InvalidHeader(_))
+        ));
+    }
+}
@@ -0,0 +1,75 @@
+//! Ready-made option sets for common places DEFLATE data shows up.
+//! Each preset picks the container, strictness, window and tail handling the format calls for,
+//! so users don't have to know the subtleties of every format.
+
+use crate::container::Format;
+use crate::inflate::{InflateOptions, TailHandling};
+
+/// A named set of inflate options for a common use case.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    /// The concatenated IDAT chunks of a PNG image: a strict zlib stream with nothing after it.
+    PngIdat,
+    /// A `Content-Encoding: gzip` HTTP body.
+    /// Servers are known to append junk after the gzip member, so it is ignored.
+    HttpGzip,
+    /// A zlib-compressed git object. In pack files the next object follows right away,
+    /// so anything after the stream is ignored.
+    GitObject,
+    /// A permessage-deflate WebSocket message (RFC 7692): raw DEFLATE data whose trailing
+    /// sync flush marker was stripped, decoded with the negotiated window.
+    WebSocketMessage {
+        /// The negotiated `max_window_bits`, from 8 to 15.
+        max_window_bits: u8,
+    },
+}
+
+impl Preset {
+    /// Get the inflate options for this preset.
+    pub fn options(&self) -> InflateOptions {
+        let options = InflateOptions::new();
+        match *self {
+            Preset::PngIdat => options.format(Format::Zlib).tail(TailHandling::Reject),
+            Preset::HttpGzip => options.format(Format::Gzip),
+            Preset::GitObject => options.format(Format::Zlib),
+            Preset::WebSocketMessage { max_window_bits } => options
+                .window_bits(max_window_bits)
+                .tail(TailHandling::SyncFlush),
+        }
+    }
+}
+
+impl From<Preset> for InflateOptions {
+    fn from(preset: Preset) -> Self {
+        preset.options()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inflate::inflate_to_vec_with_options;
+
+    #[test]
+    fn test_preset_options() {
+        assert_eq!(Preset::PngIdat.options().format, Format::Zlib);
+        assert_eq!(Preset::HttpGzip.options().format, Format::Gzip);
+        let options = InflateOptions::from(Preset::WebSocketMessage {
+            max_window_bits: 10,
+        });
+        assert_eq!(options.window_size, 1024);
+        assert_eq!(options.tail, TailHandling::SyncFlush);
+    }
+
+    #[test]
+    fn test_preset_png_rejects_trailing_data() {
+        let raw = b"scanline scanline scanline".to_vec();
+        let mut data = miniz_oxide::deflate::compress_to_vec_zlib(&raw, 9);
+        let options = Preset::PngIdat.options();
+        assert_eq!(inflate_to_vec_with_options(&data, &options).unwrap(), raw);
+        data.extend(b"junk");
+        assert!(inflate_to_vec_with_options(&data, &options).is_err());
+        let options = Preset::GitObject.options();
+        assert_eq!(inflate_to_vec_with_options(&data, &options).unwrap(), raw);
+    }
+}
@@ -0,0 +1,230 @@
+//! Inputs and checks for round-trip testing the compressor against the decoder.
+//!
+//! The property tests in `tests/roundtrip_test.rs` draw inputs from [`sample`], which derives
+//! them from a seed: a failure reported with its seed can be replayed exactly, without the
+//! property test runner. [`check_round_trip`] compresses and decompresses an input at a
+//! level and locates the first byte that came back wrong, and [`minimize`] shrinks a failing
+//! input, e.g. one from the fuzzer, to a short one that still fails.
+
+use crate::deflate::{deflate_to_vec_with_level, MAX_MATCH_LEN};
+use crate::error::InflateError;
+use crate::inflate::{inflate_to_vec, MAX_DISTANCE};
+use alloc::vec::Vec;
+use core::fmt;
+
+/// The kinds of inputs [`sample`] generates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleKind {
+    /// Uniformly random bytes, which don't compress.
+    Random,
+    /// Words from a small vocabulary, which compress well with short matches.
+    Text,
+    /// Random binary data repeating earlier pieces of itself at long distances, with matches
+    /// longer than `MAX_MATCH_LEN` and runs of a single byte.
+    LongMatches,
+}
+
+impl SampleKind {
+    /// Every kind of input.
+    pub const ALL: [SampleKind; 3] = [
+        SampleKind::Random,
+        SampleKind::Text,
+        SampleKind::LongMatches,
+    ];
+}
+
+/// A small, fast pseudo-random generator, so that samples only depend on their seed.
+/// See <https://prng.di.unimi.it/splitmix64.c>.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Get a number below `n`, which must not be 0.
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+const WORDS: [&[u8]; 12] = [
+    b"the", b"DEFLATE", b"stream", b"of", b"Huffman", b"codes", b"and", b"a", b"window",
+    b"inflate", b"block", b"match",
+];
+
+/// Generate `len` bytes of the given kind. The same seed always gives the same bytes.
+pub fn sample(kind: SampleKind, seed: u64, len: usize) -> Vec<u8> {
+    let mut rng = SplitMix64(seed);
+    let mut data = Vec::with_capacity(len);
+    match kind {
+        SampleKind::Random => data.extend((0..len).map(|_| rng.next() as u8)),
+        SampleKind::Text => {
+            while data.len() < len {
+                data.extend_from_slice(WORDS[rng.below(WORDS.len())]);
+                data.push(if rng.below(8) == 0 { b'\n' } else { b' ' });
+            }
+        }
+        SampleKind::LongMatches => {
+            while data.len() < len {
+                match rng.below(4) {
+                    // A copy of an earlier piece, up to the largest distance.
+                    0 | 1 if !data.is_empty() => {
+                        let distance = 1 + rng.below(data.len().min(MAX_DISTANCE));
+                        let length = 1 + rng.below(4 * MAX_MATCH_LEN);
+                        let start = data.len() - distance;
+                        // Byte by byte: the copy overlaps itself when it is longer than the
+                        // distance, as DEFLATE matches do.
+                        for i in 0..length {
+                            data.push(data[start + i]);
+                        }
+                    }
+                    2 => {
+                        let byte = rng.next() as u8;
+                        let length = 1 + rng.below(2 * MAX_MATCH_LEN);
+                        data.extend((0..length).map(|_| byte));
+                    }
+                    _ => {
+                        let length = 1 + rng.below(64);
+                        data.extend((0..length).map(|_| rng.next() as u8));
+                    }
+                }
+            }
+        }
+    }
+    data.truncate(len);
+    data
+}
+
+/// How a round trip went wrong.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoundTripFailure {
+    /// The compressed data did not decompress.
+    Decode(InflateError),
+    /// The decompressed data differs from the input, starting at this offset.
+    /// It is the length of the shorter of the two when one is a prefix of the other.
+    Mismatch { offset: usize },
+}
+
+impl fmt::Display for RoundTripFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RoundTripFailure::Decode(error) => write!(f, "Decompression failed: {}", error),
+            RoundTripFailure::Mismatch { offset } => {
+                write!(f, "Decompressed data differs at offset {}", offset)
+            }
+        }
+    }
+}
+
+/// Find the first offset at which two byte strings differ, if they do.
+pub fn first_difference(a: &[u8], b: &[u8]) -> Option<usize> {
+    match a.iter().zip(b).position(|(x, y)| x != y) {
+        Some(offset) => Some(offset),
+        None if a.len() != b.len() => Some(a.len().min(b.len())),
+        None => None,
+    }
+}
+
+/// Compress the data at the given level and decompress it again, checking that the data
+/// comes back unchanged.
+///
+/// # Panics
+///
+/// Panics if the level is above `MAX_LEVEL`.
+pub fn check_round_trip(data: &[u8], level: u8) -> Result<(), RoundTripFailure> {
+    let compressed = deflate_to_vec_with_level(data, level);
+    let output = inflate_to_vec(&compressed).map_err(RoundTripFailure::Decode)?;
+    match first_difference(data, &output) {
+        Some(offset) => Err(RoundTripFailure::Mismatch { offset }),
+        None => Ok(()),
+    }
+}
+
+/// Shrink an input `fails` returns true for to a shorter one it still returns true for.
+/// Ever smaller chunks are cut out of the input, keeping every cut after which it still
+/// fails, down to single bytes; the result is then 1-minimal: removing any one byte of it
+/// makes it pass. The input is returned as is if it doesn't fail.
+pub fn minimize(data: &[u8], mut fails: impl FnMut(&[u8]) -> bool) -> Vec<u8> {
+    let mut data = data.to_vec();
+    if !fails(&data) {
+        return data;
+    }
+    let mut chunk_len = data.len().div_ceil(2);
+    while chunk_len > 0 {
+        let mut start = 0;
+        while start < data.len() {
+            let end = (start + chunk_len).min(data.len());
+            let mut candidate = data[..start].to_vec();
+            candidate.extend_from_slice(&data[end..]);
+            if fails(&candidate) {
+                data = candidate;
+            } else {
+                start = end;
+            }
+        }
+        chunk_len /= 2;
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deflate::MAX_LEVEL;
+
+    #[test]
+    fn test_sample_is_deterministic() {
+        for kind in SampleKind::ALL {
+            let data = sample(kind, 42, 5000);
+            assert_eq!(data.len(), 5000);
+            assert_eq!(sample(kind, 42, 5000), data);
+            assert_ne!(sample(kind, 43, 5000), data);
+            assert_eq!(sample(kind, 42, 100), data[..100]);
+        }
+    }
+
+    #[test]
+    fn test_sample_compressibility() {
+        let compressed_len = |kind| deflate_to_vec_with_level(&sample(kind, 7, 20000), 6).len();
+        assert!(compressed_len(SampleKind::Random) > 20000);
+        assert!(compressed_len(SampleKind::Text) < 10000);
+        assert!(compressed_len(SampleKind::LongMatches) < 10000);
+    }
+
+    #[test]
+    fn test_check_round_trip() {
+        for kind in SampleKind::ALL {
+            for level in 0..=MAX_LEVEL {
+                assert_eq!(
+                    check_round_trip(&sample(kind, level as u64, 3000), level),
+                    Ok(())
+                );
+            }
+        }
+        assert_eq!(check_round_trip(b"", 6), Ok(()));
+    }
+
+    #[test]
+    fn test_first_difference() {
+        assert_eq!(first_difference(b"abc", b"abc"), None);
+        assert_eq!(first_difference(b"abc", b"abd"), Some(2));
+        assert_eq!(first_difference(b"abc", b"ab"), Some(2));
+    }
+
+    #[test]
+    fn test_minimize() {
+        // Fails whenever both an `x` and a later `y` are present.
+        let fails = |data: &[u8]| {
+            data.iter()
+                .position(|&b| b == b'x')
+                .is_some_and(|i| data[i..].contains(&b'y'))
+        };
+        assert_eq!(minimize(b"abxcdefgyhij", fails), b"xy");
+        assert_eq!(minimize(b"abc", fails), b"abc");
+    }
+}
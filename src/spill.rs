@@ -0,0 +1,87 @@
+//! Decompress into a temporary file instead of memory.
+//!
+//! Outputs can be far larger than the available RAM: with the spilling sink only the window
+//! of recent output needed for back-references stays in memory, everything else goes to disk.
+
+use crate::stream::InflateWriter;
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Result, Seek, SeekFrom};
+
+/// Decompressed data stored in a temporary file.
+///
+/// The file is deleted once the handle is dropped. The handle reads the data from the start
+/// and can seek anywhere in it.
+#[derive(Debug)]
+pub struct SpilledOutput {
+    file: File,
+    len: u64,
+}
+
+impl SpilledOutput {
+    /// Get the length of the decompressed data in bytes.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Check if the decompressed data is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get the underlying temporary file.
+    pub fn into_file(self) -> File {
+        self.file
+    }
+}
+
+impl Read for SpilledOutput {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.file.read(buf)
+    }
+}
+
+impl Seek for SpilledOutput {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        self.file.seek(pos)
+    }
+}
+
+/// Inflate the raw DEFLATE stream read from `input` into a temporary file.
+/// Neither the compressed nor the decompressed data is held in memory as a whole.
+pub fn inflate_to_spill<R: Read>(mut input: R) -> Result<SpilledOutput> {
+    let mut writer = InflateWriter::new(BufWriter::new(tempfile::tempfile()?));
+    io::copy(&mut input, &mut writer)?;
+    let mut file = writer.finish()?.into_inner().map_err(|e| e.into_error())?;
+    let len = file.stream_position()?;
+    file.rewind()?;
+    Ok(SpilledOutput { file, len })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inflate_to_spill() {
+        let raw: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = miniz_oxide::deflate::compress_to_vec(&raw, 6);
+        let mut spilled = inflate_to_spill(&compressed[..]).unwrap();
+        assert_eq!(spilled.len(), raw.len() as u64);
+
+        let mut output = Vec::new();
+        spilled.read_to_end(&mut output).unwrap();
+        assert_eq!(output, raw);
+
+        spilled.seek(SeekFrom::Start(100_000)).unwrap();
+        let mut buf = [0; 4];
+        spilled.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, raw[100_000..100_004]);
+    }
+
+    #[test]
+    fn test_inflate_to_spill_truncated() {
+        let compressed = miniz_oxide::deflate::compress_to_vec(b"cut short, cut short", 6);
+        let err = inflate_to_spill(&compressed[..compressed.len() - 2]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,318 @@
+//! Decode a DEFLATE stream one step at a time, like a debugger.
+//!
+//! An [`InflateStepper`] moves forward by one block header, one symbol of a compressed block or
+//! one piece of a stored block per [`InflateStepper::step`], and shows the fields each step
+//! decoded. Breakpoints let [`InflateStepper::run_until_break`] skip straight to the interesting
+//! moment instead of stepping through every symbol before it.
+
+use crate::bit_stream::BitReader;
+use crate::disasm::{Field, FieldKind};
+use crate::error::{InflateError, Result};
+use crate::inflate::{inflate_step, BlockState, InflateOptions, InflateState, TailHandling};
+use alloc::vec::Vec;
+
+/// A condition on which [`InflateStepper::run_until_break`] stops.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// Break on the step that outputs the byte at this offset.
+    OutputOffset(usize),
+    /// Break once the header of the block with this index is decoded.
+    Block(usize),
+    /// Break on a back-reference reaching further back than this many bytes.
+    DistanceOver(usize),
+    /// Break on any error. Errors always stop the stepper; this also breaks on
+    /// back-references outside the window that permissive validation lets through.
+    Error,
+}
+
+/// Why [`InflateStepper::run_until_break`] stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// The last step hit this breakpoint.
+    Breakpoint(Breakpoint),
+    /// The final block has been decoded.
+    Finished,
+    /// Decoding failed; the stepper cannot go any further.
+    Error(InflateError),
+}
+
+/// A raw DEFLATE decoder that can be stepped through symbol by symbol.
+/// Every field decoded is kept, as in a disassembly, along with the whole output.
+#[derive(Debug)]
+pub struct InflateStepper<'a> {
+    data: &'a [u8],
+    bit_reader: BitReader<'a>,
+    state: InflateState,
+    /// Position within the current block.
+    block: BlockState,
+    /// Index of the first field decoded by the last step.
+    step_start: usize,
+    /// The error decoding stopped at, if any.
+    error: Option<InflateError>,
+    breakpoints: Vec<Breakpoint>,
+}
+
+impl<'a> InflateStepper<'a> {
+    /// Create a stepper at the start of a raw DEFLATE stream.
+    pub fn new(data: &'a [u8]) -> Self {
+        Self::configured(data, &InflateOptions::default())
+    }
+
+    /// Create a stepper configured by the given options.
+    /// The stream is always raw DEFLATE and nothing after it is looked at, so the format must
+    /// be [`Format::Raw`](crate::container::Format::Raw) and the tail handling
+    /// [`TailHandling::Ignore`]; anything else fails with [`InflateError::UnsupportedOption`].
+    /// All the other options are honored.
+    pub fn with_options(data: &'a [u8], options: &InflateOptions) -> Result<Self> {
+        options.check_raw(&[TailHandling::Ignore])?;
+        Ok(Self::configured(data, options))
+    }
+
+    /// Create the decoder with options already checked.
+    fn configured(data: &'a [u8], options: &InflateOptions) -> Self {
+        let mut state = InflateState::from_options(options);
+        if let Some(dictionary) = &options.dictionary {
+            state.set_dictionary(dictionary);
+        }
+        state.trace = Some(Vec::new());
+        Self {
+            data,
+            bit_reader: BitReader::new_strict(data),
+            state,
+            block: BlockState::Header,
+            step_start: 0,
+            error: None,
+            breakpoints: Vec::new(),
+        }
+    }
+
+    /// Add a condition for [`InflateStepper::run_until_break`] to stop on.
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.push(breakpoint);
+    }
+
+    /// Remove all breakpoints.
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    /// Get the breakpoints set.
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    /// Decode the next step of the stream.
+    /// Returns whether the stream is finished. Once a step fails, every later step fails
+    /// with the same error.
+    pub fn step(&mut self) -> Result<bool> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        if self.is_finished() {
+            return Ok(true);
+        }
+        if self.data.is_empty() {
+            self.error = Some(InflateError::EmptyInput);
+            return Err(InflateError::EmptyInput);
+        }
+        self.step_start = self.fields().len();
+        match inflate_step(&mut self.bit_reader, &mut self.state, &self.block) {
+            Ok(next) => {
+                if let Some(next) = next {
+                    self.block = next;
+                }
+                Ok(self.is_finished())
+            }
+            Err(error) => {
+                self.error = Some(error);
+                Err(self.state.surface(error))
+            }
+        }
+    }
+
+    /// Step until a breakpoint is hit, the stream ends or decoding fails.
+    pub fn run_until_break(&mut self) -> StopReason {
+        loop {
+            let before = (
+                self.state.total_out(),
+                self.state.block_stats.blocks,
+                self.state.warnings.len(),
+            );
+            match self.step() {
+                Err(error) => return StopReason::Error(error),
+                Ok(finished) => {
+                    let hit = self.breakpoints.iter().find(|b| self.is_hit(**b, before));
+                    if let Some(&breakpoint) = hit {
+                        return StopReason::Breakpoint(breakpoint);
+                    }
+                    if finished {
+                        return StopReason::Finished;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Check if the last step hit a breakpoint, given the output length, the number of blocks
+    /// and the number of warnings before it.
+    fn is_hit(&self, breakpoint: Breakpoint, before: (usize, usize, usize)) -> bool {
+        let (output_len, blocks, warnings) = before;
+        match breakpoint {
+            Breakpoint::OutputOffset(offset) => {
+                (output_len..self.state.total_out()).contains(&offset)
+            }
+            Breakpoint::Block(index) => blocks <= index && index < self.state.block_stats.blocks,
+            Breakpoint::DistanceOver(max) => self.last_step_fields().iter().any(|field| {
+                matches!(field.kind, FieldKind::DistanceCode { distance, .. } if distance > max)
+            }),
+            Breakpoint::Error => self.state.warnings.len() > warnings,
+        }
+    }
+
+    /// Check if the final block of the stream has been decoded.
+    pub fn is_finished(&self) -> bool {
+        matches!(self.block, BlockState::Done)
+    }
+
+    /// Get the error decoding stopped at, if any.
+    pub fn error(&self) -> Option<InflateError> {
+        self.error
+    }
+
+    /// Get the bit offset of the next step.
+    pub fn bit_offset(&self) -> usize {
+        self.bit_reader.bits_consumed()
+    }
+
+    /// Get the index of the current block, if one has started.
+    pub fn block_index(&self) -> Option<usize> {
+        self.state.block_stats.blocks.checked_sub(1)
+    }
+
+    /// Get the data decompressed so far.
+    pub fn output(&self) -> &[u8] {
+        self.state.decompressed()
+    }
+
+    /// Get every field decoded so far, in stream order.
+    pub fn fields(&self) -> &[Field] {
+        self.state.trace.as_deref().unwrap_or_default()
+    }
+
+    /// Get the fields decoded by the last step.
+    pub fn last_step_fields(&self) -> &[Field] {
+        &self.fields()[self.step_start..]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::container::Format;
+    use crate::deflate::{deflate_fixed_tokens_to_vec, Token};
+    use crate::inflate::MatchValidation;
+    use alloc::vec;
+
+    /// A stored block holding "abc", then a final fixed block with "d", a match of length 4
+    /// at distance 2 and another match of length 3 at distance 6.
+    fn two_blocks() -> Vec<u8> {
+        let mut data = vec![0b000, 3, 0, !3, !0, b'a', b'b', b'c'];
+        data.extend(deflate_fixed_tokens_to_vec(&[
+            Token::Literal(b'd'),
+            Token::Match {
+                length: 4,
+                distance: 2,
+            },
+            Token::Match {
+                length: 3,
+                distance: 6,
+            },
+        ]));
+        data
+    }
+
+    #[test]
+    fn test_stepper_steps_through_stream() {
+        let data = two_blocks();
+        let mut stepper = InflateStepper::new(&data);
+        let mut steps = 0;
+        while !stepper.step().unwrap() {
+            steps += 1;
+        }
+        // Two headers, a stored piece and four symbols, the last of which finishes the stream.
+        assert_eq!(steps, 6);
+        assert_eq!(stepper.output(), b"abcdcdcdcdc");
+        assert_eq!(
+            stepper.output(),
+            crate::inflate::inflate_to_vec(&data).unwrap()
+        );
+        assert_eq!(stepper.last_step_fields()[0].kind, FieldKind::EndOfBlock);
+        assert_eq!(stepper.run_until_break(), StopReason::Finished);
+    }
+
+    #[test]
+    fn test_stepper_breakpoints() {
+        let data = two_blocks();
+        let mut stepper = InflateStepper::new(&data);
+        stepper.add_breakpoint(Breakpoint::Block(1));
+        stepper.add_breakpoint(Breakpoint::OutputOffset(5));
+        stepper.add_breakpoint(Breakpoint::DistanceOver(5));
+
+        assert_eq!(
+            stepper.run_until_break(),
+            StopReason::Breakpoint(Breakpoint::Block(1))
+        );
+        assert_eq!(stepper.block_index(), Some(1));
+        assert_eq!(stepper.output(), b"abc");
+        assert_eq!(
+            stepper.run_until_break(),
+            StopReason::Breakpoint(Breakpoint::OutputOffset(5))
+        );
+        assert_eq!(stepper.output(), b"abcdcdcd");
+        assert_eq!(
+            stepper.run_until_break(),
+            StopReason::Breakpoint(Breakpoint::DistanceOver(5))
+        );
+        assert!(stepper.last_step_fields().iter().any(|f| f.kind
+            == FieldKind::DistanceCode {
+                code: 4,
+                distance: 6
+            }));
+        assert_eq!(stepper.run_until_break(), StopReason::Finished);
+    }
+
+    #[test]
+    fn test_stepper_errors() {
+        let data = two_blocks();
+        let mut stepper = InflateStepper::new(&data[..data.len() - 1]);
+        stepper.add_breakpoint(Breakpoint::Error);
+        assert_eq!(
+            stepper.run_until_break(),
+            StopReason::Error(InflateError::UnexpectedEof)
+        );
+        assert_eq!(stepper.step(), Err(InflateError::UnexpectedEof));
+        assert_eq!(
+            InflateStepper::new(&[]).run_until_break(),
+            StopReason::Error(InflateError::EmptyInput)
+        );
+
+        // Without the stored block, the first match reaches before the start of the output.
+        let options = InflateOptions {
+            validation: MatchValidation::Permissive,
+            ..Default::default()
+        };
+        let mut stepper = InflateStepper::with_options(&data[8..], &options).unwrap();
+        stepper.add_breakpoint(Breakpoint::Error);
+        assert_eq!(
+            stepper.run_until_break(),
+            StopReason::Breakpoint(Breakpoint::Error)
+        );
+        assert_eq!(stepper.output().len(), 5);
+
+        let options = InflateOptions::new().format(Format::Gzip);
+        assert!(matches!(
+            InflateStepper::with_options(&data, &options),
+            Err(InflateError::UnsupportedOption(_))
+        ));
+    }
+}
@@ -0,0 +1,903 @@
+//! Streaming adapters around the DEFLATE decoder.
+//! These types accept compressed data piece by piece instead of requiring the whole input up front.
+//!
+//! Output buffering can be bounded with a high-water mark: an [`Inflater`] stops decoding once
+//! that much output is waiting to be taken, and an [`InflateReader`] only pulls compressed input
+//! once its consumer has drained the output, so a slow consumer holds back the source instead of
+//! letting decompressed data pile up in memory.
+//!
+//! With the `futures` feature, [`AsyncInflateReader`] does the same over an `AsyncRead` source.
+
+use crate::bit_stream::BitReader;
+use crate::error::{self, InflateError};
+use crate::inflate::{
+    inflate_step, BlockState, BlockStats, InflateOptions, InflateState, TailHandling, MAX_DISTANCE,
+};
+#[cfg(feature = "futures")]
+use futures_io::AsyncRead;
+use std::io::{ErrorKind, Read, Result, Write};
+#[cfg(feature = "futures")]
+use std::pin::Pin;
+#[cfg(feature = "futures")]
+use std::task::{ready, Context, Poll};
+
+/// What an [`Inflater`] needs after consuming the input it was fed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InflateStatus {
+    /// The stream is not over yet; feed more input.
+    NeedsInput,
+    /// The final block has been decoded. Further input is ignored.
+    Finished,
+    /// The output reached the high-water mark. Take some of it, then feed more input or
+    /// an empty slice to carry on with the input already fed.
+    OutputFull,
+}
+
+/// A push-style decoder for a raw DEFLATE stream arriving in pieces, e.g. from a network.
+///
+/// The decoder stops wherever the input runs out, even in the middle of a block or symbol,
+/// and resumes from there on the next [`Inflater::feed`]: the Huffman tables of the current
+/// block, the bits of an incomplete symbol and the window are all kept across calls.
+/// Decompressed data accumulates until it is collected with [`Inflater::take_output`],
+/// or up to the high-water mark set with [`Inflater::set_output_high_water_mark`].
+#[derive(Debug)]
+pub struct Inflater {
+    /// Compressed bytes that have not been fully consumed yet.
+    input: Vec<u8>,
+    /// Bit offset of the next undecoded bit within `input`.
+    bit_offset: usize,
+    /// Decoder state, holding the recent output used to resolve back-references.
+    state: InflateState,
+    /// Position within the current block.
+    block: BlockState,
+    /// Start of the output that has not been taken yet.
+    pending: usize,
+    /// Amount of untaken output at which decoding pauses.
+    high_water_mark: Option<usize>,
+    /// Whether input after the end of the stream is an error.
+    reject_trailing: bool,
+}
+
+impl Default for Inflater {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Inflater {
+    /// Create a decoder at the start of a stream.
+    pub fn new() -> Self {
+        Self {
+            input: Vec::new(),
+            bit_offset: 0,
+            state: InflateState::new(),
+            block: BlockState::Header,
+            pending: 0,
+            high_water_mark: None,
+            reject_trailing: false,
+        }
+    }
+
+    /// Create a decoder configured by the given options.
+    /// The stream is always raw DEFLATE, so the format must be
+    /// [`Format::Raw`](crate::container::Format::Raw) and the tail handling either
+    /// [`TailHandling::Ignore`] or [`TailHandling::Reject`], which makes input fed after the
+    /// end of the stream an error; anything else fails with
+    /// [`InflateError::UnsupportedOption`]. All the other options are honored.
+    pub fn with_options(options: &InflateOptions) -> error::Result<Self> {
+        options.check_raw(&[TailHandling::Ignore, TailHandling::Reject])?;
+        let mut state = InflateState::from_options(options);
+        if let Some(dictionary) = &options.dictionary {
+            state.set_dictionary(dictionary);
+        }
+        Ok(Self {
+            pending: state.output.len(),
+            state,
+            reject_trailing: options.tail == TailHandling::Reject,
+            ..Self::new()
+        })
+    }
+
+    /// Get the block counters of the stream decoded so far.
+    pub fn block_stats(&self) -> BlockStats {
+        self.state.block_stats
+    }
+
+    /// Pause decoding whenever at least `mark` bytes of output are waiting to be taken,
+    /// or never with `None`, the default.
+    /// The output may overshoot the mark by one piece: a match of at most 258 bytes, or the
+    /// part of a stored block that was already fed.
+    pub fn set_output_high_water_mark(&mut self, mark: Option<usize>) {
+        self.high_water_mark = mark;
+    }
+
+    /// Decode as much of the stream as the input fed so far allows, up to the high-water mark.
+    /// Errors are reported as soon as they are certain; a problem that more input could still
+    /// resolve is left for later calls.
+    pub fn feed(&mut self, input: &[u8]) -> error::Result<InflateStatus> {
+        if !self.is_finished() {
+            self.input.extend_from_slice(input);
+            self.decode_available(false)?;
+        } else if self.reject_trailing {
+            self.input.extend_from_slice(input);
+        }
+        self.check_tail()?;
+        Ok(self.status())
+    }
+
+    /// Declare that no more input will arrive and decode the rest of the stream.
+    /// Fails if the stream ends before its final block, with [`InflateError::EmptyInput`]
+    /// if no data was fed at all.
+    ///
+    /// With a high-water mark set, this stops at the mark like [`Inflater::feed`]: take the
+    /// output and call it again until the stream [is finished](Inflater::is_finished).
+    pub fn finish(&mut self) -> error::Result<()> {
+        // Nothing is consumed before a block starts, so no block and no input means no data at all.
+        if self.state.block_stats.blocks == 0 && self.input.is_empty() {
+            return Err(self.state.surface(InflateError::EmptyInput));
+        }
+        self.decode_available(true)?;
+        if !self.is_finished() && !self.is_output_full() {
+            return Err(self.state.surface(InflateError::UnexpectedEof));
+        }
+        self.check_tail()
+    }
+
+    /// Fail if input follows the end of the stream and the options reject it.
+    fn check_tail(&self) -> error::Result<()> {
+        if self.reject_trailing && !self.remaining_input().is_empty() {
+            return Err(InflateError::TrailingData);
+        }
+        Ok(())
+    }
+
+    /// Check if the final block of the stream has been decoded.
+    pub fn is_finished(&self) -> bool {
+        matches!(self.block, BlockState::Done)
+    }
+
+    /// Check if the untaken output reached the high-water mark.
+    fn is_output_full(&self) -> bool {
+        self.high_water_mark
+            .is_some_and(|mark| self.output().len() >= mark)
+    }
+
+    fn status(&self) -> InflateStatus {
+        if self.is_finished() {
+            InflateStatus::Finished
+        } else if self.is_output_full() {
+            InflateStatus::OutputFull
+        } else {
+            InflateStatus::NeedsInput
+        }
+    }
+
+    /// Get the decompressed data that has not been taken yet.
+    pub fn output(&self) -> &[u8] {
+        &self.state.output[self.pending..]
+    }
+
+    /// Get the input fed after the end of the stream, once it is finished: the bytes left
+    /// unread, where whatever follows the stream starts.
+    /// The stream ends on a byte boundary, so this starts at the byte after its last bit.
+    pub fn remaining_input(&self) -> &[u8] {
+        if !self.is_finished() {
+            return &[];
+        }
+        &self.input[self.bit_offset.div_ceil(8)..]
+    }
+
+    /// Take the decompressed data produced since the last call.
+    pub fn take_output(&mut self) -> Vec<u8> {
+        let output = self.output().to_vec();
+        // Back-references resolve from the window from now on.
+        self.state.discard_output(0);
+        self.pending = 0;
+        output
+    }
+
+    /// Copy as much of the untaken output as fits into `buf` and mark it as taken.
+    /// Returns the number of bytes copied.
+    fn read_output(&mut self, buf: &mut [u8]) -> usize {
+        let output = self.output();
+        let n = output.len().min(buf.len());
+        buf[..n].copy_from_slice(&output[..n]);
+        self.consume_output(n);
+        n
+    }
+
+    /// Mark the first `n` bytes of the untaken output as taken.
+    ///
+    /// # Panics
+    /// Panics if `n` is larger than the untaken output.
+    pub fn consume_output(&mut self, n: usize) {
+        assert!(
+            n <= self.output().len(),
+            "consumed more output than available"
+        );
+        self.pending += n;
+        // Move the taken output into the window in batches, not on every small read.
+        if self.pending >= MAX_DISTANCE {
+            let untaken = self.state.output.len() - self.pending;
+            self.state.discard_output(untaken);
+            self.pending = 0;
+        }
+    }
+
+    /// Decode what the input fed so far allows, for a reader pulling its input from a source.
+    /// Returns whether more input is needed before any output is available; `source_eof`
+    /// says the source has none left, in which case the rest of the stream is decoded.
+    fn needs_input(&mut self, source_eof: bool) -> error::Result<bool> {
+        while self.output().is_empty() && !self.is_finished() {
+            if source_eof {
+                self.finish()?;
+                continue;
+            }
+            // Carry on with the input held back by the high-water mark first.
+            self.feed(&[])?;
+            return Ok(self.output().is_empty());
+        }
+        Ok(false)
+    }
+
+    /// Decode every piece of the stream that is completely available in the input buffer.
+    /// When `at_end` is set, no more input will arrive, so the last piece is decoded
+    /// with whatever bits are left.
+    fn decode_available(&mut self, at_end: bool) -> error::Result<()> {
+        let mut bit_reader = BitReader::new_strict(&self.input);
+        bit_reader.advance(self.bit_offset);
+        while !self.is_finished() && !self.is_output_full() && !bit_reader.eof() {
+            let start = self.state.output.len();
+            let block_stats = self.state.block_stats;
+            match inflate_step(&mut bit_reader, &mut self.state, &self.block) {
+                // Without hitting the end of the input, every bit of the piece was real data.
+                Ok(next) if at_end || !bit_reader.eof() => {
+                    if let Some(next) = next {
+                        self.block = next;
+                    }
+                    self.bit_offset = bit_reader.bits_consumed();
+                }
+                Err(e) if !bit_reader.eof() => return Err(self.state.surface(e)),
+                // The error may come from the zeros read past the end of the input.
+                Err(_) if at_end => return Err(self.state.surface(InflateError::UnexpectedEof)),
+                // The piece is incomplete, roll it back and wait for more input.
+                _ => {
+                    self.state.output.truncate(start);
+                    self.state.block_stats = block_stats;
+                    break;
+                }
+            }
+        }
+
+        // Drop the consumed input.
+        self.input.drain(..self.bit_offset / 8);
+        self.bit_offset %= 8;
+        Ok(())
+    }
+}
+
+/// Size of the chunks an [`InflateReader`] pulls from its source.
+const INPUT_CHUNK_LEN: usize = 4096;
+
+/// A reader that inflates the compressed data read from another reader.
+///
+/// Compressed input is only pulled from the source once the output decoded so far has been
+/// read, and decoding pauses at the high-water mark, so the data buffered in memory stays
+/// bounded however well the stream compresses and however slowly it is read.
+#[derive(Debug)]
+pub struct InflateReader<R: Read> {
+    inner: R,
+    inflater: Inflater,
+    /// Whether the source reached its end.
+    inner_eof: bool,
+}
+
+impl<R: Read> InflateReader<R> {
+    /// Create a new InflateReader over the compressed data of `inner`, buffering at most
+    /// about `MAX_DISTANCE` bytes of output.
+    pub fn new(inner: R) -> Self {
+        Self::with_high_water_mark(inner, MAX_DISTANCE)
+    }
+
+    /// Create a new InflateReader that pauses decoding once `mark` bytes of output are
+    /// waiting to be read.
+    pub fn with_high_water_mark(inner: R, mark: usize) -> Self {
+        Self::with_inflater(inner, Inflater::new(), mark)
+    }
+
+    /// Create a new InflateReader decoding with the given options, as
+    /// [`Inflater::with_options`] does, and failing on the same options.
+    pub fn with_options(inner: R, options: &InflateOptions) -> Result<Self> {
+        Ok(Self::with_inflater(
+            inner,
+            Inflater::with_options(options)?,
+            MAX_DISTANCE,
+        ))
+    }
+
+    fn with_inflater(inner: R, mut inflater: Inflater, mark: usize) -> Self {
+        inflater.set_output_high_water_mark(Some(mark.max(1)));
+        Self {
+            inner,
+            inflater,
+            inner_eof: false,
+        }
+    }
+
+    /// Get a reference to the wrapped reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the wrapped reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Check if the final block of the stream has been decoded.
+    pub fn is_finished(&self) -> bool {
+        self.inflater.is_finished()
+    }
+
+    /// Get the number of decompressed bytes waiting to be read.
+    pub fn buffered_output_len(&self) -> usize {
+        self.inflater.output().len()
+    }
+
+    /// Make some output available, pulling input from the source only if the input already
+    /// pulled does not decode any further.
+    fn fill_output(&mut self) -> Result<()> {
+        let mut chunk = [0; INPUT_CHUNK_LEN];
+        while self.inflater.needs_input(self.inner_eof)? {
+            match self.inner.read(&mut chunk) {
+                Ok(0) => self.inner_eof = true,
+                Ok(n) => {
+                    self.inflater.feed(&chunk[..n])?;
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for InflateReader<R> {
+    /// Read decompressed data. Data following the final block is ignored.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        self.fill_output()?;
+        Ok(self.inflater.read_output(buf))
+    }
+}
+
+/// The asynchronous counterpart of [`InflateReader`]: an `AsyncRead` inflating the compressed
+/// data read from another `AsyncRead`, e.g. the body of an HTTP response.
+///
+/// It pulls input and buffers output exactly as [`InflateReader`] does, decoding with the same
+/// resumable [`Inflater`], and only waits on the source when the output is drained. The traits
+/// are those of futures-io; wrap tokio types with the compatibility layer of `tokio-util`.
+#[cfg(feature = "futures")]
+#[derive(Debug)]
+pub struct AsyncInflateReader<R: AsyncRead + Unpin> {
+    inner: R,
+    inflater: Inflater,
+    /// Whether the source reached its end.
+    inner_eof: bool,
+}
+
+#[cfg(feature = "futures")]
+impl<R: AsyncRead + Unpin> AsyncInflateReader<R> {
+    /// Create a new AsyncInflateReader over the compressed data of `inner`, buffering at most
+    /// about `MAX_DISTANCE` bytes of output.
+    pub fn new(inner: R) -> Self {
+        Self::with_high_water_mark(inner, MAX_DISTANCE)
+    }
+
+    /// Create a new AsyncInflateReader that pauses decoding once `mark` bytes of output are
+    /// waiting to be read.
+    pub fn with_high_water_mark(inner: R, mark: usize) -> Self {
+        Self::with_inflater(inner, Inflater::new(), mark)
+    }
+
+    /// Create a new AsyncInflateReader decoding with the given options, as
+    /// [`Inflater::with_options`] does, and failing on the same options.
+    pub fn with_options(inner: R, options: &InflateOptions) -> Result<Self> {
+        Ok(Self::with_inflater(
+            inner,
+            Inflater::with_options(options)?,
+            MAX_DISTANCE,
+        ))
+    }
+
+    fn with_inflater(inner: R, mut inflater: Inflater, mark: usize) -> Self {
+        inflater.set_output_high_water_mark(Some(mark.max(1)));
+        Self {
+            inner,
+            inflater,
+            inner_eof: false,
+        }
+    }
+
+    /// Get a reference to the wrapped reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the wrapped reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Check if the final block of the stream has been decoded.
+    pub fn is_finished(&self) -> bool {
+        self.inflater.is_finished()
+    }
+
+    /// Get the number of decompressed bytes waiting to be read.
+    pub fn buffered_output_len(&self) -> usize {
+        self.inflater.output().len()
+    }
+}
+
+#[cfg(feature = "futures")]
+impl<R: AsyncRead + Unpin> AsyncRead for AsyncInflateReader<R> {
+    /// Read decompressed data. Data following the final block is ignored.
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize>> {
+        let this = self.get_mut();
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        let mut chunk = [0; INPUT_CHUNK_LEN];
+        while this.inflater.needs_input(this.inner_eof)? {
+            match ready!(Pin::new(&mut this.inner).poll_read(cx, &mut chunk)) {
+                Ok(0) => this.inner_eof = true,
+                Ok(n) => {
+                    this.inflater.feed(&chunk[..n])?;
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Poll::Ready(Err(e)),
+            }
+        }
+        Poll::Ready(Ok(this.inflater.read_output(buf)))
+    }
+}
+
+/// A writer that inflates the compressed bytes written into it.
+///
+/// Compressed data is decoded by an [`Inflater`] as it arrives, and its output is written
+/// to the wrapped writer. Back-references into output already written are resolved from a
+/// fixed-size [`Window`](crate::window::Window), so memory use doesn't grow with the stream.
+///
+/// Call [`InflateWriter::finish`] once all compressed data has been written, so the last
+/// block is flushed and a truncated stream is reported.
+#[derive(Debug)]
+pub struct InflateWriter<W: Write> {
+    inner: W,
+    inflater: Inflater,
+}
+
+impl<W: Write> InflateWriter<W> {
+    /// Create a new InflateWriter that writes the decompressed data to `inner`.
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            inflater: Inflater::new(),
+        }
+    }
+
+    /// Create a new InflateWriter decoding with the given options, as
+    /// [`Inflater::with_options`] does, and failing on the same options.
+    pub fn with_options(inner: W, options: &InflateOptions) -> Result<Self> {
+        Ok(Self {
+            inner,
+            inflater: Inflater::with_options(options)?,
+        })
+    }
+
+    /// Get a reference to the wrapped writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the wrapped writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Check if the final block of the stream has been decoded.
+    pub fn is_finished(&self) -> bool {
+        self.inflater.is_finished()
+    }
+
+    /// Decode whatever is left of the stream, flush the wrapped writer and return it.
+    /// Fails if the stream ends before its final block.
+    pub fn finish(mut self) -> Result<W> {
+        let result = self.inflater.finish();
+        self.inner.write_all(&self.inflater.take_output())?;
+        result?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for InflateWriter<W> {
+    /// Accept compressed bytes and write out the data they decode to.
+    /// Data following the final block is ignored.
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.inflater.feed(buf)?;
+        self.inner.write_all(&self.inflater.take_output())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::container::Format;
+
+    fn sample_data() -> Vec<u8> {
+        let mut data = Vec::new();
+        for i in 0..1000u32 {
+            data.extend_from_slice(format!("line {} of {}\n", i % 97, i % 13).as_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn test_inflater_resumes_mid_block() {
+        let raw = sample_data();
+        for level in [0, 1, 9] {
+            let compressed = miniz_oxide::deflate::compress_to_vec(&raw, level);
+            let mut inflater = Inflater::new();
+            let mut output = Vec::new();
+            // One byte at a time, so every symbol gets split at some point.
+            for (i, byte) in compressed.iter().enumerate() {
+                let status = inflater.feed(&[*byte]).unwrap();
+                output.extend(inflater.take_output());
+                if i + 1 < compressed.len() {
+                    assert_eq!(status, InflateStatus::NeedsInput);
+                }
+            }
+            inflater.finish().unwrap();
+            output.extend(inflater.take_output());
+            assert_eq!(output, raw);
+        }
+    }
+
+    #[test]
+    fn test_inflater_trailing_data_ignored() {
+        let raw = b"hello hello hello".to_vec();
+        let mut compressed = miniz_oxide::deflate::compress_to_vec(&raw, 6);
+        compressed.extend(b"junk");
+        let mut inflater = Inflater::new();
+        assert_eq!(inflater.feed(&compressed), Ok(InflateStatus::Finished));
+        assert_eq!(inflater.output(), raw);
+        assert_eq!(inflater.remaining_input(), b"junk");
+        assert_eq!(inflater.feed(b"more"), Ok(InflateStatus::Finished));
+        assert_eq!(inflater.remaining_input(), b"junk");
+    }
+
+    #[test]
+    fn test_inflater_trailing_data_rejected() {
+        let raw = b"hello hello hello".to_vec();
+        let compressed = miniz_oxide::deflate::compress_to_vec(&raw, 6);
+        let options = InflateOptions::new().tail(TailHandling::Reject);
+        let mut inflater = Inflater::with_options(&options).unwrap();
+        assert_eq!(inflater.feed(&compressed), Ok(InflateStatus::Finished));
+        assert_eq!(inflater.finish(), Ok(()));
+        assert_eq!(inflater.feed(b""), Ok(InflateStatus::Finished));
+        assert_eq!(inflater.feed(b"junk"), Err(InflateError::TrailingData));
+        assert_eq!(inflater.finish(), Err(InflateError::TrailingData));
+
+        // Junk in the same piece of input as the end of the stream.
+        let mut inflater = Inflater::with_options(&options).unwrap();
+        let mut junk_suffixed = compressed.clone();
+        junk_suffixed.push(0);
+        assert_eq!(
+            inflater.feed(&junk_suffixed),
+            Err(InflateError::TrailingData)
+        );
+        assert_eq!(inflater.output(), raw);
+    }
+
+    #[test]
+    fn test_inflater_reports_errors_early() {
+        // A block with the reserved type 11, followed by more bytes.
+        let mut inflater = Inflater::new();
+        assert_eq!(
+            inflater.feed(&[0b111, 0, 0]),
+            Err(InflateError::InvalidBlockType)
+        );
+        let mut inflater = Inflater::new();
+        assert_eq!(inflater.feed(&[0b001, 5]), Ok(InflateStatus::NeedsInput));
+        assert_eq!(inflater.finish(), Err(InflateError::UnexpectedEof));
+    }
+
+    #[test]
+    fn test_inflater_empty_input() {
+        let mut inflater = Inflater::new();
+        assert_eq!(inflater.feed(&[]), Ok(InflateStatus::NeedsInput));
+        assert_eq!(inflater.finish(), Err(InflateError::EmptyInput));
+        let mut inflater = Inflater::new();
+        assert_eq!(inflater.feed(&[0x03, 0x00]), Ok(InflateStatus::Finished));
+        assert_eq!(inflater.finish(), Ok(()));
+        assert_eq!(inflater.output(), b"");
+    }
+
+    #[test]
+    fn test_inflater_with_options() {
+        let raw = sample_data();
+        let compressed = miniz_oxide::deflate::compress_to_vec(&raw, 6);
+        let mut inflater = Inflater::new();
+        for chunk in compressed.chunks(3) {
+            inflater.feed(chunk).unwrap();
+        }
+        assert_eq!(inflater.block_stats().blocks, 1);
+
+        let options = InflateOptions {
+            max_output_size: Some(100),
+            ..Default::default()
+        };
+        let mut inflater = Inflater::with_options(&options).unwrap();
+        assert_eq!(
+            inflater.feed(&compressed),
+            Err(InflateError::OutputLimitExceeded)
+        );
+
+        // The stream is always raw DEFLATE, whatever the options ask for.
+        for options in [
+            InflateOptions::new().format(Format::Zlib),
+            InflateOptions::new().tail(TailHandling::Concatenated),
+        ] {
+            assert!(matches!(
+                Inflater::with_options(&options),
+                Err(InflateError::UnsupportedOption(_))
+            ));
+            assert!(InflateReader::with_options(&compressed[..], &options).is_err());
+            assert!(InflateWriter::with_options(Vec::new(), &options).is_err());
+        }
+    }
+
+    #[test]
+    fn test_inflater_high_water_mark() {
+        // Zeros compress about a thousand times, so a few bytes of input decode to a lot.
+        let raw = vec![0u8; 1 << 20];
+        let compressed = miniz_oxide::deflate::compress_to_vec(&raw, 9);
+        let mut inflater = Inflater::new();
+        inflater.set_output_high_water_mark(Some(1000));
+        let mut status = inflater.feed(&compressed).unwrap();
+        let mut output = Vec::new();
+        while status == InflateStatus::OutputFull {
+            assert!(inflater.output().len() < 1000 + 258);
+            output.extend(inflater.take_output());
+            status = inflater.feed(&[]).unwrap();
+        }
+        assert_eq!(status, InflateStatus::Finished);
+        output.extend(inflater.take_output());
+        assert_eq!(output, raw);
+    }
+
+    /// A reader counting the bytes pulled from it.
+    struct Counting<'a> {
+        data: &'a [u8],
+        pulled: usize,
+    }
+
+    impl Read for Counting<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            let n = self.data.len().min(buf.len());
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            self.pulled += n;
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_inflate_reader_slow_consumer() {
+        let mut raw = vec![0u8; 8 << 20];
+        raw.extend(sample_data());
+        let compressed = miniz_oxide::deflate::compress_to_vec(&raw, 9);
+        let source = Counting {
+            data: &compressed,
+            pulled: 0,
+        };
+        let mut reader = InflateReader::with_high_water_mark(source, 4096);
+        let mut output = Vec::new();
+        let mut buf = [0; 1000];
+        loop {
+            let n = reader.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            output.extend_from_slice(&buf[..n]);
+            assert!(reader.buffered_output_len() < 4096 + 258);
+            // The source is only read as fast as the output gets consumed.
+            if output.len() < raw.len() / 2 {
+                assert_eq!(
+                    reader.get_ref().pulled,
+                    INPUT_CHUNK_LEN.min(compressed.len())
+                );
+            }
+        }
+        assert!(reader.is_finished());
+        assert_eq!(output, raw);
+        assert_eq!(reader.get_ref().pulled, compressed.len());
+    }
+
+    #[test]
+    fn test_inflate_reader() {
+        let raw = sample_data();
+        for level in [0, 6] {
+            let compressed = miniz_oxide::deflate::compress_to_vec(&raw, level);
+            let mut output = Vec::new();
+            InflateReader::new(&compressed[..])
+                .read_to_end(&mut output)
+                .unwrap();
+            assert_eq!(output, raw);
+        }
+
+        let compressed = miniz_oxide::deflate::compress_to_vec(&raw, 6);
+        let mut reader = InflateReader::new(&compressed[..compressed.len() / 2]);
+        let error = reader.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+    }
+
+    /// An asynchronous source handing out its data a little at a time, and only on every
+    /// other poll.
+    #[cfg(feature = "futures")]
+    struct Stuttering<'a> {
+        data: &'a [u8],
+        ready: bool,
+    }
+
+    #[cfg(feature = "futures")]
+    impl AsyncRead for Stuttering<'_> {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<Result<usize>> {
+            self.ready = !self.ready;
+            if !self.ready {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            let n = self.data.len().min(buf.len()).min(100);
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            Poll::Ready(Ok(n))
+        }
+    }
+
+    /// Read an `AsyncRead` to its end by polling it in a loop.
+    /// Returns the data, or the error, and the number of times it was pending.
+    #[cfg(feature = "futures")]
+    fn poll_to_end(reader: &mut (impl AsyncRead + Unpin)) -> (Result<Vec<u8>>, usize) {
+        let mut cx = Context::from_waker(std::task::Waker::noop());
+        let mut output = Vec::new();
+        let mut buf = [0; 1000];
+        let mut pending = 0;
+        loop {
+            match Pin::new(&mut *reader).poll_read(&mut cx, &mut buf) {
+                Poll::Pending => pending += 1,
+                Poll::Ready(Ok(0)) => return (Ok(output), pending),
+                Poll::Ready(Ok(n)) => output.extend_from_slice(&buf[..n]),
+                Poll::Ready(Err(e)) => return (Err(e), pending),
+            }
+        }
+    }
+
+    #[cfg(feature = "futures")]
+    #[test]
+    fn test_async_inflate_reader() {
+        let raw = sample_data();
+        let compressed = miniz_oxide::deflate::compress_to_vec(&raw, 6);
+        let mut reader = AsyncInflateReader::with_high_water_mark(
+            Stuttering {
+                data: &compressed,
+                ready: false,
+            },
+            4096,
+        );
+        let (output, pending) = poll_to_end(&mut reader);
+        assert_eq!(output.unwrap(), raw);
+        assert!(pending > 0);
+        assert!(reader.is_finished());
+        assert_eq!(reader.buffered_output_len(), 0);
+
+        let mut reader = AsyncInflateReader::new(Stuttering {
+            data: &compressed[..compressed.len() / 2],
+            ready: false,
+        });
+        let (output, _) = poll_to_end(&mut reader);
+        assert_eq!(output.unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_inflate_writer_chunks() {
+        let raw = sample_data();
+        let compressed = miniz_oxide::deflate::compress_to_vec(&raw, 6);
+        for chunk_size in [5, 64, 1024, compressed.len()] {
+            let mut writer = InflateWriter::new(Vec::new());
+            for chunk in compressed.chunks(chunk_size) {
+                writer.write_all(chunk).unwrap();
+            }
+            assert!(writer.is_finished());
+            assert_eq!(writer.finish().unwrap(), raw);
+        }
+    }
+
+    #[test]
+    fn test_inflate_writer_bounded_memory() {
+        use crate::roundtrip::{sample, SampleKind};
+        // Matches reaching all the way back, into output long written out.
+        let raw = sample(SampleKind::LongMatches, 1, 16 * MAX_DISTANCE);
+        let compressed = crate::deflate::deflate_to_vec(&raw);
+        let mut writer = InflateWriter::new(Vec::new());
+        let mut largest_output = 0;
+        for chunk in compressed.chunks(1024) {
+            writer.write_all(chunk).unwrap();
+            largest_output = largest_output.max(writer.inflater.state.output.capacity());
+        }
+        assert_eq!(writer.inflater.state.window.len(), MAX_DISTANCE);
+        // Output doesn't pile up beyond what a few chunks decode to.
+        assert!(largest_output <= 2 * MAX_DISTANCE, "{}", largest_output);
+        assert_eq!(writer.finish().unwrap(), raw);
+    }
+
+    #[test]
+    fn test_inflate_writer_stored_blocks() {
+        let raw = sample_data();
+        let compressed = miniz_oxide::deflate::compress_to_vec(&raw, 0);
+        let mut writer = InflateWriter::new(Vec::new());
+        for chunk in compressed.chunks(100) {
+            writer.write_all(chunk).unwrap();
+        }
+        assert_eq!(writer.finish().unwrap(), raw);
+    }
+
+    #[test]
+    fn test_streams_with_options() {
+        let raw = sample_data();
+        let compressed = miniz_oxide::deflate::compress_to_vec(&raw, 6);
+        let options = InflateOptions::new().max_output(100);
+
+        let mut writer = InflateWriter::with_options(Vec::new(), &options).unwrap();
+        let err = writer.write_all(&compressed).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        let mut reader = InflateReader::with_options(&compressed[..], &options).unwrap();
+        let err = reader.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        let options = InflateOptions::new().max_output(raw.len());
+        let mut reader = InflateReader::with_options(&compressed[..], &options).unwrap();
+        let mut output = Vec::new();
+        reader.read_to_end(&mut output).unwrap();
+        assert_eq!(output, raw);
+    }
+
+    #[test]
+    fn test_inflate_writer_truncated() {
+        let raw = sample_data();
+        let compressed = miniz_oxide::deflate::compress_to_vec(&raw, 6);
+        let mut writer = InflateWriter::new(Vec::new());
+        writer
+            .write_all(&compressed[..compressed.len() / 2])
+            .unwrap();
+        assert!(!writer.is_finished());
+        assert!(writer.finish().is_err());
+    }
+}
@@ -0,0 +1,529 @@
+//! Build crafted DEFLATE streams for tests.
+//!
+//! Fixture files only cover what some compressor happened to produce. A [`StreamBuilder`]
+//! writes a stream block by block with a [`BitWriter`], so a test can ask for exactly the
+//! structure it needs: a dynamic header with given HLIT and HDIST, a match at the largest
+//! distance, or code lengths run-length encoded with each repeat symbol at its limits.
+//! [`test_vectors`] lists a stream of each kind along with what it decodes to.
+
+use crate::bit_stream::BitWriter;
+use crate::codes::{
+    DEFLATE64_DISTANCE_CODES, DEFLATE64_LENGTH_CODE, DISTANCE_CODE_TABLE, END_BLOCK_CODE,
+};
+use crate::deflate::{
+    huffman_code_lengths, run_length_code_lengths, symbol_frequencies, write_dynamic_header,
+    write_tokens, CodeLengthSymbol, Token, MAX_MATCH_LEN, MAX_STORED_BLOCK_LEN,
+};
+use crate::error::{InflateError, Result};
+use crate::huffman::HuffmanEncodeTable;
+use crate::inflate::{
+    fixed_distance_code_lengths, fixed_literal_code_lengths, BFINAL_LEN, BFINAL_VALUE,
+    BTYPE_DYNAMIC_HUFFMAN, BTYPE_FIXED_HUFFMAN, BTYPE_LEN, BTYPE_NO_COMPRESSION,
+    DEFLATE64_MAX_DISTANCE, DYN_TABLE_MAX_BITS, HDIST_BASE, HLIT_BASE, MAX_DISTANCE,
+};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// A symbol of the code length alphabet, as written in a dynamic block header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodeLength {
+    /// A code length from 0 to 15 (symbols 0-15).
+    Length(u8),
+    /// Repeat the previous code length 3-6 times (symbol 16).
+    RepeatPrevious(usize),
+    /// Repeat a zero code length 3-10 times (symbol 17) or 11-138 times (symbol 18).
+    RepeatZero(usize),
+}
+
+impl CodeLength {
+    /// Get the `(symbol, extra value, extra bits)` the code length is written as.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a length is above 15 or a repeat count is out of range.
+    fn encode(self) -> CodeLengthSymbol {
+        match self {
+            CodeLength::Length(len) => {
+                assert!(len <= 15, "Invalid code length {}", len);
+                (len as usize, 0, 0)
+            }
+            CodeLength::RepeatPrevious(count) => {
+                assert!((3..=6).contains(&count), "Invalid repeat count {}", count);
+                (16, count - 3, 2)
+            }
+            CodeLength::RepeatZero(count @ 3..=10) => (17, count - 3, 3),
+            CodeLength::RepeatZero(count @ 11..=138) => (18, count - 11, 7),
+            CodeLength::RepeatZero(count) => panic!("Invalid zero repeat count {}", count),
+        }
+    }
+}
+
+/// The codes a dynamic block header declares, and how their lengths are encoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DynamicCodes {
+    /// Code lengths of the literal/length alphabet. Their number is HLIT.
+    pub lit_lengths: Vec<u8>,
+    /// Code lengths of the distance alphabet. Their number is HDIST.
+    pub dis_lengths: Vec<u8>,
+    /// The code length symbols the header is written with, or `None` to run-length encode
+    /// the lengths the way the compressor does. Crafted symbols don't have to match the
+    /// lengths, which is how invalid headers are written.
+    pub code_lengths: Option<Vec<CodeLength>>,
+}
+
+impl DynamicCodes {
+    /// Build codes for the tokens, declaring `hlit` literal/length and `hdist` distance codes.
+    /// Every symbol below those counts can get a code, as at least two symbols of each
+    /// alphabet do when it has room for them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the tokens use a symbol beyond the declared counts.
+    pub fn for_tokens(tokens: &[Token], hlit: usize, hdist: usize) -> Self {
+        let (mut lit_freqs, mut dis_freqs) = symbol_frequencies(tokens);
+        lit_freqs.resize(lit_freqs.len().max(hlit), 0);
+        dis_freqs.resize(dis_freqs.len().max(hdist), 0);
+        assert!(lit_freqs[hlit..].iter().all(|&freq| freq == 0));
+        assert!(dis_freqs[hdist..].iter().all(|&freq| freq == 0));
+        Self {
+            lit_lengths: huffman_code_lengths(&lit_freqs[..hlit], DYN_TABLE_MAX_BITS),
+            dis_lengths: huffman_code_lengths(&dis_freqs[..hdist], DYN_TABLE_MAX_BITS),
+            code_lengths: None,
+        }
+    }
+}
+
+/// Write a DEFLATE stream one block at a time.
+#[derive(Debug, Default)]
+pub struct StreamBuilder {
+    writer: BitWriter,
+}
+
+impl StreamBuilder {
+    /// Create a builder for an empty stream.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Write a block header.
+    fn block_header(&mut self, b_type: usize, is_final: bool) {
+        let b_final = if is_final { BFINAL_VALUE } else { 0 };
+        self.writer.write_bits(b_final, BFINAL_LEN);
+        self.writer.write_bits(b_type, BTYPE_LEN);
+    }
+
+    /// Write a stored block holding `data`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the data is longer than `MAX_STORED_BLOCK_LEN`.
+    pub fn stored_block(&mut self, data: &[u8], is_final: bool) {
+        assert!(data.len() <= MAX_STORED_BLOCK_LEN);
+        self.block_header(BTYPE_NO_COMPRESSION, is_final);
+        let len = data.len() as u16;
+        self.writer.write_bytes(&len.to_le_bytes());
+        self.writer.write_bytes(&(!len).to_le_bytes());
+        self.writer.write_bytes(data);
+    }
+
+    /// Write a fixed Huffman block encoding the tokens.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a match is longer than `MAX_MATCH_LEN`, shorter than `MIN_MATCH_LEN`,
+    /// or reaches further back than `MAX_DISTANCE`.
+    pub fn fixed_block(&mut self, tokens: &[Token], is_final: bool) {
+        self.block_header(BTYPE_FIXED_HUFFMAN, is_final);
+        let lit_codes = HuffmanEncodeTable::new(&fixed_literal_code_lengths());
+        let dis_codes = HuffmanEncodeTable::new(&fixed_distance_code_lengths());
+        write_tokens(&mut self.writer, tokens, &lit_codes, &dis_codes);
+    }
+
+    /// Write a dynamic Huffman block declaring the given codes and encoding the tokens.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a token uses a symbol without a code, or for the same matches as
+    /// [`StreamBuilder::fixed_block`].
+    pub fn dynamic_block(&mut self, codes: &DynamicCodes, tokens: &[Token], is_final: bool) {
+        self.block_header(BTYPE_DYNAMIC_HUFFMAN, is_final);
+        let cl_symbols = match &codes.code_lengths {
+            Some(code_lengths) => code_lengths.iter().map(|c| c.encode()).collect(),
+            None => {
+                let mut cl_symbols = run_length_code_lengths(&codes.lit_lengths);
+                cl_symbols.extend(run_length_code_lengths(&codes.dis_lengths));
+                cl_symbols
+            }
+        };
+        write_dynamic_header(
+            &mut self.writer,
+            codes.lit_lengths.len(),
+            codes.dis_lengths.len(),
+            &cl_symbols,
+        );
+        let lit_codes = HuffmanEncodeTable::new(&codes.lit_lengths);
+        let dis_codes = HuffmanEncodeTable::new(&codes.dis_lengths);
+        write_tokens(&mut self.writer, tokens, &lit_codes, &dis_codes);
+    }
+
+    /// Write a fixed Huffman block of Deflate64 holding a single match. Its length is written
+    /// with length code 285, which covers 3 to 65538 bytes in Deflate64, and its distance may
+    /// use distance codes 30 and 31, which reach up to `DEFLATE64_MAX_DISTANCE` back.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length or the distance is out of the range of Deflate64.
+    pub fn deflate64_match_block(&mut self, length: usize, distance: usize, is_final: bool) {
+        let (code, base, extra_bits) = DEFLATE64_LENGTH_CODE;
+        assert!((base..base + (1 << extra_bits)).contains(&length));
+        assert!((1..=DEFLATE64_MAX_DISTANCE).contains(&distance));
+        self.block_header(BTYPE_FIXED_HUFFMAN, is_final);
+        let lit_codes = HuffmanEncodeTable::new(&fixed_literal_code_lengths());
+        let dis_codes = HuffmanEncodeTable::new(&fixed_distance_code_lengths());
+        lit_codes.write_symbol(&mut self.writer, code);
+        self.writer.write_bits(length - base, extra_bits);
+        let (code, base, extra_bits) = *DISTANCE_CODE_TABLE
+            .iter()
+            .chain(DEFLATE64_DISTANCE_CODES)
+            .rev()
+            .find(|&&(_, base, _)| base <= distance)
+            .unwrap();
+        dis_codes.write_symbol(&mut self.writer, code);
+        self.writer.write_bits(distance - base, extra_bits);
+        lit_codes.write_symbol(&mut self.writer, END_BLOCK_CODE);
+    }
+
+    /// Get the stream written, the last byte padded with zeros.
+    pub fn into_vec(self) -> Vec<u8> {
+        self.writer.into_vec()
+    }
+}
+
+/// A crafted stream and what decoding it gives.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestVector {
+    /// What the stream exercises, e.g. `dynamic_hlit_257_hdist_1`.
+    pub name: String,
+    /// The raw DEFLATE stream.
+    pub deflate: Vec<u8>,
+    /// The decompressed data, or the error the stream must be rejected with.
+    pub expected: Result<Vec<u8>>,
+}
+
+impl TestVector {
+    fn new(name: impl Into<String>, deflate: Vec<u8>, expected: Result<Vec<u8>>) -> Self {
+        Self {
+            name: name.into(),
+            deflate,
+            expected,
+        }
+    }
+}
+
+/// Encode data as literals only.
+fn literals(data: &[u8]) -> Vec<Token> {
+    data.iter().map(|&byte| Token::Literal(byte)).collect()
+}
+
+/// A byte followed by `count` maximum-length matches at distance 1, and the output they give.
+fn max_length_run(byte: u8, count: usize) -> (Vec<Token>, Vec<u8>) {
+    let mut tokens = vec![Token::Literal(byte)];
+    tokens.extend((0..count).map(|_| Token::Match {
+        length: MAX_MATCH_LEN,
+        distance: 1,
+    }));
+    (tokens, vec![byte; 1 + count * MAX_MATCH_LEN])
+}
+
+/// Literal/length code lengths for the code length edge cases: symbols 0-6 and the
+/// end-of-block symbol get 3 bits, and everything in between gets none.
+fn sparse_lit_lengths() -> Vec<u8> {
+    let mut lengths = vec![0; HLIT_BASE];
+    lengths[..7].fill(3);
+    lengths[HLIT_BASE - 1] = 3;
+    lengths
+}
+
+/// A Deflate64 stream and its output: 40000 bytes in a stored block, then a match of 50000
+/// bytes at distance 40000, which only Deflate64 can express.
+pub fn deflate64_stream() -> (Vec<u8>, Vec<u8>) {
+    let mut output: Vec<u8> = (0..40_000).map(|i| (i * 7 % 251) as u8).collect();
+    let mut builder = StreamBuilder::new();
+    builder.stored_block(&output, false);
+    builder.deflate64_match_block(50_000, 40_000, true);
+    for i in 0..50_000 {
+        output.push(output[i]);
+    }
+    (builder.into_vec(), output)
+}
+
+/// List the crafted streams: every block type on its own, dynamic headers at the limits of
+/// HLIT and HDIST, maximum-length matches, distance-1 runs, a match at the largest distance,
+/// and code lengths using each repeat symbol at its limits, plus a few invalid headers.
+pub fn test_vectors() -> Vec<TestVector> {
+    let mut vectors = Vec::new();
+    let text = b"crafted, not compressed";
+
+    // Stored blocks only, including an empty one in the middle.
+    let mut builder = StreamBuilder::new();
+    builder.stored_block(&text[..8], false);
+    builder.stored_block(b"", false);
+    builder.stored_block(&text[8..], true);
+    vectors.push(TestVector::new(
+        "stored_only",
+        builder.into_vec(),
+        Ok(text.to_vec()),
+    ));
+
+    // Fixed blocks only, the second one matching into the first.
+    let mut builder = StreamBuilder::new();
+    builder.fixed_block(&literals(b"abcd"), false);
+    builder.fixed_block(
+        &[Token::Match {
+            length: 8,
+            distance: 4,
+        }],
+        true,
+    );
+    vectors.push(TestVector::new(
+        "fixed_only",
+        builder.into_vec(),
+        Ok(b"abcdabcdabcd".to_vec()),
+    ));
+
+    // Dynamic headers with the smallest and largest valid HLIT and HDIST.
+    let tokens = [
+        Token::Literal(b'x'),
+        Token::Match {
+            length: 10,
+            distance: 1,
+        },
+    ];
+    for (hlit, hdist) in [(HLIT_BASE, HDIST_BASE), (286, 30), (HLIT_BASE + 8, 30)] {
+        let tokens = if hlit == HLIT_BASE {
+            &tokens[..1]
+        } else {
+            &tokens[..]
+        };
+        let expected = if hlit == HLIT_BASE {
+            b"x".to_vec()
+        } else {
+            vec![b'x'; 11]
+        };
+        let mut builder = StreamBuilder::new();
+        builder.dynamic_block(&DynamicCodes::for_tokens(tokens, hlit, hdist), tokens, true);
+        vectors.push(TestVector::new(
+            format!("dynamic_hlit_{}_hdist_{}", hlit, hdist),
+            builder.into_vec(),
+            Ok(expected),
+        ));
+    }
+
+    // Runs of maximum-length matches at distance 1, in both kinds of Huffman blocks.
+    let (tokens, expected) = max_length_run(b'z', 40);
+    let mut builder = StreamBuilder::new();
+    builder.fixed_block(&tokens, true);
+    vectors.push(TestVector::new(
+        "fixed_max_length_run",
+        builder.into_vec(),
+        Ok(expected.clone()),
+    ));
+    let mut builder = StreamBuilder::new();
+    builder.dynamic_block(&DynamicCodes::for_tokens(&tokens, 286, 30), &tokens, true);
+    vectors.push(TestVector::new(
+        "dynamic_max_length_run",
+        builder.into_vec(),
+        Ok(expected),
+    ));
+
+    // Distance-1 runs of every length, each starting from a new literal.
+    let mut tokens = Vec::new();
+    let mut expected = Vec::new();
+    for length in 3..=MAX_MATCH_LEN {
+        let byte = length as u8;
+        tokens.extend([
+            Token::Literal(byte),
+            Token::Match {
+                length,
+                distance: 1,
+            },
+        ]);
+        expected.extend(vec![byte; length + 1]);
+    }
+    let mut builder = StreamBuilder::new();
+    builder.fixed_block(&tokens, true);
+    vectors.push(TestVector::new(
+        "distance_1_runs",
+        builder.into_vec(),
+        Ok(expected),
+    ));
+
+    // A maximum-length match at the largest distance, reaching into a stored block.
+    let window: Vec<u8> = (0..MAX_DISTANCE).map(|i| (i * 7 % 251) as u8).collect();
+    let mut builder = StreamBuilder::new();
+    builder.stored_block(&window[..MAX_STORED_BLOCK_LEN / 2], false);
+    builder.stored_block(&window[MAX_STORED_BLOCK_LEN / 2..], false);
+    builder.fixed_block(
+        &[Token::Match {
+            length: MAX_MATCH_LEN,
+            distance: MAX_DISTANCE,
+        }],
+        true,
+    );
+    let mut expected = window.clone();
+    expected.extend_from_slice(&window[..MAX_MATCH_LEN]);
+    vectors.push(TestVector::new(
+        "max_distance_match",
+        builder.into_vec(),
+        Ok(expected),
+    ));
+
+    // Code lengths written with each repeat symbol at the ends of its range:
+    // 16 repeating 3 and 6 times, 17 repeating 3 and 10 times, 18 repeating 11 and 138 times.
+    let codes = DynamicCodes {
+        lit_lengths: sparse_lit_lengths(),
+        dis_lengths: vec![2; 4],
+        code_lengths: Some(vec![
+            CodeLength::Length(3),
+            CodeLength::RepeatPrevious(6),
+            CodeLength::RepeatZero(138),
+            CodeLength::RepeatZero(11),
+            CodeLength::RepeatZero(10),
+            CodeLength::RepeatZero(3),
+            CodeLength::RepeatZero(87),
+            CodeLength::Length(3),
+            CodeLength::Length(2),
+            CodeLength::RepeatPrevious(3),
+        ]),
+    };
+    let data = [0, 1, 2, 3, 4, 5, 6, 6, 5, 4];
+    let mut builder = StreamBuilder::new();
+    builder.dynamic_block(&codes, &literals(&data), true);
+    vectors.push(TestVector::new(
+        "code_length_repeat_limits",
+        builder.into_vec(),
+        Ok(data.to_vec()),
+    ));
+
+    // A repeat of the last literal/length length carrying over into the distance lengths: the
+    // code lengths of both alphabets are a single run-length encoded sequence.
+    let mut lit_lengths = vec![0; HLIT_BASE];
+    lit_lengths[0] = 1;
+    lit_lengths[1] = 2;
+    lit_lengths[HLIT_BASE - 1] = 2;
+    let crossing = DynamicCodes {
+        lit_lengths,
+        dis_lengths: vec![2; 4],
+        code_lengths: Some(vec![
+            CodeLength::Length(1),
+            CodeLength::Length(2),
+            CodeLength::RepeatZero(138),
+            CodeLength::RepeatZero(116),
+            CodeLength::Length(2),
+            CodeLength::RepeatPrevious(4),
+        ]),
+    };
+    let data = [0, 1, 0];
+    let mut builder = StreamBuilder::new();
+    builder.dynamic_block(&crossing, &literals(&data), true);
+    vectors.push(TestVector::new(
+        "code_length_repeat_across_alphabets",
+        builder.into_vec(),
+        Ok(data.to_vec()),
+    ));
+
+    // Invalid code lengths: a repeat of the previous length with none before it, and a zero
+    // run going past the end of the code lengths.
+    for (name, code_lengths, position) in [
+        (
+            "code_length_repeat_first",
+            vec![CodeLength::RepeatPrevious(3)],
+            0,
+        ),
+        (
+            "code_length_run_overflow",
+            vec![
+                CodeLength::Length(3),
+                CodeLength::RepeatPrevious(6),
+                CodeLength::RepeatZero(138),
+                CodeLength::RepeatZero(138),
+            ],
+            145,
+        ),
+    ] {
+        let codes = DynamicCodes {
+            code_lengths: Some(code_lengths),
+            ..codes.clone()
+        };
+        let mut builder = StreamBuilder::new();
+        builder.dynamic_block(&codes, &[], true);
+        vectors.push(TestVector::new(
+            name,
+            builder.into_vec(),
+            Err(InflateError::InvalidCodeLengthEncoding { position }),
+        ));
+    }
+
+    vectors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inflate::{inflate_to_vec, inspect};
+
+    #[test]
+    fn test_vectors_decode() {
+        let vectors = test_vectors();
+        for vector in &vectors {
+            assert_eq!(
+                inflate_to_vec(&vector.deflate),
+                vector.expected,
+                "{}",
+                vector.name
+            );
+            let reference = miniz_oxide::inflate::decompress_to_vec(&vector.deflate);
+            assert_eq!(
+                reference.is_ok(),
+                vector.expected.is_ok(),
+                "{}",
+                vector.name
+            );
+        }
+        let mut names: Vec<_> = vectors.iter().map(|v| &v.name).collect();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), vectors.len());
+    }
+
+    #[test]
+    fn test_dynamic_header_counts() {
+        let vector = test_vectors()
+            .into_iter()
+            .find(|v| v.name == "dynamic_hlit_286_hdist_30")
+            .unwrap();
+        let blocks = inspect(&vector.deflate).unwrap();
+        assert_eq!(
+            blocks[0].dynamic.map(|h| (h.hlit, h.hdist)),
+            Some((286, 30))
+        );
+    }
+
+    #[test]
+    fn test_code_length_encode() {
+        assert_eq!(CodeLength::Length(15).encode(), (15, 0, 0));
+        assert_eq!(CodeLength::RepeatPrevious(6).encode(), (16, 3, 2));
+        assert_eq!(CodeLength::RepeatZero(10).encode(), (17, 7, 3));
+        assert_eq!(CodeLength::RepeatZero(11).encode(), (18, 0, 7));
+    }
+
+    #[test]
+    fn test_stored_block_alignment() {
+        let mut builder = StreamBuilder::new();
+        builder.fixed_block(&literals(b"a"), false);
+        builder.stored_block(b"bc", true);
+        assert_eq!(inflate_to_vec(&builder.into_vec()).unwrap(), b"abc");
+    }
+}
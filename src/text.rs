@@ -0,0 +1,143 @@
+//! Options for the text the library and the command line tool write for people to read.
+//!
+//! By default, text is written to be read: numbers are rounded to what matters and errors are
+//! described in words, both of which may improve from one release to the next. With stable
+//! output, the text is guaranteed to be byte-identical across platforms and across releases
+//! with the same major version, so that it can be compared against golden files, e.g. to grade
+//! submissions automatically:
+//! - errors are given by their [code](crate::error::InflateError::code), not their message,
+//! - floating point numbers are written with a fixed number of decimals,
+//! - the entries of maps and histograms are listed in key order,
+//! - nothing depends on the time, the locale or the environment.
+
+use crate::error::InflateError;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+/// Number of decimals of the floating point numbers in stable output.
+pub const STABLE_DECIMALS: usize = 6;
+
+/// Number of decimals of the floating point numbers otherwise.
+const READABLE_DECIMALS: usize = 2;
+
+/// How text output is written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TextOptions {
+    /// Write byte-identical text across platforms and releases, for machines to compare.
+    pub stable_output: bool,
+}
+
+impl TextOptions {
+    /// Options for stable output.
+    pub fn stable() -> Self {
+        Self {
+            stable_output: true,
+        }
+    }
+
+    /// Write an error.
+    pub fn error(&self, error: &InflateError) -> String {
+        if self.stable_output {
+            error.code().to_string()
+        } else {
+            error.to_string()
+        }
+    }
+
+    /// Write a floating point number.
+    pub fn float(&self, value: f64) -> String {
+        let decimals = if self.stable_output {
+            STABLE_DECIMALS
+        } else {
+            READABLE_DECIMALS
+        };
+        // Negative zero and NaN payloads must not show through.
+        let value = if value == 0.0 { 0.0 } else { value };
+        if value.is_nan() {
+            return "nan".to_string();
+        }
+        format!("{:.*}", decimals, value)
+    }
+}
+
+/// How a table is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableFormat {
+    /// A Markdown table, to paste into documents.
+    Markdown,
+    /// Comma-separated values with a header line, for spreadsheets. Defined in RFC 4180.
+    Csv,
+}
+
+impl TableFormat {
+    /// Write a table with the given column names and rows of cells, one line per row.
+    pub fn table(&self, columns: &[&str], rows: &[Vec<String>]) -> String {
+        let mut table = self.row(columns.iter().copied());
+        if *self == TableFormat::Markdown {
+            table.push_str(&self.row(columns.iter().map(|_| "---")));
+        }
+        for row in rows {
+            table.push_str(&self.row(row.iter().map(String::as_str)));
+        }
+        table
+    }
+
+    /// Write a line of a table.
+    fn row<'a>(&self, cells: impl Iterator<Item = &'a str>) -> String {
+        let cells: Vec<String> = cells.map(|cell| self.cell(cell)).collect();
+        match self {
+            TableFormat::Markdown => format!("| {} |\n", cells.join(" | ")),
+            TableFormat::Csv => format!("{}\r\n", cells.join(",")),
+        }
+    }
+
+    /// Escape a cell where it would break the table.
+    fn cell(&self, cell: &str) -> String {
+        match self {
+            TableFormat::Markdown => cell.replace('|', "\\|"),
+            TableFormat::Csv if cell.contains([',', '"', '\r', '\n']) => {
+                format!("\"{}\"", cell.replace('"', "\"\""))
+            }
+            TableFormat::Csv => cell.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_text_options() {
+        let stable = TextOptions::stable();
+        let readable = TextOptions::default();
+        assert_eq!(stable.float(2.0 / 3.0), "0.666667");
+        assert_eq!(readable.float(2.0 / 3.0), "0.67");
+        assert_eq!(stable.float(-0.0), "0.000000");
+        assert_eq!(stable.float(f64::NAN), "nan");
+        assert_eq!(stable.float(f64::INFINITY), "inf");
+        assert_eq!(stable.error(&InflateError::UnexpectedEof), "unexpected_eof");
+        assert_eq!(
+            readable.error(&InflateError::UnexpectedEof),
+            "Unexpected end of data"
+        );
+    }
+
+    #[test]
+    fn test_table_format() {
+        let rows = [
+            vec!["1".to_string(), "a|b".to_string()],
+            vec!["2".to_string(), "say \"hi\", then go".to_string()],
+        ];
+        assert_eq!(
+            TableFormat::Markdown.table(&["n", "text"], &rows),
+            "| n | text |\n| --- | --- |\n| 1 | a\\|b |\n| 2 | say \"hi\", then go |\n"
+        );
+        assert_eq!(
+            TableFormat::Csv.table(&["n", "text"], &rows),
+            "n,text\r\n1,a|b\r\n2,\"say \"\"hi\"\", then go\"\r\n"
+        );
+    }
+}
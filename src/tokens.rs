@@ -0,0 +1,204 @@
+//! Iterate over the LZ77 tokens of a DEFLATE stream instead of its decompressed bytes.
+//!
+//! [`TokenIter`] decodes lazily, one token per call to `next`, so tools that only need the
+//! token view of a stream, such as visualizers, transcoders or statistics, never hold more
+//! than the window of its output.
+
+use crate::bit_stream::BitReader;
+use crate::disasm::FieldKind;
+use crate::error::{InflateError, Result};
+use crate::inflate::{inflate_step, BlockState, InflateState, MAX_DISTANCE};
+use alloc::vec::Vec;
+
+/// An element of a DEFLATE stream, as yielded by [`TokenIter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Token<'a> {
+    /// A byte copied as is.
+    Literal(u8),
+    /// A copy of `length` bytes starting `distance` bytes back in the output.
+    Match { length: usize, distance: usize },
+    /// The end of a compressed block.
+    EndOfBlock,
+    /// The whole data of a stored block, borrowed from the input.
+    StoredBlock(&'a [u8]),
+}
+
+/// An iterator over the tokens of a raw DEFLATE stream.
+/// Decoding errors are yielded once, after which the iterator ends.
+#[derive(Debug)]
+pub struct TokenIter<'a> {
+    data: &'a [u8],
+    bit_reader: BitReader<'a>,
+    state: InflateState,
+    block: BlockState,
+    failed: bool,
+}
+
+impl<'a> TokenIter<'a> {
+    /// Create an iterator at the start of a raw DEFLATE stream.
+    pub fn new(data: &'a [u8]) -> Self {
+        let mut state = InflateState::new();
+        state.trace = Some(Vec::new());
+        Self {
+            data,
+            bit_reader: BitReader::new_strict(data),
+            state,
+            block: BlockState::Header,
+            failed: false,
+        }
+    }
+
+    /// Get the bit offset of the next token.
+    pub fn bit_offset(&self) -> usize {
+        self.bit_reader.bits_consumed()
+    }
+
+    /// Get the number of bytes the tokens yielded so far decompress to.
+    pub fn output_offset(&self) -> usize {
+        self.state.total_out()
+    }
+
+    /// Decode the next token, if the stream has not ended.
+    fn next_token(&mut self) -> Result<Option<Token<'a>>> {
+        if self.data.is_empty() {
+            return Err(InflateError::EmptyInput);
+        }
+        loop {
+            if let BlockState::Stored {
+                remaining,
+                is_final,
+            } = self.block
+            {
+                return self.stored_block(remaining, is_final).map(Some);
+            }
+            if matches!(self.block, BlockState::Done) {
+                return Ok(None);
+            }
+            let next = inflate_step(&mut self.bit_reader, &mut self.state, &self.block)?;
+            if let Some(next) = next {
+                self.block = next;
+            }
+            // Matches resolve from the window, so the output can go, in batches.
+            if self.state.output.len() >= MAX_DISTANCE {
+                self.state.discard_output(0);
+            }
+            let fields = self.state.trace.as_mut().map(core::mem::take);
+            let mut length = 0;
+            for field in fields.unwrap_or_default() {
+                match field.kind {
+                    FieldKind::Literal(byte) => return Ok(Some(Token::Literal(byte))),
+                    FieldKind::LengthCode { length: n, .. } => length = n,
+                    FieldKind::DistanceCode { distance, .. } => {
+                        return Ok(Some(Token::Match { length, distance }))
+                    }
+                    FieldKind::EndOfBlock => return Ok(Some(Token::EndOfBlock)),
+                    // An empty stored block goes straight to the next block.
+                    FieldKind::StoredLen(0) => return Ok(Some(Token::StoredBlock(&[]))),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Take the data of the stored block the bit reader is at, as a whole.
+    fn stored_block(&mut self, len: usize, is_final: bool) -> Result<Token<'a>> {
+        let start = self.bit_reader.bits_consumed() / 8;
+        let data = self
+            .data
+            .get(start..start + len)
+            .ok_or(InflateError::UnexpectedEof)?;
+        self.bit_reader.advance(len * 8);
+        self.state.output.extend_from_slice(data);
+        self.block = if is_final {
+            BlockState::Done
+        } else {
+            BlockState::Header
+        };
+        Ok(Token::StoredBlock(data))
+    }
+}
+
+impl<'a> Iterator for TokenIter<'a> {
+    type Item = Result<Token<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        match self.next_token() {
+            Ok(token) => token.map(Ok),
+            Err(error) => {
+                self.failed = true;
+                Some(Err(error))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deflate::{self, deflate_fixed_tokens_to_vec};
+    use alloc::vec;
+
+    #[test]
+    fn test_token_iter() {
+        // A stored block holding "abc", an empty stored block, then a final fixed block.
+        let mut data = vec![0b000, 3, 0, !3, !0, b'a', b'b', b'c'];
+        data.extend([0b000, 0, 0, !0, !0]);
+        data.extend(deflate_fixed_tokens_to_vec(&[
+            deflate::Token::Literal(b'd'),
+            deflate::Token::Match {
+                length: 4,
+                distance: 2,
+            },
+        ]));
+        let tokens: Result<Vec<_>> = TokenIter::new(&data).collect();
+        assert_eq!(
+            tokens.unwrap(),
+            [
+                Token::StoredBlock(b"abc"),
+                Token::StoredBlock(&[]),
+                Token::Literal(b'd'),
+                Token::Match {
+                    length: 4,
+                    distance: 2
+                },
+                Token::EndOfBlock,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_token_iter_matches_output() {
+        let raw = b"tokens all the way down, all the way down ".repeat(4000);
+        let data = miniz_oxide::deflate::compress_to_vec(&raw, 6);
+        let mut output = Vec::new();
+        let mut tokens = TokenIter::new(&data);
+        while let Some(token) = tokens.next() {
+            match token.unwrap() {
+                Token::Literal(byte) => output.push(byte),
+                Token::Match { length, distance } => {
+                    for _ in 0..length {
+                        output.push(output[output.len() - distance]);
+                    }
+                }
+                Token::StoredBlock(data) => output.extend_from_slice(data),
+                Token::EndOfBlock => {}
+            }
+            assert_eq!(output.len(), tokens.output_offset());
+        }
+        assert_eq!(output, raw);
+    }
+
+    #[test]
+    fn test_token_iter_errors() {
+        let data = vec![0b000, 3, 0, !3, !0, b'a'];
+        let mut tokens = TokenIter::new(&data);
+        assert_eq!(tokens.next(), Some(Err(InflateError::UnexpectedEof)));
+        assert_eq!(tokens.next(), None);
+        let mut tokens = TokenIter::new(&[]);
+        assert_eq!(tokens.next(), Some(Err(InflateError::EmptyInput)));
+        assert_eq!(tokens.next(), None);
+    }
+}
@@ -0,0 +1,122 @@
+//! Export disassemblies as JSON traces for external tools.
+//!
+//! A trace is a JSON object whose layout is versioned by its `schema_version` field, so that
+//! visualization tools built on it keep working across releases. Within a schema version, fields
+//! and enum names are only ever added, never renamed or removed; anything else bumps the version.
+//!
+//! Schema version 1:
+//!
+//! ```text
+//! {
+//!   "schema_version": 1,
+//!   "format": "raw" | "zlib" | "gzip",
+//!   "data_bits": <number of bits in the input>,
+//!   "end_bit_offset": <bit offset at which decoding stopped>,
+//!   "error": null | "<error message, or error code with stable output>",
+//!   "fields": [
+//!     {
+//!       "bit_offset": <offset of the first bit of the field>,
+//!       "bit_len": <number of bits>,
+//!       "block_index": null | <index of the block>,
+//!       "output_offset": <output offset the field relates to>,
+//!       "kind": "<field kind>",
+//!       "value": <data of the field kind, absent for kinds without data>
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! Field kinds are the snake case names of [`FieldKind`]'s variants, e.g. `"distance_extra"`,
+//! and their values are numbers, booleans, format names, or objects of those for kinds with
+//! named data, e.g. `{"code": 257, "length": 3}`. Alphabets are `"literal_length"` or
+//! `"distance"`.
+
+use crate::container::Format;
+#[cfg(doc)]
+use crate::disasm::FieldKind;
+use crate::disasm::{Disassembly, Field};
+use crate::text::TextOptions;
+use serde::{Deserialize, Serialize};
+
+/// The schema version written into traces.
+pub const TRACE_SCHEMA_VERSION: u32 = 1;
+
+/// A disassembly in its exported form.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Trace {
+    pub schema_version: u32,
+    pub format: Format,
+    pub data_bits: usize,
+    pub end_bit_offset: usize,
+    /// The message of the error decoding stopped at, if any.
+    /// With stable output, the [code](crate::error::InflateError::code) of the error instead.
+    pub error: Option<String>,
+    pub fields: Vec<Field>,
+}
+
+impl Trace {
+    /// Build the trace of a disassembly.
+    pub fn new(disassembly: &Disassembly) -> Self {
+        Self::with_options(disassembly, &TextOptions::default())
+    }
+
+    /// Build the trace of a disassembly, with its text written as the options say.
+    pub fn with_options(disassembly: &Disassembly, options: &TextOptions) -> Self {
+        Self {
+            schema_version: TRACE_SCHEMA_VERSION,
+            format: disassembly.format,
+            data_bits: disassembly.data_bits,
+            end_bit_offset: disassembly.end_bit_offset,
+            error: disassembly.error.map(|e| options.error(&e)),
+            fields: disassembly.fields.clone(),
+        }
+    }
+
+    /// Serialize the trace as JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("traces always serialize")
+    }
+
+    /// Parse a trace from JSON.
+    /// Traces written with a newer schema version than `TRACE_SCHEMA_VERSION` are rejected.
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        let trace: Self = serde_json::from_str(json)?;
+        if trace.schema_version > TRACE_SCHEMA_VERSION {
+            return Err(serde::de::Error::custom(format!(
+                "unsupported trace schema version {}",
+                trace.schema_version
+            )));
+        }
+        Ok(trace)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disasm::disassemble;
+
+    #[test]
+    fn test_trace_round_trip() {
+        let data = miniz_oxide::deflate::compress_to_vec_zlib(&b"trace me ".repeat(20), 9);
+        let trace = Trace::new(&disassemble(&data, Format::Zlib));
+        let json = trace.to_json();
+        assert!(json.starts_with(r#"{"schema_version":1,"format":"zlib","#));
+        assert!(json.contains(r#""kind":"hlit","value":"#));
+        assert_eq!(Trace::from_json(&json).unwrap(), trace);
+
+        let newer = json.replacen(r#""schema_version":1"#, r#""schema_version":2"#, 1);
+        assert!(Trace::from_json(&newer).is_err());
+    }
+
+    #[test]
+    fn test_trace_stable_output() {
+        let disassembly = disassemble(&[0b111], Format::Raw);
+        let trace = Trace::with_options(&disassembly, &TextOptions::stable());
+        assert_eq!(trace.error.as_deref(), Some("invalid_block_type"));
+        assert_eq!(
+            Trace::new(&disassembly).error,
+            Some(crate::error::InflateError::InvalidBlockType.to_string())
+        );
+    }
+}
@@ -0,0 +1,319 @@
+//! Re-encode a DEFLATE stream from its LZ77 tokens, without finding the matches again.
+//!
+//! [`transcode`] decodes a stream into the tokens of a [`TokenIter`] and writes them out again
+//! with other blocks: other Huffman codes, or other block boundaries, while the matches stay
+//! the ones the original compressor found. Comparing the sizes shows what the block layer of
+//! a compressor is worth apart from its match finder, e.g. how much smaller the IDAT stream
+//! of a PNG image gets with dynamic codes in every block.
+
+use crate::bit_stream::BitWriter;
+use crate::deflate::{
+    self, write_dynamic_block, write_fixed_block, write_stored_block, MAX_STORED_BLOCK_LEN,
+};
+use crate::error::Result;
+use crate::inflate::repeat_with_overlap;
+use crate::tokens::{Token, TokenIter};
+use alloc::vec::Vec;
+use core::ops::Range;
+
+/// Number of bits of a block header.
+const BLOCK_HEADER_BITS: usize = 3;
+/// Number of bits of the LEN and NLEN fields of a stored block.
+const STORED_LEN_BITS: usize = 32;
+
+/// How the blocks of the new stream are encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlockEncoding {
+    /// Fixed Huffman codes.
+    Fixed,
+    /// Dynamic Huffman codes built for the tokens of each block.
+    Dynamic,
+    /// The smallest of stored, fixed Huffman and dynamic Huffman, block by block.
+    #[default]
+    Smallest,
+}
+
+/// Where the blocks of the new stream start and end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlockSplit {
+    /// At the same places as in the original stream. Empty blocks are left out.
+    #[default]
+    Keep,
+    /// A single block for the whole stream.
+    Single,
+    /// A new block every given number of tokens, each byte of a stored block counting as one.
+    Tokens(usize),
+}
+
+/// Settings of [`transcode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TranscodeOptions {
+    /// How the blocks are encoded.
+    pub encoding: BlockEncoding,
+    /// Where the blocks start and end.
+    pub split: BlockSplit,
+}
+
+/// A block of the new stream.
+#[derive(Debug)]
+struct Block {
+    tokens: Vec<deflate::Token>,
+    /// The range of the output the tokens decode to.
+    output: Range<usize>,
+}
+
+/// Cut a stream of tokens into blocks, keeping the output they decode to.
+#[derive(Debug)]
+struct BlockSplitter {
+    split: BlockSplit,
+    blocks: Vec<Block>,
+    tokens: Vec<deflate::Token>,
+    output: Vec<u8>,
+    block_start: usize,
+}
+
+impl BlockSplitter {
+    fn new(split: BlockSplit) -> Self {
+        if let BlockSplit::Tokens(n) = split {
+            assert!(n > 0, "Blocks need at least one token");
+        }
+        Self {
+            split,
+            blocks: Vec::new(),
+            tokens: Vec::new(),
+            output: Vec::new(),
+            block_start: 0,
+        }
+    }
+
+    /// Add a token to the current block, once its output is appended.
+    fn push(&mut self, token: deflate::Token) {
+        self.tokens.push(token);
+        if self.split == BlockSplit::Tokens(self.tokens.len()) {
+            self.finish_block();
+        }
+    }
+
+    /// End the current block, unless it is empty.
+    fn finish_block(&mut self) {
+        if self.tokens.is_empty() {
+            return;
+        }
+        self.blocks.push(Block {
+            tokens: core::mem::take(&mut self.tokens),
+            output: self.block_start..self.output.len(),
+        });
+        self.block_start = self.output.len();
+    }
+
+    /// Get the blocks, at least one, and the output they decode to.
+    fn finish(mut self) -> (Vec<Block>, Vec<u8>) {
+        self.finish_block();
+        if self.blocks.is_empty() {
+            self.blocks.push(Block {
+                tokens: Vec::new(),
+                output: 0..0,
+            });
+        }
+        (self.blocks, self.output)
+    }
+}
+
+/// Decode a raw DEFLATE stream into blocks of tokens, cut as the split says.
+fn split_blocks(data: &[u8], split: BlockSplit) -> Result<(Vec<Block>, Vec<u8>)> {
+    let mut splitter = BlockSplitter::new(split);
+    for token in TokenIter::new(data) {
+        match token? {
+            Token::Literal(byte) => {
+                splitter.output.push(byte);
+                splitter.push(deflate::Token::Literal(byte));
+            }
+            Token::Match { length, distance } => {
+                repeat_with_overlap(&mut splitter.output, distance, length);
+                splitter.push(deflate::Token::Match { length, distance });
+            }
+            Token::StoredBlock(bytes) => {
+                for &byte in bytes {
+                    splitter.output.push(byte);
+                    splitter.push(deflate::Token::Literal(byte));
+                }
+                if split == BlockSplit::Keep {
+                    splitter.finish_block();
+                }
+            }
+            Token::EndOfBlock => {
+                if split == BlockSplit::Keep {
+                    splitter.finish_block();
+                }
+            }
+        }
+    }
+    Ok(splitter.finish())
+}
+
+/// Number of bits stored blocks holding `len` bytes take, starting `bit_offset` bits into
+/// the stream.
+fn stored_bits(bit_offset: usize, len: usize) -> usize {
+    let n_blocks = len.div_ceil(MAX_STORED_BLOCK_LEN).max(1);
+    let mut end = bit_offset;
+    for i in 0..n_blocks {
+        let block_len = (len - i * MAX_STORED_BLOCK_LEN).min(MAX_STORED_BLOCK_LEN);
+        end = (end + BLOCK_HEADER_BITS).next_multiple_of(8) + STORED_LEN_BITS + block_len * 8;
+    }
+    end - bit_offset
+}
+
+/// Write data as stored blocks, as many as it takes.
+fn write_stored_blocks(writer: &mut BitWriter, data: &[u8], is_final: bool) {
+    let n_blocks = data.len().div_ceil(MAX_STORED_BLOCK_LEN).max(1);
+    for i in 0..n_blocks {
+        let start = i * MAX_STORED_BLOCK_LEN;
+        let chunk = &data[start..data.len().min(start + MAX_STORED_BLOCK_LEN)];
+        write_stored_block(writer, chunk, is_final && i == n_blocks - 1);
+    }
+}
+
+/// Write a block with the given encoding.
+fn write_block(
+    writer: &mut BitWriter,
+    block: &Block,
+    output: &[u8],
+    encoding: BlockEncoding,
+    is_final: bool,
+) {
+    match encoding {
+        BlockEncoding::Fixed => write_fixed_block(writer, &block.tokens, is_final),
+        BlockEncoding::Dynamic => write_dynamic_block(writer, &block.tokens, is_final),
+        BlockEncoding::Smallest => {
+            // Huffman blocks take the same bits wherever they start, stored blocks do not.
+            let mut fixed = BitWriter::new();
+            write_fixed_block(&mut fixed, &block.tokens, is_final);
+            let mut dynamic = BitWriter::new();
+            write_dynamic_block(&mut dynamic, &block.tokens, is_final);
+            let huffman = if dynamic.bits_written() < fixed.bits_written() {
+                dynamic
+            } else {
+                fixed
+            };
+            let data = &output[block.output.clone()];
+            if stored_bits(writer.bits_written(), data.len()) < huffman.bits_written() {
+                write_stored_blocks(writer, data, is_final);
+            } else {
+                writer.append(&huffman);
+            }
+        }
+    }
+}
+
+/// Decode a raw DEFLATE stream into LZ77 tokens and encode them again with the blocks the
+/// options ask for. The new stream decompresses to the same bytes. The data of stored blocks
+/// becomes literals, which `Smallest` may store again.
+///
+/// # Panics
+///
+/// Panics if the split is `BlockSplit::Tokens(0)`.
+pub fn transcode(data: &[u8], options: &TranscodeOptions) -> Result<Vec<u8>> {
+    let (blocks, output) = split_blocks(data, options.split)?;
+    let mut writer = BitWriter::new();
+    let last = blocks.len() - 1;
+    for (i, block) in blocks.iter().enumerate() {
+        write_block(&mut writer, block, &output, options.encoding, i == last);
+    }
+    Ok(writer.into_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deflate::deflate_stored_to_vec;
+    use crate::error::InflateError;
+    use crate::inflate::inflate_to_vec;
+    use crate::roundtrip::{sample, SampleKind};
+
+    /// Count the compressed blocks of a stream.
+    fn compressed_blocks(data: &[u8]) -> usize {
+        TokenIter::new(data)
+            .filter(|token| token == &Ok(Token::EndOfBlock))
+            .count()
+    }
+
+    #[test]
+    fn test_transcode() {
+        let raw = sample(SampleKind::Text, 7, 200_000);
+        let data = miniz_oxide::deflate::compress_to_vec(&raw, 6);
+        let encodings = [
+            BlockEncoding::Fixed,
+            BlockEncoding::Dynamic,
+            BlockEncoding::Smallest,
+        ];
+        let splits = [
+            BlockSplit::Keep,
+            BlockSplit::Single,
+            BlockSplit::Tokens(1000),
+        ];
+        for encoding in encodings {
+            for split in splits {
+                let options = TranscodeOptions { encoding, split };
+                let transcoded = transcode(&data, &options).unwrap();
+                assert_eq!(inflate_to_vec(&transcoded).unwrap(), raw, "{options:?}");
+            }
+        }
+
+        let n_tokens = TokenIter::new(&data)
+            .filter(|token| token != &Ok(Token::EndOfBlock))
+            .count();
+        let options = |split| TranscodeOptions {
+            encoding: BlockEncoding::Dynamic,
+            split,
+        };
+        let single = transcode(&data, &options(BlockSplit::Single)).unwrap();
+        assert_eq!(compressed_blocks(&single), 1);
+        let split = transcode(&data, &options(BlockSplit::Tokens(1000))).unwrap();
+        assert_eq!(compressed_blocks(&split), n_tokens.div_ceil(1000));
+        let kept = transcode(&data, &options(BlockSplit::Keep)).unwrap();
+        assert_eq!(compressed_blocks(&kept), compressed_blocks(&data));
+    }
+
+    #[test]
+    fn test_transcode_smallest() {
+        let raw = sample(SampleKind::Text, 3, 100_000);
+        let data = miniz_oxide::deflate::compress_to_vec(&raw, 6);
+        let size = |encoding| {
+            let options = TranscodeOptions {
+                encoding,
+                split: BlockSplit::Tokens(2000),
+            };
+            transcode(&data, &options).unwrap().len()
+        };
+        let smallest = size(BlockEncoding::Smallest);
+        assert!(smallest <= size(BlockEncoding::Fixed));
+        assert!(smallest <= size(BlockEncoding::Dynamic));
+
+        // Random data stays stored, in as many blocks as it takes.
+        let raw = sample(SampleKind::Random, 3, 100_000);
+        let data = deflate_stored_to_vec(&raw);
+        let options = TranscodeOptions {
+            split: BlockSplit::Single,
+            ..Default::default()
+        };
+        let transcoded = transcode(&data, &options).unwrap();
+        assert_eq!(transcoded, data);
+        assert_eq!(stored_bits(0, raw.len()), data.len() * 8);
+        assert_eq!(stored_bits(5, 0), 3 + 32);
+    }
+
+    #[test]
+    fn test_transcode_errors() {
+        let empty = transcode(&[0x03, 0x00], &TranscodeOptions::default()).unwrap();
+        assert_eq!(inflate_to_vec(&empty), Ok(Vec::new()));
+        let data = miniz_oxide::deflate::compress_to_vec(&b"truncated".repeat(100), 6);
+        assert_eq!(
+            transcode(&data[..data.len() / 2], &TranscodeOptions::default()),
+            Err(InflateError::UnexpectedEof)
+        );
+        assert_eq!(
+            transcode(&[], &TranscodeOptions::default()),
+            Err(InflateError::EmptyInput)
+        );
+    }
+}
@@ -0,0 +1,280 @@
+//! Check a DEFLATE stream for every problem it has, instead of stopping at the first one.
+//!
+//! Like a linter, [`validate`] keeps going after an error: back-references outside the window
+//! are recorded and decoded as far as possible, and after a corrupted block the decoder
+//! resynchronizes on the next bit offset at which a whole block decodes cleanly.
+//! The resynchronization is a heuristic: random bits decode as a valid fixed Huffman block
+//! surprisingly often, so problems reported after the first one may be false positives.
+//!
+//! [`verify_independent_blocks`] is a stricter check for streams whose blocks are meant to be
+//! self-contained, as in seekable DEFLATE: each block is decoded again on its own, with no
+//! output before it, and compared with the result of the sequential decoding.
+
+use crate::bit_stream::BitReader;
+use crate::error::{InflateError, Result};
+use crate::inflate::{
+    inflate_block, InflateState, MatchValidation, MatchWarning, MatchWarningKind, MAX_DISTANCE,
+};
+use alloc::vec::Vec;
+
+/// A problem found while validating a stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Problem {
+    /// Index of the block the problem was found in, counting resynchronized blocks.
+    pub block_index: usize,
+    /// Bit offset of the header of that block.
+    pub block_bit_offset: usize,
+    /// Bit offset at which the problem was detected.
+    pub bit_offset: usize,
+    /// Output offset at which the problem was detected.
+    pub output_offset: usize,
+    /// What went wrong.
+    pub error: InflateError,
+    /// Bit offset of the block decoding resumed at, if the error required resynchronization.
+    pub resumed_at: Option<usize>,
+}
+
+/// Find the first bit offset after `from_bit` at which a whole block decodes without error.
+/// The state is left as it was.
+pub(crate) fn resync(data: &[u8], from_bit: usize, state: &mut InflateState) -> Option<usize> {
+    let start = state.output.len();
+    let warnings = state.warnings.len();
+    let block_stats = state.block_stats;
+    (from_bit + 1..data.len() * 8).find(|&bit_offset| {
+        let mut bit_reader = BitReader::new_strict(data);
+        bit_reader.advance(bit_offset);
+        let decoded = inflate_block(&mut bit_reader, state).is_ok() && !bit_reader.eof();
+        state.output.truncate(start);
+        state.warnings.truncate(warnings);
+        state.block_stats = block_stats;
+        decoded
+    })
+}
+
+/// Validate a raw DEFLATE stream and list all problems found, in stream order.
+/// An empty list means the stream decodes cleanly.
+pub fn validate(data: &[u8]) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    if data.is_empty() {
+        problems.push(Problem {
+            block_index: 0,
+            block_bit_offset: 0,
+            bit_offset: 0,
+            output_offset: 0,
+            error: InflateError::EmptyInput,
+            resumed_at: None,
+        });
+        return problems;
+    }
+    let mut state = InflateState::with_validation(MatchValidation::Permissive, MAX_DISTANCE);
+    let mut bit_offset = 0;
+    let mut block_index = 0;
+
+    loop {
+        let mut bit_reader = BitReader::new_strict(data);
+        bit_reader.advance(bit_offset);
+        let output_start = state.output.len();
+        let warnings_start = state.warnings.len();
+        let result = inflate_block(&mut bit_reader, &mut state);
+
+        for warning in &state.warnings[warnings_start..] {
+            problems.push(Problem {
+                block_index,
+                block_bit_offset: bit_offset,
+                bit_offset: warning.bit_offset,
+                output_offset: warning.output_offset,
+                error: match warning.kind {
+                    MatchWarningKind::BeforeStart => InflateError::DistanceTooFar,
+                    MatchWarningKind::OutsideWindow => InflateError::DistanceOutsideWindow,
+                },
+                resumed_at: None,
+            });
+        }
+
+        match result {
+            Ok(true) => break,
+            Ok(false) => {
+                bit_offset = bit_reader.bits_consumed();
+                block_index += 1;
+            }
+            Err(error) => {
+                let problem_bit_offset = bit_reader.bits_consumed();
+                state.output.truncate(output_start);
+                let resumed_at = if error == InflateError::UnexpectedEof {
+                    None
+                } else {
+                    resync(data, bit_offset, &mut state)
+                };
+                problems.push(Problem {
+                    block_index,
+                    block_bit_offset: bit_offset,
+                    bit_offset: problem_bit_offset,
+                    output_offset: state.total_out(),
+                    error,
+                    resumed_at,
+                });
+                match resumed_at {
+                    Some(offset) => {
+                        bit_offset = offset;
+                        block_index += 1;
+                    }
+                    None => break,
+                }
+            }
+        }
+    }
+    problems
+}
+
+/// The outcome of decoding one block on its own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockVerification {
+    /// Index of the block in the stream.
+    pub block_index: usize,
+    /// Bit offset of the header of the block.
+    pub bit_offset: usize,
+    /// Output offset of the first byte of the block in the sequential decoding.
+    pub output_offset: usize,
+    /// Back-references reaching before the start of the block, into earlier blocks.
+    /// Their output offsets are relative to the start of the block.
+    pub cross_block_references: Vec<MatchWarning>,
+    /// Whether decoding the block on its own gave the same bytes as the sequential decoding.
+    pub matches_sequential: bool,
+}
+
+impl BlockVerification {
+    /// Check if the block decodes the same without the blocks before it.
+    pub fn is_self_contained(&self) -> bool {
+        self.cross_block_references.is_empty() && self.matches_sequential
+    }
+}
+
+/// Decode a raw DEFLATE stream, then decode each of its blocks again independently and
+/// compare the two results. Fails if the stream itself does not decode.
+/// A block that is not self-contained reports the back-references that reach into earlier
+/// blocks; those bytes are taken as zeros in its independent decoding.
+pub fn verify_independent_blocks(data: &[u8]) -> Result<Vec<BlockVerification>> {
+    if data.is_empty() {
+        return Err(InflateError::EmptyInput);
+    }
+    // Sequential decoding, recording where each block starts in the input and the output.
+    let mut state = InflateState::new();
+    let mut bit_reader = BitReader::new_strict(data);
+    let mut starts = Vec::new();
+    loop {
+        starts.push((bit_reader.bits_consumed(), state.output.len()));
+        if inflate_block(&mut bit_reader, &mut state)? {
+            break;
+        }
+    }
+    let ends = starts
+        .iter()
+        .skip(1)
+        .map(|&(_, output_offset)| output_offset);
+    let ends = ends.chain([state.output.len()]);
+
+    let mut verifications = Vec::new();
+    for (block_index, (&(bit_offset, output_offset), output_end)) in
+        starts.iter().zip(ends).enumerate()
+    {
+        let mut block_reader = BitReader::new_strict(data);
+        block_reader.advance(bit_offset);
+        let mut block_state =
+            InflateState::with_validation(MatchValidation::Permissive, MAX_DISTANCE);
+        // The block decoded once already, so only the missing history can make a difference.
+        let decoded = inflate_block(&mut block_reader, &mut block_state).is_ok();
+        let cross_block_references = block_state
+            .warnings
+            .into_iter()
+            .filter(|w| w.kind == MatchWarningKind::BeforeStart)
+            .collect();
+        verifications.push(BlockVerification {
+            block_index,
+            bit_offset,
+            output_offset,
+            cross_block_references,
+            matches_sequential: decoded
+                && block_state.output == state.output[output_offset..output_end],
+        });
+    }
+    Ok(verifications)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_validate_clean_stream() {
+        let raw = b"nothing wrong here, nothing wrong at all".repeat(20);
+        let data = miniz_oxide::deflate::compress_to_vec(&raw, 6);
+        assert!(validate(&data).is_empty());
+    }
+
+    #[test]
+    fn test_validate_empty_input() {
+        let problems = validate(&[]);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].error, InflateError::EmptyInput);
+    }
+
+    #[test]
+    fn test_validate_truncated_stream() {
+        let raw = b"cut short".repeat(20);
+        let data = miniz_oxide::deflate::compress_to_vec(&raw, 6);
+        let problems = validate(&data[..data.len() / 2]);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].error, InflateError::UnexpectedEof);
+        assert_eq!(problems[0].resumed_at, None);
+    }
+
+    #[test]
+    fn test_validate_continues_after_corrupted_block() {
+        // A stored block with a broken NLEN, followed by a valid final stored block at bit 64.
+        let mut data = vec![0b000, 3, 0, 0, 0, b'a', b'b', b'c'];
+        data.extend([0b001, 2, 0, !2, !0, b'o', b'k']);
+        let problems = validate(&data);
+        assert_eq!(problems[0].error, InflateError::InvalidLenNlen);
+        assert_eq!(problems[0].block_index, 0);
+        assert_eq!(problems[0].block_bit_offset, 0);
+        assert!(problems[0].resumed_at.is_some_and(|offset| offset <= 64));
+        assert!(!problems.iter().any(|p| p.error.is_truncation()));
+    }
+
+    #[test]
+    fn test_verify_independent_blocks() {
+        // Stored blocks never reference anything.
+        let raw: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        let data = crate::deflate::deflate_stored_to_vec(&raw);
+        let verifications = verify_independent_blocks(&data).unwrap();
+        assert_eq!(verifications.len(), 2);
+        assert!(verifications
+            .iter()
+            .all(BlockVerification::is_self_contained));
+        assert_eq!(verifications[1].output_offset, 65535);
+
+        // A stored block holding "abc", then a fixed block repeating it from 3 bytes back.
+        let mut data = vec![0b000, 3, 0, !3, !0, b'a', b'b', b'c'];
+        data.extend(crate::deflate::deflate_fixed_tokens_to_vec(&[
+            crate::deflate::Token::Match {
+                length: 3,
+                distance: 3,
+            },
+        ]));
+        let verifications = verify_independent_blocks(&data).unwrap();
+        assert!(verifications[0].is_self_contained());
+        let second = &verifications[1];
+        assert_eq!(second.bit_offset, 64);
+        assert_eq!(second.output_offset, 3);
+        assert!(!second.is_self_contained());
+        assert!(!second.matches_sequential);
+        assert_eq!(second.cross_block_references.len(), 1);
+        assert_eq!(second.cross_block_references[0].distance, 3);
+
+        assert_eq!(
+            verify_independent_blocks(&[0b111]),
+            Err(InflateError::InvalidBlockType)
+        );
+    }
+}
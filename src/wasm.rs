@@ -0,0 +1,99 @@
+//! JavaScript bindings through wasm-bindgen, to drive a browser visualizer with this crate.
+//!
+//! Build the crate for the web with the `wasm` feature, as a `cdylib`:
+//!
+//! ```sh
+//! cargo rustc --lib --release --target wasm32-unknown-unknown --features wasm --crate-type cdylib
+//! wasm-bindgen --target web --out-dir pkg target/wasm32-unknown-unknown/release/inflate_toy.wasm
+//! ```
+//!
+//! Byte buffers cross the boundary as `Uint8Array`s. Structured results are returned as JSON
+//! strings, for `JSON.parse`, and errors are thrown as JavaScript `Error`s carrying the message
+//! of the [`InflateError`].
+
+use crate::container::{detect_format, header_len};
+use crate::disasm::{annotate, disassemble};
+use crate::error::{InflateError, Result};
+use crate::inflate::{analyze, decompress_auto, inflate_to_vec};
+use crate::trace::Trace;
+use wasm_bindgen::prelude::*;
+
+/// Convert an error into one thrown on the JavaScript side.
+fn js_error(error: InflateError) -> JsError {
+    JsError::new(&error.to_string())
+}
+
+/// Inflate a raw DEFLATE stream.
+#[wasm_bindgen]
+pub fn inflate(data: &[u8]) -> std::result::Result<Vec<u8>, JsError> {
+    inflate_to_vec(data).map_err(js_error)
+}
+
+/// Decompress a raw DEFLATE, zlib or gzip stream, detecting its format.
+#[wasm_bindgen]
+pub fn decompress(data: &[u8]) -> std::result::Result<Vec<u8>, JsError> {
+    decompress_auto(data)
+        .map(|(output, _)| output)
+        .map_err(js_error)
+}
+
+/// Get the annotations of every field of a stream as a JSON array of
+/// `{"bit_range": {"start", "end"}, "label", "value"}` objects.
+/// A corrupted stream is annotated up to where decoding stopped.
+#[wasm_bindgen(js_name = annotateJson)]
+pub fn annotate_json(data: &[u8]) -> String {
+    serde_json::to_string(&annotate(data, detect_format(data)))
+        .expect("annotations always serialize")
+}
+
+/// Get the fields of a stream, with their bit offsets and values, as a JSON trace.
+#[wasm_bindgen(js_name = traceJson)]
+pub fn trace_json(data: &[u8]) -> String {
+    Trace::new(&disassemble(data, detect_format(data))).to_json()
+}
+
+/// Analyze a stream of any format into the JSON of its statistics.
+fn analyze_json(data: &[u8]) -> Result<String> {
+    let start = header_len(data, detect_format(data))?;
+    let stats = analyze(&data[start..])?;
+    Ok(serde_json::to_string(&stats).expect("statistics always serialize"))
+}
+
+/// Get the compression statistics of a stream, block by block and as a whole, as JSON.
+#[wasm_bindgen(js_name = statsJson)]
+pub fn stats_json(data: &[u8]) -> std::result::Result<String, JsError> {
+    analyze_json(data).map_err(js_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deflate::deflate_to_vec;
+
+    #[test]
+    fn test_annotate_json() {
+        let data = deflate_to_vec(b"wasm wasm wasm");
+        let json: serde_json::Value = serde_json::from_str(&annotate_json(&data)).unwrap();
+        let annotations = json.as_array().unwrap();
+        assert_eq!(
+            annotations.len(),
+            annotate(&data, detect_format(&data)).len()
+        );
+        assert_eq!(annotations[0]["bit_range"]["start"], 0);
+        assert!(annotations[0]["label"].is_string());
+    }
+
+    #[test]
+    fn test_analyze_json() {
+        let data = crate::container::wrap(
+            &deflate_to_vec(b"wasm wasm wasm"),
+            b"wasm wasm wasm",
+            crate::container::Format::Gzip,
+        );
+        let json: serde_json::Value = serde_json::from_str(&analyze_json(&data).unwrap()).unwrap();
+        assert_eq!(json["total"]["output_len"], 14);
+        assert_eq!(json["blocks"].as_array().unwrap().len(), 1);
+        assert_eq!(analyze_json(&[]), Err(InflateError::EmptyInput));
+        assert!(trace_json(&data).contains("\"schema_version\":1"));
+    }
+}
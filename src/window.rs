@@ -0,0 +1,184 @@
+//! A sliding window over the most recent output of a stream.
+//!
+//! Back-references reach at most `MAX_DISTANCE` bytes back, so a streaming decoder doesn't need
+//! to keep all of its output around to resolve them: once output has been handed on, e.g. to a
+//! writer, only its last 32 KiB are kept, in a [`Window`]. The window is a fixed-size ring
+//! buffer, so decompressing a stream of any length takes constant memory. Deflate64 reaches
+//! twice as far, so its window is created with [`Window::with_capacity`].
+
+use crate::inflate::MAX_DISTANCE;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// A ring buffer holding the last `capacity` bytes pushed into it, `MAX_DISTANCE` by default.
+///
+/// The buffer is only allocated on the first push, so a decoder that never hands its output
+/// on doesn't pay for it.
+#[derive(Debug, Clone)]
+pub struct Window {
+    /// The bytes, wrapping around; empty until something is pushed.
+    buf: Vec<u8>,
+    /// Index in `buf` where the next byte goes.
+    end: usize,
+    /// Number of bytes held, at most `capacity`.
+    len: usize,
+    /// Number of bytes the window keeps.
+    capacity: usize,
+}
+
+impl Default for Window {
+    fn default() -> Self {
+        Self::with_capacity(MAX_DISTANCE)
+    }
+}
+
+impl Window {
+    /// Create an empty window of `MAX_DISTANCE` bytes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an empty window keeping the last `capacity` bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn with_capacity(capacity: usize) -> Self {
+        assert!(capacity > 0, "window of zero bytes");
+        Self {
+            buf: Vec::new(),
+            end: 0,
+            len: 0,
+            capacity,
+        }
+    }
+
+    /// Number of bytes the window keeps.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of bytes held, at most the capacity.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check if nothing has been pushed yet.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Append bytes, dropping the oldest ones beyond the capacity.
+    pub fn push(&mut self, data: &[u8]) {
+        let capacity = self.capacity;
+        if self.buf.is_empty() {
+            self.buf = vec![0; capacity];
+        }
+        // Only the tail of a long slice survives.
+        let data = &data[data.len().saturating_sub(capacity)..];
+        let first = data.len().min(capacity - self.end);
+        self.buf[self.end..self.end + first].copy_from_slice(&data[..first]);
+        self.buf[..data.len() - first].copy_from_slice(&data[first..]);
+        self.end = (self.end + data.len()) % capacity;
+        self.len = (self.len + data.len()).min(capacity);
+    }
+
+    /// Get the byte `dist` bytes back from the end, if the window holds it.
+    /// A distance of 1 is the last byte pushed.
+    pub fn get(&self, dist: usize) -> Option<u8> {
+        if dist == 0 || dist > self.len {
+            return None;
+        }
+        Some(self.buf[(self.end + self.capacity - dist) % self.capacity])
+    }
+
+    /// Append the `len` bytes starting `dist` bytes back from the end to `out`.
+    ///
+    /// # Panics
+    /// Panics if `dist` is larger than the window, or `len` is larger than `dist`.
+    pub fn copy_to(&self, dist: usize, len: usize, out: &mut Vec<u8>) {
+        let end = out.len();
+        out.resize(end + len, 0);
+        self.copy_into(dist, &mut out[end..]);
+    }
+
+    /// Fill `out` with the bytes starting `dist` bytes back from the end.
+    ///
+    /// # Panics
+    /// Panics if `dist` is larger than the window, or `out` is longer than `dist`.
+    pub fn copy_into(&self, dist: usize, out: &mut [u8]) {
+        let len = out.len();
+        assert!(dist <= self.len, "distance beyond the window");
+        assert!(len <= dist, "copy past the end of the window");
+        let start = (self.end + self.capacity - dist) % self.capacity;
+        let first = len.min(self.capacity - start);
+        out[..first].copy_from_slice(&self.buf[start..start + first]);
+        out[first..].copy_from_slice(&self.buf[..len - first]);
+    }
+
+    /// Get the bytes held, oldest first, as two slices.
+    pub fn as_slices(&self) -> (&[u8], &[u8]) {
+        if self.len < self.capacity {
+            // Nothing has wrapped around yet.
+            return (&self.buf[self.end - self.len..self.end], &[]);
+        }
+        (&self.buf[self.end..], &self.buf[..self.end])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_push_and_get() {
+        let mut window = Window::new();
+        assert!(window.is_empty());
+        assert_eq!(window.get(1), None);
+        window.push(b"abc");
+        assert_eq!(window.len(), 3);
+        assert_eq!(window.get(1), Some(b'c'));
+        assert_eq!(window.get(3), Some(b'a'));
+        assert_eq!(window.get(4), None);
+        assert_eq!(window.as_slices(), (&b"abc"[..], &b""[..]));
+    }
+
+    #[test]
+    fn test_window_capacity() {
+        let mut window = Window::with_capacity(4);
+        assert_eq!(window.capacity(), 4);
+        window.push(b"abcdef");
+        window.push(b"g");
+        assert_eq!(window.len(), 4);
+        assert_eq!(window.get(4), Some(b'd'));
+        let (front, back) = window.as_slices();
+        assert_eq!([front, back].concat(), b"defg");
+        assert_eq!(Window::new().capacity(), MAX_DISTANCE);
+    }
+
+    #[test]
+    fn test_window_wraps_around() {
+        let data: Vec<u8> = (0..3 * MAX_DISTANCE + 123)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        let mut window = Window::new();
+        for chunk in data.chunks(1000) {
+            window.push(chunk);
+        }
+        let tail = &data[data.len() - MAX_DISTANCE..];
+        assert_eq!(window.len(), MAX_DISTANCE);
+        let (front, back) = window.as_slices();
+        assert_eq!([front, back].concat(), tail);
+
+        let mut out = Vec::new();
+        window.copy_to(MAX_DISTANCE, 500, &mut out);
+        window.copy_to(200, 200, &mut out);
+        assert_eq!(out[..500], tail[..500]);
+        assert_eq!(out[500..], tail[tail.len() - 200..]);
+
+        // A push longer than the window replaces all of it.
+        window.push(&data[..MAX_DISTANCE + 1]);
+        let (front, back) = window.as_slices();
+        assert_eq!([front, back].concat(), data[1..MAX_DISTANCE + 1]);
+    }
+}
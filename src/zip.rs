@@ -0,0 +1,659 @@
+//! Extract ZIP archives, through their central directory or sequentially without it.
+//!
+//! A ZIP archive is a sequence of entries, each a local file header followed by the entry
+//! data, and ends with a central directory listing every entry with the offset of its local
+//! header, itself followed by the end-of-central-directory record. [`ZipArchive`] reads an
+//! archive held in memory the way unzip does: it finds the end-of-central-directory record,
+//! lists the entries from the central directory and extracts them by name.
+//!
+//! The central directory sits at the end of an archive, so it is out of reach when the archive
+//! arrives over a socket. [`ZipStreamReader`] instead walks the local file headers one after the
+//! other and extracts each entry as soon as its data has arrived. Entries whose sizes are only
+//! given in a data descriptor after their data are supported: deflated data ends by itself, and
+//! stored data ends where a data descriptor with a matching size is found.
+//! Entries may be stored, deflated, or compressed with Deflate64 (method 9).
+//! The format is described in PKWARE's APPNOTE.TXT; ZIP64 and encryption are not supported.
+
+use crate::checksum::crc32;
+use crate::error::InflateError;
+use crate::inflate::{inflate_to_vec_with_options, InflateOptions};
+use crate::stream::Inflater;
+use std::io::{ErrorKind, Read, Result};
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0605_4b50;
+const DATA_DESCRIPTOR_SIGNATURE: u32 = 0x0807_4b50;
+
+const LOCAL_HEADER_LEN: usize = 30;
+const CENTRAL_HEADER_LEN: usize = 46;
+const END_OF_CENTRAL_DIRECTORY_LEN: usize = 22;
+/// The archive comment closing the end-of-central-directory record is at most this long.
+const MAX_COMMENT_LEN: usize = u16::MAX as usize;
+/// Length of a data descriptor without its optional signature: CRC-32 and both sizes.
+const DATA_DESCRIPTOR_LEN: usize = 12;
+const SIGNATURE_LEN: usize = 4;
+
+const FLAG_ENCRYPTED: u16 = 0x0001;
+const FLAG_DATA_DESCRIPTOR: u16 = 0x0008;
+
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATED: u16 = 8;
+const METHOD_DEFLATE64: u16 = 9;
+
+/// Number of bytes read from the underlying reader at a time.
+const READ_CHUNK_LEN: usize = 8 * 1024;
+
+/// An entry extracted from a ZIP archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZipEntry {
+    /// The file name, as stored in the archive.
+    pub name: Vec<u8>,
+    /// The compression method: 0 for stored, 8 for deflated, 9 for Deflate64.
+    pub method: u16,
+    /// The CRC-32 of the data, from the local header or the data descriptor.
+    /// The data is checked against it.
+    pub crc32: u32,
+    /// The size of the compressed data.
+    pub compressed_size: u64,
+    /// The extracted data.
+    pub data: Vec<u8>,
+}
+
+/// Read a little-endian u16 at the given position.
+fn read_u16_le(data: &[u8], pos: usize) -> u16 {
+    u16::from_le_bytes([data[pos], data[pos + 1]])
+}
+
+/// Read a little-endian u32 at the given position.
+fn read_u32_le(data: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]])
+}
+
+/// A reader extracting the entries of a ZIP archive one by one, in archive order.
+#[derive(Debug)]
+pub struct ZipStreamReader<R: Read> {
+    reader: R,
+    /// Bytes read from the reader but not consumed yet.
+    buf: Vec<u8>,
+    /// Whether the reader has no more data.
+    reader_eof: bool,
+    /// Whether the last entry has been extracted.
+    done: bool,
+}
+
+impl<R: Read> ZipStreamReader<R> {
+    /// Create a reader extracting the archive read from `reader`.
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buf: Vec::new(),
+            reader_eof: false,
+            done: false,
+        }
+    }
+
+    /// Read one more chunk into the buffer.
+    /// Returns false once the reader has no more data.
+    fn read_chunk(&mut self) -> Result<bool> {
+        if self.reader_eof {
+            return Ok(false);
+        }
+        let start = self.buf.len();
+        self.buf.resize(start + READ_CHUNK_LEN, 0);
+        let n = loop {
+            match self.reader.read(&mut self.buf[start..]) {
+                Ok(n) => break n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    self.buf.truncate(start);
+                    return Err(e);
+                }
+            }
+        };
+        self.buf.truncate(start + n);
+        if n == 0 {
+            self.reader_eof = true;
+        }
+        Ok(!self.reader_eof)
+    }
+
+    /// Make sure at least `n` bytes are buffered.
+    fn fill(&mut self, n: usize) -> Result<()> {
+        while self.buf.len() < n {
+            if !self.read_chunk()? {
+                return Err(InflateError::UnexpectedEof.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Extract the next entry.
+    /// Returns None once the local headers give way to the central directory,
+    /// or the archive ends. After an error, no more entries are returned.
+    pub fn next_entry(&mut self) -> Result<Option<ZipEntry>> {
+        if self.done {
+            return Ok(None);
+        }
+        let entry = self.read_entry();
+        if entry.is_err() {
+            self.done = true;
+        }
+        entry
+    }
+
+    /// Read the next local header and the entry data following it.
+    fn read_entry(&mut self) -> Result<Option<ZipEntry>> {
+        while self.buf.len() < SIGNATURE_LEN && self.read_chunk()? {}
+        if self.buf.is_empty() {
+            self.done = true;
+            return Ok(None);
+        }
+        self.fill(SIGNATURE_LEN)?;
+        match read_u32_le(&self.buf, 0) {
+            LOCAL_HEADER_SIGNATURE => {}
+            CENTRAL_HEADER_SIGNATURE | END_OF_CENTRAL_DIRECTORY_SIGNATURE => {
+                self.done = true;
+                return Ok(None);
+            }
+            _ => return Err(InflateError::InvalidHeader("Invalid ZIP local header").into()),
+        }
+
+        self.fill(LOCAL_HEADER_LEN)?;
+        let header = &self.buf[..LOCAL_HEADER_LEN];
+        let flags = read_u16_le(header, 6);
+        let method = read_u16_le(header, 8);
+        let mut expected_crc = read_u32_le(header, 14);
+        let mut compressed_size = read_u32_le(header, 18) as u64;
+        let name_len = read_u16_le(header, 26) as usize;
+        let extra_len = read_u16_le(header, 28) as usize;
+        if flags & FLAG_ENCRYPTED != 0 {
+            return Err(
+                InflateError::InvalidHeader("Encrypted ZIP entries are not supported").into(),
+            );
+        }
+        let header_len = LOCAL_HEADER_LEN + name_len + extra_len;
+        self.fill(header_len)?;
+        let name = self.buf[LOCAL_HEADER_LEN..LOCAL_HEADER_LEN + name_len].to_vec();
+        self.buf.drain(..header_len);
+
+        let has_descriptor = flags & FLAG_DATA_DESCRIPTOR != 0;
+        let data = match method {
+            METHOD_DEFLATED | METHOD_DEFLATE64 => self.read_deflated(method == METHOD_DEFLATE64)?,
+            METHOD_STORED if has_descriptor => self.read_stored_until_descriptor()?,
+            METHOD_STORED => {
+                let len = compressed_size as usize;
+                self.fill(len)?;
+                self.buf.drain(..len).collect()
+            }
+            _ => return Err(InflateError::InvalidHeader("Unsupported compression method").into()),
+        };
+
+        if has_descriptor {
+            self.fill(SIGNATURE_LEN)?;
+            if read_u32_le(&self.buf, 0) == DATA_DESCRIPTOR_SIGNATURE {
+                self.buf.drain(..SIGNATURE_LEN);
+            }
+            self.fill(DATA_DESCRIPTOR_LEN)?;
+            expected_crc = read_u32_le(&self.buf, 0);
+            compressed_size = read_u32_le(&self.buf, 4) as u64;
+            self.buf.drain(..DATA_DESCRIPTOR_LEN);
+        }
+        let found = crc32(&data);
+        if found != expected_crc {
+            return Err(InflateError::ChecksumMismatch {
+                expected: expected_crc,
+                found,
+            }
+            .into());
+        }
+        Ok(Some(ZipEntry {
+            name,
+            method,
+            crc32: expected_crc,
+            compressed_size,
+            data,
+        }))
+    }
+
+    /// Inflate deflated or Deflate64 entry data, which tells where it ends by itself.
+    fn read_deflated(&mut self, deflate64: bool) -> Result<Vec<u8>> {
+        let mut inflater = Inflater::with_options(&InflateOptions::new().deflate64(deflate64))?;
+        let mut output = Vec::new();
+        inflater.feed(&self.buf)?;
+        self.buf.clear();
+        while !inflater.is_finished() {
+            if !self.read_chunk()? {
+                inflater.finish()?;
+                break;
+            }
+            inflater.feed(&self.buf)?;
+            self.buf.clear();
+            output.extend(inflater.take_output());
+        }
+        output.extend(inflater.take_output());
+        self.buf = inflater.remaining_input().to_vec();
+        Ok(output)
+    }
+
+    /// Read stored entry data of unknown size: it ends at the first data descriptor
+    /// signature whose compressed size field matches the length of the data before it.
+    fn read_stored_until_descriptor(&mut self) -> Result<Vec<u8>> {
+        let mut pos = 0;
+        loop {
+            while pos + SIGNATURE_LEN + DATA_DESCRIPTOR_LEN <= self.buf.len() {
+                if read_u32_le(&self.buf, pos) == DATA_DESCRIPTOR_SIGNATURE
+                    && read_u32_le(&self.buf, pos + SIGNATURE_LEN + 4) as usize == pos
+                {
+                    return Ok(self.buf.drain(..pos).collect());
+                }
+                pos += 1;
+            }
+            if !self.read_chunk()? {
+                return Err(InflateError::UnexpectedEof.into());
+            }
+        }
+    }
+}
+
+impl<R: Read> Iterator for ZipStreamReader<R> {
+    type Item = Result<ZipEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_entry().transpose()
+    }
+}
+
+/// An entry of a ZIP archive, as listed in its central directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZipFileInfo {
+    /// The file name, as stored in the archive.
+    pub name: Vec<u8>,
+    /// The compression method: 0 for stored, 8 for deflated, 9 for Deflate64.
+    pub method: u16,
+    /// The general purpose bit flags.
+    pub flags: u16,
+    /// The CRC-32 of the uncompressed data.
+    pub crc32: u32,
+    /// The size of the compressed data.
+    pub compressed_size: u64,
+    /// The size of the uncompressed data.
+    pub uncompressed_size: u64,
+    /// The offset of the local file header from the start of the archive.
+    pub local_header_offset: u64,
+}
+
+impl ZipFileInfo {
+    /// Check if the entry is a directory rather than a file.
+    pub fn is_dir(&self) -> bool {
+        self.name.ends_with(b"/")
+    }
+}
+
+/// A ZIP archive held in memory, read through its central directory.
+#[derive(Debug, Clone)]
+pub struct ZipArchive<'a> {
+    data: &'a [u8],
+    entries: Vec<ZipFileInfo>,
+}
+
+impl<'a> ZipArchive<'a> {
+    /// Parse the end-of-central-directory record and the central directory of an archive.
+    pub fn new(data: &'a [u8]) -> crate::error::Result<Self> {
+        let eocd = find_end_of_central_directory(data)?;
+        let count = read_u16_le(data, eocd + 10) as usize;
+        let directory_len = read_u32_le(data, eocd + 12) as usize;
+        let directory_offset = read_u32_le(data, eocd + 16) as usize;
+        let directory = directory_offset
+            .checked_add(directory_len)
+            .and_then(|end| data.get(directory_offset..end))
+            .ok_or(InflateError::InvalidHeader("Invalid ZIP central directory"))?;
+
+        let mut entries = Vec::with_capacity(count);
+        let mut pos = 0;
+        for _ in 0..count {
+            let header = directory
+                .get(pos..pos + CENTRAL_HEADER_LEN)
+                .ok_or(InflateError::UnexpectedEof)?;
+            if read_u32_le(header, 0) != CENTRAL_HEADER_SIGNATURE {
+                return Err(InflateError::InvalidHeader("Invalid ZIP central header"));
+            }
+            let name_len = read_u16_le(header, 28) as usize;
+            let extra_len = read_u16_le(header, 30) as usize;
+            let comment_len = read_u16_le(header, 32) as usize;
+            let name = directory
+                .get(pos + CENTRAL_HEADER_LEN..pos + CENTRAL_HEADER_LEN + name_len)
+                .ok_or(InflateError::UnexpectedEof)?;
+            entries.push(ZipFileInfo {
+                name: name.to_vec(),
+                method: read_u16_le(header, 10),
+                flags: read_u16_le(header, 8),
+                crc32: read_u32_le(header, 16),
+                compressed_size: read_u32_le(header, 20) as u64,
+                uncompressed_size: read_u32_le(header, 24) as u64,
+                local_header_offset: read_u32_le(header, 42) as u64,
+            });
+            pos += CENTRAL_HEADER_LEN + name_len + extra_len + comment_len;
+        }
+        Ok(Self { data, entries })
+    }
+
+    /// List the entries of the archive, in central directory order.
+    pub fn list(&self) -> &[ZipFileInfo] {
+        &self.entries
+    }
+
+    /// Find the entry with the given name.
+    pub fn by_name(&self, name: &[u8]) -> Option<&ZipFileInfo> {
+        self.entries.iter().find(|entry| entry.name == name)
+    }
+
+    /// Extract the entry with the given name, or return None if the archive has no such entry.
+    pub fn extract_by_name(&self, name: &[u8]) -> crate::error::Result<Option<Vec<u8>>> {
+        self.by_name(name)
+            .map(|entry| self.extract(entry))
+            .transpose()
+    }
+
+    /// Extract an entry, checking its size and CRC-32 against the central directory.
+    pub fn extract(&self, entry: &ZipFileInfo) -> crate::error::Result<Vec<u8>> {
+        if entry.flags & FLAG_ENCRYPTED != 0 {
+            return Err(InflateError::InvalidHeader(
+                "Encrypted ZIP entries are not supported",
+            ));
+        }
+        let offset = entry.local_header_offset as usize;
+        let header = self
+            .data
+            .get(offset..offset + LOCAL_HEADER_LEN)
+            .ok_or(InflateError::UnexpectedEof)?;
+        if read_u32_le(header, 0) != LOCAL_HEADER_SIGNATURE {
+            return Err(InflateError::InvalidHeader("Invalid ZIP local header"));
+        }
+        // The name and extra field lengths may differ from those in the central directory.
+        let name_len = read_u16_le(header, 26) as usize;
+        let extra_len = read_u16_le(header, 28) as usize;
+        let start = offset + LOCAL_HEADER_LEN + name_len + extra_len;
+        let compressed = self
+            .data
+            .get(start..start + entry.compressed_size as usize)
+            .ok_or(InflateError::UnexpectedEof)?;
+
+        let data = match entry.method {
+            METHOD_STORED => compressed.to_vec(),
+            METHOD_DEFLATED | METHOD_DEFLATE64 => {
+                let size = entry.uncompressed_size as usize;
+                let options = InflateOptions::new()
+                    .max_output(size)
+                    .size_hint(size)
+                    .deflate64(entry.method == METHOD_DEFLATE64);
+                inflate_to_vec_with_options(compressed, &options)?
+            }
+            _ => {
+                return Err(InflateError::InvalidHeader(
+                    "Unsupported compression method",
+                ))
+            }
+        };
+        if data.len() as u64 != entry.uncompressed_size {
+            return Err(InflateError::InvalidHeader("ZIP entry size mismatch"));
+        }
+        let found = crc32(&data);
+        if found != entry.crc32 {
+            return Err(InflateError::ChecksumMismatch {
+                expected: entry.crc32,
+                found,
+            });
+        }
+        Ok(data)
+    }
+}
+
+/// Find the end-of-central-directory record, searching backwards past the archive comment.
+fn find_end_of_central_directory(data: &[u8]) -> crate::error::Result<usize> {
+    if data.len() < END_OF_CENTRAL_DIRECTORY_LEN {
+        return Err(InflateError::UnexpectedEof);
+    }
+    let last = data.len() - END_OF_CENTRAL_DIRECTORY_LEN;
+    let first = last.saturating_sub(MAX_COMMENT_LEN);
+    (first..=last)
+        .rev()
+        .find(|&pos| {
+            read_u32_le(data, pos) == END_OF_CENTRAL_DIRECTORY_SIGNATURE
+                && pos + END_OF_CENTRAL_DIRECTORY_LEN + read_u16_le(data, pos + 20) as usize
+                    == data.len()
+        })
+        .ok_or(InflateError::InvalidHeader(
+            "Missing ZIP end of central directory",
+        ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A reader handing out its data a few bytes at a time, like a slow socket, and
+    /// interrupted by a signal before every read.
+    struct Trickle<'a>(&'a [u8], bool);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            self.1 = !self.1;
+            if self.1 {
+                return Err(ErrorKind::Interrupted.into());
+            }
+            let n = buf.len().min(self.0.len()).min(7);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    /// Append a local header, the entry data and, with unknown sizes, a data descriptor.
+    fn push_entry(archive: &mut Vec<u8>, entry: TestEntry<'_>, descriptor: bool) {
+        let (name, method, data, uncompressed) = entry;
+        let (flags, size, crc) = if descriptor {
+            (FLAG_DATA_DESCRIPTOR, 0, 0)
+        } else {
+            (0, data.len() as u32, crc32(uncompressed))
+        };
+        archive.extend(LOCAL_HEADER_SIGNATURE.to_le_bytes());
+        archive.extend(20u16.to_le_bytes());
+        archive.extend(flags.to_le_bytes());
+        archive.extend(method.to_le_bytes());
+        archive.extend([0; 4]);
+        archive.extend(crc.to_le_bytes());
+        archive.extend(size.to_le_bytes());
+        archive.extend([0; 4]);
+        archive.extend((name.len() as u16).to_le_bytes());
+        archive.extend(0u16.to_le_bytes());
+        archive.extend(name);
+        archive.extend(data);
+        if descriptor {
+            archive.extend(DATA_DESCRIPTOR_SIGNATURE.to_le_bytes());
+            archive.extend(crc32(uncompressed).to_le_bytes());
+            archive.extend((data.len() as u32).to_le_bytes());
+            archive.extend([0; 4]);
+        }
+    }
+
+    #[test]
+    fn test_zip_stream_reader() {
+        let text = b"streamed zip entry, streamed zip entry".repeat(50);
+        let deflated = miniz_oxide::deflate::compress_to_vec(&text, 6);
+        let mut archive = Vec::new();
+        push_entry(
+            &mut archive,
+            (b"a.txt", METHOD_DEFLATED, &deflated, &text),
+            true,
+        );
+        let stored = b"stored, size unknown";
+        push_entry(
+            &mut archive,
+            (b"b.txt", METHOD_STORED, stored, stored),
+            true,
+        );
+        push_entry(
+            &mut archive,
+            (b"c.txt", METHOD_STORED, b"stored", b"stored"),
+            false,
+        );
+        archive.extend(CENTRAL_HEADER_SIGNATURE.to_le_bytes());
+        archive.extend([0; 42]);
+
+        let entries: Vec<_> = ZipStreamReader::new(Trickle(&archive, false))
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].name, b"a.txt");
+        assert_eq!(entries[0].data, text);
+        assert_eq!(entries[0].compressed_size, deflated.len() as u64);
+        assert_eq!(entries[0].crc32, crc32(&text));
+        assert_eq!(entries[1].data, b"stored, size unknown");
+        assert_eq!(entries[2].name, b"c.txt");
+        assert_eq!(entries[2].data, b"stored");
+    }
+
+    #[test]
+    fn test_zip_stream_reader_truncated() {
+        let mut archive = Vec::new();
+        let data = b"never ends";
+        push_entry(&mut archive, (b"a.txt", METHOD_STORED, data, data), true);
+        archive.truncate(archive.len() - 8);
+        let mut reader = ZipStreamReader::new(&archive[..]);
+        let err = reader.next_entry().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_zip_stream_reader_checksum() {
+        for descriptor in [false, true] {
+            let mut archive = Vec::new();
+            push_entry(
+                &mut archive,
+                (b"a.txt", METHOD_STORED, b"sum", b"sum"),
+                descriptor,
+            );
+            // The CRC-32 is in the descriptor, 12 bytes from the end, or in the local header.
+            let crc_pos = if descriptor { archive.len() - 12 } else { 14 };
+            archive[crc_pos] ^= 1;
+            let mut reader = ZipStreamReader::new(&archive[..]);
+            let err = reader.next_entry().unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidData);
+            let inner = err
+                .into_inner()
+                .unwrap()
+                .downcast::<InflateError>()
+                .unwrap();
+            assert!(matches!(*inner, InflateError::ChecksumMismatch { .. }));
+            assert!(reader.next().is_none());
+        }
+    }
+
+    /// The name, method, compressed data and uncompressed data of an entry.
+    type TestEntry<'a> = (&'a [u8], u16, &'a [u8], &'a [u8]);
+
+    /// Build an archive with a central directory and the given comment.
+    fn build_archive(entries: &[TestEntry<'_>], comment: &[u8]) -> Vec<u8> {
+        let mut archive = Vec::new();
+        let mut directory = Vec::new();
+        for &(name, method, data, uncompressed) in entries {
+            let offset = archive.len() as u32;
+            let mut fields = Vec::new();
+            fields.extend(0u16.to_le_bytes());
+            fields.extend(method.to_le_bytes());
+            fields.extend([0; 4]);
+            fields.extend(crc32(uncompressed).to_le_bytes());
+            fields.extend((data.len() as u32).to_le_bytes());
+            fields.extend((uncompressed.len() as u32).to_le_bytes());
+            fields.extend((name.len() as u16).to_le_bytes());
+            fields.extend(0u16.to_le_bytes());
+
+            archive.extend(LOCAL_HEADER_SIGNATURE.to_le_bytes());
+            archive.extend(20u16.to_le_bytes());
+            archive.extend(&fields);
+            archive.extend(name);
+            archive.extend(data);
+
+            directory.extend(CENTRAL_HEADER_SIGNATURE.to_le_bytes());
+            directory.extend(20u16.to_le_bytes());
+            directory.extend(20u16.to_le_bytes());
+            directory.extend(&fields);
+            directory.extend([0; 10]);
+            directory.extend(offset.to_le_bytes());
+            directory.extend(name);
+        }
+        let directory_offset = archive.len() as u32;
+        archive.extend(&directory);
+        archive.extend(END_OF_CENTRAL_DIRECTORY_SIGNATURE.to_le_bytes());
+        archive.extend([0; 4]);
+        archive.extend((entries.len() as u16).to_le_bytes());
+        archive.extend((entries.len() as u16).to_le_bytes());
+        archive.extend((directory.len() as u32).to_le_bytes());
+        archive.extend(directory_offset.to_le_bytes());
+        archive.extend((comment.len() as u16).to_le_bytes());
+        archive.extend(comment);
+        archive
+    }
+
+    #[test]
+    fn test_zip_archive() {
+        let text = b"zip archive entry, zip archive entry".repeat(40);
+        let deflated = miniz_oxide::deflate::compress_to_vec(&text, 6);
+        let archive = build_archive(
+            &[
+                (b"docs/", METHOD_STORED, b"", b""),
+                (b"docs/a.txt", METHOD_DEFLATED, &deflated, &text),
+                (b"b.txt", METHOD_STORED, b"stored", b"stored"),
+            ],
+            b"comment with a fake \x50\x4b\x05\x06 signature",
+        );
+        let zip = ZipArchive::new(&archive).unwrap();
+        let names: Vec<_> = zip.list().iter().map(|entry| &entry.name[..]).collect();
+        assert_eq!(names, [&b"docs/"[..], b"docs/a.txt", b"b.txt"]);
+        assert!(zip.list()[0].is_dir());
+        assert_eq!(zip.list()[1].uncompressed_size, text.len() as u64);
+
+        assert_eq!(zip.extract_by_name(b"docs/a.txt").unwrap().unwrap(), text);
+        assert_eq!(zip.extract_by_name(b"b.txt").unwrap().unwrap(), b"stored");
+        assert_eq!(zip.extract_by_name(b"missing.txt").unwrap(), None);
+    }
+
+    #[test]
+    fn test_zip_deflate64() {
+        let (deflate64, data) = crate::testgen::deflate64_stream();
+        let archive = build_archive(&[(b"big.bin", METHOD_DEFLATE64, &deflate64, &data)], b"");
+        let zip = ZipArchive::new(&archive).unwrap();
+        assert_eq!(zip.extract_by_name(b"big.bin").unwrap().unwrap(), data);
+
+        let mut archive = Vec::new();
+        push_entry(
+            &mut archive,
+            (b"big.bin", METHOD_DEFLATE64, &deflate64, &data),
+            true,
+        );
+        let entries: Vec<_> = ZipStreamReader::new(Trickle(&archive, false))
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(entries[0].method, METHOD_DEFLATE64);
+        assert_eq!(entries[0].data, data);
+    }
+
+    #[test]
+    fn test_zip_archive_corrupt() {
+        let archive = build_archive(&[(b"a.txt", METHOD_STORED, b"stored", b"stored")], b"");
+        let mut corrupt = archive.clone();
+        corrupt[LOCAL_HEADER_LEN + 5] ^= 1;
+        let zip = ZipArchive::new(&corrupt).unwrap();
+        assert!(matches!(
+            zip.extract_by_name(b"a.txt"),
+            Err(InflateError::ChecksumMismatch { .. })
+        ));
+
+        assert_eq!(
+            ZipArchive::new(&archive[..archive.len() - 1]).unwrap_err(),
+            InflateError::InvalidHeader("Missing ZIP end of central directory")
+        );
+    }
+}
@@ -0,0 +1,317 @@
+//! Tests of the `inflate-toy` binary.
+
+#![cfg(all(feature = "std", feature = "serde"))]
+
+use inflate_toy::container::Format;
+use inflate_toy::disasm::{annotate, Annotation};
+use inflate_toy::entropy::{entropy_table, estimate};
+use inflate_toy::huffman::HuffmanLeaf;
+use inflate_toy::inflate::{analyze, inspect, BlockInfo, StreamStats};
+use inflate_toy::text::{TableFormat, TextOptions};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, UNIX_EPOCH};
+
+fn data_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("data")
+        .join(name)
+}
+
+fn inflate_toy(args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_inflate-toy"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_gunzip() {
+    let dir = tempfile::tempdir().unwrap();
+    let gz = dir.path().join("lorem.gz");
+    let compressed = std::fs::read(data_path("lorem_ipsum_data.gz")).unwrap();
+    std::fs::write(&gz, &compressed).unwrap();
+
+    let output = inflate_toy(&["gunzip", "-k", gz.to_str().unwrap()]);
+    assert!(output.status.success(), "{:?}", output);
+    let decompressed = dir.path().join("lorem");
+    assert_eq!(
+        std::fs::read(&decompressed).unwrap(),
+        std::fs::read(data_path("lorem_ipsum_data")).unwrap()
+    );
+    let mtime = u32::from_le_bytes(compressed[4..8].try_into().unwrap());
+    if mtime != 0 {
+        let modified = std::fs::metadata(&decompressed)
+            .unwrap()
+            .modified()
+            .unwrap();
+        assert_eq!(modified, UNIX_EPOCH + Duration::from_secs(mtime.into()));
+    }
+    assert!(gz.exists());
+
+    // The output exists now: gunzip refuses to overwrite it unless forced.
+    assert!(!inflate_toy(&["-d", gz.to_str().unwrap()]).status.success());
+    assert!(inflate_toy(&["-d", "-f", gz.to_str().unwrap()])
+        .status
+        .success());
+    assert!(!gz.exists());
+}
+
+#[test]
+fn test_gunzip_rejects_bad_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let no_suffix = dir.path().join("lorem");
+    std::fs::write(&no_suffix, b"not compressed").unwrap();
+    assert!(!inflate_toy(&["gunzip", no_suffix.to_str().unwrap()])
+        .status
+        .success());
+
+    // A flipped bit in the CRC.
+    let gz = dir.path().join("corrupt.gz");
+    let mut compressed = std::fs::read(data_path("lorem_ipsum_data.gz")).unwrap();
+    let crc = compressed.len() - 8;
+    compressed[crc] ^= 1;
+    std::fs::write(&gz, &compressed).unwrap();
+    let output = inflate_toy(&["gunzip", gz.to_str().unwrap()]);
+    assert!(!output.status.success());
+    assert!(gz.exists());
+}
+
+#[test]
+fn test_decompress_reports_error_location() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("truncated.deflate");
+    let compressed = std::fs::read(data_path("lorem_ipsum_data.deflate")).unwrap();
+    std::fs::write(&path, &compressed[..100]).unwrap();
+    let output = inflate_toy(&[path.to_str().unwrap()]);
+    assert!(!output.status.success());
+    let message = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        message.contains("Unexpected end of data at bit "),
+        "{}",
+        message
+    );
+    assert!(
+        message.contains(" of block 0, while reading "),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn test_decompress_narration() {
+    let path = data_path("lorem_ipsum_data.deflate");
+    let output = inflate_toy(&[path.to_str().unwrap(), "--trace"]);
+    assert!(output.status.success());
+    assert_eq!(
+        output.stdout,
+        std::fs::read(data_path("lorem_ipsum_data")).unwrap()
+    );
+    let narration = String::from_utf8(output.stderr).unwrap();
+    assert!(narration.starts_with("block 0 at bit 0: "), "{}", narration);
+    assert!(narration.contains(": match length "));
+
+    let output = inflate_toy(&[path.to_str().unwrap(), "-v"]);
+    let narration = String::from_utf8(output.stderr).unwrap();
+    assert!(narration.contains("end of block: "));
+    assert!(!narration.contains(": match length "));
+}
+
+#[test]
+fn test_explain() {
+    let path = data_path("lorem_ipsum_data.gz");
+    let output = inflate_toy(&["explain", path.to_str().unwrap()]);
+    assert!(output.status.success());
+    let narration = String::from_utf8(output.stdout).unwrap();
+    assert!(narration.starts_with("The data starts with a gzip header of "));
+    assert!(narration.contains("Block 1 at bit "));
+    assert!(narration.contains("  Match of length "));
+    assert!(narration.ends_with("The gzip trailer of 8 bytes ends the data.\n"));
+
+    let output = inflate_toy(&["explain", path.to_str().unwrap(), "--bit-offset", "0"]);
+    let explanation = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(explanation, "bit 0 is bit 0 of the gzip header\n");
+}
+
+#[test]
+fn test_decompress_hexdump() {
+    let path = data_path("lorem_ipsum_data.deflate");
+    let raw = std::fs::read(data_path("lorem_ipsum_data")).unwrap();
+    let output = inflate_toy(&[path.to_str().unwrap(), "--hex", "--width", "32"]);
+    assert!(output.status.success());
+    let dump = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(dump.lines().count(), raw.len().div_ceil(32));
+    assert!(dump.lines().nth(1).unwrap().starts_with("00000020: "));
+
+    let output = inflate_toy(&[path.to_str().unwrap(), "--hex", "--offset-base", "dec"]);
+    let dump = String::from_utf8(output.stdout).unwrap();
+    assert!(dump.lines().nth(1).unwrap().starts_with("00000016: "));
+
+    let output = inflate_toy(&[path.to_str().unwrap(), "--hex", "--width", "0"]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_decompress_compare() {
+    let path = data_path("lorem_ipsum_data.deflate");
+    let output = inflate_toy(&[path.to_str().unwrap(), "--compare"]);
+    if cfg!(feature = "compare") {
+        assert!(output.status.success());
+        let report = String::from_utf8(output.stdout).unwrap();
+        assert!(report.ends_with("outputs match\n"), "{}", report);
+    } else {
+        assert_eq!(output.status.code(), Some(2));
+    }
+}
+
+#[test]
+fn test_subcommands() {
+    let path = data_path("lorem_ipsum_data.deflate");
+    let raw = std::fs::read(data_path("lorem_ipsum_data")).unwrap();
+    let output = inflate_toy(&["inflate", path.to_str().unwrap()]);
+    assert_eq!(output.stdout, raw);
+
+    let output = inflate_toy(&["inspect", path.to_str().unwrap()]);
+    let listing = String::from_utf8(output.stdout).unwrap();
+    assert!(listing.starts_with("block 0: dynamic Huffman, final, bits 0.."));
+
+    let output = inflate_toy(&["huffman", path.to_str().unwrap()]);
+    let codes = String::from_utf8(output.stdout).unwrap();
+    assert!(codes.contains("block 0 literal/length codes:\n"));
+    assert!(codes.contains("block 0 distance codes:\n"));
+    assert!(codes.contains("Symbol Length   Code\n------ ------   ----\n"));
+
+    let output = inflate_toy(&["bench", path.to_str().unwrap(), "--iterations", "2"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .contains("2 iterations"));
+
+    let output = inflate_toy(&["inspect", "--help"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .starts_with("Usage: inflate-toy inspect"));
+    assert_eq!(inflate_toy(&[]).status.code(), Some(2));
+}
+
+#[test]
+fn test_stats_report() {
+    let path = data_path("lorem_ipsum_data.deflate");
+    let data = std::fs::read(&path).unwrap();
+    let path = path.to_str().unwrap();
+    let stats = analyze(&data).unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    for (name, format) in [
+        ("stats.md", TableFormat::Markdown),
+        ("stats.csv", TableFormat::Csv),
+    ] {
+        let report = dir.path().join(name);
+        let output = inflate_toy(&["stats", path, "--report", report.to_str().unwrap()]);
+        assert!(output.status.success());
+        assert!(String::from_utf8(output.stdout)
+            .unwrap()
+            .starts_with("blocks: "));
+        assert_eq!(
+            std::fs::read_to_string(&report).unwrap(),
+            stats.block_table(format, &TextOptions::default())
+        );
+    }
+
+    let report = dir.path().join("stats.txt");
+    let output = inflate_toy(&["stats", path, "--report", report.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(2));
+    let output = inflate_toy(&["inspect", path, "--report", "blocks.md"]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_entropy() {
+    let path = data_path("lorem_ipsum_data.deflate");
+    let data = std::fs::read(&path).unwrap();
+    let path = path.to_str().unwrap();
+    let output = inflate_toy(&["entropy", path, "--stable-output"]);
+    assert!(output.status.success());
+    let blocks = estimate(&data).unwrap();
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        entropy_table(&blocks, TableFormat::Markdown, &TextOptions::stable())
+    );
+
+    let output = inflate_toy(&["entropy", path, "--json"]);
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json.as_array().unwrap().len(), blocks.len());
+    assert_eq!(
+        json[0]["literal_length"]["huffman_bits"],
+        blocks[0].literal_length.huffman_bits
+    );
+}
+
+#[test]
+fn test_json_output() {
+    let path = data_path("lorem_ipsum_data.deflate");
+    let data = std::fs::read(&path).unwrap();
+    let path = path.to_str().unwrap();
+
+    let output = inflate_toy(&["inspect", path, "--json"]);
+    let blocks: Vec<BlockInfo> = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(blocks, inspect(&data).unwrap());
+
+    let output = inflate_toy(&["stats", path, "--json"]);
+    let stats: StreamStats = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(stats, analyze(&data).unwrap());
+
+    let output = inflate_toy(&["annotate", path, "--json"]);
+    let annotations: Vec<Annotation> = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(annotations, annotate(&data, Format::Raw));
+
+    // The fields before an error are still listed, and the error goes to stderr.
+    let dir = tempfile::tempdir().unwrap();
+    let truncated = dir.path().join("truncated.deflate");
+    std::fs::write(&truncated, &data[..data.len() / 2]).unwrap();
+    let output = inflate_toy(&["annotate", truncated.to_str().unwrap(), "--json"]);
+    assert!(!output.status.success());
+    let annotations: Vec<Annotation> = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(!annotations.is_empty());
+    let message = String::from_utf8(output.stderr).unwrap();
+    assert!(message.contains("error = "), "{}", message);
+
+    let output = inflate_toy(&["huffman", path, "--block", "0", "--json"]);
+    let tables: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(tables[0]["alphabet"], "literal_length");
+    assert_eq!(tables[1]["alphabet"], "distance");
+    let code: HuffmanLeaf = serde_json::from_value(tables[0]["codes"][0].clone()).unwrap();
+    assert!(code.len > 0);
+    assert_eq!(
+        inflate_toy(&["huffman", path, "--dot", "--json"])
+            .status
+            .code(),
+        Some(2)
+    );
+}
+
+#[test]
+fn test_deflate() {
+    let dir = tempfile::tempdir().unwrap();
+    let raw_path = data_path("lorem_ipsum_data");
+    let raw = std::fs::read(&raw_path).unwrap();
+    for (flag, magic) in [("--gzip", &[0x1f, 0x8b][..]), ("--zlib", &[0x78][..])] {
+        let compressed = dir.path().join("compressed");
+        let output = inflate_toy(&[
+            "deflate",
+            raw_path.to_str().unwrap(),
+            flag,
+            "--level",
+            "9",
+            "-o",
+            compressed.to_str().unwrap(),
+        ]);
+        assert!(output.status.success());
+        assert!(std::fs::read(&compressed).unwrap().starts_with(magic));
+        let output = inflate_toy(&["inflate", compressed.to_str().unwrap()]);
+        assert_eq!(output.stdout, raw);
+    }
+    let output = inflate_toy(&["deflate", raw_path.to_str().unwrap(), "--level", "10"]);
+    assert_eq!(output.status.code(), Some(2));
+}
@@ -0,0 +1,149 @@
+//! Golden compatibility test against the reference decoders installed on the system.
+//!
+//! The test is opt-in: set `INFLATE_TOY_COMPAT=1` to run it. Every gzip file of the corpus is
+//! decoded by each available reference decoder and by the toy decoder, and the outputs must agree.
+//! The resulting compatibility matrix is written to `tests/out/compat_matrix.md`.
+
+#![cfg(feature = "std")]
+
+use std::collections::BTreeMap;
+use std::env;
+use std::io::{Result, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+const ENABLE_VAR: &str = "INFLATE_TOY_COMPAT";
+const DATA_FILES_CONFIG: &str = "manifest.json";
+const MATRIX_FILE: &str = "compat_matrix.md";
+
+/// Reference decoders: a name and a command line reading gzip data on stdin and writing
+/// the decompressed data to stdout.
+const REFERENCE_DECODERS: &[(&str, &[&str])] = &[
+    ("gzip", &["gzip", "-dc"]),
+    ("pigz", &["pigz", "-dc"]),
+    (
+        "python-gzip",
+        &[
+            "python3",
+            "-c",
+            "import gzip, sys; sys.stdout.buffer.write(gzip.decompress(sys.stdin.buffer.read()))",
+        ],
+    ),
+    (
+        "python-zlib",
+        &[
+            "python3",
+            "-c",
+            "import zlib, sys; sys.stdout.buffer.write(zlib.decompress(sys.stdin.buffer.read(), 31))",
+        ],
+    ),
+];
+
+/// The outcome of comparing one reference decoder with the toy decoder on one file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Match,
+    Mismatch,
+    ReferenceFailed,
+    Unavailable,
+}
+
+impl Outcome {
+    fn label(&self) -> &'static str {
+        match self {
+            Outcome::Match => "match",
+            Outcome::Mismatch => "MISMATCH",
+            Outcome::ReferenceFailed => "reference failed",
+            Outcome::Unavailable => "n/a",
+        }
+    }
+}
+
+fn get_test_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests")
+}
+
+/// Run a reference decoder on the given input.
+/// Returns None if the decoder is not installed.
+fn run_reference(command: &[&str], input: &[u8]) -> Option<Result<Vec<u8>>> {
+    let mut child = Command::new(command[0])
+        .args(&command[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    let mut stdin = child.stdin.take().unwrap();
+    let input = input.to_vec();
+    let writer = std::thread::spawn(move || stdin.write_all(&input));
+    let result = child.wait_with_output().and_then(|output| {
+        writer.join().unwrap()?;
+        if output.status.success() {
+            Ok(output.stdout)
+        } else {
+            Err(std::io::Error::other("reference decoder failed"))
+        }
+    });
+    Some(result)
+}
+
+#[test]
+fn test_compat_matrix() -> Result<()> {
+    if env::var_os(ENABLE_VAR).is_none() {
+        println!(
+            "Skipped: set {} to run the compatibility matrix",
+            ENABLE_VAR
+        );
+        return Ok(());
+    }
+
+    let data_dir = get_test_dir().join("data");
+    let manifest = std::fs::read_to_string(data_dir.join(DATA_FILES_CONFIG))?;
+    let data_files: BTreeMap<String, String> = serde_json::from_str(&manifest)?;
+
+    let mut matrix = BTreeMap::new();
+    for name in data_files.keys() {
+        let gzip_data = std::fs::read(data_dir.join(format!("{}.gz", name)))?;
+        let (toy_output, _) = inflate_toy::inflate::decompress_auto(&gzip_data)?;
+        for (decoder, command) in REFERENCE_DECODERS {
+            let outcome = match run_reference(command, &gzip_data) {
+                None => Outcome::Unavailable,
+                Some(Err(_)) => Outcome::ReferenceFailed,
+                Some(Ok(output)) if output == toy_output => Outcome::Match,
+                Some(Ok(_)) => Outcome::Mismatch,
+            };
+            matrix.insert((name.as_str(), *decoder), outcome);
+        }
+    }
+
+    // Record the matrix as a Markdown table.
+    let mut report = String::from("| file |");
+    for (decoder, _) in REFERENCE_DECODERS {
+        report.push_str(&format!(" {} |", decoder));
+    }
+    report.push_str("\n|---|");
+    report.push_str(&"---|".repeat(REFERENCE_DECODERS.len()));
+    report.push('\n');
+    for name in data_files.keys() {
+        report.push_str(&format!("| {} |", name));
+        for (decoder, _) in REFERENCE_DECODERS {
+            report.push_str(&format!(
+                " {} |",
+                matrix[&(name.as_str(), *decoder)].label()
+            ));
+        }
+        report.push('\n');
+    }
+    let out_dir = get_test_dir().join("out");
+    std::fs::create_dir_all(&out_dir)?;
+    std::fs::write(out_dir.join(MATRIX_FILE), &report)?;
+    println!("{}", report);
+
+    let mismatches: Vec<_> = matrix
+        .iter()
+        .filter(|(_, outcome)| **outcome == Outcome::Mismatch)
+        .map(|(key, _)| key)
+        .collect();
+    assert!(mismatches.is_empty(), "Diverging outputs: {:?}", mismatches);
+    Ok(())
+}
@@ -1,3 +1,4 @@
+use inflate_toy::hexdump::{hexdump, HexdumpOptions};
 use std::fs::File;
 use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
@@ -111,7 +112,7 @@ run_test!(
         assert_eq!(content, test_content);
 
         let another_file_path = setup.create_file("another_test.txt", b"Hello, Rust!")?;
-        assert!(setup.compare_files(&file_path, &another_file_path)? == false);
+        assert!(!setup.compare_files(&file_path, &another_file_path)?);
 
         Ok(())
     }
@@ -133,40 +134,7 @@ run_tests!(
             vec![]
         });
         // print the output in hex format
-        println!("Output: \n{}", display_data(&output));
+        println!("Output: \n{}", hexdump(&output, &HexdumpOptions::default()));
         Ok(())
     }
 );
-
-/// Display the data in hex format.
-fn display_data(data: &[u8]) -> String {
-    let mut result = String::new();
-
-    for (i, chunk) in data.chunks(16).enumerate() {
-        // Print the offset
-        result.push_str(&format!("{:08x}: ", i * 16));
-
-        // Print the byte values in hex
-        for byte in chunk {
-            result.push_str(&format!("{:02x} ", byte));
-        }
-
-        // If the chunk is less than 16 bytes, fill the gap
-        for _ in 0..(16 - chunk.len()) {
-            result.push_str("   ");
-        }
-
-        // Print the ASCII representation
-        result.push_str(" |");
-        for byte in chunk {
-            if byte.is_ascii_graphic() {
-                result.push(*byte as char);
-            } else {
-                result.push('.');
-            }
-        }
-        result.push_str("|\n");
-    }
-
-    result
-}
@@ -4,6 +4,7 @@ import gzip
 import os
 import struct
 import json
+import zlib
 
 
 def generate_random_data(file_path: str, size: int) -> None:
@@ -27,6 +28,43 @@ def generate_lorem_ipsum(file_path: str, size: int) -> None:
         f.write(lorem * (size // len(lorem)))
 
 
+def generate_png(file_path: str, width: int, height: int) -> None:
+    """Write an RGB gradient whose scanlines cycle through the five filter types,
+    with the zlib stream split over several IDAT chunks."""
+    def chunk(kind: bytes, data: bytes) -> bytes:
+        crc = zlib.crc32(kind + data)
+        return struct.pack('>I', len(data)) + kind + data + struct.pack('>I', crc)
+
+    def paeth(a: int, b: int, c: int) -> int:
+        p = a + b - c
+        pa, pb, pc = abs(p - a), abs(p - b), abs(p - c)
+        return a if pa <= pb and pa <= pc else b if pb <= pc else c
+
+    rows = [bytes(v for x in range(width) for v in (x * 16 % 256, y * 16 % 256, (x ^ y) * 8 % 256))
+            for y in range(height)]
+    filtered = b''
+    prev = bytes(width * 3)
+    for y, row in enumerate(rows):
+        filter_type = y % 5
+        out = bytearray([filter_type])
+        for i, byte in enumerate(row):
+            a = row[i - 3] if i >= 3 else 0
+            b = prev[i]
+            c = prev[i - 3] if i >= 3 else 0
+            prediction = [0, a, b, (a + b) // 2, paeth(a, b, c)][filter_type]
+            out.append((byte - prediction) % 256)
+        filtered += out
+        prev = row
+    stream = zlib.compress(filtered, 9)
+    png = b'\x89PNG\r\n\x1a\n'
+    png += chunk(b'IHDR', struct.pack('>IIBBBBB', width, height, 8, 2, 0, 0, 0))
+    for i in range(0, len(stream), 64):
+        png += chunk(b'IDAT', stream[i:i + 64])
+    png += chunk(b'IEND', b'')
+    with open(file_path, 'wb') as f:
+        f.write(png)
+
+
 def compress_with_gzip(input_file: str, output_file: str) -> None:
     with open(input_file, 'rb') as f_in:
         with gzip.open(output_file, 'wb') as f_out:
@@ -36,6 +74,7 @@ def compress_with_gzip(input_file: str, output_file: str) -> None:
 DATA_DIR = 'data'
 
 MANIFEST_FILE = 'manifest.json'
+PNG_FILE = 'gradient.png'
 
 GENERATORS = {
     'random_data': [generate_random_data, 512],
@@ -118,6 +157,8 @@ def main():
     with open(get_data_path(MANIFEST_FILE), 'w') as f:
         json.dump(manifest, f)
 
+    generate_png(get_data_path(PNG_FILE), 16, 16)
+
 
 if __name__ == '__main__':
     main()
@@ -1,4 +1,6 @@
-use miniz_oxide;
+#![cfg(feature = "std")]
+
+use inflate_toy::hexdump::{hexdump, HexdumpOptions};
 use std::collections::HashMap;
 use std::env;
 use std::io::Read;
@@ -31,45 +33,12 @@ fn get_test_dir() -> PathBuf {
         .join("data")
 }
 
-/// Display the data in hex format.
-fn display_data(data: &[u8]) -> String {
-    let mut result = String::new();
-
-    for (i, chunk) in data.chunks(16).enumerate() {
-        // Print the offset
-        result.push_str(&format!("{:08x}: ", i * 16));
-
-        // Print the byte values in hex
-        for byte in chunk {
-            result.push_str(&format!("{:02x} ", byte));
-        }
-
-        // If the chunk is less than 16 bytes, fill the gap
-        for _ in 0..(16 - chunk.len()) {
-            result.push_str("   ");
-        }
-
-        // Print the ASCII representation
-        result.push_str(" |");
-        for byte in chunk {
-            if byte.is_ascii_graphic() {
-                result.push(*byte as char);
-            } else {
-                result.push('.');
-            }
-        }
-        result.push_str("|\n");
-    }
-
-    result
-}
-
 fn test_inflator<P>(inflater: &dyn Inflate, deflate_path: &P, raw_data_path: &P) -> Result<()>
 where
     P: AsRef<Path>,
 {
     // Read the DEFLATE data
-    let mut deflate_file = std::fs::File::open(&deflate_path)?;
+    let mut deflate_file = std::fs::File::open(deflate_path)?;
     let mut deflate_data = Vec::new();
     deflate_file.read_to_end(&mut deflate_data)?;
 
@@ -77,7 +46,7 @@ where
     println!(
         "DEFLATE data({}):\n{}",
         deflate_data.len(),
-        display_data(&deflate_data)
+        hexdump(&deflate_data, &HexdumpOptions::default())
     );
 
     // Decompress the DEFLATE data using your custom inflater
@@ -87,16 +56,20 @@ where
     println!(
         "DECOMPRESSED data({}):\n{}",
         decompressed_data.len(),
-        display_data(&decompressed_data)
+        hexdump(&decompressed_data, &HexdumpOptions::default())
     );
 
     // Read the RAW data
-    let mut raw_data_file = std::fs::File::open(&raw_data_path)?;
+    let mut raw_data_file = std::fs::File::open(raw_data_path)?;
     let mut raw_data = Vec::new();
     raw_data_file.read_to_end(&mut raw_data)?;
 
     // Display the RAW data
-    println!("RAW data({}):\n{}", raw_data.len(), display_data(&raw_data));
+    println!(
+        "RAW data({}):\n{}",
+        raw_data.len(),
+        hexdump(&raw_data, &HexdumpOptions::default())
+    );
 
     // Compare the DECOMPRESSED data with the RAW data
     assert_eq!(decompressed_data, raw_data);
@@ -134,3 +107,68 @@ fn test_inflators() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_crafted_vectors() -> Result<()> {
+    for vector in inflate_toy::testgen::test_vectors() {
+        println!("Test: {}", vector.name);
+        let raw_data = match vector.expected {
+            Ok(raw_data) => raw_data,
+            Err(error) => {
+                assert!(miniz_oxide::inflate::decompress_to_vec(&vector.deflate).is_err());
+                assert_eq!(
+                    inflate_toy::inflate::inflate_to_vec(&vector.deflate),
+                    Err(error)
+                );
+                continue;
+            }
+        };
+        for (inflator_name, inflator) in INFLATORS {
+            println!("Inflator: {}", inflator_name);
+            assert_eq!(inflator.inflate_to_vec(&vector.deflate), raw_data);
+        }
+        let mut decompressed_data = Vec::new();
+        inflate_toy::stream::InflateReader::new(&vector.deflate[..])
+            .read_to_end(&mut decompressed_data)?;
+        assert_eq!(decompressed_data, raw_data);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_gzip_files() -> Result<()> {
+    let data_files = get_data_files(DATA_FILES_CONFIG);
+    let options = inflate_toy::preset::Preset::HttpGzip.options();
+
+    for name in data_files.keys() {
+        println!("Test: {}", name);
+        let gzip_data = std::fs::read(get_test_dir().join(format!("{}.gz", name)))?;
+        let raw_data = std::fs::read(get_test_dir().join(name))?;
+        let decompressed_data =
+            inflate_toy::inflate::inflate_to_vec_with_options(&gzip_data, &options)?;
+        assert_eq!(decompressed_data, raw_data);
+
+        let (decompressed_data, format) = inflate_toy::inflate::decompress_auto(&gzip_data)?;
+        assert_eq!(format, inflate_toy::container::Format::Gzip);
+        assert_eq!(decompressed_data, raw_data);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_png_file() -> Result<()> {
+    let png = std::fs::read(get_test_dir().join("gradient.png"))?;
+    let (header, filtered) = inflate_toy::png::scanlines(&png)?;
+    assert_eq!((header.width, header.height), (16, 16));
+    let filters: Vec<u8> = filtered.chunks(1 + 16 * 3).map(|row| row[0]).collect();
+    assert_eq!(filters, (0..16).map(|y| y % 5).collect::<Vec<u8>>());
+
+    let pixels = inflate_toy::png::unfilter(&header, &filtered)?;
+    let expected: Vec<u8> = (0..16u8)
+        .flat_map(|y| (0..16u8).flat_map(move |x| [x * 16, y * 16, (x ^ y) * 8]))
+        .collect();
+    assert_eq!(pixels, expected);
+    Ok(())
+}
@@ -0,0 +1,45 @@
+//! Property-based round trips through the compressor and the decoder, cross-checked against
+//! miniz_oxide in both directions.
+//! Inputs are drawn from seeds: a failing `(kind, seed, len)` can be replayed with
+//! `inflate_toy::roundtrip::sample`, and shrunk further with `roundtrip::minimize`.
+
+use inflate_toy::deflate::{deflate_to_vec_with_level, MAX_LEVEL};
+use inflate_toy::inflate::inflate_to_vec;
+use inflate_toy::roundtrip::{check_round_trip, first_difference, sample, SampleKind};
+use proptest::prelude::*;
+
+/// Inputs of every kind: arbitrary short byte strings, and generated samples.
+fn input() -> impl Strategy<Value = Vec<u8>> {
+    prop_oneof![
+        prop::collection::vec(any::<u8>(), 0..2048),
+        (
+            prop::sample::select(SampleKind::ALL.to_vec()),
+            any::<u64>(),
+            0..20_000usize
+        )
+            .prop_map(|(kind, seed, len)| sample(kind, seed, len)),
+    ]
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(48))]
+
+    #[test]
+    fn test_round_trip(data in input(), level in 0..=MAX_LEVEL) {
+        prop_assert_eq!(check_round_trip(&data, level), Ok(()));
+    }
+
+    #[test]
+    fn test_miniz_inflates_ours(data in input(), level in 0..=MAX_LEVEL) {
+        let compressed = deflate_to_vec_with_level(&data, level);
+        let output = miniz_oxide::inflate::decompress_to_vec(&compressed).unwrap();
+        prop_assert_eq!(first_difference(&data, &output), None);
+    }
+
+    #[test]
+    fn test_we_inflate_miniz(data in input(), level in 0..=10u8) {
+        let compressed = miniz_oxide::deflate::compress_to_vec(&data, level);
+        let output = inflate_to_vec(&compressed).unwrap();
+        prop_assert_eq!(first_difference(&data, &output), None);
+    }
+}
@@ -0,0 +1,152 @@
+//! Size and speed comparison of the toy encoder with zlib's levels, as implemented by miniz_oxide.
+//!
+//! `tests/data/size_baseline.json` holds the sizes the toy encoder compresses fixed samples to
+//! at each level, as a baseline for encoder work: [`test_size_baseline`] fails if an output
+//! grows past it. A change that shrinks the outputs updates the baseline with the sizes the
+//! failing test prints.
+//!
+//! The comparison with zlib is opt-in: set `INFLATE_TOY_SIZE_BENCH=1` to run it. Every file of
+//! the corpus is compressed by the toy encoder at each level and by miniz_oxide at levels 1 to 9,
+//! every output is checked to decompress back to the original, and the compressed sizes and
+//! encode times are written to `tests/out/size_table.md`. The corpus is random, so these sizes
+//! are only comparable within one run.
+
+use inflate_toy::deflate::{deflate_to_vec_with_level, MAX_LEVEL};
+use inflate_toy::inflate::inflate_to_vec;
+use inflate_toy::roundtrip::{sample, SampleKind};
+use std::collections::BTreeMap;
+use std::env;
+use std::io::Result;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+const ENABLE_VAR: &str = "INFLATE_TOY_SIZE_BENCH";
+const DATA_FILES_CONFIG: &str = "manifest.json";
+const TABLE_FILE: &str = "size_table.md";
+const BASELINE_FILE: &str = "size_baseline.json";
+
+/// Seed and length of the samples of the baseline.
+const SAMPLE_SEED: u64 = 2014;
+const SAMPLE_LEN: usize = 16 * 1024;
+
+/// Number of times each file is compressed, keeping the fastest run.
+const RUNS: usize = 3;
+
+/// An encoder turning raw data into a DEFLATE stream.
+type Encoder = Box<dyn Fn(&[u8]) -> Vec<u8>>;
+
+fn get_test_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests")
+}
+
+/// Compress with the given encoder, returning the output and the fastest encode time.
+fn measure(data: &[u8], encode: impl Fn(&[u8]) -> Vec<u8>) -> (Vec<u8>, Duration) {
+    let mut best = Duration::MAX;
+    let mut output = Vec::new();
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        output = encode(data);
+        best = best.min(start.elapsed());
+    }
+    (output, best)
+}
+
+/// Compress a sample of each kind at each level and check no output is larger than the
+/// baseline.
+#[test]
+fn test_size_baseline() -> Result<()> {
+    let path = get_test_dir().join("data").join(BASELINE_FILE);
+    let baseline: BTreeMap<String, Vec<usize>> =
+        serde_json::from_str(&std::fs::read_to_string(path)?)?;
+
+    let mut sizes = BTreeMap::new();
+    for kind in SampleKind::ALL {
+        let raw = sample(kind, SAMPLE_SEED, SAMPLE_LEN);
+        let kind_sizes: Vec<usize> = (0..=MAX_LEVEL)
+            .map(|level| deflate_to_vec_with_level(&raw, level).len())
+            .collect();
+        sizes.insert(format!("{:?}", kind), kind_sizes);
+    }
+    let current = serde_json::to_string_pretty(&sizes)?;
+    assert_eq!(
+        sizes.keys().collect::<Vec<_>>(),
+        baseline.keys().collect::<Vec<_>>(),
+        "{}",
+        current
+    );
+    for (kind, kind_sizes) in &sizes {
+        assert_eq!(kind_sizes.len(), baseline[kind].len(), "{}", current);
+        for (level, (size, expected)) in kind_sizes.iter().zip(&baseline[kind]).enumerate() {
+            assert!(
+                size <= expected,
+                "{} at level {} grew from {} to {} bytes; current sizes:\n{}",
+                kind,
+                level,
+                expected,
+                size,
+                current
+            );
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_size_table() -> Result<()> {
+    if env::var_os(ENABLE_VAR).is_none() {
+        println!("Skipped: set {} to run the size comparison", ENABLE_VAR);
+        return Ok(());
+    }
+
+    let data_dir = get_test_dir().join("data");
+    let manifest = std::fs::read_to_string(data_dir.join(DATA_FILES_CONFIG))?;
+    let data_files: BTreeMap<String, String> = serde_json::from_str(&manifest)?;
+
+    let mut encoders: Vec<(String, Encoder)> = Vec::new();
+    for level in 0..=MAX_LEVEL {
+        encoders.push((
+            format!("toy-{}", level),
+            Box::new(move |data| deflate_to_vec_with_level(data, level)),
+        ));
+    }
+    for level in 1..=9 {
+        encoders.push((
+            format!("zlib-{}", level),
+            Box::new(move |data| miniz_oxide::deflate::compress_to_vec(data, level)),
+        ));
+    }
+
+    let mut report = String::from("| file | size |");
+    for (name, _) in &encoders {
+        report.push_str(&format!(" {} |", name));
+    }
+    report.push_str("\n|---|---|");
+    report.push_str(&"---|".repeat(encoders.len()));
+    report.push('\n');
+    for name in data_files.keys() {
+        let raw = std::fs::read(data_dir.join(name))?;
+        report.push_str(&format!("| {} | {} |", name, raw.len()));
+        for (encoder, encode) in &encoders {
+            let (compressed, time) = measure(&raw, encode);
+            assert_eq!(
+                inflate_to_vec(&compressed).unwrap(),
+                raw,
+                "{} on {}",
+                encoder,
+                name
+            );
+            report.push_str(&format!(
+                " {} ({:.2} ms) |",
+                compressed.len(),
+                time.as_secs_f64() * 1000.0
+            ));
+        }
+        report.push('\n');
+    }
+
+    let out_dir = get_test_dir().join("out");
+    std::fs::create_dir_all(&out_dir)?;
+    std::fs::write(out_dir.join(TABLE_FILE), &report)?;
+    println!("{}", report);
+    Ok(())
+}
@@ -0,0 +1,59 @@
+//! Compatibility of exported traces with the ones recorded by earlier releases.
+//!
+//! `tests/data/trace_v1.json` is the trace of `STREAM` as written with schema version 1.
+//! It must keep parsing, and decoding the same stream must keep producing it: a change to the
+//! trace layout or field names needs a new schema version and a new recording.
+
+#![cfg(all(feature = "std", feature = "serde"))]
+
+use inflate_toy::container::Format;
+use inflate_toy::disasm::{disassemble, FieldKind};
+use inflate_toy::text::TextOptions;
+use inflate_toy::trace::Trace;
+use std::path::PathBuf;
+
+/// A stored block holding "hi", then a dynamic block with a match.
+const STREAM: &[u8] = &[
+    0x00, 0x02, 0x00, 0xfd, 0xff, 0x68, 0x69, 0x6d, 0xc5, 0xb1, 0x09, 0x00, 0x00, 0x08, 0x03, 0xc1,
+    0x55, 0x1c, 0xc0, 0xb1, 0x14, 0x2c, 0x1e, 0x02, 0x36, 0xae, 0xef, 0x02, 0xb9, 0xe6, 0xa6, 0x41,
+    0x19, 0xb6, 0xd3, 0x52, 0x0f,
+];
+
+fn read_recorded_trace(name: &str) -> String {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("data")
+        .join(name);
+    std::fs::read_to_string(path).unwrap()
+}
+
+#[test]
+fn test_recorded_trace_v1() {
+    let recorded = Trace::from_json(&read_recorded_trace("trace_v1.json")).unwrap();
+    assert_eq!(recorded.schema_version, 1);
+    assert_eq!(recorded.format, Format::Raw);
+    assert_eq!(recorded.error, None);
+    assert_eq!(recorded.fields[0].kind, FieldKind::BlockFinal(false));
+    assert!(recorded
+        .fields
+        .iter()
+        .any(|f| matches!(f.kind, FieldKind::DistanceExtra { distance: 7 })));
+
+    let current = Trace::new(&disassemble(STREAM, Format::Raw));
+    assert_eq!(current, recorded);
+}
+
+#[test]
+fn test_stable_output_of_failed_decoding() {
+    let options = TextOptions::stable();
+    let disassembly = disassemble(&STREAM[..STREAM.len() - 2], Format::Raw);
+    let trace = Trace::with_options(&disassembly, &options);
+    assert_eq!(trace.error.as_deref(), Some("unexpected_eof"));
+
+    // A block header with the reserved block type.
+    let disassembly = disassemble(&[0b111, 0], Format::Raw);
+    assert_eq!(
+        disassembly.explain_with(8, &options),
+        "bit 8 comes after decoding failed at bit 3: invalid_block_type"
+    );
+}